@@ -0,0 +1,130 @@
+//! ホストの関数を組み込み関数として登録し、スクリプトから呼ぶ（`cargo run --example custom_builtin`）
+
+use ronkey::error::EvalError;
+use ronkey::evaluator::{EvalResult, Evaluator, Response};
+use ronkey::lexer::Lexer;
+use ronkey::object::Object;
+use ronkey::parser::Parser;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// 整数の配列の合計と平均を返すホストの関数
+fn stats(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let values = match arguments.as_slice() {
+        [Object::Array(values)] => values,
+        _ => {
+            let message = "argument to `stats` must be Array".to_string();
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let mut sum = 0;
+
+    for value in values {
+        match value {
+            Object::Integer(value) => sum += value,
+            value => {
+                let message = format!("`stats` expects Integers, got {}", value.get_type());
+                return Err(EvalError::InvalidArgument(message));
+            }
+        }
+    }
+
+    let mean = if values.is_empty() {
+        Object::Null
+    } else {
+        Object::Integer(sum / values.len() as isize)
+    };
+
+    Ok(Object::Array(vec![Object::Integer(sum), mean]))
+}
+
+fn eval(evaluator: &mut Evaluator, input: &str) -> Result<String, EvalError> {
+    let mut lexer = Lexer::new(input);
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+
+    match evaluator.eval(program) {
+        Response::Reply(result) => Ok(result.to_string()),
+        Response::NoReply => Ok(String::new()),
+        Response::Error(error) => Err(error.kind().clone()),
+    }
+}
+
+/// `stats` と、呼ばれた回数をホストと共有するクロージャーの `tick` を登録する
+fn new_evaluator(ticks: Rc<Cell<isize>>) -> Evaluator {
+    let mut evaluator = Evaluator::new();
+    evaluator.register_builtin("stats", stats);
+    evaluator.register_builtin("tick", move |_, arguments| {
+        if !arguments.is_empty() {
+            let error = EvalError::WrongArity {
+                got: arguments.len(),
+                want: "0".to_string(),
+            };
+            return Err(error);
+        }
+
+        ticks.set(ticks.get() + 1);
+        Ok(Object::Integer(ticks.get()))
+    });
+    evaluator
+}
+
+fn main() {
+    let ticks = Rc::new(Cell::new(0));
+    let mut evaluator = new_evaluator(ticks.clone());
+
+    for input in [
+        "stats([3, 5, 10])",
+        "map([[1], [2, 4]], stats)",
+        "stats([1, true])",
+        "tick(); tick()",
+    ] {
+        match eval(&mut evaluator, input) {
+            Ok(result) => println!("{} => {}", input, result),
+            Err(error) => println!("{} => error: {}", input, error),
+        }
+    }
+
+    println!("tick was called {} times", ticks.get());
+}
+
+#[test]
+fn test_custom_builtin() {
+    let ticks = Rc::new(Cell::new(0));
+    let mut evaluator = new_evaluator(ticks.clone());
+
+    assert_eq!(
+        eval(&mut evaluator, "stats([3, 5, 10])"),
+        Ok("[18, 6]".to_string())
+    );
+    assert_eq!(
+        eval(&mut evaluator, "map([[1], [2, 4]], stats)"),
+        Ok("[[1, 1], [6, 3]]".to_string())
+    );
+    assert_eq!(
+        eval(&mut evaluator, "let s = stats([]); s[1] ?? 0"),
+        Ok("0".to_string())
+    );
+    assert_eq!(
+        eval(&mut evaluator, "stats([1, true])"),
+        Err(EvalError::InvalidArgument(
+            "`stats` expects Integers, got Boolean".to_string()
+        ))
+    );
+    assert_eq!(
+        eval(&mut evaluator, "tick(1)"),
+        Err(EvalError::WrongArity {
+            got: 1,
+            want: "0".to_string()
+        })
+    );
+    assert_eq!(
+        eval(
+            &mut evaluator,
+            "let twice = fn() { tick(); tick() }; twice()"
+        ),
+        Ok("2".to_string())
+    );
+    assert_eq!(ticks.get(), 2);
+}
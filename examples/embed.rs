@@ -0,0 +1,70 @@
+//! ホストの値を注入してスクリプトを評価し、結果と束縛を読み出す（`cargo run --example embed`）
+
+use ronkey::evaluator::{Evaluator, Response};
+use ronkey::lexer::Lexer;
+use ronkey::object::{MapKey, MapPair, Object};
+use ronkey::parser::Parser;
+use std::collections::BTreeMap;
+
+const SCRIPT: &str = r#"
+let total = reduce(orders, 0, fn(sum, order) { sum + order["price"] * order["count"] });
+let label = join([config["currency"], total], " ");
+label
+"#;
+
+fn order(price: isize, count: isize) -> Object {
+    map(vec![
+        ("price", Object::Integer(price)),
+        ("count", Object::Integer(count)),
+    ])
+}
+
+fn map(entries: Vec<(&str, Object)>) -> Object {
+    let mut pairs = BTreeMap::new();
+
+    for (key, value) in entries {
+        let key = Object::String(key.to_string());
+        pairs.insert(MapKey::from(&key), MapPair::new(key, value));
+    }
+
+    Object::Map(pairs)
+}
+
+/// スクリプトの結果と、スクリプトが束縛した `total` を返す
+fn run() -> Result<(String, Object), String> {
+    let mut evaluator = Evaluator::new();
+    evaluator.set(
+        "config",
+        map(vec![("currency", Object::String("JPY".to_string()))]),
+    );
+    evaluator.set("orders", Object::Array(vec![order(120, 3), order(80, 2)]));
+
+    let mut lexer = Lexer::new(SCRIPT);
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+
+    if let Some(error) = parser.get_errors().first() {
+        return Err(error.to_string());
+    }
+
+    let result = match evaluator.eval(program) {
+        Response::Reply(result) => result.to_string(),
+        Response::NoReply => String::new(),
+        Response::Error(error) => return Err(error.to_string()),
+    };
+    let total = evaluator.get("total").unwrap_or(Object::Null);
+
+    Ok((result, total))
+}
+
+fn main() {
+    match run() {
+        Ok((result, total)) => println!("{} (total = {})", result, total),
+        Err(error) => eprintln!("error: {}", error),
+    }
+}
+
+#[test]
+fn test_embed() {
+    assert_eq!(run(), Ok(("JPY 520".to_string(), Object::Integer(520))));
+}
@@ -0,0 +1,95 @@
+//! 信頼できないスクリプトを制限つきで評価する（`cargo run --example sandboxed`）
+
+use ronkey::evaluator::{Evaluator, Operation, Response};
+use ronkey::lexer::Lexer;
+use ronkey::parser::Parser;
+use ronkey::trace::AuditEvent;
+use ronkey::vfs::MemoryVfs;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// 評価の結果と、監査ログに残った組み込み関数の名前
+struct Outcome {
+    result: Result<String, String>,
+    audited: Vec<String>,
+}
+
+/// 文の数・副作用・非決定的な関数・読めるファイル・使える組み込み関数を制限して評価する
+fn run_untrusted(input: &str) -> Outcome {
+    let audited = Rc::new(RefCell::new(vec![]));
+    let log = Rc::clone(&audited);
+
+    let mut vfs = MemoryVfs::new();
+    vfs.insert("data.txt", "3,4");
+
+    let mut evaluator = Evaluator::new();
+    evaluator.set_statement_limit(Some(1_000));
+    evaluator.set_pure(true);
+    evaluator.set_deterministic(true);
+    evaluator.set_vfs(Some(Rc::new(vfs)));
+    evaluator.set_audit(Some(Rc::new(RefCell::new(move |event: &AuditEvent| {
+        log.borrow_mut().push(event.name.clone())
+    }))));
+    evaluator.push_interceptor(Rc::new(RefCell::new(
+        |_: &Evaluator, operation: &Operation| match operation {
+            Operation::Import { path } => Err(format!("imports are disabled: {}", path).into()),
+            _ => Ok(()),
+        },
+    )));
+
+    let mut lexer = Lexer::new(input);
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+
+    let result = match parser.get_errors().first() {
+        Some(error) => Err(error.to_string()),
+        None => match evaluator.eval(program) {
+            Response::Reply(result) => Ok(result.to_string()),
+            Response::NoReply => Ok(String::new()),
+            Response::Error(error) => Err(error.to_string()),
+        },
+    };
+
+    let audited = audited.borrow().clone();
+    Outcome { result, audited }
+}
+
+const INPUTS: [&str; 5] = [
+    r#"split(read_file("data.txt"), ",")"#,
+    "let loop = fn(n) { loop(n + 1) }; loop(0)",
+    r#"puts("hello")"#,
+    "stopwatch()",
+    r#"import "net" as net;"#,
+];
+
+fn main() {
+    for input in INPUTS.iter() {
+        let outcome = run_untrusted(input);
+        println!("{}", input);
+        println!("  => {:?} (audited: {:?})", outcome.result, outcome.audited);
+    }
+}
+
+#[test]
+fn test_sandboxed() {
+    let expected = [
+        Ok("[3, 4]"),
+        Err("statement limit exceeded: 1000"),
+        Err("`puts` has side effects and is not allowed here"),
+        Err("`stopwatch` has side effects and is not allowed here"),
+        Err("imports are disabled: net"),
+    ];
+
+    for (input, expected) in INPUTS.iter().zip(expected.iter()) {
+        let outcome = run_untrusted(input);
+        assert_eq!(
+            outcome.result,
+            expected.map(str::to_string).map_err(str::to_string),
+            "{}",
+            input
+        );
+    }
+
+    assert_eq!(run_untrusted(INPUTS[0]).audited, vec!["read_file"]);
+    assert_eq!(run_untrusted(INPUTS[2]).audited, vec!["puts"]);
+}
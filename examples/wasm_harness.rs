@@ -0,0 +1,86 @@
+//! Web のプレイグラウンドから呼ぶ形の入口（`cargo run --example wasm_harness`）
+//!
+//! ファイルシステムも標準出力も使わず、`std` フィーチャーなしのライブラリにある API だけで書いているので、
+//! `wasm32-unknown-unknown` 向けには `playground` を `wasm-bindgen` などで公開すればよい
+
+use ronkey::evaluator::{Evaluator, Response};
+use ronkey::lexer::Lexer;
+use ronkey::parser::Parser;
+
+/// プレイグラウンドで 1 回に評価できる文の数
+const PLAYGROUND_STEPS: usize = 100_000;
+
+/// 評価して、`puts` の出力を 1 行ずつ `on_output` に渡し、結果を JSON で返す
+pub fn playground(source: &str, on_output: impl FnMut(&str) + 'static) -> String {
+    let mut lexer = Lexer::new(source);
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+
+    if let Some(error) = parser.get_errors().first() {
+        return format!("{{\"ok\":false,\"error\":{}}}", json_string(error));
+    }
+
+    let mut evaluator = Evaluator::new();
+    evaluator.set_statement_limit(Some(PLAYGROUND_STEPS));
+    evaluator.set_deterministic(true);
+    evaluator.set_output_callback(on_output);
+
+    match evaluator.eval(program) {
+        Response::Reply(result) => format!(
+            "{{\"ok\":true,\"result\":{}}}",
+            json_string(&result.to_string())
+        ),
+        Response::NoReply => "{\"ok\":true,\"result\":null}".to_string(),
+        Response::Error(error) => format!(
+            "{{\"ok\":false,\"error\":{}}}",
+            json_string(&error.to_string())
+        ),
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::from("\"");
+
+    for ch in value.chars() {
+        match ch {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            ch if (ch as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => json.push(ch),
+        }
+    }
+
+    json.push('"');
+    json
+}
+
+fn main() {
+    let source = r#"puts("step 1"); puts("step 2"); join(["done", "!"], "")"#;
+    let result = playground(source, |line| println!("[output] {}", line));
+    println!("{}", result);
+}
+
+#[test]
+fn test_playground() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let lines = Rc::new(RefCell::new(vec![]));
+    let log = Rc::clone(&lines);
+    let result = playground(r#"puts("a"); puts(1, [2]); "x" + chr(34)"#, move |line| {
+        log.borrow_mut().push(line.to_string())
+    });
+
+    assert_eq!(result, r#"{"ok":true,"result":"x\""}"#);
+    assert_eq!(*lines.borrow(), vec!["a", "1", "[2]"]);
+
+    assert_eq!(
+        playground("let f = fn() { f() }; f()", |_| ()),
+        r#"{"ok":false,"error":"statement limit exceeded: 100000"}"#
+    );
+    assert_eq!(
+        playground("let = 1", |_| ()),
+        r#"{"ok":false,"error":"expected next token to be Ident, got = instead"}"#
+    );
+}
@@ -1,26 +1,53 @@
-use crate::token::Token;
-use std::collections::BTreeMap;
-use std::fmt;
+use crate::error::EvalError;
+use crate::numeric::Float;
+#[cfg(feature = "rational")]
+use crate::numeric::Rational;
+use crate::symbol::Symbol;
+use crate::token::{Position, Token};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
 
 /// 文
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Statement {
     /// let
-    Let { name: Expression, value: Expression },
+    Let {
+        name: Expression,
+        value: Expression,
+        position: Position,
+    },
     /// return
-    Return(Expression),
+    Return(Expression, Position),
     /// 式
-    Expression(Expression),
+    Expression(Expression, Position),
     /// ブロック
     Block(Vec<Statement>),
 }
 
+impl Statement {
+    /// 文の始まりの位置（ブロックや評価器が組み立てた文なら None）
+    pub fn position(&self) -> Option<Position> {
+        let position = match self {
+            Self::Let { position, .. } => position,
+            Self::Return(_, position) | Self::Expression(_, position) => position,
+            Self::Block(_) => return None,
+        };
+
+        Some(*position).filter(Position::is_known)
+    }
+}
+
 impl fmt::Display for Statement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Let { name, value } => write!(f, "let {} = {};", name, value),
-            Self::Return(expression) => write!(f, "return {};", expression),
-            Self::Expression(expression) => write!(f, "{}", expression),
+            Self::Let { name, value, .. } => write!(f, "let {} = {};", name, value),
+            Self::Return(expression, _) => write!(f, "return {};", expression),
+            Self::Expression(expression, _) => write!(f, "{}", expression),
             Self::Block(statements) => {
                 for statement in statements.iter() {
                     write!(f, "{}", statement)?;
@@ -35,9 +62,13 @@ impl fmt::Display for Statement {
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Expression {
     /// 識別子
-    Identifier(String),
+    Identifier(Symbol),
     /// 数値
     Integer(isize),
+    /// 浮動小数点数
+    Float(Float),
+    #[cfg(feature = "rational")]
+    Rational(Rational),
     /// 文字列
     String(String),
     /// 前置演算子
@@ -61,11 +92,27 @@ pub enum Expression {
         consequence: Box<Statement>,
         alternative: Option<Box<Statement>>,
     },
+    /// try
+    Try {
+        body: Box<Statement>,
+        parameter: Box<Expression>,
+        handler: Box<Statement>,
+    },
+    /// match
+    Match {
+        subject: Box<Expression>,
+        arms: Vec<MatchArm>,
+    },
     /// 関数
     Function {
         parameters: Vec<Expression>,
         body: Box<Statement>,
     },
+    /// マクロ
+    Macro {
+        parameters: Vec<Expression>,
+        body: Box<Statement>,
+    },
     /// 呼び出し
     Call {
         function: Box<Expression>,
@@ -78,8 +125,16 @@ pub enum Expression {
         left: Box<Expression>,
         index: Box<Expression>,
     },
+    /// スライス
+    Slice {
+        left: Box<Expression>,
+        start: Option<Box<Expression>>,
+        end: Option<Box<Expression>>,
+    },
     /// マップ
     Map(BTreeMap<Expression, Expression>),
+    /// yield
+    Yield(Box<Expression>),
 }
 
 impl fmt::Display for Expression {
@@ -87,6 +142,15 @@ impl fmt::Display for Expression {
         match self {
             Self::Identifier(value) => write!(f, "{}", value),
             Self::Integer(value) => write!(f, "{}", value),
+            Self::Float(value) => write!(f, "{}", value),
+            #[cfg(feature = "rational")]
+            Self::Rational(value) if value.denominator() == 1 => {
+                write!(f, "{}r", value.numerator())
+            }
+            #[cfg(feature = "rational")]
+            Self::Rational(value) => {
+                write!(f, "({} / {}r)", value.numerator(), value.denominator())
+            }
             Self::String(value) => write!(f, "{}", value),
             Self::Prefix { operator, right } => write!(f, "({}{})", operator, right),
             Self::Infix {
@@ -104,10 +168,27 @@ impl fmt::Display for Expression {
                 Some(a) => write!(f, "if {} {{ {} }} else {{ {} }}", condition, consequence, a),
                 None => write!(f, "if {} {}", condition, consequence),
             },
+            Self::Try {
+                body,
+                parameter,
+                handler,
+            } => write!(
+                f,
+                "try {{ {} }} catch ({}) {{ {} }}",
+                body, parameter, handler
+            ),
+            Self::Match { subject, arms } => {
+                let arms = arms.iter().map(MatchArm::to_string).collect::<Vec<_>>();
+                write!(f, "match {} {{ {} }}", subject, arms.join(", "))
+            }
             Self::Function { parameters, body } => {
                 let parameters = parameters.iter().map(Self::to_string).collect::<Vec<_>>();
                 write!(f, "fn ({}) {{ {} }}", parameters.join(", "), body)
             }
+            Self::Macro { parameters, body } => {
+                let parameters = parameters.iter().map(Self::to_string).collect::<Vec<_>>();
+                write!(f, "macro ({}) {{ {} }}", parameters.join(", "), body)
+            }
             Self::Call {
                 function,
                 arguments,
@@ -124,6 +205,11 @@ impl fmt::Display for Expression {
                 write!(f, "[{}]", elements)
             }
             Self::Index { left, index } => write!(f, "({}[{}])", left, index),
+            Self::Slice { left, start, end } => {
+                let start = start.as_ref().map(|e| e.to_string()).unwrap_or_default();
+                let end = end.as_ref().map(|e| e.to_string()).unwrap_or_default();
+                write!(f, "({}[{}:{}])", left, start, end)
+            }
             Self::Map(pairs) => {
                 let pairs = pairs
                     .iter()
@@ -132,11 +218,75 @@ impl fmt::Display for Expression {
                     .join(", ");
                 write!(f, "{{{}}}", pairs)
             }
+            Self::Yield(value) => write!(f, "yield {}", value),
+        }
+    }
+}
+
+/// match の腕
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: Expression,
+}
+
+impl fmt::Display for MatchArm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.guard {
+            Some(guard) => write!(f, "{} if {} => {}", self.pattern, guard, self.body),
+            None => write!(f, "{} => {}", self.pattern, self.body),
+        }
+    }
+}
+
+/// パターン
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Pattern {
+    /// _
+    Wildcard,
+    /// 束縛
+    Binding(Symbol),
+    /// リテラル
+    Literal(Expression),
+    /// 配列（`...rest` で残りの要素を束縛する）
+    Array {
+        elements: Vec<Pattern>,
+        rest: Option<Symbol>,
+    },
+    /// マップ（キーは文字列）
+    Map(Vec<(String, Pattern)>),
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wildcard => write!(f, "_"),
+            Self::Binding(name) => write!(f, "{}", name),
+            Self::Literal(expression) => write!(f, "{}", expression),
+            Self::Array { elements, rest } => {
+                let mut elements = elements.iter().map(Self::to_string).collect::<Vec<_>>();
+                if let Some(rest) = rest {
+                    elements.push(format!("...{}", rest));
+                }
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Self::Map(entries) => {
+                let entries = entries
+                    .iter()
+                    .map(|(key, pattern)| match pattern {
+                        Self::Binding(name) if **name == **key => key.to_string(),
+                        _ => format!("{}: {}", key, pattern),
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
         }
     }
 }
 
 /// プログラム
+#[derive(Clone, Debug)]
 pub struct Program {
     pub statements: Vec<Statement>,
 }
@@ -146,3 +296,416 @@ impl Program {
         Self { statements: vec![] }
     }
 }
+
+impl Default for Program {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 式を書きかえる関数
+pub type Modifier<'a> = dyn FnMut(&mut Expression) -> Result<(), EvalError> + 'a;
+
+/// プログラム中のすべての式を、子から親の順に書きかえる
+pub fn modify_program(program: &mut Program, f: &mut Modifier) -> Result<(), EvalError> {
+    for statement in program.statements.iter_mut() {
+        modify_statement(statement, f)?;
+    }
+
+    Ok(())
+}
+
+pub fn modify_statement(statement: &mut Statement, f: &mut Modifier) -> Result<(), EvalError> {
+    match statement {
+        Statement::Let { value, .. } => modify_expression(value, f),
+        Statement::Return(expression, _) | Statement::Expression(expression, _) => {
+            modify_expression(expression, f)
+        }
+        Statement::Block(statements) => {
+            for statement in statements.iter_mut() {
+                modify_statement(statement, f)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+pub fn modify_expression(expression: &mut Expression, f: &mut Modifier) -> Result<(), EvalError> {
+    match expression {
+        Expression::Prefix { right, .. } => modify_expression(right, f)?,
+        Expression::Infix { left, right, .. } => {
+            modify_expression(left, f)?;
+            modify_expression(right, f)?;
+        }
+        Expression::Grouped(expression) | Expression::Yield(expression) => {
+            modify_expression(expression, f)?
+        }
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+        } => {
+            modify_expression(condition, f)?;
+            modify_statement(consequence, f)?;
+
+            if let Some(alternative) = alternative {
+                modify_statement(alternative, f)?;
+            }
+        }
+        Expression::Try { body, handler, .. } => {
+            modify_statement(body, f)?;
+            modify_statement(handler, f)?;
+        }
+        Expression::Match { subject, arms } => {
+            modify_expression(subject, f)?;
+
+            for arm in arms.iter_mut() {
+                if let Some(guard) = &mut arm.guard {
+                    modify_expression(guard, f)?;
+                }
+                modify_expression(&mut arm.body, f)?;
+            }
+        }
+        Expression::Function { body, .. } | Expression::Macro { body, .. } => {
+            modify_statement(body, f)?
+        }
+        Expression::Call {
+            function,
+            arguments,
+        } => {
+            modify_expression(function, f)?;
+
+            for argument in arguments.iter_mut() {
+                modify_expression(argument, f)?;
+            }
+        }
+        Expression::Array(elements) => {
+            for element in elements.iter_mut() {
+                modify_expression(element, f)?;
+            }
+        }
+        Expression::Index { left, index } => {
+            modify_expression(left, f)?;
+            modify_expression(index, f)?;
+        }
+        Expression::Slice { left, start, end } => {
+            modify_expression(left, f)?;
+
+            for bound in start.iter_mut().chain(end.iter_mut()) {
+                modify_expression(bound, f)?;
+            }
+        }
+        Expression::Map(pairs) => {
+            let mut modified = BTreeMap::new();
+
+            for (mut key, mut value) in core::mem::take(pairs) {
+                modify_expression(&mut key, f)?;
+                modify_expression(&mut value, f)?;
+                modified.insert(key, value);
+            }
+
+            *pairs = modified;
+        }
+        Expression::Identifier(_)
+        | Expression::Integer(_)
+        | Expression::Float(_)
+        | Expression::String(_)
+        | Expression::Boolean(_) => (),
+        #[cfg(feature = "rational")]
+        Expression::Rational(_) => (),
+    }
+
+    f(expression)
+}
+
+/// 関数の本体が参照する自由変数（引数と、本体の `let` で束縛する名前を除いた識別子）を返す
+pub fn free_variables(parameters: &[Expression], body: &Statement) -> BTreeSet<Symbol> {
+    let mut bound = parameters
+        .iter()
+        .filter_map(|parameter| match parameter {
+            Expression::Identifier(name) => Some(name.clone()),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+    let_names_in_statement(body, &mut bound);
+
+    let mut free = BTreeSet::new();
+    free_in_statement(body, &bound, &mut free);
+    free
+}
+
+fn free_in_statement(statement: &Statement, bound: &BTreeSet<Symbol>, free: &mut BTreeSet<Symbol>) {
+    match statement {
+        Statement::Let { value, .. } => free_in_expression(value, bound, free),
+        Statement::Return(expression, _) | Statement::Expression(expression, _) => {
+            free_in_expression(expression, bound, free)
+        }
+        Statement::Block(statements) => {
+            for statement in statements.iter() {
+                free_in_statement(statement, bound, free);
+            }
+        }
+    }
+}
+
+fn free_in_expression(
+    expression: &Expression,
+    bound: &BTreeSet<Symbol>,
+    free: &mut BTreeSet<Symbol>,
+) {
+    match expression {
+        Expression::Identifier(name) if !bound.contains(name) => {
+            free.insert(name.clone());
+        }
+        Expression::Function { parameters, body } | Expression::Macro { parameters, body } => {
+            let inner = free_variables(parameters, body);
+            free.extend(inner.into_iter().filter(|name| !bound.contains(name)));
+        }
+        Expression::Try {
+            body,
+            parameter,
+            handler,
+        } => {
+            free_in_statement(body, bound, free);
+
+            let mut bound = bound.clone();
+            if let Expression::Identifier(name) = parameter.as_ref() {
+                bound.insert(name.clone());
+            }
+            free_in_statement(handler, &bound, free);
+        }
+        Expression::Match { subject, arms } => {
+            free_in_expression(subject, bound, free);
+
+            for arm in arms.iter() {
+                let mut bound = bound.clone();
+                pattern_names(&arm.pattern, &mut bound);
+
+                if let Pattern::Literal(literal) = &arm.pattern {
+                    free_in_expression(literal, &bound, free);
+                }
+                if let Some(guard) = &arm.guard {
+                    free_in_expression(guard, &bound, free);
+                }
+                free_in_expression(&arm.body, &bound, free);
+            }
+        }
+        expression => {
+            let (expressions, statements) = children(expression);
+
+            for expression in expressions {
+                free_in_expression(expression, bound, free);
+            }
+            for statement in statements {
+                free_in_statement(statement, bound, free);
+            }
+        }
+    }
+}
+
+/// 関数の本体で `let` が束縛する名前を集める（内側の関数の本体は含めない）
+fn let_names_in_statement(statement: &Statement, names: &mut BTreeSet<Symbol>) {
+    match statement {
+        Statement::Let { name, value, .. } => {
+            if let Expression::Identifier(name) = name {
+                names.insert(name.clone());
+            }
+            let_names_in_expression(value, names);
+        }
+        Statement::Return(expression, _) | Statement::Expression(expression, _) => {
+            let_names_in_expression(expression, names)
+        }
+        Statement::Block(statements) => {
+            for statement in statements.iter() {
+                let_names_in_statement(statement, names);
+            }
+        }
+    }
+}
+
+fn let_names_in_expression(expression: &Expression, names: &mut BTreeSet<Symbol>) {
+    if let Expression::Function { .. } | Expression::Macro { .. } = expression {
+        return;
+    }
+
+    let (expressions, statements) = children(expression);
+
+    for expression in expressions {
+        let_names_in_expression(expression, names);
+    }
+    for statement in statements {
+        let_names_in_statement(statement, names);
+    }
+}
+
+fn pattern_names(pattern: &Pattern, names: &mut BTreeSet<Symbol>) {
+    match pattern {
+        Pattern::Binding(name) => {
+            names.insert(name.clone());
+        }
+        Pattern::Array { elements, rest } => {
+            for element in elements.iter() {
+                pattern_names(element, names);
+            }
+            names.extend(rest.iter().cloned());
+        }
+        Pattern::Map(entries) => {
+            for (_, pattern) in entries.iter() {
+                pattern_names(pattern, names);
+            }
+        }
+        Pattern::Wildcard | Pattern::Literal(_) => (),
+    }
+}
+
+/// 式の直下にある式と文
+fn children(expression: &Expression) -> (Vec<&Expression>, Vec<&Statement>) {
+    match expression {
+        Expression::Prefix { right, .. } => (vec![right], vec![]),
+        Expression::Infix { left, right, .. } => (vec![left, right], vec![]),
+        Expression::Grouped(expression) | Expression::Yield(expression) => {
+            (vec![expression], vec![])
+        }
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+        } => {
+            let mut statements = vec![consequence.as_ref()];
+            statements.extend(alternative.as_deref());
+            (vec![condition], statements)
+        }
+        Expression::Try { body, handler, .. } => (vec![], vec![body, handler]),
+        Expression::Match { subject, arms } => {
+            let mut expressions = vec![subject.as_ref()];
+
+            for arm in arms.iter() {
+                if let Pattern::Literal(literal) = &arm.pattern {
+                    expressions.push(literal);
+                }
+                expressions.extend(arm.guard.iter());
+                expressions.push(&arm.body);
+            }
+
+            (expressions, vec![])
+        }
+        Expression::Function { body, .. } | Expression::Macro { body, .. } => (vec![], vec![body]),
+        Expression::Call {
+            function,
+            arguments,
+        } => {
+            let mut expressions = vec![function.as_ref()];
+            expressions.extend(arguments.iter());
+            (expressions, vec![])
+        }
+        Expression::Array(elements) => (elements.iter().collect(), vec![]),
+        Expression::Index { left, index } => (vec![left, index], vec![]),
+        Expression::Slice { left, start, end } => {
+            let mut expressions = vec![left.as_ref()];
+            expressions.extend(start.as_deref());
+            expressions.extend(end.as_deref());
+            (expressions, vec![])
+        }
+        Expression::Map(pairs) => (
+            pairs.iter().flat_map(|(key, value)| [key, value]).collect(),
+            vec![],
+        ),
+        Expression::Identifier(_)
+        | Expression::Integer(_)
+        | Expression::Float(_)
+        | Expression::String(_)
+        | Expression::Boolean(_) => (vec![], vec![]),
+        #[cfg(feature = "rational")]
+        Expression::Rational(_) => (vec![], vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{free_variables, modify_program, Expression, Program, Statement};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> Program {
+        let mut lexer = Lexer::new(input);
+        let mut parser = Parser::new(&mut lexer);
+        parser.parse_program()
+    }
+
+    #[test]
+    fn test_modify() {
+        let tests = vec![
+            ("1", "2"),
+            ("1 + 2", "(2 + 2)"),
+            ("-1", "(-2)"),
+            ("[1, [1]][1]", "([2, [2]][2])"),
+            ("{1: 1}", "{2: 2}"),
+            ("if (1) { 1 } else { 1 }", "if 2 { 2 } else { 2 }"),
+            ("fn(x) { return 1; }", "fn (x) { return 2; }"),
+            ("let x = f(1);", "let x = f(2);"),
+            ("try { 1 } catch (e) { 1 }", "try { 2 } catch (e) { 2 }"),
+            ("match (1) { x if 1 => 1 }", "match 2 { x if 2 => 2 }"),
+        ];
+
+        for (input, expected) in tests {
+            let mut program = parse(input);
+
+            modify_program(&mut program, &mut |expression| {
+                if let Expression::Integer(1) = expression {
+                    *expression = Expression::Integer(2);
+                }
+                Ok(())
+            })
+            .unwrap();
+
+            let actual = program
+                .statements
+                .iter()
+                .map(Statement::to_string)
+                .collect::<String>();
+            assert_eq!(actual, expected);
+        }
+
+        let mut program = parse("[1, 2]");
+        let result = modify_program(&mut program, &mut |expression| match expression {
+            Expression::Integer(2) => Err("two".into()),
+            _ => Ok(()),
+        });
+        assert_eq!(result, Err("two".into()));
+    }
+
+    #[test]
+    fn test_free_variables() {
+        let tests = vec![
+            ("fn(x) { x + y }", vec!["y"]),
+            ("fn(x) { let y = x; y + z }", vec!["z"]),
+            ("fn(x) { fn(y) { x + y + z } }", vec!["z"]),
+            (
+                "fn() { if (a) { let b = 1; b } else { c } }",
+                vec!["a", "c"],
+            ),
+            ("fn() { try { f() } catch (e) { e + g } }", vec!["f", "g"]),
+            (
+                "fn(v) { match (v) { [h, ...t] if h > k => t, x => x + d } }",
+                vec!["d", "k"],
+            ),
+            ("fn() { {key: value}.key }", vec!["key", "value"]),
+            ("fn(n) { fib(n - 1) }", vec!["fib"]),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+
+            let free = match &program.statements[0] {
+                Statement::Expression(Expression::Function { parameters, body }, _) => {
+                    free_variables(parameters, body)
+                }
+                statement => panic!("not a function: {}", statement),
+            };
+
+            let free = free.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+            assert_eq!(free, expected, "{}", input);
+        }
+    }
+}
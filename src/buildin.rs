@@ -1,26 +1,627 @@
-use crate::evaluator::EvalResult;
-use crate::object::Object;
-use std::collections::BTreeMap;
+use crate::error::EvalError;
+use crate::evaluator::{is_truthy, EvalResult, Evaluator, Response};
+use crate::host::HostResource;
+#[cfg(feature = "data")]
+use crate::json;
+use crate::lexer::Lexer;
+#[cfg(feature = "std")]
+use crate::numeric::Float;
+#[cfg(feature = "rational")]
+use crate::numeric::{ArithmeticError, Rational};
+use crate::numeric::{Number, Rounding};
+use crate::object::{Buildin, MapKey, MapPair, Object, MAX_LENGTH};
+use crate::parser::Parser;
+use crate::token::Token;
+use crate::vfs::{MemoryVfs, OpenMode, VfsFile};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "date")]
+use chrono::format::{Item, StrftimeItems};
+#[cfg(feature = "date")]
+use chrono::NaiveDate;
+use core::cell::RefCell;
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::sync::atomic::Ordering as AtomicOrdering;
+use hashbrown::HashMap;
 
-pub fn new() -> BTreeMap<String, Object> {
-    let mut buildins = BTreeMap::new();
+/// 組み込み関数の引数の数
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    /// 最小と最大（両端を含む）
+    Range(usize, usize),
+    AtLeast(usize),
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(count) => write!(f, "{}", count),
+            Self::Range(min, max) if min + 1 == *max => write!(f, "{} or {}", min, max),
+            Self::Range(min, max) => write!(f, "{} to {}", min, max),
+            Self::AtLeast(min) => write!(f, "{} or more", min),
+        }
+    }
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Self::Exact(want) => count == want,
+            Self::Range(min, max) => (min..=max).contains(&count),
+            Self::AtLeast(min) => count >= min,
+        }
+    }
+
+    /// 引数の数を確かめる（どの組み込み関数でも同じメッセージになる）
+    pub fn check(&self, count: usize) -> Result<(), EvalError> {
+        if !self.accepts(count) {
+            let error = EvalError::WrongArity {
+                got: count,
+                want: self.to_string(),
+            };
+            return Err(error);
+        }
+
+        Ok(())
+    }
+}
+
+/// 組み込み関数とその説明
+#[derive(Clone, Copy)]
+pub struct BuildinSpec {
+    /// 呼び出しの形（`len(value)` など。名前は `(` の前）
+    pub signature: &'static str,
+    pub function: fn(&mut Evaluator, Vec<Object>) -> EvalResult,
+    pub arity: Arity,
+    /// 1 行の説明
+    pub summary: &'static str,
+}
+
+impl BuildinSpec {
+    const fn new(
+        signature: &'static str,
+        function: fn(&mut Evaluator, Vec<Object>) -> EvalResult,
+        arity: Arity,
+        summary: &'static str,
+    ) -> Self {
+        Self {
+            signature,
+            function,
+            arity,
+            summary,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.signature.find('(') {
+            Some(index) => &self.signature[..index],
+            None => self.signature,
+        }
+    }
+}
+
+/// 有効なフィーチャーで使えるすべての組み込み関数
+pub fn specs() -> Vec<BuildinSpec> {
+    use Arity::{AtLeast, Exact, Range};
+
+    let mut specs = vec![
+        BuildinSpec::new(
+            "len(value)",
+            len,
+            Exact(1),
+            "Length of a String (in bytes) or an Array",
+        ),
+        BuildinSpec::new(
+            "first(array)",
+            first,
+            Exact(1),
+            "First element, or null if empty",
+        ),
+        BuildinSpec::new(
+            "last(array)",
+            last,
+            Exact(1),
+            "Last element, or null if empty",
+        ),
+        BuildinSpec::new(
+            "rest(array)",
+            rest,
+            Exact(1),
+            "All elements but the first, or null if empty",
+        ),
+        BuildinSpec::new(
+            "push(array, value)",
+            push,
+            Exact(2),
+            "New array with the value appended",
+        ),
+        BuildinSpec::new(
+            "pop(array)",
+            pop,
+            Exact(1),
+            "New array without the last element, or null if empty",
+        ),
+        BuildinSpec::new(
+            "slice(value, start, end)",
+            slice,
+            Range(2, 3),
+            "Part of an Array or String; negative indexes count from the end",
+        ),
+        BuildinSpec::new(
+            "remove_at(array, index)",
+            remove_at,
+            Exact(2),
+            "New array without the element at the index",
+        ),
+        BuildinSpec::new(
+            "zip(left, right)",
+            zip,
+            Exact(2),
+            "Pairs of elements at the same positions",
+        ),
+        BuildinSpec::new(
+            "enumerate(array)",
+            enumerate,
+            Exact(1),
+            "Pairs of each index and element",
+        ),
+        BuildinSpec::new(
+            "flatten(array, depth)",
+            flatten,
+            Range(1, 2),
+            "Flattens nested arrays by depth levels (default 1)",
+        ),
+        BuildinSpec::new(
+            "unique(array)",
+            unique,
+            Exact(1),
+            "Elements without duplicates, in first-seen order",
+        ),
+        BuildinSpec::new(
+            "index_of(value, target)",
+            index_of,
+            Exact(2),
+            "First index of a value in an Array or a substring in a String, or null",
+        ),
+        BuildinSpec::new(
+            "char_at(string, index)",
+            char_at,
+            Exact(2),
+            "Character at the index, or null if out of range",
+        ),
+        BuildinSpec::new(
+            "ord(character)",
+            ord,
+            Exact(1),
+            "Code point of a single character",
+        ),
+        BuildinSpec::new("chr(code)", chr, Exact(1), "Character for a code point"),
+        BuildinSpec::new(
+            "chars(string)",
+            chars,
+            Exact(1),
+            "Array of the characters of a String",
+        ),
+        BuildinSpec::new(
+            "join(array, separator)",
+            join,
+            Exact(2),
+            "Elements joined into a String",
+        ),
+        BuildinSpec::new(
+            "split(string, separator)",
+            split,
+            Exact(2),
+            "Parts of a String; an empty separator splits into characters",
+        ),
+        BuildinSpec::new(
+            "substr(string, start, length)",
+            substr,
+            Exact(3),
+            "length characters from start",
+        ),
+        BuildinSpec::new(
+            "replace(string, pattern, replacement)",
+            replace,
+            Exact(3),
+            "Replaces every occurrence of pattern",
+        ),
+        BuildinSpec::new(
+            "starts_with(value, prefix)",
+            starts_with,
+            Exact(2),
+            "Whether a String starts with the prefix or an Array with the element",
+        ),
+        BuildinSpec::new(
+            "ends_with(value, suffix)",
+            ends_with,
+            Exact(2),
+            "Whether a String ends with the suffix or an Array with the element",
+        ),
+        BuildinSpec::new(
+            "format(template, ...)",
+            format,
+            AtLeast(1),
+            "Fills {} and {N} placeholders with the arguments",
+        ),
+        BuildinSpec::new(
+            "insert(map, key, value)",
+            insert,
+            Exact(3),
+            "New map with the key set to the value",
+        ),
+        BuildinSpec::new(
+            "remove(map, key)",
+            remove,
+            Exact(2),
+            "New map without the key",
+        ),
+        BuildinSpec::new(
+            "binary_search(array, value)",
+            binary_search,
+            Exact(2),
+            "Index of the value in a sorted Array, or null",
+        ),
+        BuildinSpec::new(
+            "min_by(array, function)",
+            min_by,
+            Exact(2),
+            "Element with the smallest result of the function",
+        ),
+        BuildinSpec::new(
+            "max_by(array, function)",
+            max_by,
+            Exact(2),
+            "Element with the largest result of the function",
+        ),
+        BuildinSpec::new(
+            "map(array, function)",
+            map,
+            Exact(2),
+            "Results of the function for each element",
+        ),
+        BuildinSpec::new(
+            "filter(array, function)",
+            filter,
+            Exact(2),
+            "Elements for which the function is truthy",
+        ),
+        BuildinSpec::new(
+            "reduce(array, initial, function)",
+            reduce,
+            Exact(3),
+            "Folds the elements with the function",
+        ),
+        BuildinSpec::new(
+            "chunk(array, size)",
+            chunk,
+            Exact(2),
+            "Splits an Array into arrays of the size",
+        ),
+        BuildinSpec::new(
+            "zeros(rows, columns)",
+            zeros,
+            Exact(2),
+            "Matrix filled with 0",
+        ),
+        BuildinSpec::new(
+            "transpose(matrix)",
+            transpose,
+            Exact(1),
+            "Transposed matrix",
+        ),
+        BuildinSpec::new(
+            "matmul(left, right)",
+            matmul,
+            Exact(2),
+            "Product of two matrices",
+        ),
+        BuildinSpec::new(
+            "compare(left, right, collation)",
+            compare,
+            Range(2, 3),
+            "-1, 0 or 1 by the collation",
+        ),
+        BuildinSpec::new(
+            "sort(array, collation)",
+            sort,
+            Range(1, 2),
+            "Sorted Array by the collation",
+        ),
+        BuildinSpec::new(
+            "id(value)",
+            id,
+            Exact(1),
+            "Identity of a value as an Integer; values that are `is` share it",
+        ),
+        BuildinSpec::new(
+            "is(left, right)",
+            is,
+            Exact(2),
+            "Whether two values are the same: functions by identity, other values by type and value",
+        ),
+        BuildinSpec::new(
+            "throw(value)",
+            throw,
+            Exact(1),
+            "Throws a value to the nearest catch",
+        ),
+        BuildinSpec::new(
+            "error(message)",
+            error,
+            Exact(1),
+            "Error value with the message",
+        ),
+        BuildinSpec::new(
+            "is_error(value)",
+            is_error,
+            Exact(1),
+            "Whether the value is an error value",
+        ),
+        BuildinSpec::new(
+            "assert(condition, message)",
+            assert,
+            Range(1, 2),
+            "Fails unless the condition is truthy",
+        ),
+        BuildinSpec::new(
+            "assert_eq(actual, expected, message)",
+            assert_eq,
+            Range(2, 3),
+            "Fails unless the values are equal",
+        ),
+        BuildinSpec::new("type(value)", r#type, Exact(1), "Type name of a value"),
+        BuildinSpec::new("int(value)", int, Exact(1), "Converts to Integer"),
+        BuildinSpec::new("str(value)", str, Exact(1), "Converts to String"),
+        BuildinSpec::new(
+            "bool(value)",
+            bool,
+            Exact(1),
+            "Converts to Boolean by truthiness",
+        ),
+        BuildinSpec::new("floor(number)", floor, Exact(1), "Rounds down"),
+        BuildinSpec::new("ceil(number)", ceil, Exact(1), "Rounds up"),
+        BuildinSpec::new(
+            "round(number)",
+            round,
+            Exact(1),
+            "Rounds to the nearest, halves away from zero",
+        ),
+        BuildinSpec::new(
+            "sandbox(source, options)",
+            sandbox,
+            Range(1, 2),
+            "Evaluates source in a fresh, limited evaluator",
+        ),
+        BuildinSpec::new(
+            "next(generator)",
+            next,
+            Exact(1),
+            "Next value of a generator",
+        ),
+        BuildinSpec::new(
+            "take(generator, count)",
+            take,
+            Exact(2),
+            "Next count values of a generator",
+        ),
+        BuildinSpec::new("read_file(path)", read_file, Exact(1), "Contents of a file"),
+        BuildinSpec::new("import(path)", import, Exact(1), "Exports of a module"),
+        BuildinSpec::new(
+            "on_interrupt(function)",
+            on_interrupt,
+            Exact(1),
+            "Function called once when interrupted",
+        ),
+        BuildinSpec::new("open(path, mode)", open, Exact(2), "Opens a file"),
+        BuildinSpec::new(
+            "read_line(file)",
+            read_line,
+            Range(0, 1),
+            "Next line of a file, or of standard input after an optional prompt",
+        ),
+        BuildinSpec::new(
+            "input(prompt)",
+            input,
+            Range(0, 1),
+            "Next line of standard input after an optional prompt",
+        ),
+        BuildinSpec::new(
+            "write(file, text)",
+            write,
+            Exact(2),
+            "Writes text to a file",
+        ),
+        BuildinSpec::new("close(file)", close, Exact(1), "Closes a file"),
+        BuildinSpec::new(
+            "puts(...)",
+            puts,
+            AtLeast(0),
+            "Prints each argument on its own line",
+        ),
+    ];
 
-    buildins.insert("len".to_string(), Object::Buildin { function: len });
-    buildins.insert("first".to_string(), Object::Buildin { function: first });
-    buildins.insert("last".to_string(), Object::Buildin { function: last });
-    buildins.insert("rest".to_string(), Object::Buildin { function: rest });
-    buildins.insert("push".to_string(), Object::Buildin { function: push });
-    buildins.insert("puts".to_string(), Object::Buildin { function: puts });
+    #[cfg(feature = "data")]
+    specs.extend_from_slice(&[
+        BuildinSpec::new(
+            "base64_encode(value)",
+            base64_encode,
+            Exact(1),
+            "Base64 of a String or bytes",
+        ),
+        BuildinSpec::new(
+            "base64_decode(string)",
+            base64_decode,
+            Exact(1),
+            "String decoded from Base64",
+        ),
+        BuildinSpec::new(
+            "url_encode(value)",
+            url_encode,
+            Exact(1),
+            "Percent-encoded String or bytes",
+        ),
+        BuildinSpec::new(
+            "url_decode(string)",
+            url_decode,
+            Exact(1),
+            "String decoded from percent-encoding",
+        ),
+        BuildinSpec::new(
+            "json_parse(string)",
+            json_parse,
+            Exact(1),
+            "Value of a JSON text",
+        ),
+        BuildinSpec::new(
+            "json_stringify(value)",
+            json_stringify,
+            Exact(1),
+            "JSON text of a value",
+        ),
+    ]);
+
+    #[cfg(feature = "rational")]
+    specs.push(BuildinSpec::new(
+        "rational(numerator, denominator)",
+        rational,
+        Exact(2),
+        "Reduced Rational",
+    ));
+
+    #[cfg(feature = "date")]
+    specs.extend_from_slice(&[
+        BuildinSpec::new("date(year, month, day)", date, Exact(3), "Date"),
+        BuildinSpec::new(
+            "days(count)",
+            days,
+            Exact(1),
+            "Number of days to add to a Date",
+        ),
+        BuildinSpec::new(
+            "format_date(date, format)",
+            format_date,
+            Exact(2),
+            "Date formatted with strftime specifiers",
+        ),
+    ]);
+
+    #[cfg(feature = "std")]
+    specs.extend_from_slice(&[
+        BuildinSpec::new("sqrt(number)", sqrt, Exact(1), "Square root as Float"),
+        BuildinSpec::new(
+            "stopwatch()",
+            stopwatch,
+            Exact(0),
+            "Map whose elapsed_ms() returns milliseconds since the call",
+        ),
+        BuildinSpec::new(
+            "exec(program, arguments)",
+            exec,
+            Range(1, 2),
+            "Runs a command if enabled; returns stdout, stderr and status",
+        ),
+        BuildinSpec::new(
+            "read_source()",
+            read_source,
+            Exact(0),
+            "All of standard input",
+        ),
+    ]);
+
+    specs.extend_from_slice(&[
+        BuildinSpec::new("builtins()", builtins, Exact(0), "Names of all builtins"),
+        BuildinSpec::new(
+            "help(name)",
+            help,
+            Exact(1),
+            "Description and arity of a builtin",
+        ),
+    ]);
+
+    specs
+}
+
+/// 組み込み関数の表を作る。引数の数は本体を呼ぶ前に `arity` で確かめるので、本体では確かめない
+pub fn new() -> HashMap<String, Object> {
+    specs()
+        .into_iter()
+        .map(|spec| {
+            let checked = move |evaluator: &mut Evaluator, arguments: Vec<Object>| {
+                spec.arity.check(arguments.len())?;
+                (spec.function)(evaluator, arguments)
+            };
+            let function = Object::Buildin {
+                function: Buildin::new(spec.name(), checked),
+            };
+            (spec.name().to_string(), function)
+        })
+        .collect()
+}
 
-    buildins
+/// 登録されているすべての組み込み関数（ホストが登録したものも含む）の名前を名前の順に返す
+fn builtins(evaluator: &mut Evaluator, _: Vec<Object>) -> EvalResult {
+    let names = evaluator
+        .buildin_names()
+        .into_iter()
+        .map(Object::String)
+        .collect();
+    Ok(Object::Array(names))
 }
 
-fn len(arguments: Vec<Object>) -> EvalResult {
-    if arguments.len() != 1 {
-        let message = format!("wrong number of arguments. got={}, want=1", arguments.len());
-        return Err(message);
+/// 名前か組み込み関数そのものを受けとり、呼び出しの形・説明・引数の数を 1 行で返す
+/// （ホストが登録した組み込み関数は説明がないので、決まった 1 行を返す）
+fn help(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let name = match &arguments[0] {
+        Object::String(name) => name.as_str(),
+        Object::Buildin { function } => function.name(),
+        argument => {
+            let message = format!(
+                "argument to `help` must be String or Buildin Function, got {}",
+                argument.get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    match specs().into_iter().find(|spec| spec.name() == name) {
+        Some(spec) => {
+            let plural = if spec.arity == Arity::Exact(1) {
+                ""
+            } else {
+                "s"
+            };
+            let text = format!(
+                "{}: {} (takes {} argument{})",
+                spec.signature, spec.summary, spec.arity, plural
+            );
+            Ok(Object::String(text))
+        }
+        None if evaluator
+            .buildin_names()
+            .iter()
+            .any(|registered| registered == name) =>
+        {
+            let text = format!("{}(...): Registered by the host (arity not declared)", name);
+            Ok(Object::String(text))
+        }
+        None => {
+            let message = format!("no builtin named {}", name);
+            Err(message.into())
+        }
     }
+}
 
+fn len(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
     let result = match &arguments[0] {
         Object::String(value) => Object::Integer(value.len() as isize),
         Object::Array(values) => Object::Integer(values.len() as isize),
@@ -29,19 +630,329 @@ fn len(arguments: Vec<Object>) -> EvalResult {
                 "argument to `len` not supported, got {}",
                 arguments[0].get_type()
             );
-            return Err(message);
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    Ok(result)
+}
+
+/// `index` 文字目（0 から数える）の 1 文字の文字列を返す（範囲外なら null）
+fn char_at(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let result = match arguments.as_slice() {
+        [Object::String(value), Object::Integer(index)] => usize::try_from(*index)
+            .ok()
+            .and_then(|index| value.chars().nth(index))
+            .map(|ch| Object::String(ch.to_string()))
+            .unwrap_or(Object::Null),
+        [value, index] => {
+            let message = format!(
+                "arguments to `char_at` must be String and Integer, got {} and {}",
+                value.get_type(),
+                index.get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
         }
+        _ => unreachable!("arity is checked by the registry"),
     };
 
     Ok(result)
 }
 
-fn first(arguments: Vec<Object>) -> EvalResult {
-    if arguments.len() != 1 {
-        let message = format!("wrong number of arguments. got={}, want=1", arguments.len());
-        return Err(message);
+/// 1 文字の文字列のコードポイントを返す
+fn ord(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let mut chars = match &arguments[0] {
+        Object::String(value) => value.chars(),
+        argument => {
+            let message = format!(
+                "argument to `ord` must be String, got {}",
+                argument.get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => Ok(Object::Integer(ch as isize)),
+        _ => {
+            let message = format!(
+                "argument to `ord` must be a single character, got {:?}",
+                arguments[0].to_string()
+            );
+            Err(EvalError::InvalidArgument(message))
+        }
+    }
+}
+
+/// コードポイントの 1 文字の文字列を返す
+fn chr(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let code = match &arguments[0] {
+        Object::Integer(code) => *code,
+        argument => {
+            let message = format!(
+                "argument to `chr` must be Integer, got {}",
+                argument.get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    match u32::try_from(code).ok().and_then(char::from_u32) {
+        Some(ch) => Ok(Object::String(ch.to_string())),
+        None => {
+            let message = format!("invalid code point: {}", code);
+            Err(message.into())
+        }
+    }
+}
+
+/// 配列の要素を区切り文字でつなげた文字列を返す（文字列でない要素は表示形式にする）
+fn join(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (elements, separator) = match arguments.as_slice() {
+        [Object::Array(elements), Object::String(separator)] => (elements, separator),
+        _ => {
+            let types = arguments
+                .iter()
+                .map(Object::get_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "arguments to `join` must be Array and String, got {}",
+                types
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let joined = elements
+        .iter()
+        .map(Object::to_string)
+        .collect::<Vec<_>>()
+        .join(separator);
+
+    Ok(Object::String(joined))
+}
+
+/// 文字列を区切り文字で分けた配列を返す（区切り文字が空なら 1 文字ずつに分ける）
+fn split(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (value, separator) = match arguments.as_slice() {
+        [Object::String(value), Object::String(separator)] => (value, separator),
+        _ => {
+            let types = arguments
+                .iter()
+                .map(Object::get_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "arguments to `split` must be String and String, got {}",
+                types
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let parts = if separator.is_empty() {
+        value
+            .chars()
+            .map(|ch| Object::String(ch.to_string()))
+            .collect()
+    } else {
+        value
+            .split(separator.as_str())
+            .map(|part| Object::String(part.to_string()))
+            .collect()
+    };
+
+    Ok(Object::Array(parts))
+}
+
+/// テンプレートの `{}` を引数で順に、`{0}` を位置で指定した引数で置きかえる（`{{` と `}}` は波かっこ）。
+/// 使われない引数があるとエラーにする
+fn format(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (template, values) = match arguments.split_first() {
+        Some((Object::String(template), values)) => (template, values),
+        Some((template, _)) => {
+            let message = format!(
+                "argument to `format` must be String, got {}",
+                template.get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+        None => unreachable!("arity is checked by the registry"),
+    };
+
+    let mut formatted = String::new();
+    let mut used = vec![false; values.len()];
+    let mut next = 0;
+    let mut chars = template.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' => {
+                let mut placeholder = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some('{') if placeholder.is_empty() => break formatted.push('{'),
+                        Some('}') => {
+                            let index = if placeholder.is_empty() {
+                                next += 1;
+                                next - 1
+                            } else {
+                                placeholder.parse().map_err(|_| {
+                                    format!("invalid placeholder in `format`: {{{}}}", placeholder)
+                                })?
+                            };
+
+                            match values.get(index) {
+                                Some(value) => formatted.push_str(&value.to_string()),
+                                None => {
+                                    let message = format!(
+                                        "`format` has no argument {} (got {} arguments)",
+                                        index,
+                                        values.len()
+                                    );
+                                    return Err(message.into());
+                                }
+                            }
+
+                            used[index] = true;
+                            break;
+                        }
+                        Some(ch) => placeholder.push(ch),
+                        None => return Err("unclosed `{` in `format`".into()),
+                    }
+                }
+            }
+            '}' => match chars.next() {
+                Some('}') => formatted.push('}'),
+                _ => return Err("unmatched `}` in `format`".into()),
+            },
+            ch => formatted.push(ch),
+        }
+    }
+
+    if let Some(index) = used.iter().position(|used| !used) {
+        let message = format!("argument {} to `format` is not used", index);
+        return Err(message.into());
+    }
+
+    Ok(Object::String(formatted))
+}
+
+/// 文字列を 1 文字ずつの文字列の配列にする
+fn chars(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    match &arguments[0] {
+        Object::String(value) => {
+            let chars = value
+                .chars()
+                .map(|ch| Object::String(ch.to_string()))
+                .collect();
+            Ok(Object::Array(chars))
+        }
+        argument => {
+            let message = format!(
+                "argument to `chars` must be String, got {}",
+                argument.get_type()
+            );
+            Err(EvalError::InvalidArgument(message))
+        }
+    }
+}
+
+/// 文字列が接頭辞で始まるか、配列の最初の要素が値と等しいか
+fn starts_with(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    affix("starts_with", &arguments, true)
+}
+
+/// 文字列が接尾辞で終わるか、配列の最後の要素が値と等しいか
+fn ends_with(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    affix("ends_with", &arguments, false)
+}
+
+fn affix(name: &str, arguments: &[Object], start: bool) -> EvalResult {
+    let result = match arguments {
+        [Object::String(value), Object::String(affix)] if start => {
+            value.starts_with(affix.as_str())
+        }
+        [Object::String(value), Object::String(affix)] => value.ends_with(affix.as_str()),
+        [Object::Array(elements), element] if start => elements.first() == Some(element),
+        [Object::Array(elements), element] => elements.last() == Some(element),
+        _ => {
+            let types = arguments
+                .iter()
+                .map(Object::get_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "arguments to `{}` must be String and String, or Array and a value, got {}",
+                name, types
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    Ok(Object::Boolean(result))
+}
+
+/// 文字列の start 文字目から length 文字を返す（負の start は末尾から数え、文字列の外は切り詰める）
+fn substr(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (value, start, length) = match arguments.as_slice() {
+        [Object::String(value), Object::Integer(start), Object::Integer(length)] => {
+            (value, *start, *length)
+        }
+        _ => {
+            let types = arguments
+                .iter()
+                .map(Object::get_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "arguments to `substr` must be String, Integer and Integer, got {}",
+                types
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    if length < 0 {
+        let message = format!("length of `substr` must not be negative, got {}", length);
+        return Err(EvalError::InvalidArgument(message));
+    }
+
+    let len = value.chars().count() as isize;
+    let start = if start < 0 { start + len } else { start };
+    let substring = value
+        .chars()
+        .skip(start.clamp(0, len) as usize)
+        .take(length as usize)
+        .collect();
+
+    Ok(Object::String(substring))
+}
+
+/// 文字列の中の pattern をすべて replacement に置きかえる
+fn replace(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    match arguments.as_slice() {
+        [Object::String(value), Object::String(pattern), Object::String(replacement)] => {
+            Ok(Object::String(value.replace(pattern.as_str(), replacement)))
+        }
+        _ => {
+            let types = arguments
+                .iter()
+                .map(Object::get_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "arguments to `replace` must be String, String and String, got {}",
+                types
+            );
+            Err(EvalError::InvalidArgument(message))
+        }
     }
+}
 
+fn first(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
     let result = match &arguments[0] {
         Object::Array(elements) => elements.first().unwrap_or(&Object::Null).clone(),
         _ => {
@@ -49,19 +960,14 @@ fn first(arguments: Vec<Object>) -> EvalResult {
                 "argument to `first` must be Array, got {}",
                 arguments[0].get_type()
             );
-            return Err(message);
+            return Err(EvalError::InvalidArgument(message));
         }
     };
 
     Ok(result)
 }
 
-fn last(arguments: Vec<Object>) -> EvalResult {
-    if arguments.len() != 1 {
-        let message = format!("wrong number of arguments. got={}, want=1", arguments.len());
-        return Err(message);
-    }
-
+fn last(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
     let result = match &arguments[0] {
         Object::Array(elements) => elements.last().unwrap_or(&Object::Null).clone(),
         _ => {
@@ -69,19 +975,14 @@ fn last(arguments: Vec<Object>) -> EvalResult {
                 "argument to `last` must be Array, got {}",
                 arguments[0].get_type()
             );
-            return Err(message);
+            return Err(EvalError::InvalidArgument(message));
         }
     };
 
     Ok(result)
 }
 
-fn rest(arguments: Vec<Object>) -> EvalResult {
-    if arguments.len() != 1 {
-        let message = format!("wrong number of arguments. got={}, want=1", arguments.len());
-        return Err(message);
-    }
-
+fn rest(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
     let result = match &arguments[0] {
         Object::Array(elements) => match elements.split_first() {
             Some((_, tail)) => Object::Array(tail.to_vec()),
@@ -92,19 +993,14 @@ fn rest(arguments: Vec<Object>) -> EvalResult {
                 "argument to `rest` must be Array, got {}",
                 arguments[0].get_type()
             );
-            return Err(message);
+            return Err(EvalError::InvalidArgument(message));
         }
     };
 
     Ok(result)
 }
 
-fn push(arguments: Vec<Object>) -> EvalResult {
-    if arguments.len() != 2 {
-        let message = format!("wrong number of arguments. got={}, want=2", arguments.len());
-        return Err(message);
-    }
-
+fn push(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
     let result = match (&arguments[0], &arguments[1]) {
         (Object::Array(elements), object) => {
             let mut elements = elements.clone();
@@ -117,18 +1013,1823 @@ fn push(arguments: Vec<Object>) -> EvalResult {
                 "argument to `push` must be Array, got {}",
                 arguments[0].get_type()
             );
-            return Err(message);
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    Ok(result)
+}
+
+/// 配列か文字列の `start` から `end` の手前までを返す（`end` を省略すると末尾まで）。
+/// 負の添字は末尾から数え、範囲外の添字は端に収める
+fn slice(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (value, start, end) = match arguments.as_slice() {
+        [value, Object::Integer(start)] => (value, *start, None),
+        [value, Object::Integer(start), Object::Integer(end)] => (value, *start, Some(*end)),
+        _ => {
+            let types = arguments
+                .iter()
+                .map(Object::get_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "arguments to `slice` must be Array or String and Integers, got {}",
+                types
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let bounds = |len: usize| {
+        let clamp = |index: isize| {
+            let index = if index < 0 {
+                index + len as isize
+            } else {
+                index
+            };
+            index.clamp(0, len as isize) as usize
+        };
+        let start = clamp(start);
+        let end = end.map_or(len, clamp);
+        (start, end.max(start))
+    };
+
+    match value {
+        Object::Array(elements) => {
+            let (start, end) = bounds(elements.len());
+            Ok(Object::Array(elements[start..end].to_vec()))
+        }
+        Object::String(value) => {
+            let (start, end) = bounds(value.chars().count());
+            let sliced = value.chars().skip(start).take(end - start).collect();
+            Ok(Object::String(sliced))
+        }
+        _ => {
+            let message = format!(
+                "argument to `slice` must be Array or String, got {}",
+                value.get_type()
+            );
+            Err(EvalError::InvalidArgument(message))
+        }
+    }
+}
+
+/// 最後の要素を除いた新しい配列を返す（空の配列なら null）
+fn pop(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let result = match &arguments[0] {
+        Object::Array(elements) => match elements.split_last() {
+            Some((_, init)) => Object::Array(init.to_vec()),
+            _ => Object::Null,
+        },
+        _ => {
+            let message = format!(
+                "argument to `pop` must be Array, got {}",
+                arguments[0].get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
         }
     };
 
     Ok(result)
 }
 
-fn puts(arguments: Vec<Object>) -> EvalResult {
-    for argument in arguments.iter() {
-        println!("{}", argument);
+/// 添字の要素を除いた新しい配列を返す（添字が範囲外ならそのまま）
+fn remove_at(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (elements, index) = match arguments.as_slice() {
+        [Object::Array(elements), Object::Integer(index)] => (elements, *index),
+        _ => {
+            let types = arguments
+                .iter()
+                .map(Object::get_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "arguments to `remove_at` must be Array and Integer, got {}",
+                types
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let mut elements = elements.clone();
+
+    if let Ok(index) = usize::try_from(index) {
+        if index < elements.len() {
+            elements.remove(index);
+        }
     }
 
-    let result = Object::Null;
+    Ok(Object::Array(elements))
+}
+
+/// 2 つの配列の同じ位置の要素を組にした配列を返す（長さは短い方にそろえる）
+fn zip(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    match arguments.as_slice() {
+        [Object::Array(left), Object::Array(right)] => {
+            let pairs = left
+                .iter()
+                .zip(right.iter())
+                .map(|(left, right)| Object::Array(vec![left.clone(), right.clone()]))
+                .collect();
+            Ok(Object::Array(pairs))
+        }
+        _ => {
+            let types = arguments
+                .iter()
+                .map(Object::get_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!("arguments to `zip` must be Array and Array, got {}", types);
+            Err(EvalError::InvalidArgument(message))
+        }
+    }
+}
+
+/// 添字と要素を組にした配列を返す
+fn enumerate(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    match &arguments[0] {
+        Object::Array(elements) => {
+            let pairs = elements
+                .iter()
+                .enumerate()
+                .map(|(i, element)| {
+                    Object::Array(vec![Object::Integer(i as isize), element.clone()])
+                })
+                .collect();
+            Ok(Object::Array(pairs))
+        }
+        argument => {
+            let message = format!(
+                "argument to `enumerate` must be Array, got {}",
+                argument.get_type()
+            );
+            Err(EvalError::InvalidArgument(message))
+        }
+    }
+}
+
+/// 入れ子の配列を depth 段（省略すると 1 段）だけ平らにする
+fn flatten(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (elements, depth) = match arguments.as_slice() {
+        [Object::Array(elements)] => (elements, 1),
+        [Object::Array(elements), Object::Integer(depth)] if *depth >= 0 => {
+            (elements, *depth as usize)
+        }
+        [Object::Array(_), Object::Integer(depth)] => {
+            let message = format!("depth of `flatten` must not be negative, got {}", depth);
+            return Err(EvalError::InvalidArgument(message));
+        }
+        _ => {
+            let types = arguments
+                .iter()
+                .map(Object::get_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "arguments to `flatten` must be Array and Integer, got {}",
+                types
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let mut flattened = vec![];
+    flatten_into(&mut flattened, elements, depth);
+    Ok(Object::Array(flattened))
+}
+
+/// 重複する要素を除いた配列を返す（最初に現れた順に並ぶ。マップのキーと同じ規則で等しさを判定する）
+fn unique(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let elements = match &arguments[0] {
+        Object::Array(elements) => elements,
+        argument => {
+            let message = format!(
+                "argument to `unique` must be Array, got {}",
+                argument.get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let mut seen = BTreeSet::new();
+    let mut unique = vec![];
+
+    for element in elements.iter() {
+        if seen.insert(map_key(element)?) {
+            unique.push(element.clone());
+        }
+    }
+
+    Ok(Object::Array(unique))
+}
+
+/// 配列で値と等しい最初の要素の添字、または文字列で部分文字列が始まる最初の文字の位置を返す（なければ null）
+fn index_of(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let index = match arguments.as_slice() {
+        [Object::Array(elements), value] => elements.iter().position(|element| element == value),
+        [Object::String(value), Object::String(pattern)] => value
+            .find(pattern.as_str())
+            .map(|offset| value[..offset].chars().count()),
+        _ => {
+            let types = arguments
+                .iter()
+                .map(Object::get_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "arguments to `index_of` must be Array and a value, or String and String, got {}",
+                types
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let result = match index {
+        Some(index) => Object::Integer(index as isize),
+        None => Object::Null,
+    };
     Ok(result)
 }
+
+fn flatten_into(flattened: &mut Vec<Object>, elements: &[Object], depth: usize) {
+    for element in elements.iter() {
+        match element {
+            Object::Array(nested) if depth > 0 => flatten_into(flattened, nested, depth - 1),
+            element => flattened.push(element.clone()),
+        }
+    }
+}
+
+/// キーと値を加えた新しいマップを返す（同じキーがあれば値を置きかえる）
+fn insert(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let mut pairs = map_argument("insert", &arguments[0])?.clone();
+    let key = arguments[1].clone();
+    let value = arguments[2].clone();
+    pairs.insert(map_key(&key)?, MapPair::new(key, value));
+
+    Ok(Object::Map(pairs))
+}
+
+/// キーを取り除いた新しいマップを返す（キーがなければそのまま）
+fn remove(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let mut pairs = map_argument("remove", &arguments[0])?.clone();
+    pairs.remove(&map_key(&arguments[1])?);
+
+    Ok(Object::Map(pairs))
+}
+
+fn map_argument<'a>(
+    name: &str,
+    argument: &'a Object,
+) -> Result<&'a BTreeMap<MapKey, MapPair>, EvalError> {
+    match argument {
+        Object::Map(pairs) => Ok(pairs),
+        _ => {
+            let message = format!(
+                "argument to `{}` must be Map, got {}",
+                name,
+                argument.get_type()
+            );
+            Err(EvalError::InvalidArgument(message))
+        }
+    }
+}
+
+fn map_key(key: &Object) -> Result<MapKey, EvalError> {
+    match MapKey::from(key) {
+        MapKey::Unusable => {
+            let error = EvalError::UnusableMapKey(key.get_type());
+            Err(error)
+        }
+        map_key => Ok(map_key),
+    }
+}
+
+fn binary_search(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let elements = match &arguments[0] {
+        Object::Array(elements) => elements,
+        _ => {
+            let message = format!(
+                "argument to `binary_search` must be Array, got {}",
+                arguments[0].get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let mut low = 0;
+    let mut high = elements.len();
+
+    while low < high {
+        let middle = low + (high - low) / 2;
+
+        match collate(&elements[middle], &arguments[1], Collation::CodePoint)? {
+            Ordering::Less => low = middle + 1,
+            Ordering::Greater => high = middle,
+            Ordering::Equal => return Ok(Object::Integer(middle as isize)),
+        }
+    }
+
+    let result = Object::Null;
+    Ok(result)
+}
+
+fn min_by(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    extreme_by("min_by", Ordering::Less, evaluator, arguments)
+}
+
+fn max_by(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    extreme_by("max_by", Ordering::Greater, evaluator, arguments)
+}
+
+/// 配列と関数を受けとる高階の組み込み関数の引数を確かめる
+fn array_and_function<'a>(
+    name: &str,
+    arguments: &'a [Object],
+) -> Result<(&'a [Object], &'a Object), EvalError> {
+    match arguments {
+        [Object::Array(elements), function] => Ok((elements, function)),
+        [argument, _] => {
+            let message = format!(
+                "argument to `{}` must be Array, got {}",
+                name,
+                argument.get_type()
+            );
+            Err(EvalError::InvalidArgument(message))
+        }
+        _ => unreachable!("arity is checked by the registry"),
+    }
+}
+
+/// 各要素に関数を適用した結果の配列を返す
+fn map(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (elements, function) = array_and_function("map", &arguments)?;
+
+    let result = elements
+        .iter()
+        .map(|element| evaluator.apply_function(function.clone(), vec![element.clone()]))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Object::Array(result))
+}
+
+/// 関数の結果が真になる要素だけの配列を返す
+fn filter(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (elements, predicate) = array_and_function("filter", &arguments)?;
+    let mut result = vec![];
+
+    for element in elements.iter() {
+        if is_truthy(evaluator.apply_function(predicate.clone(), vec![element.clone()])?) {
+            result.push(element.clone());
+        }
+    }
+
+    Ok(Object::Array(result))
+}
+
+/// 初期値から始めて、累積値と各要素に関数を順に適用した結果を返す
+fn reduce(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (elements, initial, function) = match arguments.as_slice() {
+        [Object::Array(elements), initial, function] => (elements, initial, function),
+        [argument, _, _] => {
+            let message = format!(
+                "argument to `reduce` must be Array, got {}",
+                argument.get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+        _ => unreachable!("arity is checked by the registry"),
+    };
+
+    let mut accumulator = initial.clone();
+
+    for element in elements.iter() {
+        accumulator =
+            evaluator.apply_function(function.clone(), vec![accumulator, element.clone()])?;
+    }
+
+    Ok(accumulator)
+}
+
+/// キー関数の結果が `ordering` の向きに最も大きい要素を返す
+fn extreme_by(
+    name: &str,
+    ordering: Ordering,
+    evaluator: &mut Evaluator,
+    arguments: Vec<Object>,
+) -> EvalResult {
+    let elements = match &arguments[0] {
+        Object::Array(elements) => elements,
+        _ => {
+            let message = format!(
+                "argument to `{}` must be Array, got {}",
+                name,
+                arguments[0].get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let mut result: Option<(Object, Object)> = None;
+
+    for element in elements.iter() {
+        let key = evaluator.apply_function(arguments[1].clone(), vec![element.clone()])?;
+
+        result = match result {
+            Some((current, current_key))
+                if collate(&key, &current_key, Collation::CodePoint)? != ordering =>
+            {
+                Some((current, current_key))
+            }
+            _ => Some((element.clone(), key)),
+        };
+    }
+
+    let result = result.map(|(element, _)| element).unwrap_or(Object::Null);
+    Ok(result)
+}
+
+fn chunk(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let result = match (&arguments[0], &arguments[1]) {
+        (Object::Array(elements), Object::Integer(size)) if *size > 0 => {
+            let chunks = elements
+                .chunks(*size as usize)
+                .map(|chunk| Object::Array(chunk.to_vec()))
+                .collect();
+            Object::Array(chunks)
+        }
+        (Object::Array(_), Object::Integer(size)) => {
+            let message = format!("chunk size must be positive, got {}", size);
+            return Err(EvalError::InvalidArgument(message));
+        }
+        _ => {
+            let message = format!(
+                "arguments to `chunk` must be Array and Integer, got {} and {}",
+                arguments[0].get_type(),
+                arguments[1].get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    Ok(result)
+}
+
+/// 文字列の照合順序
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Collation {
+    /// UTF-8 のバイト列として比べる
+    Bytes,
+    /// Unicode のコードポイントの列として比べる（UTF-8 ではバイト列の順序と一致する）
+    CodePoint,
+    /// 小文字にそろえたコードポイントの列として比べる（大文字と小文字だけが違えば等しい）
+    CaseInsensitive,
+}
+
+impl Collation {
+    fn from_object(name: &str, object: &Object) -> Result<Self, EvalError> {
+        match object {
+            Object::String(mode) if mode == "bytes" => Ok(Self::Bytes),
+            Object::String(mode) if mode == "codepoint" => Ok(Self::CodePoint),
+            Object::String(mode) if mode == "case_insensitive" => Ok(Self::CaseInsensitive),
+            _ => {
+                let message = format!(
+                    "collation for `{}` must be \"bytes\", \"codepoint\" or \"case_insensitive\", got {}",
+                    name, object
+                );
+                Err(EvalError::InvalidArgument(message))
+            }
+        }
+    }
+
+    fn compare(self, left: &str, right: &str) -> Ordering {
+        match self {
+            Self::Bytes => left.as_bytes().cmp(right.as_bytes()),
+            Self::CodePoint => left.chars().cmp(right.chars()),
+            Self::CaseInsensitive => left
+                .chars()
+                .flat_map(char::to_lowercase)
+                .cmp(right.chars().flat_map(char::to_lowercase)),
+        }
+    }
+}
+
+/// 整数どうし、または文字列どうしを照合順序にしたがって比べる
+fn collate(left: &Object, right: &Object, collation: Collation) -> Result<Ordering, EvalError> {
+    match (left, right) {
+        (Object::Integer(left), Object::Integer(right)) => Ok(left.cmp(right)),
+        (Object::String(left), Object::String(right)) => Ok(collation.compare(left, right)),
+        _ => {
+            let message = format!(
+                "cannot compare {} with {}",
+                left.get_type(),
+                right.get_type()
+            );
+            Err(message.into())
+        }
+    }
+}
+
+/// 配列の配列を、どの行も同じ長さの行列として受けとる
+fn matrix(name: &str, argument: &Object) -> Result<Vec<Vec<Object>>, EvalError> {
+    let rows = match argument {
+        Object::Array(rows) => rows,
+        _ => {
+            let message = format!(
+                "argument to `{}` must be a matrix, got {}",
+                name,
+                argument.get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let mut matrix: Vec<Vec<Object>> = Vec::with_capacity(rows.len());
+
+    for (i, row) in rows.iter().enumerate() {
+        match row {
+            Object::Array(elements) if i == 0 || elements.len() == matrix[0].len() => {
+                matrix.push(elements.clone())
+            }
+            Object::Array(elements) => {
+                let message = format!(
+                    "ragged matrix in `{}`: row {} has {} columns, expected {}",
+                    name,
+                    i,
+                    elements.len(),
+                    matrix[0].len()
+                );
+                return Err(message.into());
+            }
+            _ => {
+                let message = format!(
+                    "argument to `{}` must be a matrix, got a row of {}",
+                    name,
+                    row.get_type()
+                );
+                return Err(EvalError::InvalidArgument(message));
+            }
+        }
+    }
+
+    Ok(matrix)
+}
+
+/// 行数 x 列数
+fn shape(matrix: &[Vec<Object>]) -> String {
+    format!("{}x{}", matrix.len(), matrix.first().map_or(0, Vec::len))
+}
+
+fn transposed(matrix: &[Vec<Object>]) -> Vec<Vec<Object>> {
+    let columns = matrix.first().map_or(0, Vec::len);
+
+    (0..columns)
+        .map(|j| matrix.iter().map(|row| row[j].clone()).collect())
+        .collect()
+}
+
+fn from_rows(rows: Vec<Vec<Object>>) -> Object {
+    Object::Array(rows.into_iter().map(Object::Array).collect())
+}
+
+/// `r` 行 `c` 列の 0 の行列
+fn zeros(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (rows, columns) = match arguments.as_slice() {
+        [Object::Integer(rows), Object::Integer(columns)] if *rows >= 0 && *columns >= 0 => {
+            (*rows as usize, *columns as usize)
+        }
+        _ => {
+            let arguments = arguments
+                .iter()
+                .map(Object::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "arguments to `zeros` must be non-negative Integers, got {}",
+                arguments
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    // 行数か列数の片方が 0 でも、もう片方の長さのぶんは確保するので、それぞれも上限を超えさせない
+    match rows.checked_mul(columns) {
+        Some(length) if length <= MAX_LENGTH && rows <= MAX_LENGTH && columns <= MAX_LENGTH => (),
+        _ => return Err(EvalError::LengthLimit(MAX_LENGTH)),
+    }
+
+    let result = from_rows(vec![vec![Object::Integer(0); columns]; rows]);
+    Ok(result)
+}
+
+fn transpose(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let matrix = matrix("transpose", &arguments[0])?;
+
+    let result = from_rows(transposed(&matrix));
+    Ok(result)
+}
+
+/// 行列の積（要素の演算は `+` と `*` と同じ規則で行う）
+fn matmul(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let left = matrix("matmul", &arguments[0])?;
+    let right = matrix("matmul", &arguments[1])?;
+    let inner = left.first().map_or(0, Vec::len);
+
+    if inner != right.len() {
+        let message = format!(
+            "matrix shapes do not match in `matmul`: {} and {}",
+            shape(&left),
+            shape(&right)
+        );
+        return Err(message.into());
+    }
+
+    let columns = transposed(&right);
+    let mut product = Vec::with_capacity(left.len());
+
+    for row in left.iter() {
+        let mut cells = Vec::with_capacity(columns.len());
+
+        for column in columns.iter() {
+            let mut sum = Object::Integer(0);
+
+            for (a, b) in row.iter().zip(column.iter()) {
+                let term =
+                    evaluator.eval_infix_expression(a.clone(), &Token::Asterisk, b.clone())?;
+                sum = evaluator.eval_infix_expression(sum, &Token::Plus, term)?;
+            }
+
+            cells.push(sum);
+        }
+
+        product.push(cells);
+    }
+
+    let result = from_rows(product);
+    Ok(result)
+}
+
+/// 2 つの値を比べて -1、0、1 を返す（省略時の照合順序は "codepoint"）
+fn compare(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let collation = match arguments.len() {
+        2 => Collation::CodePoint,
+        3 => Collation::from_object("compare", &arguments[2])?,
+        _ => unreachable!("arity is checked by the registry"),
+    };
+
+    let result = match collate(&arguments[0], &arguments[1], collation)? {
+        Ordering::Less => Object::Integer(-1),
+        Ordering::Equal => Object::Integer(0),
+        Ordering::Greater => Object::Integer(1),
+    };
+
+    Ok(result)
+}
+
+/// 整数か文字列の配列を安定ソートした配列を返す（省略時の照合順序は "codepoint"）
+fn sort(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (elements, collation) = match arguments.as_slice() {
+        [Object::Array(elements)] => (elements, Collation::CodePoint),
+        [Object::Array(elements), collation] => {
+            (elements, Collation::from_object("sort", collation)?)
+        }
+        [argument] | [argument, _] => {
+            let message = format!(
+                "argument to `sort` must be Array, got {}",
+                argument.get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+        _ => unreachable!("arity is checked by the registry"),
+    };
+
+    if let Some(first) = elements.first() {
+        for element in elements.iter() {
+            collate(first, element, collation)?;
+        }
+    }
+
+    let mut sorted = elements.clone();
+    sorted.sort_by(|left, right| collate(left, right, collation).unwrap_or(Ordering::Equal));
+
+    let result = Object::Array(sorted);
+    Ok(result)
+}
+
+/// FNV-1a によるハッシュ関数
+struct FnvHasher(u64);
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes.iter() {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// 値の同一性を表す整数を返す。`is` で同じ値は同じ整数になり、関数・組み込み関数・ジェネレーターは
+/// 指すものから、ほかの値は型と値から求める。同じでない値もハッシュが衝突すれば同じ整数になりうる。
+/// 関数などの整数はアドレスから求めるので、実行ごとに変わる
+fn id(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let mut hasher = FnvHasher(0xcbf29ce484222325);
+    identity_hash(&arguments[0], &mut hasher);
+
+    let result = Object::Integer(hasher.finish() as isize);
+    Ok(result)
+}
+
+/// `same` が同じとみなす値が同じハッシュになるように、`same` と同じ規則でハッシュする
+fn identity_hash(value: &Object, hasher: &mut FnvHasher) {
+    match value {
+        Object::Function { env, .. } | Object::Macro { env, .. } => {
+            value.get_type().hash(hasher);
+            env.hash(hasher);
+        }
+        Object::Bound {
+            function,
+            arguments,
+        } => {
+            value.get_type().hash(hasher);
+            identity_hash(function, hasher);
+            arguments.len().hash(hasher);
+            arguments
+                .iter()
+                .for_each(|argument| identity_hash(argument, hasher));
+        }
+        Object::Array(elements) => {
+            value.get_type().hash(hasher);
+            elements.len().hash(hasher);
+            elements
+                .iter()
+                .for_each(|element| identity_hash(element, hasher));
+        }
+        // 組み込み関数とジェネレーターのハッシュは、すでに指すものから求める
+        value => value.hash(hasher),
+    }
+}
+
+/// 2 つの値が同じものか。関数・組み込み関数・ジェネレーターは同じものを指すときだけ同じで、
+/// ほかの値は型と値が等しければ同じ（`1 == 1.0` は true だが、`is(1, 1.0)` は false）
+fn is(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let result = Object::Boolean(same(&arguments[0], &arguments[1]));
+    Ok(result)
+}
+
+/// 組み込み関数とジェネレーターは `==` がすでに参照先で比べる
+fn same(left: &Object, right: &Object) -> bool {
+    match (left, right) {
+        // 関数リテラルを評価するたびに環境をつくるので、環境が同じなら同じ関数
+        (Object::Function { env: left, .. }, Object::Function { env: right, .. }) => left == right,
+        (
+            Object::Bound {
+                function: left,
+                arguments: left_arguments,
+            },
+            Object::Bound {
+                function: right,
+                arguments: right_arguments,
+            },
+        ) => {
+            same(left, right)
+                && left_arguments.len() == right_arguments.len()
+                && left_arguments
+                    .iter()
+                    .zip(right_arguments.iter())
+                    .all(|(left, right)| same(left, right))
+        }
+        (Object::Array(left), Object::Array(right)) => {
+            left.len() == right.len() && left.iter().zip(right.iter()).all(|(l, r)| same(l, r))
+        }
+        (left, right) => left == right,
+    }
+}
+
+fn throw(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let value = arguments.into_iter().next().unwrap();
+    evaluator.throw(value)
+}
+
+/// 条件が偽なら評価エラーにする（2 つめの引数はエラーメッセージに添える）
+fn assert(_: &mut Evaluator, mut arguments: Vec<Object>) -> EvalResult {
+    let note = arguments.get(1).map(Object::to_string);
+
+    if is_truthy(arguments.remove(0)) {
+        return Ok(Object::Null);
+    }
+
+    let message = match note {
+        Some(note) => format!("assertion failed: {}", note),
+        None => "assertion failed".to_string(),
+    };
+    Err(message.into())
+}
+
+/// 2 つの値が等しくなければ、両方の値を示す評価エラーにする（3 つめの引数はエラーメッセージに添える）
+fn assert_eq(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (actual, expected, note) = match arguments.as_slice() {
+        [actual, expected] => (actual, expected, None),
+        [actual, expected, note] => (actual, expected, Some(note)),
+        _ => unreachable!("arity is checked by the registry"),
+    };
+
+    if actual == expected {
+        return Ok(Object::Null);
+    }
+
+    // 文字列は引用符で囲み、1 と "1" を見分けられるようにする
+    let render = |value: &Object| match value {
+        Object::String(value) => format!("{:?}", value),
+        value => value.to_string(),
+    };
+    let values = format!("expected {}, got {}", render(expected), render(actual));
+
+    let message = match note {
+        Some(note) => format!("assertion failed: {}: {}", note, values),
+        None => format!("assertion failed: {}", values),
+    };
+    Err(message.into())
+}
+
+fn error(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let result = match &arguments[0] {
+        Object::String(message) => Object::Error(message.clone()),
+        _ => {
+            let message = format!(
+                "argument to `error` must be String, got {}",
+                arguments[0].get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    Ok(result)
+}
+
+fn is_error(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let result = Object::Boolean(matches!(arguments[0], Object::Error(_)));
+    Ok(result)
+}
+
+/// 値の型の名前を返す
+fn r#type(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    Ok(Object::String(arguments[0].get_type()))
+}
+
+/// 整数に変換する（文字列は前後の空白を除いて 10 進数として読み、小数は 0 に向けて切り捨てる）
+fn int(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let converted = match &arguments[0] {
+        Object::Integer(value) => Some(*value),
+        Object::Boolean(value) => Some(*value as isize),
+        Object::String(value) => value.trim().parse().ok(),
+        Object::Float(value) => {
+            let value = value.0;
+            let in_range = value >= isize::MIN as f64 && value < isize::MAX as f64;
+            Some(value as isize).filter(|_| in_range)
+        }
+        #[cfg(feature = "rational")]
+        Object::Rational(value) => Some(value.numerator() / value.denominator()),
+        argument => {
+            let message = format!(
+                "argument to `int` not supported, got {}",
+                argument.get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    match converted {
+        Some(value) => Ok(Object::Integer(value)),
+        None => {
+            let message = match &arguments[0] {
+                Object::String(value) => format!("cannot convert {:?} to Integer", value),
+                argument => format!("cannot convert {} to Integer", argument),
+            };
+            Err(message.into())
+        }
+    }
+}
+
+/// 表示形式の文字列に変換する
+fn str(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    Ok(Object::String(arguments[0].to_string()))
+}
+
+/// `if` の条件と同じ規則で真偽値に変換する（false と null だけが偽）
+fn bool(_: &mut Evaluator, mut arguments: Vec<Object>) -> EvalResult {
+    Ok(Object::Boolean(is_truthy(arguments.remove(0))))
+}
+
+/// ソースを束縛を引き継がない新しい評価器で評価する。
+/// エラーは `{"kind": ..., "error": ...}` のマップで返す。
+/// 出力は捨て、入力はいつも終わりで、監査の関数と割りこみも引き継がない。
+/// `{"capture_output": true}` なら `{"result": ..., "output": ...}` のマップで出力も返す。
+/// 文の数と呼び出しの深さは呼び出し側の残りを超えられず、呼び出し側の上限に達したときと
+/// 中断されたときは呼び出し側のエラーにする
+fn sandbox(outer: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (source, options) = match arguments.as_slice() {
+        [Object::String(source)] => (source, None),
+        [Object::String(source), Object::Map(options)] => (source, Some(options)),
+        _ => {
+            let types = arguments
+                .iter()
+                .map(Object::get_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "arguments to `sandbox` must be String and Map, got {}",
+                types
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let statements_left = outer.statements_left();
+    let calls_left = outer.calls_left();
+
+    let output = Rc::new(RefCell::new(String::new()));
+    let sink: Rc<RefCell<dyn fmt::Write>> = output.clone();
+    let mut capture_output = false;
+
+    let mut evaluator = Evaluator::new();
+    evaluator.set_pure(outer.is_pure());
+    evaluator.set_deterministic(outer.is_deterministic());
+    evaluator.set_output(Some(sink));
+    evaluator.set_input(Some(Rc::new(RefCell::new(|| None))));
+    evaluator.set_interrupt(outer.interrupt());
+    evaluator.set_statement_limit(statements_left);
+    evaluator.set_recursion_limit(calls_left);
+
+    for pair in options.iter().flat_map(|options| options.values()) {
+        match (&pair.key, &pair.value) {
+            (Object::String(key), Object::Integer(limit)) if key == "max_steps" && *limit >= 0 => {
+                let limit =
+                    statements_left.map_or(*limit as usize, |left| left.min(*limit as usize));
+                evaluator.set_statement_limit(Some(limit));
+            }
+            (Object::String(key), Object::Integer(limit)) if key == "max_depth" && *limit >= 0 => {
+                evaluator.set_recursion_limit(calls_left.min(*limit as usize));
+            }
+            (Object::String(key), Object::Boolean(capture)) if key == "capture_output" => {
+                capture_output = *capture;
+            }
+            (Object::String(key), Object::Map(files)) if key == "files" => {
+                let mut vfs = MemoryVfs::new();
+
+                for file in files.values() {
+                    match (&file.key, &file.value) {
+                        (Object::String(path), Object::String(contents)) => {
+                            vfs.insert(path, contents)
+                        }
+                        _ => {
+                            let message = format!("invalid sandbox file: {}", file);
+                            return Err(message.into());
+                        }
+                    }
+                }
+
+                evaluator.set_vfs(Some(Rc::new(vfs)));
+            }
+            _ => {
+                let message = format!("invalid sandbox option: {}", pair);
+                return Err(message.into());
+            }
+        }
+    }
+
+    let mut lexer = Lexer::with_interner(source, evaluator.interner());
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+
+    if parser.exists_errors() {
+        return Ok(sandbox_error("parse", parser.get_errors().join(", ")));
+    }
+
+    let response = evaluator.eval(program);
+
+    if let (Some(limit), Some(left)) = (evaluator.statement_limit(), evaluator.statements_left()) {
+        if statements_left.is_some() {
+            outer.charge_statements(limit - left);
+        }
+    }
+
+    let result = match response {
+        Response::Reply(result) => result,
+        Response::NoReply => Object::Null,
+        Response::Error(error) => match error.kind() {
+            // 入れ子の評価器が受けとった中断の要求を、呼び出し側でもう一度受けとる
+            EvalError::Interrupted => {
+                if let Some(interrupt) = outer.interrupt() {
+                    interrupt.store(true, AtomicOrdering::SeqCst);
+                }
+                outer.check_interrupt()?;
+                return Err(EvalError::Interrupted);
+            }
+            EvalError::StatementLimit(_) if outer.statements_left() == Some(0) => {
+                let limit = outer.statement_limit().unwrap_or(0);
+                return Err(EvalError::StatementLimit(limit));
+            }
+            EvalError::RecursionLimit(limit) if *limit == calls_left => {
+                return Err(EvalError::RecursionLimit(outer.recursion_limit()));
+            }
+            EvalError::StatementLimit(_) | EvalError::RecursionLimit(_) => {
+                sandbox_error("limit", error.to_string())
+            }
+            _ => sandbox_error("runtime", error.to_string()),
+        },
+    };
+
+    if !capture_output {
+        return Ok(result);
+    }
+
+    let mut pairs = BTreeMap::new();
+    let output = Object::String(output.borrow().clone());
+
+    for (key, value) in [("result", result), ("output", output)] {
+        let key = Object::String(key.to_string());
+        pairs.insert(MapKey::from(&key), MapPair::new(key, value));
+    }
+
+    Ok(Object::Map(pairs))
+}
+
+/// ジェネレーターの次の値を返す（終わっていれば null）
+fn next(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    match &arguments[0] {
+        Object::Generator(generator) => Ok(evaluator.resume(generator)?.unwrap_or(Object::Null)),
+        argument => {
+            let message = format!(
+                "argument to `next` must be Generator, got {}",
+                argument.get_type()
+            );
+            Err(EvalError::InvalidArgument(message))
+        }
+    }
+}
+
+/// ジェネレーターから最大 n 個の値を取り出して配列で返す
+fn take(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    match (&arguments[0], &arguments[1]) {
+        (Object::Generator(generator), Object::Integer(count)) => {
+            let mut values = vec![];
+
+            while (values.len() as isize) < *count {
+                match evaluator.resume(generator)? {
+                    Some(value) => values.push(value),
+                    None => break,
+                }
+            }
+
+            Ok(Object::Array(values))
+        }
+        (generator, count) => {
+            let message = format!(
+                "arguments to `take` must be Generator and Integer, got {} and {}",
+                generator.get_type(),
+                count.get_type()
+            );
+            Err(EvalError::InvalidArgument(message))
+        }
+    }
+}
+
+fn read_file(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    evaluator.audited(
+        "read_file",
+        arguments,
+        |evaluator, arguments| match &arguments[0] {
+            Object::String(path) => evaluator.journaled("read_file", |evaluator| {
+                let contents = evaluator.read_file(path)?;
+                Ok(Object::String(contents))
+            }),
+            argument => {
+                let message = format!(
+                    "argument to `read_file` must be String, got {}",
+                    argument.get_type()
+                );
+                Err(EvalError::InvalidArgument(message))
+            }
+        },
+    )
+}
+
+/// モジュールを読みこみ、公開された束縛をマップで返す（`import "path" as name` の中身）
+fn import(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    evaluator.audited(
+        "import",
+        arguments,
+        |evaluator, arguments| match &arguments[0] {
+            Object::String(path) => evaluator.import(path),
+            argument => {
+                let message = format!(
+                    "argument to `import` must be String, got {}",
+                    argument.get_type()
+                );
+                Err(EvalError::InvalidArgument(message))
+            }
+        },
+    )
+}
+
+/// 中断（Ctrl-C）されたときに、評価をやめる前に呼ぶ関数を登録する
+fn on_interrupt(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    match &arguments[0] {
+        handler @ (Object::Function { .. } | Object::Buildin { .. } | Object::Bound { .. }) => {
+            evaluator.set_interrupt_handler(handler.clone());
+        }
+        argument => {
+            let message = format!(
+                "argument to `on_interrupt` must be Function, got {}",
+                argument.get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    }
+
+    Ok(Object::Null)
+}
+
+/// `open` で開いたファイルの表（評価器を破棄するときに残りをすべて閉じる）
+#[derive(Default)]
+struct OpenFiles {
+    files: BTreeMap<usize, Box<dyn VfsFile>>,
+    next: usize,
+}
+
+impl HostResource for OpenFiles {
+    fn release(&mut self) {
+        for (_, mut file) in core::mem::take(&mut self.files) {
+            let _ = file.close();
+        }
+    }
+}
+
+fn open(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    evaluator.audited("open", arguments, |evaluator, arguments| {
+        let (path, mode) = match arguments.as_slice() {
+            [Object::String(path), Object::String(mode)] => (path, mode),
+            [path, mode] => {
+                let message = format!(
+                    "arguments to `open` must be String and String, got {} and {}",
+                    path.get_type(),
+                    mode.get_type()
+                );
+                return Err(EvalError::InvalidArgument(message));
+            }
+            _ => unreachable!("arity is checked by the registry"),
+        };
+
+        let mode = match mode.as_str() {
+            "r" => OpenMode::Read,
+            "w" => OpenMode::Write,
+            "a" => OpenMode::Append,
+            _ => {
+                let message = format!(
+                    "mode for `open` must be \"r\", \"w\" or \"a\", got {}",
+                    mode
+                );
+                return Err(EvalError::InvalidArgument(message));
+            }
+        };
+
+        let file = evaluator.open_file(path, mode)?;
+
+        let files = evaluator.host_mut().get_or_insert_with(OpenFiles::default);
+        let id = files.next;
+        files.next += 1;
+        files.files.insert(id, file);
+
+        let result = Object::File(id);
+        Ok(result)
+    })
+}
+
+/// ファイルのハンドルを受けとる組み込み関数の引数を確かめ、開いているファイルを返す
+fn open_file<'a>(
+    name: &str,
+    evaluator: &'a mut Evaluator,
+    arguments: &[Object],
+) -> Result<(usize, &'a mut Box<dyn VfsFile>), EvalError> {
+    let id = match &arguments[0] {
+        Object::File(id) => *id,
+        argument => {
+            let message = format!(
+                "argument to `{}` must be File, got {}",
+                name,
+                argument.get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    match evaluator
+        .host_mut()
+        .get_mut::<OpenFiles>()
+        .and_then(|files| files.files.get_mut(&id))
+    {
+        Some(file) => Ok((id, file)),
+        None => {
+            let message = format!("file #{} is not open", id);
+            Err(message.into())
+        }
+    }
+}
+
+/// ファイルか、引数がなければ標準入力から 1 行を読む（文字列を渡すとプロンプトとして表示する。終わりなら null）
+fn read_line(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    evaluator.audited("read_line", arguments, |evaluator, arguments| {
+        match arguments.as_slice() {
+            [] => return evaluator.read_input("read_line", None),
+            [Object::String(prompt)] => return evaluator.read_input("read_line", Some(prompt)),
+            _ => (),
+        }
+
+        let (_, file) = open_file("read_line", evaluator, &arguments)?;
+
+        let result = match file.read_line()? {
+            Some(line) => Object::String(line),
+            None => Object::Null,
+        };
+
+        Ok(result)
+    })
+}
+
+/// 標準入力から 1 行を読む（文字列を渡すとプロンプトとして表示する。入力の終わりなら null）
+fn input(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    evaluator.audited("input", arguments, |evaluator, arguments| {
+        match arguments.as_slice() {
+            [] => evaluator.read_input("input", None),
+            [Object::String(prompt)] => evaluator.read_input("input", Some(prompt)),
+            [argument] => {
+                let message = format!(
+                    "argument to `input` must be String, got {}",
+                    argument.get_type()
+                );
+                Err(EvalError::InvalidArgument(message))
+            }
+            _ => unreachable!("arity is checked by the registry"),
+        }
+    })
+}
+
+fn write(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    evaluator.audited("write", arguments, |evaluator, arguments| {
+        evaluator.check_pure("write")?;
+        let (_, file) = open_file("write", evaluator, &arguments)?;
+
+        match &arguments[1] {
+            Object::String(text) => file.write(text)?,
+            argument => {
+                let message = format!(
+                    "argument to `write` must be String, got {}",
+                    argument.get_type()
+                );
+                return Err(EvalError::InvalidArgument(message));
+            }
+        }
+
+        Ok(Object::Null)
+    })
+}
+
+fn close(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    evaluator.audited("close", arguments, |evaluator, arguments| {
+        let (id, file) = open_file("close", evaluator, &arguments)?;
+        let result = file.close();
+
+        if let Some(files) = evaluator.host_mut().get_mut::<OpenFiles>() {
+            files.files.remove(&id);
+        }
+
+        result?;
+        Ok(Object::Null)
+    })
+}
+
+fn sandbox_error(kind: &str, error: String) -> Object {
+    let mut pairs = BTreeMap::new();
+
+    for (key, value) in [("kind", kind.to_string()), ("error", error)] {
+        let key = Object::String(key.to_string());
+        pairs.insert(MapKey::from(&key), MapPair::new(key, Object::String(value)));
+    }
+
+    Object::Map(pairs)
+}
+
+/// 分子と分母から約分した有理数をつくる
+#[cfg(feature = "rational")]
+fn rational(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (numerator, denominator) = match arguments.as_slice() {
+        [Object::Integer(numerator), Object::Integer(denominator)] => (*numerator, *denominator),
+        _ => {
+            let types = arguments
+                .iter()
+                .map(Object::get_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "arguments to `rational` must be Integer and Integer, got {}",
+                types
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    match Rational::new(numerator, denominator) {
+        Ok(value) => Ok(Object::Rational(value)),
+        Err(ArithmeticError::DivisionByZero) => Err(EvalError::DivisionByZero),
+        Err(ArithmeticError::Overflow) => {
+            let operation = format!("{} / {}", numerator, denominator);
+            Err(EvalError::IntegerOverflow(operation))
+        }
+    }
+}
+
+/// 年・月・日から日付をつくる
+#[cfg(feature = "date")]
+fn date(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (year, month, day) = match arguments.as_slice() {
+        [Object::Integer(year), Object::Integer(month), Object::Integer(day)] => {
+            (*year, *month, *day)
+        }
+        _ => {
+            let types = arguments
+                .iter()
+                .map(Object::get_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "arguments to `date` must be Integer, Integer and Integer, got {}",
+                types
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let date = i32::try_from(year).ok().and_then(|year| {
+        let month = u32::try_from(month).ok()?;
+        let day = u32::try_from(day).ok()?;
+        NaiveDate::from_ymd_opt(year, month, day)
+    });
+
+    match date {
+        Some(date) => Ok(Object::Date(date)),
+        None => {
+            let message = format!("invalid date: {}-{}-{}", year, month, day);
+            Err(message.into())
+        }
+    }
+}
+
+/// 日数の期間をつくる
+#[cfg(feature = "date")]
+fn days(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let result = match &arguments[0] {
+        Object::Integer(days) => Object::Interval(*days),
+        _ => {
+            let message = format!(
+                "argument to `days` must be Integer, got {}",
+                arguments[0].get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    Ok(result)
+}
+
+/// strftime 形式（`%Y-%m-%d` など）で日付を文字列にする
+#[cfg(feature = "date")]
+fn format_date(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let (date, format) = match arguments.as_slice() {
+        [Object::Date(date), Object::String(format)] => (date, format),
+        _ => {
+            let types = arguments
+                .iter()
+                .map(Object::get_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!(
+                "arguments to `format_date` must be Date and String, got {}",
+                types
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let items = StrftimeItems::new(format).collect::<Vec<_>>();
+
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        let message = format!("invalid date format: {}", format);
+        return Err(message.into());
+    }
+
+    let result = Object::String(date.format_with_items(items.into_iter()).to_string());
+    Ok(result)
+}
+
+/// JSON のテキストを値にする
+#[cfg(feature = "data")]
+fn json_parse(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    match &arguments[0] {
+        Object::String(text) => json::parse(text).map_err(EvalError::from),
+        argument => {
+            let message = format!(
+                "argument to `json_parse` must be String, got {}",
+                argument.get_type()
+            );
+            Err(EvalError::InvalidArgument(message))
+        }
+    }
+}
+
+/// 値を JSON のテキストにする
+#[cfg(feature = "data")]
+fn json_stringify(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    json::stringify(&arguments[0])
+        .map(Object::String)
+        .map_err(EvalError::from)
+}
+
+#[cfg(feature = "data")]
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// 文字列、またはバイト値の配列をバイト列として受けとる
+#[cfg(feature = "data")]
+fn bytes_argument(name: &str, argument: &Object) -> Result<Vec<u8>, EvalError> {
+    match argument {
+        Object::String(value) => Ok(value.as_bytes().to_vec()),
+        Object::Array(elements) => elements
+            .iter()
+            .map(|element| match element {
+                Object::Integer(byte) if (0..=255).contains(byte) => Ok(*byte as u8),
+                _ => {
+                    let message = format!("argument to `{}` must be an Array of bytes", name);
+                    Err(EvalError::InvalidArgument(message))
+                }
+            })
+            .collect(),
+        _ => {
+            let message = format!(
+                "argument to `{}` must be String or Array, got {}",
+                name,
+                argument.get_type()
+            );
+            Err(EvalError::InvalidArgument(message))
+        }
+    }
+}
+
+#[cfg(feature = "data")]
+fn string_from_bytes(name: &str, bytes: Vec<u8>) -> EvalResult {
+    match String::from_utf8(bytes) {
+        Ok(value) => Ok(Object::String(value)),
+        Err(_) => Err(format!("result of `{}` is not valid UTF-8", name).into()),
+    }
+}
+
+#[cfg(feature = "data")]
+fn base64_encode(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let bytes = bytes_argument("base64_encode", &arguments[0])?;
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    let result = Object::String(encoded);
+    Ok(result)
+}
+
+#[cfg(feature = "data")]
+fn base64_decode(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let input = match &arguments[0] {
+        Object::String(value) => value.trim_end_matches('='),
+        _ => {
+            let message = format!(
+                "argument to `base64_decode` must be String, got {}",
+                arguments[0].get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    if input.len() % 4 == 1 {
+        return Err("invalid base64 input length".into());
+    }
+
+    let mut bytes = Vec::with_capacity(input.len() * 3 / 4);
+    let mut group = 0u32;
+    let mut bits = 0;
+
+    for ch in input.bytes() {
+        let value = match BASE64_ALPHABET.iter().position(|c| *c == ch) {
+            Some(value) => value as u32,
+            None => {
+                let message = format!("invalid base64 character: {}", ch as char);
+                return Err(message.into());
+            }
+        };
+
+        group = group << 6 | value;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((group >> bits) as u8);
+            group &= (1 << bits) - 1;
+        }
+    }
+
+    string_from_bytes("base64_decode", bytes)
+}
+
+#[cfg(feature = "data")]
+fn url_encode(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let bytes = bytes_argument("url_encode", &arguments[0])?;
+    let mut encoded = String::with_capacity(bytes.len());
+
+    for byte in bytes {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    let result = Object::String(encoded);
+    Ok(result)
+}
+
+#[cfg(feature = "data")]
+fn url_decode(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let input = match &arguments[0] {
+        Object::String(value) => value.as_bytes(),
+        _ => {
+            let message = format!(
+                "argument to `url_decode` must be String, got {}",
+                arguments[0].get_type()
+            );
+            return Err(EvalError::InvalidArgument(message));
+        }
+    };
+
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut i = 0;
+
+    while i < input.len() {
+        if input[i] != b'%' {
+            bytes.push(input[i]);
+            i += 1;
+            continue;
+        }
+
+        let byte = input
+            .get(i + 1..i + 3)
+            .and_then(|hex| core::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match byte {
+            Some(byte) => bytes.push(byte),
+            None => return Err("invalid percent-encoding in `url_decode`".into()),
+        }
+
+        i += 3;
+    }
+
+    string_from_bytes("url_decode", bytes)
+}
+
+/// 平方根（負の数は NaN）
+#[cfg(feature = "std")]
+fn sqrt(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let value = number_argument("sqrt", &arguments)?;
+    Ok(Object::Float(Float(value.to_float().0.sqrt())))
+}
+
+/// 小さい方の整数に丸める
+fn floor(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let value = number_argument("floor", &arguments)?;
+    Ok(value.round(Rounding::Floor))
+}
+
+/// 大きい方の整数に丸める
+fn ceil(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let value = number_argument("ceil", &arguments)?;
+    Ok(value.round(Rounding::Ceil))
+}
+
+/// 近い方の整数に丸める（0.5 は 0 から遠い方へ）
+fn round(_: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    let value = number_argument("round", &arguments)?;
+    Ok(value.round(Rounding::Round))
+}
+
+fn number_argument(name: &str, arguments: &[Object]) -> Result<Number, EvalError> {
+    match Number::from_object(&arguments[0]) {
+        Some(value) => Ok(value),
+        None => {
+            let message = format!(
+                "argument to `{}` must be a number, got {}",
+                name,
+                arguments[0].get_type()
+            );
+            Err(EvalError::InvalidArgument(message))
+        }
+    }
+}
+
+/// 単調増加する時計の、最初に参照したときからの経過ナノ秒
+#[cfg(feature = "std")]
+fn monotonic_nanos() -> isize {
+    static EPOCH: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    EPOCH
+        .get_or_init(std::time::Instant::now)
+        .elapsed()
+        .as_nanos() as isize
+}
+
+/// 開始時刻を束縛した `elapsed_ms` を持つマップを返す
+#[cfg(feature = "std")]
+fn stopwatch(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    evaluator.audited("stopwatch", arguments, |evaluator, _| {
+        let start =
+            evaluator.nondeterministic("stopwatch", || Ok(Object::Integer(monotonic_nanos())))?;
+
+        let key = Object::String("elapsed_ms".to_string());
+        let elapsed_ms = Object::Bound {
+            function: Box::new(Object::Buildin {
+                function: Buildin::new("elapsed_ms", elapsed_ms),
+            }),
+            arguments: vec![start],
+        };
+
+        let mut pairs = BTreeMap::new();
+        pairs.insert(MapKey::from(&key), MapPair::new(key, elapsed_ms));
+
+        let result = Object::Map(pairs);
+        Ok(result)
+    })
+}
+
+#[cfg(feature = "std")]
+fn elapsed_ms(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    evaluator.audited("elapsed_ms", arguments, |evaluator, arguments| {
+        let result = match arguments.as_slice() {
+            [Object::Integer(start)] => evaluator.nondeterministic("elapsed_ms", || {
+                Ok(Object::Integer((monotonic_nanos() - start) / 1_000_000))
+            })?,
+            _ => {
+                let error = EvalError::WrongArity {
+                    got: arguments.len().saturating_sub(1),
+                    want: "0".to_string(),
+                };
+                return Err(error);
+            }
+        };
+
+        Ok(result)
+    })
+}
+
+/// 入力をすべて読んで返す（Monkey で書いたインタプリタにソースを渡すために使う）。
+/// 入力元が設定されていれば、標準入力ではなくそこから入力の終わりまで行を読む
+#[cfg(feature = "std")]
+fn read_source(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    evaluator.audited("read_source", arguments, |evaluator, _| {
+        use std::io::Read;
+
+        evaluator.check_pure("read_source")?;
+
+        let input = evaluator.input();
+
+        evaluator.nondeterministic("read_source", move || {
+            let mut source = String::new();
+
+            match input {
+                Some(input) => {
+                    while let Some(line) = (input.borrow_mut())() {
+                        source.push_str(&line);
+                        source.push('\n');
+                    }
+                }
+                None => {
+                    std::io::stdin()
+                        .read_to_string(&mut source)
+                        .map_err(|error| format!("failed to read standard input: {}", error))?;
+                }
+            }
+
+            Ok(Object::String(source))
+        })
+    })
+}
+
+/// コマンドを実行し、`stdout`・`stderr`・`status`（シグナルで終わったときは null）のマップを返す。
+/// 評価器で許可されていなければエラーにする
+#[cfg(feature = "std")]
+fn exec(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    evaluator.audited("exec", arguments, |evaluator, arguments| {
+        let (program, options) = match arguments.as_slice() {
+            [Object::String(program)] => (program.clone(), vec![]),
+            [Object::String(program), Object::Array(options)] => {
+                let options = options
+                    .iter()
+                    .map(|option| match option {
+                        Object::String(option) => Ok(option.clone()),
+                        option => Err(format!(
+                            "arguments of `exec` must be Strings, got {}",
+                            option.get_type()
+                        )),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                (program.clone(), options)
+            }
+            _ => {
+                let types = arguments
+                    .iter()
+                    .map(Object::get_type)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let message = format!(
+                    "arguments to `exec` must be String and Array, got {}",
+                    types
+                );
+                return Err(EvalError::InvalidArgument(message));
+            }
+        };
+
+        let allowed = evaluator.is_exec_allowed();
+
+        // 再生するときはコマンドを実行せずに記録した結果を返す
+        evaluator.nondeterministic("exec", move || {
+            if !allowed {
+                return Err("`exec` is not enabled (use --allow-exec)".into());
+            }
+
+            let output = std::process::Command::new(&program)
+                .args(&options)
+                .output()
+                .map_err(|error| format!("failed to run `{}`: {}", program, error))?;
+
+            let status = match output.status.code() {
+                Some(code) => Object::Integer(code as isize),
+                None => Object::Null,
+            };
+            let entries = [
+                (
+                    "stdout",
+                    Object::String(String::from_utf8_lossy(&output.stdout).into_owned()),
+                ),
+                (
+                    "stderr",
+                    Object::String(String::from_utf8_lossy(&output.stderr).into_owned()),
+                ),
+                ("status", status),
+            ];
+
+            let mut pairs = BTreeMap::new();
+
+            for (key, value) in entries.iter().cloned() {
+                let key = Object::String(key.to_string());
+                pairs.insert(MapKey::from(&key), MapPair::new(key, value));
+            }
+
+            Ok(Object::Map(pairs))
+        })
+    })
+}
+
+/// 引数を 1 行ずつ出力先に書き出す
+fn puts(evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+    evaluator.audited("puts", arguments, |evaluator, arguments| {
+        evaluator.check_pure("puts")?;
+
+        for argument in arguments.iter() {
+            evaluator.write_line(&argument.to_string())?;
+        }
+
+        let result = Object::Null;
+        Ok(result)
+    })
+}
@@ -0,0 +1,303 @@
+use crate::token::Position;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// スタックトレースに表示する段の数の上限（深い再帰では残りを省く）
+const TRACE_LIMIT: usize = 12;
+
+/// 評価エラー（表示はこれまでの英語のメッセージと同じ）
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EvalError {
+    /// 束縛されていない名前を参照した
+    UnknownIdentifier(String),
+    /// 二項演算子の左右の型が違う
+    TypeMismatch {
+        left: String,
+        operator: String,
+        right: String,
+    },
+    /// 値の型に使えない演算子（`-true` や `"a" * "b"` など）
+    UnknownOperator(String),
+    /// 関数でない値を呼び出した
+    NotAFunction(String),
+    /// 組み込み関数に渡した引数の数が違う（`want` は `2 or 3` のような表記）
+    WrongArity { got: usize, want: String },
+    /// 関数リテラルに渡した引数の数が違う
+    ArityMismatch { want: usize, got: usize },
+    /// 組み込み関数に渡した引数の型や値が違う
+    InvalidArgument(String),
+    /// 添字演算子やスライスに使えない値・添字
+    InvalidIndex(String),
+    /// マップのキーにできない値
+    UnusableMapKey(String),
+    /// 0 で割った
+    DivisionByZero,
+    /// 整数の演算があふれた（`9223372036854775807 + 1` のような式）
+    IntegerOverflow(String),
+    /// `throw` した値が捕捉されなかった
+    UncaughtThrow(String),
+    /// 実行できる文の数の上限を超えた
+    StatementLimit(usize),
+    /// 関数呼び出しの入れ子の深さの上限を超えた
+    RecursionLimit(usize),
+    /// つくろうとした配列や文字列が長さの上限（[`crate::object::MAX_LENGTH`]）を超えた
+    LengthLimit(usize),
+    /// Ctrl-C などで評価を中断した
+    Interrupted,
+    /// その他のエラー
+    Other(String),
+    /// エラーが起きた文の位置と呼び出しスタックをつけたエラー（`Evaluator::eval` が返すときにつける）
+    Located {
+        error: Box<EvalError>,
+        position: Position,
+        /// 内側の呼び出しから順に並べる
+        trace: Vec<Frame>,
+    },
+}
+
+/// 呼び出しスタックの 1 段
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Frame {
+    /// 呼び出された関数（呼び出しの式の関数の部分）
+    pub function: String,
+    /// 呼び出した関数（トップレベルなら `main`）
+    pub caller: String,
+    /// 呼び出した文の位置
+    pub call_site: Position,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "in {}, called from {}", self.function, self.caller)?;
+
+        if self.call_site.is_known() {
+            write!(f, ", {}", self.call_site)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl EvalError {
+    /// エラーに位置と呼び出しスタックをつける（すでについていれば、内側のものを残す）
+    pub fn at(self, position: Position, trace: Vec<Frame>) -> Self {
+        match self {
+            Self::Located { .. } => self,
+            error => Self::Located {
+                error: Box::new(error),
+                position,
+                trace,
+            },
+        }
+    }
+
+    /// エラーが起きた文の位置
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Self::Located { position, .. } => Some(*position).filter(Position::is_known),
+            _ => None,
+        }
+    }
+
+    /// エラーが起きたときの呼び出しスタック（内側の呼び出しから順に並べる）
+    pub fn trace(&self) -> &[Frame] {
+        match self {
+            Self::Located { trace, .. } => trace,
+            _ => &[],
+        }
+    }
+
+    /// 呼び出しスタックを 1 段 1 行で表示する（深すぎる分は省く）
+    pub fn stack_trace(&self) -> Vec<String> {
+        let trace = self.trace();
+        let mut lines = trace
+            .iter()
+            .take(TRACE_LIMIT)
+            .map(Frame::to_string)
+            .collect::<Vec<_>>();
+
+        if trace.len() > TRACE_LIMIT {
+            lines.push(format!("... {} more", trace.len() - TRACE_LIMIT));
+        }
+
+        lines
+    }
+
+    /// 位置を除いたエラーの種類
+    pub fn kind(&self) -> &EvalError {
+        match self {
+            Self::Located { error, .. } => error.kind(),
+            error => error,
+        }
+    }
+
+    /// エラーが起きた行を抜き出し、文の始まりに `^` をつけて示す（位置がなければ None）
+    pub fn excerpt(&self, source: &str) -> Option<String> {
+        let position = self.position()?;
+        let line = source.lines().nth(position.line.checked_sub(1)?)?;
+        let number = position.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let indent = line
+            .chars()
+            .take(position.column.saturating_sub(1))
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+
+        let excerpt = format!(
+            "{gutter}--> {position}\n{gutter} |\n{number} | {line}\n{gutter} | {indent}^",
+            gutter = gutter,
+            position = position,
+            number = number,
+            line = line,
+            indent = indent
+        );
+        Some(excerpt)
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownIdentifier(name) => write!(f, "identifier not found: {}", name),
+            Self::TypeMismatch {
+                left,
+                operator,
+                right,
+            } => write!(f, "type mismatch: {} {} {}", left, operator, right),
+            Self::UnknownOperator(operation) => write!(f, "unknown operator: {}", operation),
+            Self::NotAFunction(kind) => write!(f, "not a function: {}", kind),
+            Self::WrongArity { got, want } => {
+                write!(f, "wrong number of arguments. got={}, want={}", got, want)
+            }
+            Self::ArityMismatch { want, got } => {
+                write!(f, "expected arity to be {}, got {} instead", want, got)
+            }
+            Self::InvalidArgument(message) | Self::InvalidIndex(message) => {
+                write!(f, "{}", message)
+            }
+            Self::UnusableMapKey(kind) => write!(f, "unusable as map key: {}", kind),
+            Self::DivisionByZero => write!(f, "division by zero"),
+            Self::IntegerOverflow(operation) => write!(f, "integer overflow in {}", operation),
+            Self::UncaughtThrow(value) => write!(f, "uncaught throw: {}", value),
+            Self::StatementLimit(limit) => write!(f, "statement limit exceeded: {}", limit),
+            Self::RecursionLimit(limit) => {
+                write!(f, "maximum recursion depth exceeded: {}", limit)
+            }
+            Self::LengthLimit(limit) => write!(f, "maximum length exceeded: {}", limit),
+            Self::Interrupted => write!(f, "interrupted"),
+            Self::Other(message) => write!(f, "{}", message),
+            Self::Located { error, .. } => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<String> for EvalError {
+    fn from(message: String) -> Self {
+        Self::Other(message)
+    }
+}
+
+impl From<&str> for EvalError {
+    fn from(message: &str) -> Self {
+        Self::Other(message.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{EvalError, Frame};
+    use crate::token::Position;
+
+    #[test]
+    fn test_display() {
+        let tests = vec![
+            (
+                EvalError::UnknownIdentifier("x".to_string()),
+                "identifier not found: x",
+            ),
+            (
+                EvalError::TypeMismatch {
+                    left: "Integer".to_string(),
+                    operator: "+".to_string(),
+                    right: "Boolean".to_string(),
+                },
+                "type mismatch: Integer + Boolean",
+            ),
+            (
+                EvalError::WrongArity {
+                    got: 3,
+                    want: "1 or 2".to_string(),
+                },
+                "wrong number of arguments. got=3, want=1 or 2",
+            ),
+            (
+                EvalError::ArityMismatch { want: 2, got: 1 },
+                "expected arity to be 2, got 1 instead",
+            ),
+            (EvalError::DivisionByZero, "division by zero"),
+            (
+                EvalError::StatementLimit(100),
+                "statement limit exceeded: 100",
+            ),
+            (
+                EvalError::RecursionLimit(1000),
+                "maximum recursion depth exceeded: 1000",
+            ),
+            (
+                EvalError::LengthLimit(4194304),
+                "maximum length exceeded: 4194304",
+            ),
+            (EvalError::from("boom"), "boom"),
+        ];
+
+        for (error, expected) in tests {
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_located() {
+        let error = EvalError::DivisionByZero.at(Position::new(2, 5), vec![]);
+
+        assert_eq!(error.to_string(), "division by zero");
+        assert_eq!(error.kind(), &EvalError::DivisionByZero);
+        assert_eq!(error.position().map(|p| (p.line, p.column)), Some((2, 5)));
+        assert_eq!(
+            error.excerpt("let a = 1;\n    a / 0;\n"),
+            Some(" --> line 2, column 5\n  |\n2 |     a / 0;\n  |     ^".to_string())
+        );
+        assert_eq!(EvalError::DivisionByZero.excerpt("1 / 0"), None);
+    }
+
+    #[test]
+    fn test_stack_trace() {
+        let frame = |function: &str, caller: &str, line| Frame {
+            function: function.to_string(),
+            caller: caller.to_string(),
+            call_site: Position::new(line, 3),
+        };
+        let trace = (0..20).map(|line| frame("f", "f", line + 1)).collect();
+        let error = EvalError::DivisionByZero.at(Position::new(1, 1), trace);
+
+        let lines = error.stack_trace();
+        assert_eq!(lines.len(), 13);
+        assert_eq!(lines[0], "in f, called from f, line 1, column 3");
+        assert_eq!(lines[12], "... 8 more");
+
+        let error = EvalError::DivisionByZero.at(
+            Position::default(),
+            vec![frame("add", "main", 0), frame("g", "main", 4)],
+        );
+        assert_eq!(error.position(), None);
+        assert_eq!(
+            error.stack_trace(),
+            vec![
+                "in add, called from main",
+                "in g, called from main, line 4, column 3"
+            ]
+        );
+    }
+}
@@ -1,15 +1,106 @@
-use crate::ast::{Expression, Program, Statement};
+use crate::ast::{
+    free_variables, modify_expression, modify_program, Expression, MatchArm, Pattern, Program,
+    Statement,
+};
 use crate::buildin;
-use crate::object::{MapKey, MapPair, Object};
-use crate::token::Token;
-use std::collections::BTreeMap;
+use crate::error::{EvalError, Frame};
+use crate::host::{HostResource, HostState};
+use crate::lexer::Lexer;
+use crate::numeric::{ArithmeticError, Number, Numeric, Pair};
+use crate::object::{Buildin, MapKey, MapPair, Object, MAX_LENGTH};
+use crate::parser::Parser;
+use crate::session::Recorded;
+use crate::symbol::{Interner, SharedInterner, Symbol};
+use crate::token::{Position, Token};
+use crate::trace::{AuditEvent, CallGraph, Coverage, Snapshot, Step, Trace, TraceEvent};
+use crate::vfs::{OpenMode, Vfs, VfsFile};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::format;
+use alloc::rc::{Rc, Weak};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "date")]
+use chrono::{Datelike, Days, NaiveDate};
+use core::cell::RefCell;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::mem;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use hashbrown::HashMap;
 
-/// 評価エラー
-pub type EvalError = String;
+/// 関数呼び出しの入れ子の深さの既定の上限
+pub const DEFAULT_RECURSION_LIMIT: usize = 1000;
+
+/// 関数を呼び出す前に残っていてほしいスタックの大きさ（足りなければスタックを継ぎ足す）
+#[cfg(feature = "std")]
+const STACK_RED_ZONE: usize = 1 << 20;
+
+/// 1 度に継ぎ足すスタックの大きさ
+#[cfg(feature = "std")]
+const STACK_GROWTH: usize = 1 << 23;
 
 /// 評価結果
 pub type EvalResult = Result<Object, EvalError>;
 
+/// 監査ログを受けとる関数
+pub type AuditHook = Rc<RefCell<dyn FnMut(&AuditEvent)>>;
+
+/// `read_line` と `input` が読む行を返す関数（入力の終わりなら None）
+pub type InputSource = Rc<RefCell<dyn FnMut() -> Option<String>>>;
+
+/// 操作を評価する前に呼ばれ、エラーを返すとその操作を拒否する関数
+pub type Interceptor = Rc<RefCell<dyn FnMut(&Evaluator, &Operation) -> Result<(), EvalError>>>;
+
+/// 割りこみに知らせる操作
+#[derive(Clone, Copy, Debug)]
+pub enum Operation<'a> {
+    /// `let` で名前を束縛する
+    Bind { name: &'a str, value: &'a Object },
+    /// 組み込み関数を呼ぶ
+    CallBuildin {
+        name: &'a str,
+        arguments: &'a [Object],
+    },
+    /// モジュールを読みこむ
+    Import { path: &'a str },
+}
+
+/// 1 行書かれるたびに関数を呼ぶ出力先（`puts` の出力を評価の終わりを待たずに届ける）
+pub struct LineCallback<F: FnMut(&str)> {
+    callback: F,
+    /// まだ改行が書かれていない行
+    line: String,
+}
+
+impl<F: FnMut(&str)> LineCallback<F> {
+    pub fn new(callback: F) -> Self {
+        Self {
+            callback,
+            line: String::new(),
+        }
+    }
+}
+
+impl<F: FnMut(&str)> fmt::Write for LineCallback<F> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.line.push_str(s);
+
+        while let Some(i) = self.line.find('\n') {
+            let rest = self.line.split_off(i + 1);
+            self.line.pop();
+            (self.callback)(&self.line);
+            self.line = rest;
+        }
+
+        Ok(())
+    }
+}
+
 /// レスポンス
 pub enum Response {
     /// 返答する
@@ -20,39 +111,230 @@ pub enum Response {
     Error(EvalError),
 }
 
+/// 末尾位置の評価結果
+enum Tail {
+    /// 値
+    Value(Object),
+    /// 末尾呼び出し（呼び出しの式から呼ぶなら、呼び出しスタックに積む段も）
+    Call(Object, Vec<Object>, Option<Frame>),
+}
+
+/// 実行ごとに値が変わる組み込み関数の結果の記録・再生
+enum Journal {
+    Record(Vec<Recorded>),
+    Replay(VecDeque<Recorded>),
+}
+
 /// 環境
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Debug)]
 pub struct Environment {
-    store: BTreeMap<String, Object>,
-    outer: Option<Box<Environment>>,
-    buildin: BTreeMap<String, Object>,
+    store: HashMap<Symbol, Object>,
+    outer: Option<Env>,
+    /// 組み込み関数（内側のスコープと共有する）
+    buildin: Rc<RefCell<HashMap<String, Object>>>,
+    /// この環境の束縛を値で捕まえた関数の環境（束縛し直したら値を送る）
+    captures: Vec<Capture>,
+    /// 関数が捕まえた自由変数と、その値を束縛している環境までの距離（ローカルで束縛していなければ `usize::MAX`）
+    sources: HashMap<Symbol, usize>,
+}
+
+/// 自由変数を値で捕まえた関数の環境への参照
+#[derive(Debug)]
+struct Capture {
+    env: Weak<RefCell<Environment>>,
+    /// 関数を定義した環境から、この参照を登録した環境までの距離
+    depth: usize,
+}
+
+/// 共有される環境（比較とハッシュは参照先の同一性で行う）
+#[derive(Clone)]
+pub struct Env(Rc<RefCell<Environment>>);
+
+impl Env {
+    pub fn new(env: Environment) -> Self {
+        Self(Rc::new(RefCell::new(env)))
+    }
+
+    /// この環境で束縛している値（自由変数だけを捕まえた関数では、捕まえた値のすべて）
+    pub fn bindings(&self) -> BTreeMap<String, Object> {
+        sorted(&self.borrow().store)
+    }
+}
+
+impl Deref for Env {
+    type Target = RefCell<Environment>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Env {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Env({:p})", Rc::as_ptr(&self.0))
+    }
+}
+
+impl PartialEq for Env {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Env {}
+
+impl PartialOrd for Env {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Env {
+    fn cmp(&self, other: &Self) -> Ordering {
+        Rc::as_ptr(&self.0).cmp(&Rc::as_ptr(&other.0))
+    }
+}
+
+impl Hash for Env {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).hash(state);
+    }
+}
+
+/// 評価途中のジェネレーターの状態
+#[derive(Debug)]
+pub struct GeneratorState {
+    /// 本体を評価する環境
+    env: Env,
+    /// 評価中のブロックと次に評価する文の位置（内側のブロックほど後ろ）
+    frames: Vec<(Vec<Statement>, usize)>,
+    /// 値の生成を委ねているジェネレーター
+    delegate: Option<Generator>,
+    /// 評価中かどうか
+    running: bool,
+}
+
+/// 共有されるジェネレーター（比較とハッシュは参照先の同一性で行う）
+#[derive(Clone)]
+pub struct Generator(Rc<RefCell<GeneratorState>>);
+
+impl Generator {
+    fn new(env: Env, body: &Statement) -> Self {
+        let state = GeneratorState {
+            env,
+            frames: vec![(block_statements(body), 0)],
+            delegate: None,
+            running: false,
+        };
+
+        Self(Rc::new(RefCell::new(state)))
+    }
+}
+
+impl Deref for Generator {
+    type Target = RefCell<GeneratorState>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Generator({:p})", Rc::as_ptr(&self.0))
+    }
+}
+
+impl PartialEq for Generator {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Generator {}
+
+impl PartialOrd for Generator {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Generator {
+    fn cmp(&self, other: &Self) -> Ordering {
+        Rc::as_ptr(&self.0).cmp(&Rc::as_ptr(&other.0))
+    }
+}
+
+impl Hash for Generator {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).hash(state);
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Environment {
     pub fn new() -> Self {
         Self {
-            store: BTreeMap::new(),
+            store: HashMap::new(),
             outer: None,
-            buildin: buildin::new(),
+            buildin: Rc::new(RefCell::new(buildin::new())),
+            captures: vec![],
+            sources: HashMap::new(),
         }
     }
 
-    fn new_with_outer(env: Box<Environment>) -> Self {
+    fn new_with_outer(env: Env) -> Self {
+        let buildin = env.borrow().buildin.clone();
+
         Self {
-            store: BTreeMap::new(),
+            store: HashMap::new(),
             outer: Some(env),
-            buildin: buildin::new(),
+            buildin,
+            captures: vec![],
+            sources: HashMap::new(),
+        }
+    }
+
+    /// 組み込み関数を登録する（同じ名前の組み込み関数は置きかえる）。この環境と、
+    /// 環境を共有するスコープ（関数の本体や読みこむモジュール）から呼べる
+    pub fn register_builtin(
+        &mut self,
+        name: &str,
+        function: impl Fn(&mut Evaluator, Vec<Object>) -> EvalResult + 'static,
+    ) {
+        let function = Object::Buildin {
+            function: Buildin::new(name, function),
+        };
+        self.buildin.borrow_mut().insert(name.to_string(), function);
+    }
+
+    /// いちばん外側（トップレベル）の環境
+    fn root(&self) -> Option<Env> {
+        let mut root = self.outer.clone()?;
+
+        loop {
+            let outer = root.borrow().outer.clone();
+
+            match outer {
+                Some(outer) => root = outer,
+                None => return Some(root),
+            }
         }
     }
 
-    fn get(&self, name: &String) -> EvalResult {
+    fn get(&self, name: &str) -> EvalResult {
         let result = match self.store.get(name) {
             Some(object) => object.clone(),
             None => match &self.outer {
-                Some(env) => env.get(name)?,
+                Some(env) => env.borrow().get(name)?,
                 None => {
-                    let message = format!("identifier not found: {}", name).to_string();
-                    return Err(message);
+                    let error = EvalError::UnknownIdentifier(name.to_string());
+                    return Err(error);
                 }
             },
         };
@@ -60,628 +342,4868 @@ impl Environment {
         Ok(result)
     }
 
-    fn set(&mut self, name: String, object: Object) -> EvalResult {
+    fn set(&mut self, name: Symbol, object: Object) -> EvalResult {
+        self.notify(&name, &object);
         self.store.insert(name, object.clone());
         Ok(object)
     }
 
-    pub fn eval(&mut self, program: Program) -> Response {
-        let mut result = Object::Default;
+    /// 関数を定義したあとで束縛し直した値を、その名前を値で捕まえた関数の環境に送る
+    /// （関数から見て、もっと内側の環境の束縛で隠れている名前は送らない）
+    fn notify(&self, name: &Symbol, object: &Object) {
+        for capture in self.captures.iter() {
+            let env = match capture.env.upgrade() {
+                Some(env) => env,
+                None => continue,
+            };
+            let mut env = env.borrow_mut();
 
-        for statement in program.statements.iter() {
-            result = match self.eval_statement(statement) {
-                Ok(Object::Return(result)) => return Response::Reply(*result),
-                Ok(result) => result,
-                Err(error) => return Response::Error(error),
+            match env.sources.get(name) {
+                Some(depth) if capture.depth <= *depth => {
+                    env.sources.insert(name.clone(), capture.depth);
+                    env.notify(name, object);
+                    env.store.insert(name.clone(), object.clone());
+                }
+                _ => (),
             }
         }
+    }
 
-        match result {
-            Object::Let => Response::NoReply,
-            _ => Response::Reply(result),
+    /// 束縛を値で捕まえた関数の環境を登録する（破棄された関数の分はときどき取り除く）
+    fn watch(&mut self, capture: Capture) {
+        if self.captures.len() == self.captures.capacity() {
+            self.captures
+                .retain(|capture| capture.env.strong_count() > 0);
         }
+
+        self.captures.push(capture);
     }
 
-    fn eval_statement(&mut self, statement: &Statement) -> EvalResult {
-        let result = match statement {
-            Statement::Expression(expression) => self.eval_expression(expression)?,
-            Statement::Block(statements) => self.eval_block_statement(statements)?,
-            Statement::Return(expression) => self.eval_return_statement(expression)?,
-            Statement::Let { name, value } => self.eval_let_statement(name, value)?,
-        };
+    /// 内側のスコープから順に束縛を返す（外側の空のスコープ、たとえば関数ごとの環境は省く）
+    fn scopes(&self) -> Vec<BTreeMap<String, Object>> {
+        let mut scopes = vec![sorted(&self.store)];
+        let mut env = self.outer.clone();
 
-        Ok(result)
+        while let Some(outer) = env {
+            if !outer.borrow().store.is_empty() {
+                scopes.push(sorted(&outer.borrow().store));
+            }
+            env = outer.borrow().outer.clone();
+        }
+
+        scopes
     }
+}
 
-    fn eval_block_statement(&mut self, statements: &Vec<Statement>) -> EvalResult {
-        let mut result = Object::Default;
+/// `on_interrupt` で登録した中断時の処理
+struct InterruptHandler(Object);
 
-        for statement in statements {
-            result = self.eval_statement(statement)?;
+impl HostResource for InterruptHandler {}
 
-            if let Object::Return(_) = result {
-                break;
-            }
-        }
+/// 読みこんだモジュール（パスごと）と、読みこみ中のモジュールの並び（循環の検出に使う）
+#[derive(Default)]
+struct Modules {
+    loaded: BTreeMap<String, Object>,
+    loading: Vec<String>,
+}
 
-        Ok(result)
+impl HostResource for Modules {}
+
+/// 評価器
+pub struct Evaluator {
+    env: Env,
+    /// デバッグモードで記録するトレース
+    trace: Option<Trace>,
+    /// 最初に発生した評価エラーのスナップショット
+    snapshot: Option<Snapshot>,
+    /// 評価エラーが起きたいちばん内側の文の位置
+    error_position: Option<Position>,
+    /// 評価エラーが起きたときの呼び出しスタック
+    error_trace: Vec<Frame>,
+    /// 評価中の関数呼び出しのスタック（外側の呼び出しから順に並べる）
+    frames: Vec<Frame>,
+    /// 評価中の文の位置
+    current_position: Position,
+    /// `throw` で投げられ、まだ捕捉されていない値
+    thrown: Option<Object>,
+    /// 記録中の呼び出しグラフ
+    call_graph: Option<CallGraph>,
+    /// 記録中のカバレッジ
+    coverage: Option<Coverage>,
+    /// 組み込み関数の結果の記録・再生
+    journal: Option<Journal>,
+    /// 1 回の評価で実行できる文の数の上限
+    statement_limit: Option<usize>,
+    /// 上限までに実行できる残りの文の数
+    statements_left: usize,
+    /// ステップ実行で記録する式ごとの評価結果
+    steps: Option<Vec<Step>>,
+    /// ステップ実行中の式の入れ子の深さ
+    depth: usize,
+    /// 関数呼び出しの入れ子の深さの上限
+    recursion_limit: usize,
+    /// 評価中の関数呼び出しの入れ子の深さ
+    call_depth: usize,
+    /// `read_file` が読むファイルシステム
+    vfs: Option<Rc<dyn Vfs>>,
+    /// 副作用のある組み込み関数を禁止するかどうか
+    pure: bool,
+    /// 実行ごとに結果が変わる組み込み関数を禁止するかどうか
+    deterministic: bool,
+    /// `puts` の出力先（None なら標準出力）
+    output: Option<Rc<RefCell<dyn fmt::Write>>>,
+    /// `read_line` と `input` の入力元（None なら標準入力。std がなければ常に入力の終わり）
+    input: Option<InputSource>,
+    /// `exec` でコマンドを実行できるかどうか（既定は禁止）
+    exec: bool,
+    /// 組み込み関数の状態（評価器を破棄するときに後始末する）
+    host: HostState,
+    /// 中断の要求（Ctrl-C のハンドラーなど、別のスレッドから立てる）
+    interrupt: Option<Arc<AtomicBool>>,
+    /// ホストの資源にふれる組み込み関数を呼ぶたびに呼ぶ関数
+    audit: Option<AuditHook>,
+    /// 登録した順に呼ぶ割りこみ
+    interceptors: Vec<Interceptor>,
+    /// 評価中の文（割りこみがあるときだけ記録する）
+    statement: Option<String>,
+    /// `import` がモジュールを探すディレクトリ
+    import_paths: Vec<String>,
+    /// 識別子の名前の表（読みこむモジュールと共有する）
+    interner: SharedInterner,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    fn eval_return_statement(&mut self, expression: &Expression) -> EvalResult {
-        let result = self.eval_expression(expression)?;
-        let result = Box::new(result);
-        let result = Object::Return(result);
+impl Evaluator {
+    pub fn new() -> Self {
+        Self {
+            env: Env::new(Environment::new()),
+            trace: None,
+            snapshot: None,
+            error_position: None,
+            error_trace: Vec::new(),
+            frames: Vec::new(),
+            current_position: Position::default(),
+            thrown: None,
+            call_graph: None,
+            coverage: None,
+            journal: None,
+            statement_limit: None,
+            statements_left: 0,
+            steps: None,
+            depth: 0,
+            recursion_limit: DEFAULT_RECURSION_LIMIT,
+            call_depth: 0,
+            vfs: None,
+            pure: false,
+            deterministic: false,
+            output: None,
+            input: None,
+            exec: false,
+            host: HostState::new(),
+            interrupt: None,
+            audit: None,
+            interceptors: vec![],
+            statement: None,
+            import_paths: vec![],
+            interner: Interner::shared(),
+        }
+    }
 
-        Ok(result)
+    /// 識別子の名前の表。`Lexer::with_interner` に渡すと、ソースの識別子と束縛の名前が文字列を共有する
+    pub fn interner(&self) -> SharedInterner {
+        self.interner.clone()
     }
 
-    fn eval_let_statement(&mut self, name: &Expression, object: &Expression) -> EvalResult {
-        let result = match name {
-            Expression::Identifier(name) => {
-                let name = name.to_string();
-                let object = self.eval_expression(object)?;
-                self.set(name, object)?;
-                Object::Let
-            }
-            _ => return Err("unexpected error occurred in let binding".to_string()),
-        };
+    /// デバッグモードを有効にし、直近 `capacity` 件のトレースイベントを記録する
+    pub fn enable_debug(&mut self, capacity: usize) {
+        self.trace = Some(Trace::new(capacity));
+    }
 
-        Ok(result)
+    /// 呼び出しグラフの記録を始める
+    pub fn enable_call_graph(&mut self) {
+        self.call_graph = Some(CallGraph::new());
     }
 
-    fn eval_expression(&mut self, expression: &Expression) -> EvalResult {
-        let result = match expression {
-            Expression::Integer(value) => {
-                let value = *value;
-                Object::Integer(value)
-            }
-            Expression::Boolean(value) => {
-                let value = *value;
-                Object::Boolean(value)
-            }
-            Expression::String(value) => {
-                let value = value.to_string();
-                Object::String(value)
-            }
-            Expression::Prefix { operator, right } => {
-                let right = self.eval_expression(right)?;
-                self.eval_prefix_expression(operator, right)?
-            }
-            Expression::Infix {
-                left,
-                operator,
-                right,
-            } => {
-                let left = self.eval_expression(left)?;
-                let right = self.eval_expression(right)?;
-                self.eval_infix_expression(left, operator, right)?
-            }
-            Expression::Grouped(expression) => self.eval_expression(expression)?,
-            Expression::If {
-                condition,
-                consequence,
-                alternative,
-            } => {
-                let condition = self.eval_expression(condition)?;
-                self.eval_if_expression(condition, consequence, alternative)?
-            }
-            Expression::Identifier(value) => self.eval_identifier_expression(value)?,
-            Expression::Function { parameters, body } => {
-                self.eval_function_expression(parameters, body)?
-            }
-            Expression::Call {
-                function,
-                arguments,
-            } => {
-                let function = self.eval_expression(function)?;
-                let arguments = self.eval_expressions(arguments)?;
-                self.apply_function(function, arguments)?
-            }
-            Expression::Array(elements) => {
-                let elements = self.eval_expressions(elements)?;
-                Object::Array(elements)
-            }
-            Expression::Index { left, index } => {
-                let left = self.eval_expression(left)?;
-                let index = self.eval_expression(index)?;
-                self.eval_index_expression(left, index)?
-            }
-            Expression::Map(pairs) => {
-                let pairs = pairs.clone();
-                self.eval_map_expression(pairs)?
-            }
-        };
+    /// 記録した呼び出しグラフを取り出す
+    pub fn take_call_graph(&mut self) -> Option<CallGraph> {
+        self.call_graph.take()
+    }
 
-        Ok(result)
+    /// 文ごとの実行回数の記録を始める
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new());
     }
 
-    fn eval_prefix_expression(&mut self, operator: &Token, right: Object) -> EvalResult {
-        let result = match operator {
-            Token::Bang => self.eval_bang_prefix_expression(right)?,
-            Token::Minus => self.eval_minus_prefix_expression(right)?,
-            _ => {
-                let right = right.get_type();
-                let message = format!("unknown operator: {}{}", operator, right);
-                return Err(message);
-            }
-        };
+    /// 記録したカバレッジを取り出す
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
 
-        Ok(result)
+    /// 実行ごとに値が変わる組み込み関数の結果の記録を始める
+    pub fn start_recording(&mut self) {
+        self.journal = Some(Journal::Record(vec![]));
     }
 
-    fn eval_bang_prefix_expression(&mut self, right: Object) -> EvalResult {
-        let result = match right {
-            Object::Boolean(false) => Object::Boolean(true),
-            Object::Null => Object::Boolean(true),
-            _ => Object::Boolean(false),
-        };
+    /// 記録した組み込み関数の結果を取り出す
+    pub fn take_recorded(&mut self) -> Vec<Recorded> {
+        match self.journal.take() {
+            Some(Journal::Record(results)) => results,
+            _ => vec![],
+        }
+    }
 
-        Ok(result)
+    /// 組み込み関数を呼ぶかわりに、記録した結果を順に返す
+    pub fn start_replay(&mut self, results: Vec<Recorded>) {
+        self.journal = Some(Journal::Replay(results.into()));
     }
 
-    fn eval_minus_prefix_expression(&mut self, right: Object) -> EvalResult {
-        let result = match right {
-            Object::Integer(value) => {
-                let value = -value;
-                Object::Integer(value)
-            }
-            _ => {
-                let right = right.get_type();
-                let message = format!("unknown operator: -{}", right);
-                return Err(message);
-            }
-        };
+    /// 実行ごとに値が変わる組み込み関数の結果を、記録・再生にあわせて求める
+    pub fn nondeterministic(&mut self, name: &str, f: impl FnOnce() -> EvalResult) -> EvalResult {
+        self.check_pure(name)?;
 
-        Ok(result)
+        if self.deterministic {
+            let message = format!("`{}` is nondeterministic and is not allowed here", name);
+            return Err(message.into());
+        }
+
+        self.journaled(name, |_| f())
     }
 
-    fn eval_infix_expression(
+    /// 評価器の外から得た結果（ファイルの中身など）を記録し、再生中は記録した結果を返す
+    pub(crate) fn journaled(
         &mut self,
-        left: Object,
-        operator: &Token,
-        right: Object,
+        name: &str,
+        f: impl FnOnce(&Self) -> EvalResult,
     ) -> EvalResult {
-        let result = match (&left, &right) {
-            (Object::Integer(left), Object::Integer(right)) => {
-                let left = *left;
-                let right = *right;
-                self.eval_integer_infix_expression(left, operator, right)?
-            }
-            (Object::Boolean(left), Object::Boolean(right)) => {
-                let left = *left;
-                let right = *right;
-                self.eval_boolean_infix_expression(left, operator, right)?
-            }
-            (Object::String(left), Object::String(right)) => {
-                let left = left.to_string();
-                let right = right.to_string();
-                self.eval_string_infix_expression(left, operator, right)?
-            }
-            _ => {
-                let left = left.get_type();
-                let right = right.get_type();
-                let message = format!("type mismatch: {} {} {}", left, operator, right);
-                return Err(message);
-            }
-        };
+        if let Some(Journal::Replay(results)) = &mut self.journal {
+            return match results.pop_front() {
+                Some((recorded, result)) if recorded == name => Ok(result),
+                Some((recorded, _)) => {
+                    let message = format!("replay diverged: expected {}, got {}", recorded, name);
+                    Err(message.into())
+                }
+                None => {
+                    let message = format!("replay exhausted at {}", name);
+                    Err(message.into())
+                }
+            };
+        }
 
-        Ok(result)
-    }
+        let result = f(self)?;
 
-    fn eval_integer_infix_expression(
-        &mut self,
-        left: isize,
-        operator: &Token,
-        right: isize,
-    ) -> EvalResult {
-        let result = match operator {
-            Token::Plus => Object::Integer(left + right),
-            Token::Minus => Object::Integer(left - right),
-            Token::Asterisk => Object::Integer(left * right),
-            Token::Slash => Object::Integer(left / right),
-            Token::Lt => Object::Boolean(left < right),
-            Token::Gt => Object::Boolean(left > right),
-            Token::Eq => Object::Boolean(left == right),
-            Token::Ne => Object::Boolean(left != right),
-            _ => {
-                let message = format!("unknown operator: Integer {} Integer", operator);
-                return Err(message);
-            }
-        };
+        if let Some(Journal::Record(results)) = &mut self.journal {
+            results.push((name.to_string(), result.clone()));
+        }
 
         Ok(result)
     }
 
-    fn eval_boolean_infix_expression(
-        &mut self,
-        left: bool,
-        operator: &Token,
-        right: bool,
-    ) -> EvalResult {
-        let result = match operator {
-            Token::Eq => Object::Boolean(left == right),
-            Token::Ne => Object::Boolean(left != right),
-            _ => {
-                let message = format!("unknown operator: Boolean {} Boolean", operator);
-                return Err(message);
-            }
-        };
+    /// 1 回の評価で実行できる文の数を制限する（None で無制限）
+    pub fn set_statement_limit(&mut self, limit: Option<usize>) {
+        self.statement_limit = limit;
+    }
 
-        Ok(result)
+    /// 関数呼び出しの入れ子の深さを制限する（`std` なしではスレッドのスタックが足りる範囲で設定する）
+    pub fn set_recursion_limit(&mut self, limit: usize) {
+        self.recursion_limit = limit;
     }
 
-    fn eval_string_infix_expression(
-        &mut self,
-        left: String,
-        operator: &Token,
-        right: String,
-    ) -> EvalResult {
-        let result = match operator {
-            Token::Plus => Object::String(format!("{}{}", left, right)),
-            Token::Eq => Object::Boolean(left == right),
-            Token::Ne => Object::Boolean(left != right),
-            _ => {
-                let message = format!("unknown operator: String {} String", operator);
-                return Err(message);
-            }
-        };
+    pub(crate) fn statement_limit(&self) -> Option<usize> {
+        self.statement_limit
+    }
 
-        Ok(result)
+    /// 上限までに実行できる残りの文の数（上限がなければ None）
+    pub(crate) fn statements_left(&self) -> Option<usize> {
+        self.statement_limit.map(|_| self.statements_left)
     }
 
-    fn eval_if_expression(
-        &mut self,
-        condition: Object,
-        consequence: &Statement,
-        alternative: &Option<Box<Statement>>,
-    ) -> EvalResult {
-        let result = match (is_truthy(condition), alternative) {
-            (true, _) => self.eval_statement(consequence)?,
-            (_, Some(statement)) => self.eval_statement(statement)?,
-            (_, _) => Object::Null,
-        };
+    /// 入れ子の評価器で実行した文の数を、残りの文の数から差し引く
+    pub(crate) fn charge_statements(&mut self, count: usize) {
+        self.statements_left = self.statements_left.saturating_sub(count);
+    }
 
-        Ok(result)
+    pub(crate) fn recursion_limit(&self) -> usize {
+        self.recursion_limit
     }
 
-    fn eval_identifier_expression(&mut self, name: &String) -> EvalResult {
-        let result = match (self.get(name), self.buildin.get(name)) {
-            (Ok(object), _) => object,
-            (Err(_), Some(object)) => object.clone(),
-            (Err(error), None) => return Err(error),
-        };
+    /// 上限までに入れ子にできる残りの関数呼び出しの数
+    pub(crate) fn calls_left(&self) -> usize {
+        self.recursion_limit.saturating_sub(self.call_depth)
+    }
 
-        Ok(result)
+    /// `read_file` が読むファイルシステムを設定する（None でファイルを読めなくする）
+    pub fn set_vfs(&mut self, vfs: Option<Rc<dyn Vfs>>) {
+        self.vfs = vfs;
     }
 
-    fn eval_function_expression(
-        &mut self,
-        parameters: &Vec<Expression>,
-        body: &Statement,
-    ) -> EvalResult {
-        let result = Object::Function {
-            parameters: parameters.clone(),
-            body: body.clone(),
-            env: self.clone(),
-        };
+    /// 副作用のある組み込み関数（`puts` や時計）の呼び出しを禁止する
+    pub fn set_pure(&mut self, pure: bool) {
+        self.pure = pure;
+    }
 
-        Ok(result)
+    pub(crate) fn is_pure(&self) -> bool {
+        self.pure
     }
 
-    fn eval_expressions(
-        &mut self,
-        expressions: &Vec<Expression>,
-    ) -> Result<Vec<Object>, EvalError> {
-        let mut result = vec![];
+    /// 実行ごとに結果が変わる組み込み関数（時計など）の呼び出しを禁止し、同じ入力から同じ出力が
+    /// 得られるようにする。マップは常にキーの順に並ぶので、ほかに順序が変わるところはない
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
 
-        for expression in expressions.iter() {
-            result.push(self.eval_expression(expression)?);
+    pub(crate) fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// 副作用のある組み込み関数を呼べるか確かめる
+    pub(crate) fn check_pure(&self, name: &str) -> Result<(), EvalError> {
+        if self.pure {
+            let message = format!("`{}` has side effects and is not allowed here", name);
+            return Err(message.into());
         }
 
-        Ok(result)
+        Ok(())
     }
 
-    fn eval_index_expression(&mut self, left: Object, index: Object) -> EvalResult {
-        match (&left, &index) {
-            (Object::Array(elements), Object::Integer(index)) => {
-                let elements = elements.clone();
-                let index = index.clone();
-                self.eval_array_index_expression(elements, index)
-            }
-            (Object::Map(pairs), _) => {
-                let pairs = pairs.clone();
-                self.eval_map_index_expression(pairs, index)
-            }
-            _ => {
-                let message = format!("index operator not supported: {}", left.get_type());
-                return Err(message);
+    /// `exec` によるコマンドの実行を許可する。`sandbox` の中の評価器には引き継がない
+    pub fn set_exec(&mut self, exec: bool) {
+        self.exec = exec;
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn is_exec_allowed(&self) -> bool {
+        self.exec
+    }
+
+    /// `puts` の出力先を設定する（None で標準出力に戻す。std がなければ捨てる）
+    pub fn set_output(&mut self, output: Option<Rc<RefCell<dyn fmt::Write>>>) {
+        self.output = output;
+    }
+
+    /// `puts` の出力を 1 行ずつ関数に渡す（サーバーや Web のプレイグラウンドで経過を逐次表示する）
+    pub fn set_output_callback(&mut self, callback: impl FnMut(&str) + 'static) {
+        self.output = Some(Rc::new(RefCell::new(LineCallback::new(callback))));
+    }
+
+    /// `puts` の 1 行を出力する
+    pub(crate) fn write_line(&mut self, line: &str) -> Result<(), EvalError> {
+        match &self.output {
+            Some(output) => writeln!(output.borrow_mut(), "{}", line)
+                .map_err(|_| "failed to write output".into()),
+            #[cfg(feature = "std")]
+            None => {
+                std::println!("{}", line);
+                Ok(())
             }
+            #[cfg(not(feature = "std"))]
+            None => Ok(()),
         }
     }
 
-    fn eval_array_index_expression(&mut self, elements: Vec<Object>, index: isize) -> EvalResult {
-        let result = {
-            let max = elements.len() - 1;
+    /// `read_line` と `input` の入力元を設定する（None で標準入力に戻す）
+    pub fn set_input(&mut self, input: Option<InputSource>) {
+        self.input = input;
+    }
 
-            if index < 0 || index > (max as isize) {
-                Object::Null
-            } else {
-                elements[index as usize].clone()
+    #[cfg(feature = "std")]
+    pub(crate) fn input(&self) -> Option<InputSource> {
+        self.input.clone()
+    }
+
+    /// プロンプトを表示してから入力を 1 行読む（入力の終わりなら null）
+    pub(crate) fn read_input(&mut self, name: &str, prompt: Option<&str>) -> EvalResult {
+        self.check_pure(name)?;
+
+        if let Some(prompt) = prompt {
+            self.write_prompt(prompt)?;
+        }
+
+        let input = self.input.clone();
+
+        self.nondeterministic(name, move || {
+            let line = match input {
+                Some(input) => (input.borrow_mut())(),
+                #[cfg(feature = "std")]
+                None => {
+                    let mut line = String::new();
+
+                    match std::io::stdin().read_line(&mut line) {
+                        Ok(0) => None,
+                        Ok(_) => Some(line.trim_end_matches(&['\n', '\r'][..]).to_string()),
+                        Err(error) => {
+                            let message = format!("failed to read standard input: {}", error);
+                            return Err(message.into());
+                        }
+                    }
+                }
+                #[cfg(not(feature = "std"))]
+                None => None,
+            };
+
+            Ok(line.map_or(Object::Null, Object::String))
+        })
+    }
+
+    /// 改行をつけずに出力する
+    fn write_prompt(&mut self, prompt: &str) -> Result<(), EvalError> {
+        match &self.output {
+            Some(output) => write!(output.borrow_mut(), "{}", prompt)
+                .map_err(|_| "failed to write output".into()),
+            #[cfg(feature = "std")]
+            None => {
+                use std::io::Write;
+
+                std::print!("{}", prompt);
+                std::io::stdout()
+                    .flush()
+                    .map_err(|_| "failed to write output".into())
             }
-        };
+            #[cfg(not(feature = "std"))]
+            None => Ok(()),
+        }
+    }
 
-        Ok(result)
+    /// 中断の要求を受けとるフラグを設定する。フラグが立つと次の文の前で評価を中断する
+    pub fn set_interrupt(&mut self, interrupt: Option<Arc<AtomicBool>>) {
+        self.interrupt = interrupt;
     }
 
-    fn eval_map_index_expression(
+    pub(crate) fn interrupt(&self) -> Option<Arc<AtomicBool>> {
+        self.interrupt.clone()
+    }
+
+    /// ホストの資源にふれる組み込み関数（`puts` やファイル、時計）を呼ぶたびに呼ぶ関数を設定する。
+    /// 拒否された呼び出しも記録するので、信頼できないスクリプトが試みたことを監査できる
+    pub fn set_audit(&mut self, audit: Option<AuditHook>) {
+        self.audit = audit;
+    }
+
+    /// 割りこみを積む。束縛・組み込み関数の呼び出し・モジュールの読みこみの前に、積んだ順に呼ばれ、
+    /// どれかがエラーを返すと操作をせずにそのエラーで評価を終える
+    pub fn push_interceptor(&mut self, interceptor: Interceptor) {
+        self.interceptors.push(interceptor);
+    }
+
+    /// 最後に積んだ割りこみを取り除く
+    pub fn pop_interceptor(&mut self) -> Option<Interceptor> {
+        self.interceptors.pop()
+    }
+
+    /// 評価中の文（割りこみを積んでいるときだけ記録する）
+    pub fn current_statement(&self) -> Option<&str> {
+        self.statement.as_deref()
+    }
+
+    /// 積んだ割りこみを順に呼び、どれかが操作を拒否すればそのエラーを返す
+    fn intercept(&self, operation: Operation) -> Result<(), EvalError> {
+        for interceptor in self.interceptors.iter() {
+            (interceptor.borrow_mut())(self, &operation)?;
+        }
+
+        Ok(())
+    }
+
+    /// 組み込み関数の本体を実行し、監査の関数が設定されていれば呼び出しを記録する
+    pub(crate) fn audited(
         &mut self,
-        pairs: BTreeMap<MapKey, MapPair>,
-        index: Object,
+        name: &str,
+        arguments: Vec<Object>,
+        function: impl FnOnce(&mut Self, Vec<Object>) -> EvalResult,
     ) -> EvalResult {
-        let map_key = match MapKey::from(&index) {
-            MapKey::Unusable => {
-                let message = format!("unusable as map key: {}", index.get_type());
-                return Err(message.to_string());
-            }
-            map_key => map_key,
+        let audit = match &self.audit {
+            Some(audit) => Rc::clone(audit),
+            None => return function(self, arguments),
         };
 
-        let result = match pairs.get(&map_key) {
-            Some(MapPair { value, .. }) => value.clone(),
-            None => Object::Null,
+        let summary = AuditEvent::summarize(&arguments);
+        #[cfg(feature = "std")]
+        let started = std::time::Instant::now();
+
+        let result = function(self, arguments);
+
+        #[cfg(feature = "std")]
+        let duration = Some(started.elapsed());
+        #[cfg(not(feature = "std"))]
+        let duration = None;
+
+        let event = AuditEvent {
+            name: name.to_string(),
+            arguments: summary,
+            duration,
+            outcome: result.as_ref().map(|_| ()).map_err(EvalError::to_string),
         };
+        (audit.borrow_mut())(&event);
 
-        Ok(result)
+        result
     }
 
-    fn eval_map_expression(&mut self, pairs: BTreeMap<Expression, Expression>) -> EvalResult {
-        let mut map = BTreeMap::new();
-
-        for (key, value) in pairs.iter() {
-            let key = self.eval_expression(key)?;
-            let value = self.eval_expression(value)?;
+    /// 中断時に呼ぶ関数を登録する（前に登録した関数は置きかえる）
+    pub(crate) fn set_interrupt_handler(&mut self, handler: Object) {
+        self.host.insert(InterruptHandler(handler));
+    }
 
-            let map_key = match MapKey::from(&key) {
-                MapKey::Unusable => {
-                    let message = format!("unusable as map key: {}", key.get_type());
-                    return Err(message.to_string());
-                }
-                map_key => map_key,
-            };
+    /// 中断が要求されていれば、登録された関数を 1 度だけ呼んでからエラーにする
+    pub(crate) fn check_interrupt(&mut self) -> Result<(), EvalError> {
+        let requested = match &self.interrupt {
+            Some(interrupt) => interrupt.swap(false, AtomicOrdering::SeqCst),
+            None => false,
+        };
 
-            let map_pair = MapPair::new(key, value);
+        if !requested {
+            return Ok(());
+        }
 
-            map.insert(map_key, map_pair);
+        if let Some(InterruptHandler(handler)) = self.host.remove() {
+            self.apply_function(handler, vec![])?;
         }
 
-        let result = Object::Map(map);
+        Err(EvalError::Interrupted)
+    }
 
-        Ok(result)
+    /// 組み込み関数の状態
+    pub fn host(&self) -> &HostState {
+        &self.host
     }
 
-    fn apply_function(&mut self, function: Object, arguments: Vec<Object>) -> EvalResult {
-        let result = match &function {
-            Object::Function {
-                parameters,
-                body,
-                env,
-            } => {
-                self.check_arity(parameters.len(), arguments.len())?;
+    pub fn host_mut(&mut self) -> &mut HostState {
+        &mut self.host
+    }
 
-                let mut env = Self::new_with_outer(Box::new(env.clone()));
+    /// `import` がモジュールを探すディレクトリを設定する（空ならパスをそのまま読む）
+    pub fn set_import_paths(&mut self, paths: Vec<String>) {
+        self.import_paths = paths;
+    }
 
-                for (i, parameter) in parameters.iter().enumerate() {
-                    match parameter {
-                        Expression::Identifier(name) => {
-                            env.set(name.to_string(), arguments[i].clone())?;
-                        }
-                        _ => {
-                            let message = format!("invalid argument index: {}", 0).to_string();
-                            return Err(message);
-                        }
-                    }
-                }
+    /// モジュールを探して評価し、トップレベルの束縛（`_` で始まる名前を除く）をマップで返す。
+    /// 同じファイルは 1 度だけ評価する
+    pub(crate) fn import(&mut self, path: &str) -> EvalResult {
+        self.intercept(Operation::Import { path })?;
 
-                env.eval_statement(&body)?
-            }
-            Object::Buildin { function } => function(arguments)?,
-            _ => {
-                let message = format!("not a function: {}", function.get_type()).to_string();
-                return Err(message);
+        let file = if path.ends_with(".monkey") {
+            path.to_string()
+        } else {
+            format!("{}.monkey", path)
+        };
+        let candidates = if self.import_paths.is_empty() {
+            vec![file]
+        } else {
+            self.import_paths
+                .iter()
+                .map(|dir| format!("{}/{}", dir.trim_end_matches('/'), file))
+                .collect()
+        };
+
+        let (file, source) = match candidates
+            .into_iter()
+            .find_map(|file| self.read_file(&file).ok().map(|source| (file, source)))
+        {
+            Some(found) => found,
+            None => {
+                let message = format!("module not found: {}", path);
+                return Err(message.into());
             }
         };
 
-        Ok(result)
-    }
+        let mut modules = self.host.remove::<Modules>().unwrap_or_default();
+        let result = self.eval_module(&file, &source, &mut modules);
+        self.host.insert(modules);
 
-    fn check_arity(&mut self, parameters: usize, arguments: usize) -> Result<(), EvalError> {
-        if parameters == arguments {
-            Ok(())
-        } else {
-            let message = format!(
-                "expected arity to be {}, got {} instead",
-                parameters, arguments
-            )
-            .to_string();
-            Err(message)
-        }
+        result
     }
-}
 
-fn is_truthy(object: Object) -> bool {
-    match object {
-        Object::Boolean(false) => false,
-        Object::Null => false,
-        _ => true,
-    }
-}
+    fn eval_module(&self, file: &str, source: &str, modules: &mut Modules) -> EvalResult {
+        if let Some(module) = modules.loaded.get(file) {
+            return Ok(module.clone());
+        }
 
-#[cfg(test)]
-mod tests {
-    use crate::ast::{Expression, Statement};
-    use crate::evaluator::{Environment, Response};
-    use crate::lexer::Lexer;
-    use crate::object::{MapKey, MapPair, Object};
-    use crate::parser::Parser;
-    use crate::token::Token;
-    use std::collections::BTreeMap;
+        if modules.loading.iter().any(|loading| loading == file) {
+            let message = format!("import cycle: {} -> {}", modules.loading.join(" -> "), file);
+            return Err(message.into());
+        }
 
-    fn test_eval(input: &str) -> Response {
-        let mut lexer = Lexer::new(input);
+        let mut lexer = Lexer::with_interner(source, self.interner.clone());
         let mut parser = Parser::new(&mut lexer);
         let program = parser.parse_program();
-        let mut env = Environment::new();
-        env.eval(program)
+
+        // 続くエラーは最初のエラーから連鎖したものが多いので、最初の 1 つだけを返す
+        if let Some(error) = parser.get_errors().first() {
+            let message = format!("{}: {}", file, error);
+            return Err(message.into());
+        }
+
+        let mut evaluator = Evaluator::new();
+        evaluator.env.borrow_mut().buildin = self.env.borrow().buildin.clone();
+        evaluator.vfs = self.vfs.clone();
+        evaluator.pure = self.pure;
+        evaluator.deterministic = self.deterministic;
+        evaluator.output = self.output.clone();
+        evaluator.input = self.input.clone();
+        evaluator.exec = self.exec;
+        evaluator.interrupt = self.interrupt.clone();
+        evaluator.audit = self.audit.clone();
+        evaluator.interceptors = self.interceptors.clone();
+        evaluator.statement_limit = self.statement_limit;
+        evaluator.recursion_limit = self.recursion_limit;
+        evaluator.call_depth = self.call_depth;
+        evaluator.import_paths = self.import_paths.clone();
+        evaluator.interner = self.interner.clone();
+
+        modules.loading.push(file.to_string());
+        evaluator.host.insert(mem::take(modules));
+        let response = evaluator.eval(program);
+        *modules = evaluator.host.remove::<Modules>().unwrap_or_default();
+        modules.loading.pop();
+
+        // 位置はモジュールのファイルの中のものなので、import した文の位置をつけ直す
+        if let Response::Error(error) = response {
+            return Err(error.kind().clone());
+        }
+
+        let mut pairs = BTreeMap::new();
+
+        for (name, value) in evaluator.bindings() {
+            if name.starts_with('_') {
+                continue;
+            }
+
+            let key = Object::String(name);
+            pairs.insert(MapKey::from(&key), MapPair::new(key, value));
+        }
+
+        let module = Object::Map(pairs);
+        modules.loaded.insert(file.to_string(), module.clone());
+
+        Ok(module)
     }
 
-    fn assert_object(input: &str, expected: Object) {
-        match test_eval(input) {
-            Response::Reply(result) => assert_eq!(result, expected),
-            _ => unreachable!(),
+    /// 仮想ファイルシステムからファイルを読む
+    pub(crate) fn read_file(&self, path: &str) -> Result<String, EvalError> {
+        match &self.vfs {
+            Some(vfs) => vfs.read(path).map_err(EvalError::from),
+            None => {
+                let message = format!("no filesystem available to read {}", path);
+                Err(message.into())
+            }
         }
     }
 
-    fn assert_objects(tests: Vec<(&str, Object)>) {
-        for (input, expected) in tests {
-            assert_object(input, expected);
+    /// 仮想ファイルシステムのファイルを開く（書きこみは副作用として扱う）
+    pub(crate) fn open_file(
+        &self,
+        path: &str,
+        mode: OpenMode,
+    ) -> Result<Box<dyn VfsFile>, EvalError> {
+        if mode != OpenMode::Read {
+            self.check_pure("open")?;
         }
+
+        match &self.vfs {
+            Some(vfs) => vfs.open(path, mode).map_err(EvalError::from),
+            None => {
+                let message = format!("no filesystem available to open {}", path);
+                Err(message.into())
+            }
+        }
+    }
+
+    /// 直近の評価エラーのスナップショットを取り出す
+    pub fn take_snapshot(&mut self) -> Option<Snapshot> {
+        self.snapshot.take()
+    }
+
+    /// 現在のスコープから束縛を探す
+    pub fn get(&self, name: &str) -> Option<Object> {
+        self.env.borrow().get(name).ok()
     }
 
-    fn assert_errors(tests: Vec<(&str, &str)>) {
-        for (input, expected) in tests {
-            match test_eval(input) {
-                Response::Error(message) => assert_eq!(message, expected),
-                _ => unreachable!(),
-            }
-        }
+    /// 現在のスコープに束縛を加える
+    pub fn set(&mut self, name: &str, value: Object) {
+        let name = self.interner.borrow_mut().intern(name);
+        self.env.borrow_mut().store.insert(name, value);
+    }
+
+    /// 現在のスコープから束縛を取り除く
+    pub fn remove(&mut self, name: &str) -> Option<Object> {
+        self.env.borrow_mut().store.remove(name)
+    }
+
+    /// 現在のスコープの束縛を返す
+    pub fn bindings(&self) -> BTreeMap<String, Object> {
+        sorted(&self.env.borrow().store)
+    }
+
+    /// ホストの関数を組み込み関数として登録する（状態を捕まえたクロージャーでもよい）
+    pub fn register_builtin(
+        &mut self,
+        name: &str,
+        function: impl Fn(&mut Evaluator, Vec<Object>) -> EvalResult + 'static,
+    ) {
+        self.env.borrow_mut().register_builtin(name, function);
+    }
+
+    /// 登録されている組み込み関数の名前を名前の順に返す
+    pub fn buildin_names(&self) -> Vec<String> {
+        let mut names = self
+            .env
+            .borrow()
+            .buildin
+            .borrow()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// 式を 1 ノードずつ評価し、評価した順に各ノードの結果を返す
+    pub fn eval_stepping(&mut self, program: Program) -> (Response, Vec<Step>) {
+        self.steps = Some(vec![]);
+        self.depth = 0;
+
+        let response = self.eval(program);
+        let steps = self.steps.take().unwrap_or_default();
+
+        (response, steps)
+    }
+
+    pub fn eval(&mut self, program: Program) -> Response {
+        let mut program = program;
+        let mut result = Object::Default;
+
+        self.snapshot = None;
+        self.error_position = None;
+        self.error_trace.clear();
+        self.frames.clear();
+        self.call_depth = 0;
+        self.thrown = None;
+        self.statements_left = self.statement_limit.unwrap_or(0);
+
+        let defined = self.define_macros(&mut program);
+
+        if let Err(error) = self.expand_macros(&mut program) {
+            return Response::Error(error);
+        }
+
+        if defined > 0 && program.statements.is_empty() {
+            return Response::NoReply;
+        }
+
+        for statement in program.statements.iter() {
+            result = match self.eval_statement(statement) {
+                Ok(Object::Return(result)) => return Response::Reply(*result),
+                Ok(result) => result,
+                Err(error) => return Response::Error(self.locate(error)),
+            }
+        }
+
+        match result {
+            Object::Let => Response::NoReply,
+            _ => Response::Reply(result),
+        }
+    }
+
+    /// トップレベルのマクロ定義を取り除いて環境に束縛し、その数を返す
+    fn define_macros(&mut self, program: &mut Program) -> usize {
+        let mut defined = 0;
+        let mut statements = vec![];
+
+        for statement in mem::take(&mut program.statements) {
+            match statement {
+                Statement::Let {
+                    name: Expression::Identifier(name),
+                    value: Expression::Macro { parameters, body },
+                    ..
+                } => {
+                    let object = Object::Macro {
+                        parameters,
+                        body: *body,
+                        env: self.env.clone(),
+                    };
+                    self.env.borrow_mut().store.insert(name, object);
+                    defined += 1;
+                }
+                statement => statements.push(statement),
+            }
+        }
+
+        program.statements = statements;
+
+        defined
+    }
+
+    /// マクロの呼び出しを、マクロが返した式で置きかえる
+    fn expand_macros(&mut self, program: &mut Program) -> Result<(), EvalError> {
+        modify_program(program, &mut |expression| {
+            let (parameters, body, env, arguments) = match expression {
+                Expression::Call {
+                    function,
+                    arguments,
+                } => {
+                    let object = match function.as_ref() {
+                        Expression::Identifier(name) => self.env.borrow().get(name),
+                        _ => return Ok(()),
+                    };
+
+                    match object {
+                        Ok(Object::Macro {
+                            parameters,
+                            body,
+                            env,
+                        }) => (parameters, body, env, mem::take(arguments)),
+                        _ => return Ok(()),
+                    }
+                }
+                _ => return Ok(()),
+            };
+
+            self.check_arity(parameters.len(), arguments.len())?;
+
+            let mut env = Environment::new_with_outer(env);
+
+            for (parameter, argument) in parameters.iter().zip(arguments) {
+                if let Expression::Identifier(name) = parameter {
+                    env.set(name.clone(), Object::Quote(argument))?;
+                }
+            }
+
+            let outer = mem::replace(&mut self.env, Env::new(env));
+            let result = self.eval_statement(&body);
+            self.env = outer;
+
+            let result = match result? {
+                Object::Return(value) => *value,
+                object => object,
+            };
+
+            match result {
+                Object::Quote(quoted) => *expression = quoted,
+                _ => {
+                    let message = "we only support returning AST-nodes from macros".to_string();
+                    return Err(message.into());
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// quote の引数の中にある unquote を評価して、その値の式で置きかえる
+    fn eval_quote_expression(&mut self, argument: &Expression) -> EvalResult {
+        let mut quoted = argument.clone();
+
+        modify_expression(&mut quoted, &mut |expression| {
+            let argument = match expression {
+                Expression::Call {
+                    function,
+                    arguments,
+                } if is_special_form("unquote", function, arguments) => &arguments[0],
+                _ => return Ok(()),
+            };
+
+            let unquoted = match self.eval_expression(argument)? {
+                Object::Integer(value) => Expression::Integer(value),
+                Object::Float(value) => Expression::Float(value),
+                #[cfg(feature = "rational")]
+                Object::Rational(value) => Expression::Rational(value),
+                Object::Boolean(value) => Expression::Boolean(value),
+                Object::String(value) => Expression::String(value),
+                Object::Quote(expression) => expression,
+                object => {
+                    let message = format!("cannot unquote {}", object.get_type());
+                    return Err(message.into());
+                }
+            };
+
+            *expression = unquoted;
+
+            Ok(())
+        })?;
+
+        let result = Object::Quote(quoted);
+
+        Ok(result)
+    }
+
+    /// ジェネレーターを次の `yield` まで進めてその値を返す（終わっていれば None）
+    pub(crate) fn resume(&mut self, generator: &Generator) -> Result<Option<Object>, EvalError> {
+        if mem::replace(&mut generator.borrow_mut().running, true) {
+            let message = "generator is already running".to_string();
+            return Err(message.into());
+        }
+
+        let env = generator.borrow().env.clone();
+        let outer = mem::replace(&mut self.env, env);
+        let result = self.resume_frames(generator);
+        self.env = outer;
+
+        generator.borrow_mut().running = false;
+
+        result
+    }
+
+    fn resume_frames(&mut self, generator: &Generator) -> Result<Option<Object>, EvalError> {
+        loop {
+            let delegate = generator.borrow().delegate.clone();
+
+            if let Some(delegate) = delegate {
+                match self.resume(&delegate)? {
+                    Some(value) => return Ok(Some(value)),
+                    None => {
+                        generator.borrow_mut().delegate = None;
+                        continue;
+                    }
+                }
+            }
+
+            let next = {
+                let mut state = generator.borrow_mut();
+
+                match state.frames.last_mut() {
+                    Some((statements, index)) => {
+                        let statement = statements.get(*index).cloned();
+                        *index += 1;
+                        let last = state.frames.iter().all(|(s, i)| *i >= s.len());
+                        Some((statement, last))
+                    }
+                    None => None,
+                }
+            };
+
+            let (statement, last) = match next {
+                Some((Some(statement), last)) => (statement, last),
+                Some((None, _)) => {
+                    generator.borrow_mut().frames.pop();
+                    continue;
+                }
+                None => return Ok(None),
+            };
+
+            match &statement {
+                Statement::Expression(Expression::Yield(value), _) => {
+                    self.record_statement(&statement)?;
+                    let value = self.eval_expression(value)?;
+                    return Ok(Some(value));
+                }
+                Statement::Expression(
+                    Expression::If {
+                        condition,
+                        consequence,
+                        alternative,
+                    },
+                    _,
+                ) if has_yield(&statement) => {
+                    self.record_statement(&statement)?;
+                    let condition = self.eval_expression(condition)?;
+
+                    let branch = if is_truthy(condition) {
+                        Some(consequence)
+                    } else {
+                        alternative.as_ref()
+                    };
+
+                    if let Some(branch) = branch {
+                        let frame = (block_statements(branch), 0);
+                        generator.borrow_mut().frames.push(frame);
+                    }
+                }
+                _ => {
+                    let value = self.eval_statement(&statement)?;
+
+                    let value = match value {
+                        Object::Return(value) => *value,
+                        _ if last => value,
+                        _ => continue,
+                    };
+
+                    let mut state = generator.borrow_mut();
+                    state.frames.clear();
+
+                    // 最後に返したジェネレーターに続きを委ねる（ほかで共有されていなければ状態ごと引き継ぐ）
+                    if let Object::Generator(delegate) = value {
+                        match Rc::try_unwrap(delegate.0) {
+                            Ok(delegate) => {
+                                let delegate = delegate.into_inner();
+                                state.env = delegate.env;
+                                state.frames = delegate.frames;
+                                state.delegate = delegate.delegate;
+                                self.env = state.env.clone();
+                            }
+                            Err(delegate) => state.delegate = Some(Generator(delegate)),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn record(&mut self, event: impl FnOnce() -> TraceEvent) {
+        if let Some(trace) = &mut self.trace {
+            trace.record(event());
+        }
+    }
+
+    fn record_statement(&mut self, statement: &Statement) -> Result<(), EvalError> {
+        self.record(|| TraceEvent::Statement(statement.to_string()));
+
+        if let Some(position) = statement.position() {
+            self.current_position = position;
+        }
+
+        if !self.interceptors.is_empty() {
+            self.statement = Some(statement.to_string());
+        }
+
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(statement);
+        }
+
+        self.check_interrupt()?;
+
+        if let Some(limit) = self.statement_limit {
+            if self.statements_left == 0 {
+                let error = EvalError::StatementLimit(limit);
+                return Err(error);
+            }
+
+            self.statements_left -= 1;
+        }
+
+        Ok(())
+    }
+
+    fn eval_statement(&mut self, statement: &Statement) -> EvalResult {
+        if !matches!(statement, Statement::Block(_)) {
+            self.record_statement(statement)?;
+        }
+
+        let result = match statement {
+            Statement::Expression(expression, _) => self.eval_expression(expression),
+            Statement::Block(statements) => self.eval_block_statement(statements),
+            Statement::Return(expression, _) => self.eval_return_statement(expression),
+            Statement::Let { name, value, .. } => self.eval_let_statement(name, value),
+        };
+
+        if let Err(error) = &result {
+            self.capture_position(statement);
+            self.capture_snapshot(error, statement);
+        }
+
+        result
+    }
+
+    /// 関数本体の末尾位置にある呼び出しを評価せずに返す
+    fn eval_tail_statement(&mut self, statement: &Statement) -> Result<Tail, EvalError> {
+        // let 文は eval_statement に委ねるので、そちらで記録する
+        if !matches!(statement, Statement::Block(_) | Statement::Let { .. }) {
+            self.record_statement(statement)?;
+        }
+
+        let result = match statement {
+            Statement::Expression(expression, _) | Statement::Return(expression, _) => {
+                self.eval_tail_expression(expression)
+            }
+            Statement::Block(statements) => match statements.split_last() {
+                Some((last, init)) => {
+                    for statement in init {
+                        if let Object::Return(value) = self.eval_statement(statement)? {
+                            return Ok(Tail::Value(*value));
+                        }
+                    }
+
+                    self.eval_tail_statement(last)
+                }
+                None => Ok(Tail::Value(Object::Default)),
+            },
+            Statement::Let { .. } => self.eval_statement(statement).map(Tail::Value),
+        };
+
+        if let Err(error) = &result {
+            self.capture_position(statement);
+            self.capture_snapshot(error, statement);
+        }
+
+        result
+    }
+
+    fn eval_tail_expression(&mut self, expression: &Expression) -> Result<Tail, EvalError> {
+        let result = match expression {
+            Expression::Call {
+                function: callee,
+                arguments,
+            } if !is_special_form("quote", callee, arguments) => {
+                let function = self.eval_expression(callee)?;
+                let arguments = self.eval_expressions(arguments)?;
+
+                self.record(|| TraceEvent::Call {
+                    function: callee.to_string(),
+                    arguments: arguments.iter().map(Object::to_string).collect(),
+                });
+
+                if let Some(call_graph) = &mut self.call_graph {
+                    call_graph.tail_call(callee.to_string());
+                }
+
+                let caller = match self.frames.last() {
+                    Some(frame) => frame.function.clone(),
+                    None => "main".to_string(),
+                };
+                let frame = Frame {
+                    function: callee.to_string(),
+                    caller,
+                    call_site: self.current_position,
+                };
+
+                Tail::Call(function, arguments, Some(frame))
+            }
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                let condition = self.eval_expression(condition)?;
+
+                match (is_truthy(condition), alternative) {
+                    (true, _) => self.eval_tail_statement(consequence)?,
+                    (_, Some(statement)) => self.eval_tail_statement(statement)?,
+                    (_, _) => Tail::Value(Object::Null),
+                }
+            }
+            Expression::Grouped(expression) => self.eval_tail_expression(expression)?,
+            _ => Tail::Value(self.eval_expression(expression)?),
+        };
+
+        Ok(result)
+    }
+
+    fn capture_position(&mut self, statement: &Statement) {
+        self.capture_site(statement.position());
+    }
+
+    /// 最初に記録したエラーの位置と呼び出しスタックを残す
+    fn capture_site(&mut self, position: Option<Position>) {
+        if self.error_position.is_none() {
+            self.error_position = position;
+            self.error_trace = self.frames.iter().rev().cloned().collect();
+        }
+    }
+
+    /// トップレベルまで伝わったエラーに、それが起きた文の位置と呼び出しスタックをつける
+    fn locate(&mut self, error: EvalError) -> EvalError {
+        let trace = mem::take(&mut self.error_trace);
+
+        match self.error_position.take() {
+            Some(position) => error.at(position, trace),
+            None => error,
+        }
+    }
+
+    fn capture_snapshot(&mut self, error: &EvalError, statement: &Statement) {
+        if self.snapshot.is_some() {
+            return;
+        }
+
+        if let Some(trace) = &self.trace {
+            let snapshot = Snapshot::new(
+                error.to_string(),
+                statement,
+                self.env.borrow().scopes(),
+                trace.events(),
+            );
+            self.snapshot = Some(snapshot);
+        }
+    }
+
+    fn eval_block_statement(&mut self, statements: &Vec<Statement>) -> EvalResult {
+        let mut result = Object::Default;
+
+        for statement in statements {
+            result = self.eval_statement(statement)?;
+
+            if let Object::Return(_) = result {
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn eval_return_statement(&mut self, expression: &Expression) -> EvalResult {
+        let result = self.eval_expression(expression)?;
+        let result = Box::new(result);
+        let result = Object::Return(result);
+
+        Ok(result)
+    }
+
+    fn eval_let_statement(&mut self, name: &Expression, object: &Expression) -> EvalResult {
+        let result = match name {
+            Expression::Identifier(name) => {
+                let name = name.clone();
+                let object = self.eval_expression(object)?;
+                self.intercept(Operation::Bind {
+                    name: &name,
+                    value: &object,
+                })?;
+                self.env.borrow_mut().set(name, object)?;
+                Object::Let
+            }
+            _ => return Err("unexpected error occurred in let binding".into()),
+        };
+
+        Ok(result)
+    }
+
+    fn eval_expression(&mut self, expression: &Expression) -> EvalResult {
+        if self.steps.is_none() {
+            return self.eval_expression_node(expression);
+        }
+
+        self.depth += 1;
+        let result = self.eval_expression_node(expression);
+        self.depth -= 1;
+
+        if let (Some(steps), Ok(value)) = (&mut self.steps, &result) {
+            steps.push(Step {
+                depth: self.depth,
+                expression: expression.to_string(),
+                value: value.to_string(),
+            });
+        }
+
+        result
+    }
+
+    fn eval_expression_node(&mut self, expression: &Expression) -> EvalResult {
+        let result = match expression {
+            Expression::Integer(value) => {
+                let value = *value;
+                Object::Integer(value)
+            }
+            Expression::Float(value) => {
+                let value = *value;
+                Object::Float(value)
+            }
+            #[cfg(feature = "rational")]
+            Expression::Rational(value) => {
+                let value = *value;
+                Object::Rational(value)
+            }
+            Expression::Boolean(value) => {
+                let value = *value;
+                Object::Boolean(value)
+            }
+            Expression::String(value) => {
+                let value = value.to_string();
+                Object::String(value)
+            }
+            Expression::Prefix { operator, right } => {
+                let right = self.eval_expression(right)?;
+                self.eval_prefix_expression(operator, right)?
+            }
+            Expression::Infix {
+                left,
+                operator: Token::Coalesce,
+                right,
+            } => match self.eval_expression(left)? {
+                Object::Null => self.eval_expression(right)?,
+                left => left,
+            },
+            Expression::Infix {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.eval_expression(left)?;
+                let right = self.eval_expression(right)?;
+                self.eval_infix_expression(left, operator, right)?
+            }
+            Expression::Grouped(expression) => self.eval_expression(expression)?,
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                let condition = self.eval_expression(condition)?;
+                self.eval_if_expression(condition, consequence, alternative)?
+            }
+            Expression::Try {
+                body,
+                parameter,
+                handler,
+            } => self.eval_try_expression(body, parameter, handler)?,
+            Expression::Match { subject, arms } => {
+                let subject = self.eval_expression(subject)?;
+                self.eval_match_expression(subject, arms)?
+            }
+            Expression::Identifier(value) => self.eval_identifier_expression(value)?,
+            Expression::Function { parameters, body } => {
+                self.eval_function_expression(parameters, body)?
+            }
+            Expression::Macro { parameters, body } => Object::Macro {
+                parameters: parameters.to_vec(),
+                body: *body.clone(),
+                env: self.env.clone(),
+            },
+            Expression::Call {
+                function: callee,
+                arguments,
+            } if is_special_form("quote", callee, arguments) => {
+                self.eval_quote_expression(&arguments[0])?
+            }
+            Expression::Call {
+                function: callee,
+                arguments,
+            } => {
+                let function = self.eval_expression(callee)?;
+                let arguments = self.eval_expressions(arguments)?;
+
+                self.record(|| TraceEvent::Call {
+                    function: callee.to_string(),
+                    arguments: arguments.iter().map(Object::to_string).collect(),
+                });
+
+                if let Some(call_graph) = &mut self.call_graph {
+                    call_graph.enter(callee.to_string());
+                }
+
+                let caller = match self.frames.last() {
+                    Some(frame) => frame.function.clone(),
+                    None => "main".to_string(),
+                };
+                let call_site = self.current_position;
+                self.frames.push(Frame {
+                    function: callee.to_string(),
+                    caller,
+                    call_site,
+                });
+
+                let result = self.apply_function(function, arguments);
+
+                self.frames.pop();
+                self.current_position = call_site;
+
+                if let Some(call_graph) = &mut self.call_graph {
+                    call_graph.leave();
+                }
+
+                let result = result?;
+
+                self.record(|| TraceEvent::Return(result.to_string()));
+
+                result
+            }
+            Expression::Array(elements) => {
+                let elements = self.eval_expressions(elements)?;
+                Object::Array(elements)
+            }
+            Expression::Index { left, index } => {
+                let left = self.eval_expression(left)?;
+                let index = self.eval_expression(index)?;
+                self.eval_index_expression(left, index)?
+            }
+            Expression::Slice { left, start, end } => {
+                let left = self.eval_expression(left)?;
+                let start = match start {
+                    Some(start) => Some(self.eval_expression(start)?),
+                    None => None,
+                };
+                let end = match end {
+                    Some(end) => Some(self.eval_expression(end)?),
+                    None => None,
+                };
+                self.eval_slice_expression(left, start, end)?
+            }
+            Expression::Map(pairs) => {
+                let pairs = pairs.clone();
+                self.eval_map_expression(pairs)?
+            }
+            Expression::Yield(_) => {
+                let message = "yield outside generator".to_string();
+                return Err(message.into());
+            }
+        };
+
+        Ok(result)
+    }
+
+    fn eval_prefix_expression(&mut self, operator: &Token, right: Object) -> EvalResult {
+        let result = match operator {
+            Token::Bang => self.eval_bang_prefix_expression(right)?,
+            Token::Minus => self.eval_minus_prefix_expression(right)?,
+            _ => {
+                let right = right.get_type();
+                let error = EvalError::UnknownOperator(format!("{}{}", operator, right));
+                return Err(error);
+            }
+        };
+
+        Ok(result)
+    }
+
+    fn eval_bang_prefix_expression(&mut self, right: Object) -> EvalResult {
+        let result = match right {
+            Object::Boolean(false) => Object::Boolean(true),
+            Object::Null => Object::Boolean(true),
+            _ => Object::Boolean(false),
+        };
+
+        Ok(result)
+    }
+
+    fn eval_minus_prefix_expression(&mut self, right: Object) -> EvalResult {
+        let result = match Number::from_object(&right) {
+            Some(Number::Integer(value)) => self.eval_numeric_negation(value)?,
+            #[cfg(feature = "rational")]
+            Some(Number::Rational(value)) => self.eval_numeric_negation(value)?,
+            Some(Number::Float(value)) => self.eval_numeric_negation(value)?,
+            None => {
+                let right = right.get_type();
+                let error = EvalError::UnknownOperator(format!("-{}", right));
+                return Err(error);
+            }
+        };
+
+        Ok(result)
+    }
+
+    fn eval_numeric_negation<N: Numeric>(&mut self, value: N) -> EvalResult {
+        match value.neg() {
+            Ok(result) => Ok(result.into_object()),
+            Err(_) => {
+                let error = EvalError::IntegerOverflow(format!("-({})", value));
+                Err(error)
+            }
+        }
+    }
+
+    pub(crate) fn eval_infix_expression(
+        &mut self,
+        left: Object,
+        operator: &Token,
+        right: Object,
+    ) -> EvalResult {
+        if let (Some(left), Some(right)) = (Number::from_object(&left), Number::from_object(&right))
+        {
+            return match Number::promote(left, right) {
+                Pair::Integer(left, right) => {
+                    self.eval_numeric_infix_expression(left, operator, right)
+                }
+                #[cfg(feature = "rational")]
+                Pair::Rational(left, right) => {
+                    self.eval_numeric_infix_expression(left, operator, right)
+                }
+                Pair::Float(left, right) => {
+                    self.eval_numeric_infix_expression(left, operator, right)
+                }
+            };
+        }
+
+        let result = match (&left, &right) {
+            (Object::Boolean(left), Object::Boolean(right)) => {
+                let left = *left;
+                let right = *right;
+                self.eval_boolean_infix_expression(left, operator, right)?
+            }
+            (Object::String(left), Object::String(right)) => {
+                let left = left.to_string();
+                let right = right.to_string();
+                self.eval_string_infix_expression(left, operator, right)?
+            }
+            (Object::Map(left), Object::Map(right)) if operator == &Token::Plus => {
+                let mut pairs = left.clone();
+                pairs.extend(right.clone());
+                Object::Map(pairs)
+            }
+            (Object::String(value), Object::Integer(count))
+            | (Object::Integer(count), Object::String(value))
+                if operator == &Token::Asterisk =>
+            {
+                self.eval_string_repetition(value, *count)?
+            }
+            #[cfg(feature = "date")]
+            (Object::Date(_) | Object::Interval(_), Object::Date(_) | Object::Interval(_)) => {
+                self.eval_date_infix_expression(&left, operator, &right)?
+            }
+            _ => {
+                let error = EvalError::TypeMismatch {
+                    left: left.get_type(),
+                    operator: operator.to_string(),
+                    right: right.get_type(),
+                };
+                return Err(error);
+            }
+        };
+
+        Ok(result)
+    }
+
+    fn eval_numeric_infix_expression<N: Numeric>(
+        &mut self,
+        left: N,
+        operator: &Token,
+        right: N,
+    ) -> EvalResult {
+        let arithmetic = |result: Result<N, ArithmeticError>| match result {
+            Ok(result) => Ok(result.into_object()),
+            Err(ArithmeticError::Overflow) => {
+                let operation = format!("{} {} {}", left, operator, right);
+                Err(EvalError::IntegerOverflow(operation))
+            }
+            Err(ArithmeticError::DivisionByZero) => Err(EvalError::DivisionByZero),
+        };
+        let ordering = left.compare(right);
+
+        let result = match operator {
+            Token::Plus => arithmetic(left.add(right))?,
+            Token::Minus => arithmetic(left.sub(right))?,
+            Token::Asterisk => arithmetic(left.mul(right))?,
+            Token::Slash => arithmetic(left.div(right))?,
+            Token::Lt => Object::Boolean(ordering == Some(Ordering::Less)),
+            Token::Gt => Object::Boolean(ordering == Some(Ordering::Greater)),
+            Token::Eq => Object::Boolean(ordering == Some(Ordering::Equal)),
+            Token::Ne => Object::Boolean(ordering != Some(Ordering::Equal)),
+            Token::DotDot | Token::DotDotEq => {
+                let inclusive = operator == &Token::DotDotEq;
+
+                match left.range(right, inclusive) {
+                    Some(values) => {
+                        Object::Array(values?.into_iter().map(N::into_object).collect())
+                    }
+                    None => {
+                        let operation = format!("{} {} {}", N::TYPE, operator, N::TYPE);
+                        return Err(EvalError::UnknownOperator(operation));
+                    }
+                }
+            }
+            _ => {
+                let operation = format!("{} {} {}", N::TYPE, operator, N::TYPE);
+                return Err(EvalError::UnknownOperator(operation));
+            }
+        };
+
+        Ok(result)
+    }
+
+    /// 日付と期間の演算（日付 ± 期間、日付 - 日付、期間 ± 期間と同じ型どうしの比較）
+    #[cfg(feature = "date")]
+    fn eval_date_infix_expression(
+        &mut self,
+        left: &Object,
+        operator: &Token,
+        right: &Object,
+    ) -> EvalResult {
+        let ordering = match (left, right) {
+            (Object::Date(left), Object::Date(right)) => Some(left.cmp(right)),
+            (Object::Interval(left), Object::Interval(right)) => Some(left.cmp(right)),
+            _ => None,
+        };
+
+        let result = match (left, operator, right, ordering) {
+            (Object::Date(date), Token::Plus, Object::Interval(days), _)
+            | (Object::Interval(days), Token::Plus, Object::Date(date), _) => {
+                shift_date(*date, *days)?
+            }
+            (Object::Date(date), Token::Minus, Object::Interval(days), _) => {
+                shift_date(*date, days.saturating_neg())?
+            }
+            (Object::Date(left), Token::Minus, Object::Date(right), _) => {
+                Object::Interval((*left - *right).num_days() as isize)
+            }
+            (Object::Interval(left), Token::Plus | Token::Minus, Object::Interval(right), _) => {
+                let left = *left;
+                let right = *right;
+
+                match self.eval_numeric_infix_expression(left, operator, right)? {
+                    Object::Integer(days) => Object::Interval(days),
+                    result => result,
+                }
+            }
+            (_, Token::Lt, _, Some(ordering)) => Object::Boolean(ordering == Ordering::Less),
+            (_, Token::Gt, _, Some(ordering)) => Object::Boolean(ordering == Ordering::Greater),
+            (_, Token::Eq, _, Some(ordering)) => Object::Boolean(ordering == Ordering::Equal),
+            (_, Token::Ne, _, Some(ordering)) => Object::Boolean(ordering != Ordering::Equal),
+            _ => {
+                let error = EvalError::TypeMismatch {
+                    left: left.get_type(),
+                    operator: operator.to_string(),
+                    right: right.get_type(),
+                };
+                return Err(error);
+            }
+        };
+
+        Ok(result)
+    }
+
+    fn eval_boolean_infix_expression(
+        &mut self,
+        left: bool,
+        operator: &Token,
+        right: bool,
+    ) -> EvalResult {
+        let result = match operator {
+            Token::Eq => Object::Boolean(left == right),
+            Token::Ne => Object::Boolean(left != right),
+            _ => {
+                let operation = format!("Boolean {} Boolean", operator);
+                return Err(EvalError::UnknownOperator(operation));
+            }
+        };
+
+        Ok(result)
+    }
+
+    fn eval_string_infix_expression(
+        &mut self,
+        left: String,
+        operator: &Token,
+        right: String,
+    ) -> EvalResult {
+        let result = match operator {
+            Token::Plus => Object::String(format!("{}{}", left, right)),
+            Token::Eq => Object::Boolean(left == right),
+            Token::Ne => Object::Boolean(left != right),
+            _ => {
+                let operation = format!("String {} String", operator);
+                return Err(EvalError::UnknownOperator(operation));
+            }
+        };
+
+        Ok(result)
+    }
+
+    fn eval_string_repetition(&mut self, value: &str, count: isize) -> EvalResult {
+        if count < 0 {
+            let message = format!("negative repetition count: {}", count);
+            return Err(message.into());
+        }
+
+        match value.len().checked_mul(count as usize) {
+            Some(length) if length <= MAX_LENGTH => (),
+            _ => return Err(EvalError::LengthLimit(MAX_LENGTH)),
+        }
+
+        let result = Object::String(value.repeat(count as usize));
+        Ok(result)
+    }
+
+    fn eval_if_expression(
+        &mut self,
+        condition: Object,
+        consequence: &Statement,
+        alternative: &Option<Box<Statement>>,
+    ) -> EvalResult {
+        let result = match (is_truthy(condition), alternative) {
+            (true, _) => self.eval_statement(consequence)?,
+            (_, Some(statement)) => self.eval_statement(statement)?,
+            (_, _) => Object::Null,
+        };
+
+        Ok(result)
+    }
+
+    /// 中断と実行の上限のエラーは捕捉せず、そのまま評価を終える
+    fn eval_try_expression(
+        &mut self,
+        body: &Statement,
+        parameter: &Expression,
+        handler: &Statement,
+    ) -> EvalResult {
+        let error = match self.eval_statement(body) {
+            Ok(result) => return Ok(result),
+            Err(error) => error,
+        };
+
+        if matches!(
+            error.kind(),
+            EvalError::Interrupted | EvalError::StatementLimit(_) | EvalError::RecursionLimit(_)
+        ) {
+            return Err(error);
+        }
+
+        let name = match parameter {
+            Expression::Identifier(name) => name.clone(),
+            _ => return Err("unexpected error occurred in catch clause".into()),
+        };
+        let value = match self.thrown.take() {
+            Some(value) => value,
+            None => Object::String(error.to_string()),
+        };
+
+        self.snapshot = None;
+        self.error_position = None;
+        self.error_trace.clear();
+
+        let mut env = Environment::new_with_outer(self.env.clone());
+        env.set(name, value)?;
+
+        let outer = mem::replace(&mut self.env, Env::new(env));
+        let result = self.eval_statement(handler);
+        self.env = outer;
+
+        result
+    }
+
+    fn eval_match_expression(&mut self, subject: Object, arms: &[MatchArm]) -> EvalResult {
+        for arm in arms.iter() {
+            let mut bindings = vec![];
+
+            if !self.match_pattern(&arm.pattern, &subject, &mut bindings)? {
+                continue;
+            }
+
+            let mut env = Environment::new_with_outer(self.env.clone());
+
+            for (name, value) in bindings {
+                env.set(name, value)?;
+            }
+
+            let outer = mem::replace(&mut self.env, Env::new(env));
+            let result = self.eval_match_arm(arm);
+            self.env = outer;
+
+            if let Some(result) = result? {
+                return Ok(result);
+            }
+        }
+
+        let message = format!("no match arm for value: {}", subject);
+        Err(message.into())
+    }
+
+    /// ガードを満たせば腕の本体を評価する
+    fn eval_match_arm(&mut self, arm: &MatchArm) -> Result<Option<Object>, EvalError> {
+        if let Some(guard) = &arm.guard {
+            if !is_truthy(self.eval_expression(guard)?) {
+                return Ok(None);
+            }
+        }
+
+        self.eval_expression(&arm.body).map(Some)
+    }
+
+    /// パターンに値が合えば束縛を集めて true を返す
+    fn match_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value: &Object,
+        bindings: &mut Vec<(Symbol, Object)>,
+    ) -> Result<bool, EvalError> {
+        let result = match (pattern, value) {
+            (Pattern::Wildcard, _) => true,
+            (Pattern::Binding(name), _) => {
+                bindings.push((name.clone(), value.clone()));
+                true
+            }
+            (Pattern::Literal(expression), _) => self.eval_expression(expression)? == *value,
+            (Pattern::Array { elements, rest }, Object::Array(values)) => {
+                let matches_len = match rest {
+                    Some(_) => values.len() >= elements.len(),
+                    None => values.len() == elements.len(),
+                };
+
+                if !matches_len {
+                    return Ok(false);
+                }
+
+                for (element, value) in elements.iter().zip(values.iter()) {
+                    if !self.match_pattern(element, value, bindings)? {
+                        return Ok(false);
+                    }
+                }
+
+                if let Some(rest) = rest {
+                    let rest_values = values[elements.len()..].to_vec();
+                    bindings.push((rest.clone(), Object::Array(rest_values)));
+                }
+
+                true
+            }
+            (Pattern::Map(entries), Object::Map(pairs)) => {
+                for (key, pattern) in entries.iter() {
+                    match pairs.get(&MapKey::String(key.to_string())) {
+                        Some(MapPair { value, .. }) => {
+                            if !self.match_pattern(pattern, value, bindings)? {
+                                return Ok(false);
+                            }
+                        }
+                        None => return Ok(false),
+                    }
+                }
+
+                true
+            }
+            (_, _) => false,
+        };
+
+        Ok(result)
+    }
+
+    /// 値を投げ、最も近い `try` まで巻き戻す
+    pub(crate) fn throw(&mut self, value: Object) -> EvalResult {
+        let error = EvalError::UncaughtThrow(value.to_string());
+        self.thrown = Some(value);
+        Err(error)
+    }
+
+    fn eval_identifier_expression(&mut self, name: &str) -> EvalResult {
+        let env = self.env.borrow();
+
+        let result = match (env.get(name), env.buildin.borrow().get(name)) {
+            (Ok(object), _) => object,
+            (Err(_), Some(object)) => object.clone(),
+            (Err(error), None) => return Err(error),
+        };
+
+        Ok(result)
+    }
+
+    /// 関数の値を作る。関数の中で定義した関数は、本体が参照する自由変数のうち内側のスコープで
+    /// 束縛されたものを値で捕まえ、それ以外の名前はトップレベルの環境で探す。捕まえた名前や、
+    /// まだ束縛されていない名前をあとで内側のスコープで束縛し直すと、新しい値が送られる
+    fn eval_function_expression(
+        &mut self,
+        parameters: &[Expression],
+        body: &Statement,
+    ) -> EvalResult {
+        // トップレベルで定義した関数は、トップレベルの環境をそのまま外側にする
+        // （関数ごとに環境をつくるので、`is` で同じ関数かを見分けられる）
+        let root = match self.env.borrow().root() {
+            Some(root) => root,
+            None => {
+                let result = Object::Function {
+                    parameters: parameters.to_vec(),
+                    body: body.clone(),
+                    env: Env::new(Environment::new_with_outer(self.env.clone())),
+                };
+                return Ok(result);
+            }
+        };
+
+        // 定義した環境からトップレベルの手前までの環境（内側から順に並べる）
+        let mut scopes = vec![];
+        let mut scope = self.env.clone();
+
+        loop {
+            let outer = match &scope.borrow().outer {
+                Some(outer) => outer.clone(),
+                None => break,
+            };
+            scopes.push(scope);
+            scope = outer;
+        }
+
+        let mut captured = Environment::new_with_outer(root);
+        let free = free_variables(parameters, body);
+
+        for name in free.iter() {
+            let found = scopes.iter().enumerate().find_map(|(depth, scope)| {
+                let object = scope.borrow().store.get(name).cloned()?;
+                Some((depth, object))
+            });
+
+            match found {
+                Some((depth, object)) => {
+                    captured.store.insert(name.clone(), object);
+                    captured.sources.insert(name.clone(), depth);
+                }
+                None => {
+                    captured.sources.insert(name.clone(), usize::MAX);
+                }
+            }
+        }
+
+        let captured = Env::new(captured);
+
+        if !free.is_empty() {
+            for (depth, scope) in scopes.iter().enumerate() {
+                let env = Rc::downgrade(&captured.0);
+                scope.borrow_mut().watch(Capture { env, depth });
+            }
+        }
+
+        let result = Object::Function {
+            parameters: parameters.to_vec(),
+            body: body.clone(),
+            env: captured,
+        };
+
+        Ok(result)
+    }
+
+    fn eval_expressions(&mut self, expressions: &[Expression]) -> Result<Vec<Object>, EvalError> {
+        let mut result = vec![];
+
+        for expression in expressions.iter() {
+            result.push(self.eval_expression(expression)?);
+        }
+
+        Ok(result)
+    }
+
+    fn eval_index_expression(&mut self, left: Object, index: Object) -> EvalResult {
+        match (&left, &index) {
+            (Object::Array(elements), Object::Integer(index)) => {
+                let elements = elements.clone();
+                let index = *index;
+                self.eval_array_index_expression(elements, index)
+            }
+            (Object::String(value), Object::Integer(index)) => {
+                let index = *index;
+                self.eval_string_index_expression(value, index)
+            }
+            (Object::Map(pairs), _) => {
+                let pairs = pairs.clone();
+                self.eval_map_index_expression(pairs, index)
+            }
+            (Object::Error(message), Object::String(key)) if key == "message" => {
+                Ok(Object::String(message.clone()))
+            }
+            #[cfg(feature = "date")]
+            (Object::Date(date), Object::String(key)) => match key.as_str() {
+                "year" => Ok(Object::Integer(date.year() as isize)),
+                "month" => Ok(Object::Integer(date.month() as isize)),
+                "day" => Ok(Object::Integer(date.day() as isize)),
+                _ => Ok(Object::Null),
+            },
+            #[cfg(feature = "date")]
+            (Object::Interval(days), Object::String(key)) if key == "days" => {
+                Ok(Object::Integer(*days))
+            }
+            _ => {
+                let message = format!("index operator not supported: {}", left.get_type());
+                Err(EvalError::InvalidIndex(message))
+            }
+        }
+    }
+
+    fn eval_array_index_expression(&mut self, elements: Vec<Object>, index: isize) -> EvalResult {
+        let result = {
+            let max = elements.len() - 1;
+
+            if index < 0 || index > (max as isize) {
+                Object::Null
+            } else {
+                elements[index as usize].clone()
+            }
+        };
+
+        Ok(result)
+    }
+
+    /// 文字単位で添字を数え、負の添字は末尾から数える
+    fn eval_string_index_expression(&mut self, value: &str, index: isize) -> EvalResult {
+        let len = value.chars().count() as isize;
+        let index = if index < 0 { index + len } else { index };
+
+        let result = match value.chars().nth(index as usize) {
+            Some(ch) if index >= 0 => Object::String(ch.to_string()),
+            _ => Object::Null,
+        };
+
+        Ok(result)
+    }
+
+    fn eval_slice_expression(
+        &mut self,
+        left: Object,
+        start: Option<Object>,
+        end: Option<Object>,
+    ) -> EvalResult {
+        let elements = match left {
+            Object::Array(elements) => elements,
+            _ => {
+                let message = format!("slice operator not supported: {}", left.get_type());
+                return Err(EvalError::InvalidIndex(message));
+            }
+        };
+
+        let len = elements.len();
+        let start = slice_bound(start, 0, len)?;
+        let end = slice_bound(end, len, len)?;
+
+        let result = if start < end {
+            Object::Array(elements[start..end].to_vec())
+        } else {
+            Object::Array(vec![])
+        };
+
+        Ok(result)
+    }
+
+    fn eval_map_index_expression(
+        &mut self,
+        pairs: BTreeMap<MapKey, MapPair>,
+        index: Object,
+    ) -> EvalResult {
+        let map_key = match MapKey::from(&index) {
+            MapKey::Unusable => {
+                let error = EvalError::UnusableMapKey(index.get_type());
+                return Err(error);
+            }
+            map_key => map_key,
+        };
+
+        let result = match pairs.get(&map_key) {
+            Some(MapPair { value, .. }) => value.clone(),
+            None => Object::Null,
+        };
+
+        Ok(result)
+    }
+
+    fn eval_map_expression(&mut self, pairs: BTreeMap<Expression, Expression>) -> EvalResult {
+        let mut map = BTreeMap::new();
+
+        for (key, value) in pairs.iter() {
+            let key = self.eval_expression(key)?;
+            let value = self.eval_expression(value)?;
+
+            let map_key = match MapKey::from(&key) {
+                MapKey::Unusable => {
+                    let error = EvalError::UnusableMapKey(key.get_type());
+                    return Err(error);
+                }
+                map_key => map_key,
+            };
+
+            let map_pair = MapPair::new(key, value);
+
+            map.insert(map_key, map_pair);
+        }
+
+        let result = Object::Map(map);
+
+        Ok(result)
+    }
+
+    /// 関数を引数に適用する（呼び出しの入れ子が上限を超えたらエラーにする）
+    pub fn apply_function(&mut self, function: Object, arguments: Vec<Object>) -> EvalResult {
+        if self.call_depth >= self.recursion_limit {
+            let error = EvalError::RecursionLimit(self.recursion_limit);
+            return Err(error);
+        }
+
+        self.call_depth += 1;
+
+        // 呼び出しの入れ子ごとにスタックを大きく使うので、どのスレッドでも上限まで呼び出せるように継ぎ足す
+        #[cfg(feature = "std")]
+        let result = stacker::maybe_grow(STACK_RED_ZONE, STACK_GROWTH, || {
+            self.apply_function_body(function, arguments)
+        });
+        #[cfg(not(feature = "std"))]
+        let result = self.apply_function_body(function, arguments);
+
+        self.call_depth -= 1;
+
+        result
+    }
+
+    /// 関数本体の末尾位置にある呼び出しは、スタックを積まずにループで続けて評価する。
+    /// 呼び出しスタックには、最初の呼び出しの段のうえに最後の末尾呼び出しの段だけを積む
+    fn apply_function_body(&mut self, function: Object, arguments: Vec<Object>) -> EvalResult {
+        let mut function = function;
+        let mut arguments = arguments;
+        let depth = self.frames.len();
+
+        let result = loop {
+            let tail = match self.apply_once(&function, arguments) {
+                Ok(tail) => tail,
+                Err(error) => {
+                    // 末尾呼び出しした先のエラーは、呼び出し元ではなく末尾呼び出しをした文で起きたものにする
+                    let tail_site = self.frames.get(depth).map(|frame| frame.call_site);
+                    self.capture_site(tail_site);
+                    break Err(error);
+                }
+            };
+
+            match tail {
+                Tail::Call(next_function, next_arguments, frame) => {
+                    if let Some(frame) = frame {
+                        self.frames.truncate(depth);
+                        self.frames.push(frame);
+                    }
+                    function = next_function;
+                    arguments = next_arguments;
+                }
+                Tail::Value(Object::Return(value)) => break Ok(*value),
+                Tail::Value(object) => break Ok(object),
+            }
+        };
+
+        self.frames.truncate(depth);
+
+        result
+    }
+
+    /// 関数を 1 度だけ適用する（本体の末尾呼び出しは評価せずに返す）
+    fn apply_once(&mut self, function: &Object, arguments: Vec<Object>) -> Result<Tail, EvalError> {
+        let tail = match function {
+            Object::Function {
+                parameters,
+                body,
+                env,
+            } => {
+                self.check_arity(parameters.len(), arguments.len())?;
+
+                let mut env = Environment::new_with_outer(env.clone());
+
+                for (i, parameter) in parameters.iter().enumerate() {
+                    match parameter {
+                        Expression::Identifier(name) => {
+                            env.set(name.clone(), arguments[i].clone())?;
+                        }
+                        _ => {
+                            let message = format!("invalid argument index: {}", 0).to_string();
+                            return Err(message.into());
+                        }
+                    }
+                }
+
+                if has_yield(body) {
+                    let generator = Generator::new(Env::new(env), body);
+                    return Ok(Tail::Value(Object::Generator(generator)));
+                }
+
+                let outer = mem::replace(&mut self.env, Env::new(env));
+                let result = self.eval_tail_statement(body);
+                self.env = outer;
+
+                result?
+            }
+            Object::Buildin { function } => {
+                self.intercept(Operation::CallBuildin {
+                    name: function.name(),
+                    arguments: &arguments,
+                })?;
+
+                Tail::Value(function.clone().call(self, arguments)?)
+            }
+            Object::Bound {
+                function,
+                arguments: bound,
+            } => {
+                let mut bound = bound.clone();
+                bound.extend(arguments);
+                Tail::Call(*function.clone(), bound, None)
+            }
+            _ => {
+                let error = EvalError::NotAFunction(function.get_type());
+                return Err(error);
+            }
+        };
+
+        Ok(tail)
+    }
+
+    fn check_arity(&mut self, parameters: usize, arguments: usize) -> Result<(), EvalError> {
+        if parameters == arguments {
+            Ok(())
+        } else {
+            let error = EvalError::ArityMismatch {
+                want: parameters,
+                got: arguments,
+            };
+            Err(error)
+        }
+    }
+}
+
+/// スライスの境界を配列の範囲に収める
+fn slice_bound(bound: Option<Object>, default: usize, len: usize) -> Result<usize, EvalError> {
+    match bound {
+        None => Ok(default),
+        Some(Object::Integer(value)) if value < 0 => Ok(0),
+        Some(Object::Integer(value)) => Ok((value as usize).min(len)),
+        Some(object) => {
+            let message = format!("slice index must be Integer, got {}", object.get_type());
+            Err(EvalError::InvalidIndex(message))
+        }
+    }
+}
+
+/// `name(x)` の形をした特殊形式の呼び出しか
+fn is_special_form(name: &str, callee: &Expression, arguments: &[Expression]) -> bool {
+    matches!(callee, Expression::Identifier(value) if &**value == name) && arguments.len() == 1
+}
+
+/// 文の並びとしてのブロックの中身
+fn block_statements(statement: &Statement) -> Vec<Statement> {
+    match statement {
+        Statement::Block(statements) => statements.clone(),
+        statement => vec![statement.clone()],
+    }
+}
+
+/// 文として書かれた `yield` を（if の中も含めて）含むか
+fn has_yield(statement: &Statement) -> bool {
+    match statement {
+        Statement::Block(statements) => statements.iter().any(has_yield),
+        Statement::Expression(Expression::Yield(_), _) => true,
+        Statement::Expression(
+            Expression::If {
+                consequence,
+                alternative,
+                ..
+            },
+            _,
+        ) => has_yield(consequence) || alternative.as_deref().is_some_and(has_yield),
+        _ => false,
+    }
+}
+
+/// 日付を `days` 日ずらす
+#[cfg(feature = "date")]
+fn shift_date(date: NaiveDate, days: isize) -> EvalResult {
+    let shifted = if days >= 0 {
+        date.checked_add_days(Days::new(days as u64))
+    } else {
+        date.checked_sub_days(Days::new(days.unsigned_abs() as u64))
+    };
+
+    match shifted {
+        Some(shifted) => Ok(Object::Date(shifted)),
+        None => {
+            let message = format!("date out of range: {} + {} days", date, days);
+            Err(message.into())
+        }
+    }
+}
+
+/// 束縛を名前の順に並べる
+fn sorted(store: &HashMap<Symbol, Object>) -> BTreeMap<String, Object> {
+    store
+        .iter()
+        .map(|(name, object)| (name.to_string(), object.clone()))
+        .collect()
+}
+
+pub(crate) fn is_truthy(object: Object) -> bool {
+    !matches!(object, Object::Boolean(false) | Object::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Expression, Statement};
+    use crate::error::EvalError;
+    use crate::evaluator::{Evaluator, Operation, Response};
+    use crate::lexer::Lexer;
+    use crate::numeric::Float;
+    #[cfg(feature = "rational")]
+    use crate::numeric::Rational;
+    use crate::object::{MapKey, MapPair, Object};
+    use crate::parser::Parser;
+    use crate::token::{Position, Token};
+    use crate::trace::{AuditEvent, Step, TraceEvent};
+    #[cfg(feature = "std")]
+    use crate::vfs::OsVfs;
+    use crate::vfs::{MemoryVfs, Vfs};
+    #[cfg(feature = "date")]
+    use chrono::NaiveDate;
+    use std::cell::RefCell;
+    use std::collections::{BTreeMap, VecDeque};
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+    use std::sync::Arc;
+
+    fn test_eval(input: &str) -> Response {
+        let mut lexer = Lexer::new(input);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+        let mut evaluator = Evaluator::new();
+        evaluator.eval(program)
+    }
+
+    fn assert_object(input: &str, expected: Object) {
+        match test_eval(input) {
+            Response::Reply(result) => assert_eq!(result, expected),
+            _ => unreachable!(),
+        }
+    }
+
+    fn assert_objects(tests: Vec<(&str, Object)>) {
+        for (input, expected) in tests {
+            assert_object(input, expected);
+        }
+    }
+
+    fn assert_errors(tests: Vec<(&str, &str)>) {
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Response::Error(message) => assert_eq!(message.to_string(), expected),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_eval_integer_expressions() {
+        let tests = vec![
+            ("5", Object::Integer(5)),
+            ("10", Object::Integer(10)),
+            ("-5", Object::Integer(-5)),
+            ("-10", Object::Integer(-10)),
+            ("5 + 5 + 5 + 5 - 10", Object::Integer(10)),
+            ("2 * 2 * 2 * 2 * 2", Object::Integer(32)),
+            ("-50 + 100 + -50", Object::Integer(0)),
+            ("5 * 2 + 10", Object::Integer(20)),
+            ("5 + 2 * 10", Object::Integer(25)),
+            ("20 + 2 * -10", Object::Integer(0)),
+            ("50 / 2 * 2 + 10", Object::Integer(60)),
+            ("2 * (5 + 10)", Object::Integer(30)),
+            ("3 * 3 * 3 + 10", Object::Integer(37)),
+            ("3 * (3 * 3) + 10", Object::Integer(37)),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", Object::Integer(50)),
+        ];
+
+        assert_objects(tests);
+    }
+
+    #[test]
+    fn test_integer_overflow() {
+        let max = isize::MAX;
+        let min = isize::MIN;
+
+        let tests = [
+            (
+                format!("{} + 1", max),
+                format!("integer overflow in {} + 1", max),
+            ),
+            (
+                format!("-{} - 2", max),
+                format!("integer overflow in {} - 2", -max),
+            ),
+            (
+                format!("{} * 2", max),
+                format!("integer overflow in {} * 2", max),
+            ),
+            (
+                format!("(-{} - 1) / -1", max),
+                format!("integer overflow in {} / -1", min),
+            ),
+            (
+                format!("-(-{} - 1)", max),
+                format!("integer overflow in -({})", min),
+            ),
+        ];
+
+        let tests = tests
+            .iter()
+            .map(|(input, expected)| (input.as_str(), expected.as_str()))
+            .collect();
+        assert_errors(tests);
+
+        assert_objects(vec![("9223372036854775806 + 1", Object::Integer(max))]);
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        assert_errors(vec![
+            ("5 / 0", "division by zero"),
+            ("let f = fn(x) { 10 / x }; f(0)", "division by zero"),
+        ]);
+
+        assert_objects(vec![
+            (
+                "try { 1 / 0 } catch (e) { e }",
+                Object::String("division by zero".to_string()),
+            ),
+            ("5.0 / 0", Object::Float(Float(f64::INFINITY))),
+        ]);
+    }
+
+    #[test]
+    fn test_eval_float_expressions() {
+        let tests = vec![
+            ("1.5", Object::Float(Float(1.5))),
+            ("-2.5", Object::Float(Float(-2.5))),
+            ("0.1 + 0.2", Object::Float(Float(0.1 + 0.2))),
+            ("1.5 * 2", Object::Float(Float(3.0))),
+            ("3 / 2.0", Object::Float(Float(1.5))),
+            ("1 - 0.5", Object::Float(Float(0.5))),
+            ("1e3 + 1", Object::Float(Float(1001.0))),
+            ("0.1 + 0.2 == 0.3", Object::Boolean(false)),
+            ("1.0 == 1", Object::Boolean(true)),
+            ("2.5 > 2", Object::Boolean(true)),
+            ("1 < 0.5", Object::Boolean(false)),
+            (
+                "[floor(2.7), ceil(2.1), round(2.5), round(-2.5), floor(3)]",
+                Object::Array(vec![
+                    Object::Float(Float(2.0)),
+                    Object::Float(Float(3.0)),
+                    Object::Float(Float(3.0)),
+                    Object::Float(Float(-3.0)),
+                    Object::Integer(3),
+                ]),
+            ),
+            ("int(floor(-0.5)) * 2", Object::Integer(-2)),
+        ];
+
+        assert_objects(tests);
+
+        #[cfg(feature = "std")]
+        assert_objects(vec![
+            ("sqrt(2.25)", Object::Float(Float(1.5))),
+            ("sqrt(16)", Object::Float(Float(4.0))),
+            ("sqrt(3.0 * 3.0 + 4.0 * 4.0)", Object::Float(Float(5.0))),
+        ]);
+
+        assert_errors(vec![
+            (
+                r#"floor("1.5")"#,
+                "argument to `floor` must be a number, got String",
+            ),
+            ("round(1, 2)", "wrong number of arguments. got=2, want=1"),
+        ]);
+
+        match test_eval("0.1 + 0.2") {
+            Response::Reply(result) => assert_eq!(result.to_string(), "0.30000000000000004"),
+            _ => unreachable!(),
+        }
+    }
+
+    #[cfg(feature = "rational")]
+    #[test]
+    fn test_rational_expressions() {
+        let rational = |numerator, denominator| {
+            Object::Rational(Rational::new(numerator, denominator).unwrap())
+        };
+
+        let tests = vec![
+            ("3/4r", rational(3, 4)),
+            ("1/3r + 1/6r", rational(1, 2)),
+            ("rational(2, -4)", rational(-1, 2)),
+            ("rational(6, 3)", rational(2, 1)),
+            ("1r / 3 * 3", rational(1, 1)),
+            ("-(1/2r)", rational(-1, 2)),
+            ("2 - 1/2r", rational(3, 2)),
+            ("1/2r + 0.25", Object::Float(Float(0.75))),
+            ("1/3r < 1/2r", Object::Boolean(true)),
+            ("2/4r == 1/2r", Object::Boolean(true)),
+            ("1/2r == 0.5", Object::Boolean(true)),
+            ("match (4/2r) { 2r => 1, _ => 2 }", Object::Integer(1)),
+            (
+                "[floor(-5/2r), ceil(-5/2r), round(5/2r)]",
+                Object::Array(vec![
+                    Object::Integer(-3),
+                    Object::Integer(-2),
+                    Object::Integer(3),
+                ]),
+            ),
+        ];
+
+        assert_objects(tests);
+
+        let tests = vec![
+            ("1r / 0", "division by zero"),
+            ("rational(1, 0)", "division by zero"),
+            (
+                "rational(1.5, 2)",
+                "arguments to `rational` must be Integer and Integer, got Float, Integer",
+            ),
+            (
+                "rational(9223372036854775807, 1) + 1",
+                "integer overflow in 9223372036854775807/1 + 1/1",
+            ),
+            ("1r..3r", "unknown operator: Rational .. Rational"),
+        ];
+
+        assert_errors(tests);
+    }
+
+    #[cfg(feature = "date")]
+    #[test]
+    fn test_date_expressions() {
+        let date =
+            |year, month, day| Object::Date(NaiveDate::from_ymd_opt(year, month, day).unwrap());
+
+        let tests =
+            vec![
+            ("date(2024, 2, 28) + days(2)", date(2024, 3, 1)),
+            ("days(-1) + date(2024, 1, 1)", date(2023, 12, 31)),
+            ("date(2024, 1, 1) - days(1)", date(2023, 12, 31)),
+            ("date(2024, 3, 1) - date(2024, 2, 1)", Object::Interval(29)),
+            ("days(3) + days(4) - days(10)", Object::Interval(-3)),
+            ("date(2024, 1, 1) < date(2024, 1, 2)", Object::Boolean(true)),
+            ("date(2024, 1, 1) == date(2023, 12, 31) + days(1)", Object::Boolean(true)),
+            ("days(7) > days(3)", Object::Boolean(true)),
+            (
+                r#"format_date(date(2024, 7, 4), "%Y/%m/%d (%a)")"#,
+                Object::String("2024/07/04 (Thu)".to_string()),
+            ),
+            (
+                "let d = date(2024, 7, 4); [d.year, d.month, d.day, (d - date(2024, 7, 1)).days]",
+                Object::Array(vec![
+                    Object::Integer(2024),
+                    Object::Integer(7),
+                    Object::Integer(4),
+                    Object::Integer(3),
+                ]),
+            ),
+        ];
+
+        assert_objects(tests);
+
+        let displayed = vec![
+            ("date(2024, 7, 4)", "2024-07-04"),
+            ("days(1)", "1 day"),
+            ("days(-2)", "-2 days"),
+        ];
+
+        for (input, expected) in displayed {
+            match test_eval(input) {
+                Response::Reply(result) => assert_eq!(result.to_string(), expected),
+                _ => unreachable!(),
+            }
+        }
+
+        let tests = vec![
+            ("date(2023, 2, 29)", "invalid date: 2023-2-29"),
+            ("date(2024, -1, 1)", "invalid date: 2024--1-1"),
+            ("date(2024, 1)", "wrong number of arguments. got=2, want=3"),
+            ("days(1.5)", "argument to `days` must be Integer, got Float"),
+            (
+                "date(2024, 1, 1) + date(2024, 1, 1)",
+                "type mismatch: Date + Date",
+            ),
+            (
+                "date(2024, 1, 1) < days(1)",
+                "type mismatch: Date < Interval",
+            ),
+            ("date(2024, 1, 1) + 1", "type mismatch: Date + Integer"),
+            (
+                "date(262000, 1, 1) + days(1000000)",
+                "date out of range: +262000-01-01 + 1000000 days",
+            ),
+            (
+                r#"format_date(date(2024, 1, 1), "%Q")"#,
+                "invalid date format: %Q",
+            ),
+        ];
+
+        assert_errors(tests);
+    }
+
+    #[test]
+    fn test_eval_boolean_expressions() {
+        let tests = vec![
+            ("true", Object::Boolean(true)),
+            ("false", Object::Boolean(false)),
+            ("1 < 2", Object::Boolean(true)),
+            ("1 > 2", Object::Boolean(false)),
+            ("1 < 1", Object::Boolean(false)),
+            ("1 > 1", Object::Boolean(false)),
+            ("1 == 1", Object::Boolean(true)),
+            ("1 != 1", Object::Boolean(false)),
+            ("1 == 2", Object::Boolean(false)),
+            ("1 != 2", Object::Boolean(true)),
+            ("true == true", Object::Boolean(true)),
+            ("false == false", Object::Boolean(true)),
+            ("true == false", Object::Boolean(false)),
+            ("true != false", Object::Boolean(true)),
+            ("false != true", Object::Boolean(true)),
+            ("(1 < 2) == true", Object::Boolean(true)),
+            ("(1 < 2) == false", Object::Boolean(false)),
+            ("(1 > 2) == true", Object::Boolean(false)),
+            ("(1 > 2) == false", Object::Boolean(true)),
+            (r#""Hello World!" == "Hello World!""#, Object::Boolean(true)),
+            (
+                r#""Hello World!" != "Hello World!""#,
+                Object::Boolean(false),
+            ),
+        ];
+
+        assert_objects(tests);
+    }
+
+    #[test]
+    fn test_eval_bang_prefix_expressions() {
+        let tests = vec![
+            ("!true", Object::Boolean(false)),
+            ("!false", Object::Boolean(true)),
+            ("!5", Object::Boolean(false)),
+            ("!!true", Object::Boolean(true)),
+            ("!!false", Object::Boolean(false)),
+            ("!!5", Object::Boolean(true)),
+        ];
+
+        assert_objects(tests);
+    }
+
+    #[test]
+    fn test_if_else_expressions() {
+        let tests = vec![
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (false) { 10 }", Object::Null),
+            ("if (1) { 10 }", Object::Integer(10)),
+            ("if (1 < 2) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 }", Object::Null),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if (1 < 2) { 10 } else { 20 }", Object::Integer(10)),
+        ];
+
+        assert_objects(tests);
+    }
+
+    #[test]
+    fn test_return_statements() {
+        let tests = vec![
+            ("return 10;", Object::Integer(10)),
+            ("return 10; 9;", Object::Integer(10)),
+            ("return 2 * 5; 9;", Object::Integer(10)),
+            ("9; return 2 * 5; 9;", Object::Integer(10)),
+            (
+                "if (10 > 1) { if (10 > 1) { return 10; } return 1; }",
+                Object::Integer(10),
+            ),
+        ];
+
+        assert_objects(tests);
+    }
+
+    #[test]
+    fn test_error_handling() {
+        let tests = vec![
+            ("5 + true;", "type mismatch: Integer + Boolean"),
+            ("5 + true; 5;", "type mismatch: Integer + Boolean"),
+            ("-true;", "unknown operator: -Boolean"),
+            ("true + false;", "unknown operator: Boolean + Boolean"),
+            ("5; true + false; 5;", "unknown operator: Boolean + Boolean"),
+            (
+                "if (10 > 1) { true + false; }",
+                "unknown operator: Boolean + Boolean",
+            ),
+            (
+                "if (10 > 1) { if (10 > 1) { return true + false; } return 1; }",
+                "unknown operator: Boolean + Boolean",
+            ),
+            ("foobar", "identifier not found: foobar"),
+            (r#""Hello" - "World""#, "unknown operator: String - String"),
+            ("len(1)", "argument to `len` not supported, got Integer"),
+            (r#"1.."a""#, "type mismatch: Integer .. String"),
+            ("1.5..3", "unknown operator: Float .. Float"),
+            (r#""abc"[0:1]"#, "slice operator not supported: String"),
+            (
+                r#"[1, 2][true:]"#,
+                "slice index must be Integer, got Boolean",
+            ),
+            (
+                r#"len("one", "two")"#,
+                "wrong number of arguments. got=2, want=1",
+            ),
+            (
+                r#"{"name": "Monkey"}[fn(x) { x }]"#,
+                "unusable as map key: Function",
+            ),
+            (r#"{[1, fn(x) { x }]: "a"}"#, "unusable as map key: Array"),
+        ];
+
+        assert_errors(tests);
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let tests = vec![
+            ("let a = 1;\nlet b = a + true;", Some((2, 1))),
+            (
+                "let f = fn(x) {\n  let y = x;\n  y / 0\n};\nf(1)",
+                Some((3, 3)),
+            ),
+            ("try { 1 / 0 } catch (e) { e };\n  foo", Some((2, 3))),
+            ("true ?\n  missing : 0", Some((2, 3))),
+            // 末尾呼び出しした先のエラーは、末尾呼び出しをした文で起きたものにする
+            ("let g = fn() { return\n    len(1) };\ng()", Some((1, 16))),
+            (
+                "let check = fn(x) {\n  assert(x > 0, \"positive\")\n};\nlet v = 0;\nlet r = check(v);",
+                Some((2, 3)),
+            ),
+            (
+                "let f = fn(x) { x / 0 };\nlet g = fn(x) {\n  f(x)\n};\ng(1)",
+                Some((1, 17)),
+            ),
+            ("let h = fn() { len(1); 2 };\nh()", Some((1, 16))),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Response::Error(error) => assert_eq!(
+                    error
+                        .position()
+                        .map(|position| (position.line, position.column)),
+                    expected,
+                    "{}",
+                    input
+                ),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_trace() {
+        let tests = vec![
+            (
+                "let add = fn(x, y) {\n  x + y\n};\nlet twice = fn(x) {\n  let y = add(x, true);\n  y * 2\n};\ntwice(1)",
+                vec![
+                    "in add, called from twice, line 5, column 3",
+                    "in twice, called from main, line 8, column 1",
+                ],
+            ),
+            // 末尾呼び出しの段は、前の末尾呼び出しの段だけを置きかえる
+            (
+                "let f = fn() { 1 / 0; 2 };\nlet g = fn() { f() };\ng()",
+                vec![
+                    "in f, called from g, line 2, column 16",
+                    "in g, called from main, line 3, column 1",
+                ],
+            ),
+            (
+                "let check = fn(x) {\n  let ok = x > 0;\n  assert(ok, \"positive\")\n};\nlet r = check(0);",
+                vec![
+                    "in assert, called from check, line 3, column 3",
+                    "in check, called from main, line 5, column 1",
+                ],
+            ),
+            (
+                "let f = fn() { 1 / 0; 2 };\nlet g = fn() { f() };\nlet h = fn() { g() };\nh()",
+                vec![
+                    "in f, called from g, line 2, column 16",
+                    "in h, called from main, line 4, column 1",
+                ],
+            ),
+            ("let a = 1;\na / 0", vec![]),
+            (
+                "let f = fn() { 1 / 0; 2 };\nlet r = try { f() } catch (e) { e };\nr + true",
+                vec![],
+            ),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Response::Error(error) => assert_eq!(error.stack_trace(), expected, "{}", input),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_variants() {
+        let tests = vec![
+            ("foobar", EvalError::UnknownIdentifier("foobar".into())),
+            (
+                "5 + true",
+                EvalError::TypeMismatch {
+                    left: "Integer".into(),
+                    operator: "+".into(),
+                    right: "Boolean".into(),
+                },
+            ),
+            ("-true", EvalError::UnknownOperator("-Boolean".into())),
+            ("1(2)", EvalError::NotAFunction("Integer".into())),
+            (
+                "len(1, 2)",
+                EvalError::WrongArity {
+                    got: 2,
+                    want: "1".into(),
+                },
+            ),
+            (
+                "fn(x) { x }()",
+                EvalError::ArityMismatch { want: 1, got: 0 },
+            ),
+            (
+                "{fn(x) { x }: 2}",
+                EvalError::UnusableMapKey("Function".into()),
+            ),
+            ("1 / 0", EvalError::DivisionByZero),
+            (
+                "first(1)",
+                EvalError::InvalidArgument("argument to `first` must be Array, got Integer".into()),
+            ),
+            ("throw(1)", EvalError::UncaughtThrow("1".into())),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Response::Error(error) => assert_eq!(error.kind(), &expected, "{}", input),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_expressions() {
+        let tests = vec![
+            ("try { 1 } catch (e) { 2 }", Object::Integer(1)),
+            ("try { throw(5); 1 } catch (e) { e * 2 }", Object::Integer(10)),
+            (
+                "let f = fn(x) { if (x > 2) { throw([x]) } f(x + 1) }; try { f(0) } catch (e) { e }",
+                Object::Array(vec![Object::Integer(3)]),
+            ),
+            (
+                "try { try { throw(1) } catch (e) { throw(e + 1) } } catch (e) { e }",
+                Object::Integer(2),
+            ),
+            (
+                "try { 1 + true } catch (e) { e }",
+                Object::String("type mismatch: Integer + Boolean".to_string()),
+            ),
+            (
+                "let e = 1; try { throw(2) } catch (e) { e }; e",
+                Object::Integer(1),
+            ),
+            (
+                "let f = fn() { try { return 1; } catch (e) { 2 }; 3 }; f()",
+                Object::Integer(1),
+            ),
+        ];
+
+        assert_objects(tests);
+
+        let tests = vec![
+            ("throw(\"boom\")", "uncaught throw: boom"),
+            (
+                "try { throw(1) } catch (e) { throw(e + 1) }",
+                "uncaught throw: 2",
+            ),
+            ("throw()", "wrong number of arguments. got=0, want=1"),
+        ];
+
+        assert_errors(tests);
+    }
+
+    #[test]
+    fn test_help() {
+        let tests = vec![
+            (
+                r#"help("len")"#,
+                Object::String(
+                    "len(value): Length of a String (in bytes) or an Array (takes 1 argument)"
+                        .to_string(),
+                ),
+            ),
+            (
+                "help(slice)",
+                Object::String(
+                    "slice(value, start, end): Part of an Array or String; negative indexes count from the end (takes 2 or 3 arguments)"
+                        .to_string(),
+                ),
+            ),
+            (
+                r#"help("puts")"#,
+                Object::String(
+                    "puts(...): Prints each argument on its own line (takes 0 or more arguments)"
+                        .to_string(),
+                ),
+            ),
+            (
+                r#"let names = builtins(); [first(names), type(index_of(names, "help")), len(filter(names, fn(name) { is_error(help(name)) }))]"#,
+                Object::Array(vec![
+                    Object::String("assert".to_string()),
+                    Object::String("Integer".to_string()),
+                    Object::Integer(0),
+                ]),
+            ),
+        ];
+
+        assert_objects(tests);
+
+        let tests = vec![
+            (r#"help("nothing")"#, "no builtin named nothing"),
+            (
+                "help(1)",
+                "argument to `help` must be String or Buildin Function, got Integer",
+            ),
+            ("builtins(1)", "wrong number of arguments. got=1, want=0"),
+        ];
+
+        assert_errors(tests);
+    }
+
+    #[test]
+    fn test_buildin_arity() {
+        let tests = vec![
+            ("zip([1])", "wrong number of arguments. got=1, want=2"),
+            (
+                "format()",
+                "wrong number of arguments. got=0, want=1 or more",
+            ),
+            (
+                r#"slice("abc")"#,
+                "wrong number of arguments. got=1, want=2 or 3",
+            ),
+            (
+                r#"sandbox("1", {}, 1)"#,
+                "wrong number of arguments. got=3, want=1 or 2",
+            ),
+            (
+                "let f = len; f()",
+                "wrong number of arguments. got=0, want=1",
+            ),
+            (
+                "map([1], fn(x) { first() })",
+                "wrong number of arguments. got=0, want=1",
+            ),
+        ];
+
+        assert_errors(tests);
+    }
+
+    #[test]
+    fn test_assertions() {
+        let tests = vec![
+            ("assert(1 < 2)", Object::Null),
+            (r#"assert(true, "never shown"); 1"#, Object::Integer(1)),
+            ("assert_eq([1, 2], [1, 2])", Object::Null),
+            (
+                r#"try { assert_eq(1, 2) } catch (e) { e }"#,
+                Object::String("assertion failed: expected 2, got 1".to_string()),
+            ),
+        ];
+
+        assert_objects(tests);
+
+        let tests = vec![
+            ("assert(false)", "assertion failed"),
+            ("assert(first([]))", "assertion failed"),
+            (
+                r#"assert(1 > 2, "1 is not greater")"#,
+                "assertion failed: 1 is not greater",
+            ),
+            (
+                r#"assert_eq(1 + 1, "2")"#,
+                r#"assertion failed: expected "2", got 2"#,
+            ),
+            (
+                r#"assert_eq(["a"], [], "lists")"#,
+                "assertion failed: lists: expected [], got [a]",
+            ),
+            ("assert()", "wrong number of arguments. got=0, want=1 or 2"),
+            (
+                "assert_eq(1)",
+                "wrong number of arguments. got=1, want=2 or 3",
+            ),
+        ];
+
+        assert_errors(tests);
+    }
+
+    #[test]
+    fn test_match_expressions() {
+        let tests = vec![
+            ("match (1) { 1 => \"one\", _ => \"other\" }", Object::String("one".to_string())),
+            ("match (-2.5) { -2.5 => true, _ => false }", Object::Boolean(true)),
+            ("match (5) { n if n > 3 => n * 2, n => n }", Object::Integer(10)),
+            ("match (2) { n if n > 3 => n * 2, n => n }", Object::Integer(2)),
+            (
+                "match ([1, 2, 3]) { [] => 0, [x, ...rest] => rest }",
+                Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+            ),
+            ("match ([1, 2]) { [x] => x, [x, y] => x + y }", Object::Integer(3)),
+            ("match ([[1, 2]]) { [[_, y]] => y }", Object::Integer(2)),
+            (
+                "match ({\"name\": \"monkey\", \"age\": 3}) { {name, \"age\": 3} => name }",
+                Object::String("monkey".to_string()),
+            ),
+            (
+                "match ({\"name\": \"monkey\"}) { {age} => age, {name: n} => n }",
+                Object::String("monkey".to_string()),
+            ),
+            (
+                "let sum = fn(xs) { match (xs) { [] => 0, [x, ...rest] => x + sum(rest) } }; sum([1, 2, 3, 4])",
+                Object::Integer(10),
+            ),
+            ("let x = 1; match (2) { x => x }; x", Object::Integer(1)),
+        ];
+
+        assert_objects(tests);
+
+        let tests = vec![
+            ("match (3) { 1 => 1, 2 => 2 }", "no match arm for value: 3"),
+            (
+                "match ([1]) { [x] if x + true => x }",
+                "type mismatch: Integer + Boolean",
+            ),
+        ];
+
+        assert_errors(tests);
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let tests = vec![
+            ("sandbox(\"1 + 2\")", Object::Integer(3)),
+            ("sandbox(\"let a = 1;\")", Object::Null),
+            (
+                "let f = fn(n) { f(n + 1) }; sandbox(\"let f = fn(n) { n }; f(2)\", {\"max_steps\": 10})",
+                Object::Integer(2),
+            ),
+            (
+                "let secret = 1; sandbox(\"secret\")[\"error\"]",
+                Object::String("identifier not found: secret".to_string()),
+            ),
+            (
+                "sandbox(\"secret\")[\"kind\"]",
+                Object::String("runtime".to_string()),
+            ),
+            (
+                "sandbox(\"let f = fn(n) { f(n + 1) }; f(0)\", {\"max_steps\": 100})",
+                Object::Map(
+                    vec![
+                        ("error", "statement limit exceeded: 100"),
+                        ("kind", "limit"),
+                    ]
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let key = Object::String(key.to_string());
+                        let value = Object::String(value.to_string());
+                        (MapKey::from(&key), MapPair::new(key, value))
+                    })
+                    .collect(),
+                ),
+            ),
+            (
+                "sandbox(\"let f = fn(n) { 1 + f(n + 1) }; f(0)\", {\"max_depth\": 10})[\"kind\"]",
+                Object::String("limit".to_string()),
+            ),
+            (
+                "sandbox(\"let = 1\")[\"kind\"]",
+                Object::String("parse".to_string()),
+            ),
+            ("sandbox(\"throw(1)\")[\"error\"]", Object::String("uncaught throw: 1".to_string())),
+        ];
+
+        assert_objects(tests);
+
+        let tests = vec![
+            (
+                "sandbox(1)",
+                "arguments to `sandbox` must be String and Map, got Integer",
+            ),
+            (
+                "sandbox(\"1\", {\"max_steps\": -1})",
+                "invalid sandbox option: max_steps: -1",
+            ),
+            (
+                "sandbox(\"1\", {\"files\": true})",
+                "invalid sandbox option: files: true",
+            ),
+        ];
+
+        assert_errors(tests);
+    }
+
+    #[test]
+    fn test_quote_unquote() {
+        let tests = vec![
+            ("quote(5)", "5"),
+            ("quote(5 + 8)", "(5 + 8)"),
+            ("quote(foobar)", "foobar"),
+            ("quote(unquote(4))", "4"),
+            ("quote(unquote(4 + 4))", "8"),
+            ("quote(8 + unquote(4 + 4))", "(8 + 8)"),
+            ("quote(unquote(4 + 4) + 8)", "(8 + 8)"),
+            ("let foobar = 8; quote(foobar)", "foobar"),
+            ("let foobar = 8; quote(unquote(foobar))", "8"),
+            ("quote(unquote(true))", "true"),
+            ("quote(unquote(true == false))", "false"),
+            ("quote(unquote(quote(4 + 4)))", "(4 + 4)"),
+            (
+                "let quoted = quote(4 + 4); quote(unquote(4 + 4) + unquote(quoted))",
+                "(8 + (4 + 4))",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Response::Reply(Object::Quote(expression)) => {
+                    assert_eq!(expression.to_string(), expected)
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        assert_errors(vec![("quote(unquote([1]))", "cannot unquote Array")]);
+    }
+
+    #[test]
+    fn test_macro_expansion() {
+        let tests = vec![
+            (
+                "let infix = macro() { quote(1 + 2); }; infix()",
+                Object::Integer(3),
+            ),
+            (
+                "let reverse = macro(a, b) { quote(unquote(b) - unquote(a)); }; reverse(2 + 2, 10 - 5)",
+                Object::Integer(1),
+            ),
+            (
+                r#"
+                let unless = macro(condition, consequence, alternative) {
+                    quote(if (!(unquote(condition))) {
+                        unquote(consequence);
+                    } else {
+                        unquote(alternative);
+                    });
+                };
+                unless(10 > 5, "not greater", "greater");
+                "#,
+                Object::String("greater".to_string()),
+            ),
+            (
+                "let twice = macro(x) { return quote(unquote(x) + unquote(x)); }; twice(21)",
+                Object::Integer(42),
+            ),
+        ];
+
+        assert_objects(tests);
+
+        assert_errors(vec![
+            (
+                "let bad = macro() { 1 }; bad()",
+                "we only support returning AST-nodes from macros",
+            ),
+            (
+                "let one = macro(x) { x }; one()",
+                "expected arity to be 1, got 0 instead",
+            ),
+        ]);
+
+        let mut lexer = Lexer::new("let unless = macro(c) { c };");
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+        let mut evaluator = Evaluator::new();
+        assert!(matches!(evaluator.eval(program), Response::NoReply));
+    }
+
+    #[test]
+    fn test_coalesce_expressions() {
+        let tests = vec![
+            ("let x = if (false) { 1 }; x ?? 1", Object::Integer(1)),
+            ("0 ?? 1", Object::Integer(0)),
+            ("false ?? 1", Object::Boolean(false)),
+            (r#"{"a": 1}["b"] ?? 2"#, Object::Integer(2)),
+            (r#"{"a": 1}["a"] ?? 2"#, Object::Integer(1)),
+            ("[1][5] ?? [2][5] ?? 3", Object::Integer(3)),
+            ("1 ?? undefined_name", Object::Integer(1)),
+        ];
+
+        assert_objects(tests);
+    }
+
+    #[test]
+    fn test_ternary_expressions() {
+        let tests = vec![
+            ("1 < 2 ? 10 : 20", Object::Integer(10)),
+            ("1 > 2 ? 10 : 20", Object::Integer(20)),
+            ("let x = 5; x > 3 ? x > 4 ? 2 : 1 : 0", Object::Integer(2)),
+            (
+                "let sign = fn(n) { n < 0 ? -1 : n == 0 ? 0 : 1 }; [sign(-5), sign(0), sign(7)]",
+                Object::Array(vec![
+                    Object::Integer(-1),
+                    Object::Integer(0),
+                    Object::Integer(1),
+                ]),
+            ),
+            ("true ? 1 : undefined_name", Object::Integer(1)),
+        ];
+
+        assert_objects(tests);
+    }
+
+    #[test]
+    fn test_read_file() {
+        let mut vfs = MemoryVfs::new();
+        vfs.insert("a.txt", "hello");
+
+        let tests = vec![
+            (r#"read_file("a.txt")"#, Ok("hello")),
+            (r#"read_file("b.txt")"#, Err("no such file: b.txt")),
+        ];
+
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+            let program = parser.parse_program();
+            let mut evaluator = Evaluator::new();
+            evaluator.set_vfs(Some(Rc::new(vfs.clone())));
+
+            match (evaluator.eval(program), expected) {
+                (Response::Reply(Object::String(actual)), Ok(expected)) => {
+                    assert_eq!(actual, expected)
+                }
+                (Response::Error(actual), Err(expected)) => {
+                    assert_eq!(actual.to_string(), expected)
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        assert_errors(vec![
+            (
+                r#"read_file("a.txt")"#,
+                "no filesystem available to read a.txt",
+            ),
+            (
+                "read_file(1)",
+                "argument to `read_file` must be String, got Integer",
+            ),
+            (
+                r#"sandbox("", {"files": {"a.txt": 1}})"#,
+                "invalid sandbox file: a.txt: 1",
+            ),
+        ]);
+
+        assert_object(
+            r#"sandbox("read_file(1)", {"files": {"a.txt": "hello"}})["kind"]"#,
+            Object::String("runtime".to_string()),
+        );
+    }
+
+    fn test_map(entries: Vec<(&str, isize)>) -> Object {
+        let mut pairs = BTreeMap::new();
+
+        for (key, value) in entries {
+            let key = Object::String(key.to_string());
+            pairs.insert(
+                MapKey::from(&key),
+                MapPair::new(key, Object::Integer(value)),
+            );
+        }
+
+        Object::Map(pairs)
+    }
+
+    fn test_eval_with_vfs(vfs: Rc<dyn Vfs>, input: &str) -> Response {
+        let mut lexer = Lexer::new(input);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+        let mut evaluator = Evaluator::new();
+        evaluator.set_vfs(Some(vfs));
+        evaluator.eval(program)
+    }
+
+    #[test]
+    fn test_import() {
+        let mut vfs = MemoryVfs::new();
+        vfs.insert(
+            "std/list.monkey",
+            "let _step = fn(f, xs, i, acc) { if (i == len(xs)) { acc } else { _step(f, xs, i + 1, push(acc, f(xs[i]))) } }; let map = fn(xs, f) { _step(f, xs, 0, []) };",
+        );
+        vfs.insert(
+            "std/prelude.monkey",
+            r#"import "std/list" as list; let map = list.map; let twice = fn(x) { x * 2 };"#,
+        );
+        vfs.insert("a.monkey", r#"import "b" as b; let x = 1;"#);
+        vfs.insert("b.monkey", r#"import "a" as a; let y = 2;"#);
+        vfs.insert("broken.monkey", "let = 1;");
+        let vfs: Rc<dyn Vfs> = Rc::new(vfs);
+
+        let tests = vec![
+            (
+                r#"import "std/list" as list; let map = 0; list.map([1, 2], fn(x) { x + map })"#,
+                Ok("[1, 2]"),
+            ),
+            (
+                r#"import "std/prelude" as p; [p.map([1, 2], p.twice), p.list.map([3], p.twice)]"#,
+                Ok("[[2, 4], [6]]"),
+            ),
+            (r#"import "std/list" as list; list._step"#, Ok("null")),
+            (
+                r#"import "std/list.monkey" as list; list.map([1], fn(x) { -x })"#,
+                Ok("[-1]"),
+            ),
+            (
+                r#"import "missing" as m; m"#,
+                Err("module not found: missing"),
+            ),
+            (
+                r#"import "a" as a; a"#,
+                Err("import cycle: a.monkey -> b.monkey -> a.monkey"),
+            ),
+            (
+                r#"import "broken" as b; b"#,
+                Err("broken.monkey: expected next token to be Ident, got = instead"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            match (test_eval_with_vfs(Rc::clone(&vfs), input), expected) {
+                (Response::Reply(result), Ok(expected)) => {
+                    assert_eq!(result.to_string(), expected, "{}", input)
+                }
+                (Response::Error(error), Err(expected)) => {
+                    assert_eq!(error.to_string(), expected, "{}", input)
+                }
+                _ => unreachable!("{}", input),
+            }
+        }
+
+        let mut evaluator = Evaluator::new();
+        evaluator.set_vfs(Some(Rc::clone(&vfs)));
+        evaluator.set_import_paths(vec!["lib".to_string(), "std/".to_string()]);
+        let program =
+            Parser::new(&mut Lexer::new(r#"import "list" as l; l.map([1], len)"#)).parse_program();
+
+        match evaluator.eval(program) {
+            Response::Error(error) => {
+                assert_eq!(
+                    error.to_string(),
+                    "argument to `len` not supported, got Integer"
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_register_builtin() {
+        let mut vfs = MemoryVfs::new();
+        vfs.insert("counter.monkey", "let twice = fn() { count(); count() };");
+
+        let calls = Rc::new(RefCell::new(0));
+        let mut evaluator = Evaluator::new();
+        evaluator.set_vfs(Some(Rc::new(vfs)));
+        let counter = Rc::clone(&calls);
+        evaluator.register_builtin("count", move |_, _| {
+            *counter.borrow_mut() += 1;
+            Ok(Object::Integer(*counter.borrow()))
+        });
+
+        let tests = vec![
+            (
+                r#"import "counter" as c; let f = fn() { count() }; [f(), c.twice(), map([1], fn(_) { count() })]"#,
+                Ok("[1, 3, [4]]"),
+            ),
+            (
+                "help(count)",
+                Ok("count(...): Registered by the host (arity not declared)"),
+            ),
+            (r#"help("count") == help(count)"#, Ok("true")),
+            (r#"help("counter")"#, Err("no builtin named counter")),
+        ];
+
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+
+            match (evaluator.eval(parser.parse_program()), expected) {
+                (Response::Reply(result), Ok(expected)) => assert_eq!(result.to_string(), expected),
+                (Response::Error(error), Err(expected)) => assert_eq!(error.to_string(), expected),
+                _ => unreachable!(),
+            }
+        }
+
+        assert_eq!(*calls.borrow(), 4);
+        assert!(evaluator.buildin_names().contains(&"count".to_string()));
+    }
+
+    #[test]
+    fn test_file_handles() {
+        let mut vfs = MemoryVfs::new();
+        vfs.insert("a.txt", "one\ntwo");
+        let vfs: Rc<dyn Vfs> = Rc::new(vfs);
+
+        let tests = vec![
+            (
+                r#"let f = open("a.txt", "r"); [read_line(f), read_line(f), read_line(f)]"#,
+                Ok("[one, two, null]"),
+            ),
+            (r#"open("a.txt", "r")"#, Ok("<file #0>")),
+            (
+                r#"let f = open("a.txt", "r"); close(f); read_line(f)"#,
+                Err("file #0 is not open"),
+            ),
+            (
+                r#"let f = open("a.txt", "r"); write(f, "x")"#,
+                Err("file is not open for writing"),
+            ),
+            (
+                r#"open("a.txt", "w")"#,
+                Err("cannot open a.txt for writing"),
+            ),
+            (
+                r#"open("a.txt", "x")"#,
+                Err(r#"mode for `open` must be "r", "w" or "a", got x"#),
+            ),
+            (
+                "read_line(1)",
+                Err("argument to `read_line` must be File, got Integer"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            match (test_eval_with_vfs(Rc::clone(&vfs), input), expected) {
+                (Response::Reply(actual), Ok(expected)) => assert_eq!(actual.to_string(), expected),
+                (Response::Error(actual), Err(expected)) => {
+                    assert_eq!(actual.to_string(), expected)
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        assert_errors(vec![(
+            r#"open("a.txt", "r")"#,
+            "no filesystem available to open a.txt",
+        )]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_file_handles_on_disk() {
+        let path = std::env::temp_dir().join(format!("ronkey-open-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        // 文字列にエスケープはないので、改行は Rust 側で埋めこむ
+        let input = format!(
+            "let out = open(\"{0}\", \"w\");
+            write(out, \"first\n\");
+            close(out);
+            let out = open(\"{0}\", \"a\");
+            write(out, \"second\n\");
+            let in = open(\"{0}\", \"r\");
+            [read_line(in), read_line(in)]",
+            path
+        );
+
+        match test_eval_with_vfs(Rc::new(OsVfs), &input) {
+            Response::Reply(actual) => assert_eq!(actual.to_string(), "[first, null]"),
+            _ => unreachable!(),
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first\nsecond\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_generators() {
+        let tests = vec![
+            (
+                "let g = fn() { yield 1; yield 2; }(); [next(g), next(g), next(g)]",
+                Object::Array(vec![Object::Integer(1), Object::Integer(2), Object::Null]),
+            ),
+            (
+                "let naturals = fn(n) { yield n; naturals(n + 1) }; take(naturals(1), 3)",
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                    Object::Integer(3),
+                ]),
+            ),
+            (
+                r#"
+                let countdown = fn(n) {
+                    if (n > 0) {
+                        yield n;
+                        countdown(n - 1)
+                    }
+                };
+                take(countdown(3), 10)
+                "#,
+                Object::Array(vec![
+                    Object::Integer(3),
+                    Object::Integer(2),
+                    Object::Integer(1),
+                ]),
+            ),
+            (
+                r#"
+                let counter = fn() {
+                    let a = 10;
+                    yield a;
+                    let a = a + 1;
+                    yield a;
+                    return 0;
+                    yield 99;
+                };
+                take(counter(), 5)
+                "#,
+                Object::Array(vec![Object::Integer(10), Object::Integer(11)]),
+            ),
+            (
+                r#"
+                let naturals = fn(n) { yield n; naturals(n + 1) };
+                let g = naturals(0);
+                take(g, 100000);
+                next(g)
+                "#,
+                Object::Integer(100000),
+            ),
+        ];
+
+        assert_objects(tests);
+
+        assert_errors(vec![
+            ("yield 1", "yield outside generator"),
+            (
+                "next(1)",
+                "argument to `next` must be Generator, got Integer",
+            ),
+            (
+                "let g = fn() { yield next(g); }(); next(g)",
+                "generator is already running",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_let_statements() {
+        let tests = vec![
+            ("let a = 5; a;", Object::Integer(5)),
+            ("let a = 5 * 5; a;", Object::Integer(25)),
+            ("let a = 5; let b = a; b;", Object::Integer(5)),
+            (
+                "let a = 5; let b = a; let c = a + b + 5; c;",
+                Object::Integer(15),
+            ),
+        ];
+
+        assert_objects(tests);
+    }
+
+    #[test]
+    fn test_function_expressions() {
+        let input = "fn(x) { x + 2; };";
+
+        let expected_parameters = vec![Expression::Identifier("x".into())];
+        let expected_body = Statement::Block(vec![Statement::Expression(
+            Expression::Infix {
+                left: Box::new(Expression::Identifier("x".into())),
+                operator: Token::Plus,
+                right: Box::new(Expression::Integer(2)),
+            },
+            Position::default(),
+        )]);
+
+        match test_eval(input) {
+            Response::Reply(Object::Function {
+                parameters, body, ..
+            }) => {
+                assert_eq!(parameters, expected_parameters);
+                assert_eq!(body, expected_body);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_function_applications() {
+        let tests = vec![
+            (
+                "let identity = fn(x) { x; }; identity(5);",
+                Object::Integer(5),
+            ),
+            (
+                "let identity = fn(x) { return x; }; identity(5);",
+                Object::Integer(5),
+            ),
+            (
+                "let double = fn(x) { x * 2; }; double(5);",
+                Object::Integer(10),
+            ),
+            (
+                "let add = fn(x, y) { x + y; }; add(5, 5);",
+                Object::Integer(10),
+            ),
+            (
+                "let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));",
+                Object::Integer(20),
+            ),
+            ("fn(x) { x; }(5)", Object::Integer(5)),
+            (
+                "let identity = fn(x) { return x; }; identity(5) + 1;",
+                Object::Integer(6),
+            ),
+            ("let f = fn() { return 1; }; f(); 2", Object::Integer(2)),
+        ];
+
+        assert_objects(tests);
+    }
+
+    #[test]
+    fn test_tail_calls() {
+        let tests = vec![
+            (
+                "let count = fn(self, n) { if (n == 0) { 0 } else { self(self, n - 1) } }; count(count, 100000)",
+                Object::Integer(0),
+            ),
+            (
+                "let sum = fn(self, n, acc) { if (n == 0) { return acc; } return self(self, n - 1, acc + n); }; sum(sum, 10000, 0)",
+                Object::Integer(50005000),
+            ),
+            (
+                "let f = fn(self, n) { if (n < 3) { self(self, n + 1) } else { n } }; f(f, 0) + 1",
+                Object::Integer(4),
+            ),
+            (
+                "let f = fn(x) { let g = fn(y) { y * 2 }; g(x) }; f(2) + f(3)",
+                Object::Integer(10),
+            ),
+        ];
+
+        assert_objects(tests);
+    }
+
+    #[test]
+    fn test_closures() {
+        let input = "
+        let newAdder = fn(x) {
+            fn(y) { x + y };
+        };
+        let addTwo = newAdder(2);
+        addTwo(2);
+        ";
+
+        let expected = Object::Integer(4);
+
+        assert_object(input, expected);
+    }
+
+    #[test]
+    fn test_closure_captures() {
+        let input = "let make = fn(a, b) { let unused = [a, b]; fn(x) { x + a + len(unused) * 0 + limit } }; let limit = 1; make(1, 2)";
+
+        match test_eval(input) {
+            Response::Reply(Object::Function { env, .. }) => {
+                let captured = env.bindings().into_keys().collect::<Vec<_>>();
+                assert_eq!(captured, vec!["a".to_string(), "unused".to_string()]);
+            }
+            _ => unreachable!(),
+        }
+
+        let tests = vec![
+            (
+                "let make = fn(a) { fn(x) { x + a + limit } }; let limit = 10; make(1)(2)",
+                Object::Integer(13),
+            ),
+            (
+                "let f = fn() { let go = fn(n) { if (n == 0) { 0 } else { go(n - 1) } }; go(3) }; f()",
+                Object::Integer(0),
+            ),
+            (
+                "let f = fn() { let a = fn() { b() }; let b = fn() { 7 }; a() }; f()",
+                Object::Integer(7),
+            ),
+
+            // 捕まえたあとで束縛し直した名前は、トップレベルと同じく新しい値を見る
+            (
+                "let make = fn() { let x = 1; let get = fn() { x }; let x = 2; get() }; make()",
+                Object::Integer(2),
+            ),
+            (
+                "let x = 1; let make = fn() { let get = fn() { x }; let x = 2; get() }; make()",
+                Object::Integer(2),
+            ),
+            (
+                "let make = fn() { let x = 1; let mk = fn() { fn() { x } }; let get = mk(); let x = 2; get() }; make()",
+                Object::Integer(2),
+            ),
+            (
+                "let make = fn() { let x = 1; let get = try { throw(0) } catch (x) { fn() { x } }; let x = 2; get() }; make()",
+                Object::Integer(0),
+            ),
+        ];
+
+        assert_objects(tests);
+    }
+
+    #[test]
+    fn test_recursive_closures() {
+        let tests = vec![
+            (
+                "let fact = fn(n) { if (n == 0) { 1 } else { n * fact(n - 1) } }; fact(10)",
+                Object::Integer(3628800),
+            ),
+            (
+                "let even = fn(n) { if (n == 0) { true } else { odd(n - 1) } }; let odd = fn(n) { if (n == 0) { false } else { even(n - 1) } }; even(10)",
+                Object::Boolean(true),
+            ),
+            (
+                "let count = fn(n) { if (n == 0) { 0 } else { count(n - 1) } }; count(100000)",
+                Object::Integer(0),
+            ),
+            (
+                "let f = fn() { x }; let x = 5; f()",
+                Object::Integer(5),
+            ),
+            (
+                "let counter = fn() { let inner = fn() { inner }; inner }; is(counter()(), counter()())",
+                Object::Boolean(false),
+            ),
+        ];
+
+        assert_objects(tests);
+    }
+
+    #[test]
+    fn test_string_expressions() {
+        let tests = vec![
+            (
+                r#""Hello World!""#,
+                Object::String("Hello World!".to_string()),
+            ),
+            (
+                r#""Hello" + " " + "World!""#,
+                Object::String("Hello World!".to_string()),
+            ),
+            (r#""ab" * 3"#, Object::String("ababab".to_string())),
+            (r#"2 * "ab""#, Object::String("abab".to_string())),
+            (r#""ab" * 0"#, Object::String("".to_string())),
+            (
+                r#"let n = 2; "-" * (n + 1)"#,
+                Object::String("---".to_string()),
+            ),
+        ];
+
+        assert_objects(tests);
+
+        let tests = vec![
+            (r#""ab" * -1"#, "negative repetition count: -1"),
+            (
+                r#""ab" * 9223372036854775807"#,
+                "maximum length exceeded: 4194304",
+            ),
+            (r#""ab" * 2097153"#, "maximum length exceeded: 4194304"),
+            (r#""ab" + 1"#, "type mismatch: String + Integer"),
+        ];
+
+        assert_errors(tests);
+    }
+
+    #[test]
+    fn test_error_values() {
+        let tests = vec![
+            (
+                r#"error("not found")"#,
+                Object::Error("not found".to_string()),
+            ),
+            (r#"is_error(error("x"))"#, Object::Boolean(true)),
+            ("is_error(1)", Object::Boolean(false)),
+            (
+                r#"let find = fn(x) { if (x > 0) { x } else { error("negative") } }; [find(1), find(-1)]"#,
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Error("negative".to_string()),
+                ]),
+            ),
+            (
+                r#"let e = error("boom"); [e.message, e["message"]]"#,
+                Object::Array(vec![
+                    Object::String("boom".to_string()),
+                    Object::String("boom".to_string()),
+                ]),
+            ),
+            (
+                r#"try { throw(error("x")) } catch (e) { is_error(e) }"#,
+                Object::Boolean(true),
+            ),
+        ];
+
+        assert_objects(tests);
+
+        let tests = vec![
+            (
+                "error(1)",
+                "argument to `error` must be String, got Integer",
+            ),
+            ("is_error()", "wrong number of arguments. got=0, want=1"),
+            (r#"error("x") + 1"#, "type mismatch: Error + Integer"),
+            (
+                r#"error("x")["kind"]"#,
+                "index operator not supported: Error",
+            ),
+        ];
+
+        assert_errors(tests);
+    }
+
+    #[test]
+    fn test_puts() {
+        let output = Rc::new(RefCell::new(String::new()));
+        let mut evaluator = Evaluator::new();
+        evaluator.set_output(Some(output.clone()));
+
+        let input = r#"puts(1, "two", [3]); puts(); sandbox("puts(4)")"#;
+        let mut lexer = Lexer::new(input);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+
+        assert!(matches!(
+            evaluator.eval(program),
+            Response::Reply(Object::Null)
+        ));
+        assert_eq!(output.borrow().as_str(), "1\ntwo\n[3]\n");
+    }
+
+    #[test]
+    fn test_output_callback() {
+        let lines = Rc::new(RefCell::new(vec![]));
+        let mut evaluator = Evaluator::new();
+        let log = Rc::clone(&lines);
+        evaluator.set_output_callback(move |line| log.borrow_mut().push(line.to_string()));
+        evaluator.set_statement_limit(Some(100));
+
+        let input = r#"puts("start", 1); sandbox("puts(2)"); let loop = fn() { loop() }; loop()"#;
+        let mut lexer = Lexer::new(input);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+
+        assert!(matches!(evaluator.eval(program), Response::Error(_)));
+        assert_eq!(*lines.borrow(), vec!["start", "1"]);
+    }
+
+    #[test]
+    fn test_input() {
+        let eval = |evaluator: &mut Evaluator, input: &str| {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+            evaluator.eval(parser.parse_program())
+        };
+
+        let lines = Rc::new(RefCell::new(VecDeque::from(vec![
+            "7".to_string(),
+            "yes".to_string(),
+        ])));
+        let output = Rc::new(RefCell::new(String::new()));
+        let mut evaluator = Evaluator::new();
+        let source = Rc::clone(&lines);
+        evaluator.set_input(Some(Rc::new(RefCell::new(move || {
+            source.borrow_mut().pop_front()
+        }))));
+        evaluator.set_output(Some(output.clone()));
+        evaluator.start_recording();
+
+        let response = eval(
+            &mut evaluator,
+            r#"let n = int(input("guess: ")); [n + 1, read_line("again? "), input()]"#,
+        );
+
+        match response {
+            Response::Reply(result) => assert_eq!(result.to_string(), "[8, yes, null]"),
+            _ => unreachable!(),
+        }
+
+        assert_eq!(*output.borrow(), "guess: again? ");
+        assert_eq!(
+            evaluator.take_recorded(),
+            vec![
+                ("input".to_string(), Object::String("7".to_string())),
+                ("read_line".to_string(), Object::String("yes".to_string())),
+                ("input".to_string(), Object::Null),
+            ]
+        );
+
+        let tests = vec![
+            (
+                "input(1)",
+                "argument to `input` must be String, got Integer",
+            ),
+            (
+                r#"input("a", "b")"#,
+                "wrong number of arguments. got=2, want=0 or 1",
+            ),
+            (
+                "read_line(1)",
+                "argument to `read_line` must be File, got Integer",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            match eval(&mut evaluator, input) {
+                Response::Error(error) => assert_eq!(error.to_string(), expected),
+                _ => unreachable!(),
+            }
+        }
+
+        evaluator.set_pure(true);
+
+        match eval(&mut evaluator, r#"input("never shown")"#) {
+            Response::Error(error) => {
+                assert_eq!(
+                    error.to_string(),
+                    "`input` has side effects and is not allowed here"
+                )
+            }
+            _ => unreachable!(),
+        }
+
+        assert_eq!(*output.borrow(), "guess: again? ");
+    }
+
+    #[cfg(all(feature = "std", unix))]
+    #[test]
+    fn test_exec() {
+        let eval = |evaluator: &mut Evaluator, input: &str| {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+            evaluator.eval(parser.parse_program())
+        };
+
+        let mut evaluator = Evaluator::new();
+
+        match eval(&mut evaluator, r#"exec("echo", ["hi"])"#) {
+            Response::Error(error) => assert_eq!(
+                error.to_string(),
+                "`exec` is not enabled (use --allow-exec)"
+            ),
+            _ => unreachable!(),
+        }
+
+        evaluator.set_exec(true);
+
+        let input = r#"let r = exec("sh", ["-c", "echo out; echo err >&2; exit 3"]); [r.stdout, r.stderr, r.status]"#;
+
+        match eval(&mut evaluator, input) {
+            Response::Reply(result) => assert_eq!(
+                result,
+                Object::Array(vec![
+                    Object::String("out\n".to_string()),
+                    Object::String("err\n".to_string()),
+                    Object::Integer(3),
+                ])
+            ),
+            _ => unreachable!(),
+        }
+
+        match eval(&mut evaluator, r#"exec("ronkey-no-such-command")"#) {
+            Response::Error(error) => {
+                assert!(error
+                    .to_string()
+                    .starts_with("failed to run `ronkey-no-such-command`: "))
+            }
+            _ => unreachable!(),
+        }
+
+        match eval(&mut evaluator, r#"exec("echo", [1])"#) {
+            Response::Error(error) => {
+                assert_eq!(
+                    error.to_string(),
+                    "arguments of `exec` must be Strings, got Integer"
+                )
+            }
+            _ => unreachable!(),
+        }
+
+        evaluator.set_deterministic(true);
+
+        match eval(&mut evaluator, r#"exec("true")"#) {
+            Response::Error(error) => {
+                assert_eq!(
+                    error.to_string(),
+                    "`exec` is nondeterministic and is not allowed here"
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_deterministic() {
+        let tests = vec![
+            (
+                "stopwatch()",
+                Err("`stopwatch` is nondeterministic and is not allowed here"),
+            ),
+            (
+                r#"sandbox("stopwatch()")["error"]"#,
+                Ok("`stopwatch` is nondeterministic and is not allowed here"),
+            ),
+            (
+                "read_line()",
+                Err("`read_line` is nondeterministic and is not allowed here"),
+            ),
+            (
+                "read_source()",
+                Err("`read_source` is nondeterministic and is not allowed here"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+            let program = parser.parse_program();
+            let mut evaluator = Evaluator::new();
+            evaluator.set_deterministic(true);
+
+            match (evaluator.eval(program), expected) {
+                (Response::Reply(actual), Ok(expected)) => assert_eq!(actual.to_string(), expected),
+                (Response::Error(actual), Err(expected)) => {
+                    assert_eq!(actual.to_string(), expected)
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_interceptors() {
+        let seen = Rc::new(RefCell::new(vec![]));
+        let log = seen.clone();
+        let mut evaluator = Evaluator::new();
+        evaluator.set_output(Some(Rc::new(RefCell::new(String::new()))));
+
+        // 読みとり専用のセッション：すでにある束縛の上書きを拒否する
+        evaluator.push_interceptor(Rc::new(RefCell::new(
+            |evaluator: &Evaluator, operation: &Operation| match operation {
+                Operation::Bind { name, .. } if evaluator.get(name).is_some() => {
+                    let message = format!("`{}` is read-only", name);
+                    Err(message.into())
+                }
+                _ => Ok(()),
+            },
+        )));
+        // 名前空間の制限：`puts` と `import` を拒否し、呼び出しを記録する
+        evaluator.push_interceptor(Rc::new(RefCell::new(
+            move |evaluator: &Evaluator, operation: &Operation| {
+                log.borrow_mut().push(format!(
+                    "{:?} in {}",
+                    operation,
+                    evaluator.current_statement().unwrap_or_default()
+                ));
+
+                match operation {
+                    Operation::CallBuildin { name: "puts", .. } => {
+                        Err("`puts` is restricted".into())
+                    }
+                    Operation::Import { path } => Err(format!("cannot import {}", path).into()),
+                    _ => Ok(()),
+                }
+            },
+        )));
+
+        let tests = vec![
+            ("let x = 1; let y = 2; x + y", Ok("3")),
+            ("let x = 2;", Err("`x` is read-only")),
+            (
+                "let f = fn() { let x = 3; x }; f()",
+                Err("`x` is read-only"),
+            ),
+            ("let p = puts; p(1)", Err("`puts` is restricted")),
+            (r#"sandbox("puts(1)")"#, Ok("null")),
+            (r#"import "list" as list;"#, Err("cannot import list")),
+        ];
+
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+
+            match (evaluator.eval(parser.parse_program()), expected) {
+                (Response::Reply(result), Ok(expected)) => {
+                    assert_eq!(result.to_string(), expected, "{}", input)
+                }
+                (Response::Error(error), Err(expected)) => {
+                    assert_eq!(error.to_string(), expected, "{}", input)
+                }
+                _ => unreachable!("{}", input),
+            }
+        }
+
+        assert_eq!(
+            seen.borrow()[0],
+            "Bind { name: \"x\", value: Integer(1) } in let x = 1;"
+        );
+        assert!(seen
+            .borrow()
+            .iter()
+            .any(|event| event.starts_with("CallBuildin { name: \"puts\"")));
+
+        assert!(evaluator.pop_interceptor().is_some());
+        assert!(evaluator.pop_interceptor().is_some());
+        assert!(evaluator.pop_interceptor().is_none());
+    }
+
+    #[test]
+    fn test_audit() {
+        let events = Rc::new(RefCell::new(vec![]));
+        let log = events.clone();
+        let mut evaluator = Evaluator::new();
+        evaluator.set_output(Some(Rc::new(RefCell::new(String::new()))));
+        evaluator.set_audit(Some(Rc::new(RefCell::new(move |event: &AuditEvent| {
+            log.borrow_mut().push(event.clone())
+        }))));
+
+        let input = r#"puts("hi", 2); len("x"); sandbox("read_file(1)"); read_file("a.txt")"#;
+        let mut lexer = Lexer::new(input);
+        let mut parser = Parser::new(&mut lexer);
+        assert!(matches!(
+            evaluator.eval(parser.parse_program()),
+            Response::Error(_)
+        ));
+
+        let events = events.borrow();
+        let summary = events
+            .iter()
+            .map(|event| {
+                (
+                    event.name.as_str(),
+                    event.arguments.join(", "),
+                    event.outcome.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("puts", "\"hi\" (String), 2 (Integer)".to_string(), Ok(())),
+                (
+                    "read_file",
+                    "\"a.txt\" (String)".to_string(),
+                    Err("no filesystem available to read a.txt".to_string())
+                ),
+            ]
+        );
+        assert!(events.iter().all(|event| event.duration.is_some()));
+
+        let long = Object::String("x".repeat(100));
+        assert_eq!(
+            AuditEvent::summarize(&[long])[0],
+            format!("\"{}...", "x".repeat(63))
+        );
+    }
+
+    #[test]
+    fn test_on_interrupt() {
+        let output = Rc::new(RefCell::new(String::new()));
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let mut evaluator = Evaluator::new();
+        evaluator.set_output(Some(output.clone()));
+        evaluator.set_interrupt(Some(interrupt.clone()));
+
+        let mut eval = |input: &str| {
+            let mut lexer = Lexer::new(input);
+            let mut parser = Parser::new(&mut lexer);
+            evaluator.eval(parser.parse_program())
+        };
+
+        assert!(matches!(
+            eval(r#"let done = 3; on_interrupt(fn() { puts("flushed", done) })"#),
+            Response::Reply(Object::Null)
+        ));
+
+        interrupt.store(true, AtomicOrdering::SeqCst);
+        match eval("1; 2") {
+            Response::Error(error) => assert_eq!(error.kind(), &EvalError::Interrupted),
+            _ => unreachable!(),
+        }
+        assert_eq!(output.borrow().as_str(), "flushed\n3\n");
+
+        // 登録した関数は 1 度しか呼ばれない
+        interrupt.store(true, AtomicOrdering::SeqCst);
+        assert!(matches!(eval("1"), Response::Error(_)));
+        assert!(matches!(eval("1"), Response::Reply(Object::Integer(1))));
+        assert_eq!(output.borrow().as_str(), "flushed\n3\n");
+
+        // `try` の中で受けとった中断も捕捉せずに評価を終える
+        let mut evaluator = Evaluator::new();
+        evaluator.set_interrupt(Some(interrupt.clone()));
+        evaluator.set_statement_limit(Some(1000));
+        evaluator.push_interceptor(Rc::new(RefCell::new(
+            move |_: &Evaluator, operation: &Operation| {
+                if let Operation::CallBuildin { name: "len", .. } = operation {
+                    interrupt.store(true, AtomicOrdering::SeqCst);
+                }
+                Ok(())
+            },
+        )));
+
+        let program = Parser::new(&mut Lexer::new(
+            r#"let l = fn() { try { len("ctrl-c"); 1 } catch (e) { e }; l() }; l()"#,
+        ))
+        .parse_program();
+
+        match evaluator.eval(program) {
+            Response::Error(error) => assert_eq!(error.kind(), &EvalError::Interrupted),
+            _ => unreachable!(),
+        }
+
+        assert_errors(vec![(
+            "on_interrupt(1)",
+            "argument to `on_interrupt` must be Function, got Integer",
+        )]);
+    }
+
+    #[test]
+    fn test_buildin_functions() {
+        let tests = vec![
+            (r#"len("")"#, Object::Integer(0)),
+            (r#"len("four")"#, Object::Integer(4)),
+            (r#"len("hello world")"#, Object::Integer(11)),
+            ("id([1, 2]) == id([1, 2])", Object::Boolean(true)),
+            ("id([1, 2]) == id([2, 1])", Object::Boolean(false)),
+            ("let a = {1: 2}; let b = a; is(a, b)", Object::Boolean(true)),
+            ("is([1], [2])", Object::Boolean(false)),
+            (
+                "[1 == 1.0, is(1, 1.0)]",
+                Object::Array(vec![Object::Boolean(true), Object::Boolean(false)]),
+            ),
+            (
+                "let a = fn() { 1 }; let b = fn() { 1 }; [is(a, a), is(a, b), id(a) == id(b)]",
+                Object::Array(vec![
+                    Object::Boolean(true),
+                    Object::Boolean(false),
+                    Object::Boolean(false),
+                ]),
+            ),
+            ("let f = fn(x) { x }; is([f], [f])", Object::Boolean(true)),
+            ("is(len, len)", Object::Boolean(true)),
+            ("let f = fn(x) { x }; let g = f; id(f) == id(g)", Object::Boolean(true)),
+            ("id(len) == id(len)", Object::Boolean(true)),
+            // `is` で同じ値どうしだけが同じ `id` になる
+            (
+                "let f = fn(x) { x }; let g = fn(x) { x }; \
+                 let vs = [1, 1.0, \"1\", [1], [f], [g], f, g, len, puts, {1: f}, {1: g}, true]; \
+                 let n = len(vs); \
+                 let check = fn(i, j) { \
+                   if (i == n) { true } else { if (j == n) { check(i + 1, 0) } else { \
+                     if (is(vs[i], vs[j]) == (id(vs[i]) == id(vs[j]))) { check(i, j + 1) } else { [i, j] } \
+                   } } \
+                 }; \
+                 check(0, 0)",
+                Object::Boolean(true),
+            ),
+            (
+                r#"let sw = stopwatch(); let t = sw["elapsed_ms"](); t < 0"#,
+                Object::Boolean(false),
+            ),
+            (r#"char_at("añb", 1)"#, Object::String("ñ".to_string())),
+            (r#"char_at("ab", 2)"#, Object::Null),
+            (r#"char_at("ab", -1)"#, Object::Null),
+            (r#"ord("a")"#, Object::Integer(97)),
+            (r#"ord("ñ")"#, Object::Integer(241)),
+            ("chr(34)", Object::String("\"".to_string())),
+            (r#"chr(ord("z"))"#, Object::String("z".to_string())),
+            (
+                r#"join(["a", "b", "c"], ", ")"#,
+                Object::String("a, b, c".to_string()),
+            ),
+            (
+                r#"join([1, true, [2, "x"], {"k": 3}], "")"#,
+                Object::String("1true[2, x]{k: 3}".to_string()),
+            ),
+            (r#"join([], "-")"#, Object::String("".to_string())),
+            (
+                r#"let m = {"a": 1}; let n = insert(m, "b", 2); [m, n, insert(n, "a", 3)]"#,
+                Object::Array(vec![
+                    test_map(vec![("a", 1)]),
+                    test_map(vec![("a", 1), ("b", 2)]),
+                    test_map(vec![("a", 3), ("b", 2)]),
+                ]),
+            ),
+            (
+                r#"let m = {"a": 1, "b": 2}; [remove(m, "a"), remove(m, "c"), m]"#,
+                Object::Array(vec![
+                    test_map(vec![("b", 2)]),
+                    test_map(vec![("a", 1), ("b", 2)]),
+                    test_map(vec![("a", 1), ("b", 2)]),
+                ]),
+            ),
+            (
+                r#"insert({}, 1, "one")[1]"#,
+                Object::String("one".to_string()),
+            ),
+            (
+                "let a = [1, 2, 3]; [pop(a), remove_at(a, 1), remove_at(a, 3), remove_at(a, -1), a]",
+                Object::Array(vec![
+                    Object::Array(vec![Object::Integer(1), Object::Integer(2)]),
+                    Object::Array(vec![Object::Integer(1), Object::Integer(3)]),
+                    Object::Array(vec![
+                        Object::Integer(1),
+                        Object::Integer(2),
+                        Object::Integer(3),
+                    ]),
+                    Object::Array(vec![
+                        Object::Integer(1),
+                        Object::Integer(2),
+                        Object::Integer(3),
+                    ]),
+                    Object::Array(vec![
+                        Object::Integer(1),
+                        Object::Integer(2),
+                        Object::Integer(3),
+                    ]),
+                ]),
+            ),
+            ("pop([])", Object::Null),
+            (
+                r#"zip([1, 2, 3], ["a", "b"])"#,
+                Object::Array(vec![
+                    Object::Array(vec![Object::Integer(1), Object::String("a".to_string())]),
+                    Object::Array(vec![Object::Integer(2), Object::String("b".to_string())]),
+                ]),
+            ),
+            (
+                r#"enumerate(["a", "b"])"#,
+                Object::Array(vec![
+                    Object::Array(vec![Object::Integer(0), Object::String("a".to_string())]),
+                    Object::Array(vec![Object::Integer(1), Object::String("b".to_string())]),
+                ]),
+            ),
+            ("enumerate([])", Object::Array(vec![])),
+            (
+                r#"unique([3, 1, 3, "a", 1, "a", [1], [1]])"#,
+                Object::Array(vec![
+                    Object::Integer(3),
+                    Object::Integer(1),
+                    Object::String("a".to_string()),
+                    Object::Array(vec![Object::Integer(1)]),
+                ]),
+            ),
+            (
+                r#"[index_of([1, 2, 2], 2), index_of([1], "1"), index_of("日本語", "語"), index_of("abc", "x")]"#,
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Null,
+                    Object::Integer(2),
+                    Object::Null,
+                ]),
+            ),
+            (
+                "[flatten([1, [2, [3]]]), flatten([1, [2, [3]]], 2), flatten([[1]], 0)]",
+                Object::Array(vec![
+                    Object::Array(vec![
+                        Object::Integer(1),
+                        Object::Integer(2),
+                        Object::Array(vec![Object::Integer(3)]),
+                    ]),
+                    Object::Array(vec![
+                        Object::Integer(1),
+                        Object::Integer(2),
+                        Object::Integer(3),
+                    ]),
+                    Object::Array(vec![Object::Array(vec![Object::Integer(1)])]),
+                ]),
+            ),
+            (
+                r#"[int("42"), int(" -7 "), int(3.9), int(-3.9), int(true), int(12)]"#,
+                Object::Array(vec![
+                    Object::Integer(42),
+                    Object::Integer(-7),
+                    Object::Integer(3),
+                    Object::Integer(-3),
+                    Object::Integer(1),
+                    Object::Integer(12),
+                ]),
+            ),
+            (
+                r#"[str(42), str([1, "a"]), str("s"), str(first([]))]"#,
+                Object::Array(vec![
+                    Object::String("42".to_string()),
+                    Object::String("[1, a]".to_string()),
+                    Object::String("s".to_string()),
+                    Object::String("null".to_string()),
+                ]),
+            ),
+            (
+                r#"[bool(0), bool(""), bool(false), bool(first([])), bool([])]"#,
+                Object::Array(vec![
+                    Object::Boolean(true),
+                    Object::Boolean(true),
+                    Object::Boolean(false),
+                    Object::Boolean(false),
+                    Object::Boolean(true),
+                ]),
+            ),
+            (r#"int(str(123)) + 1"#, Object::Integer(124)),
+            (
+                r#"map([1, 1.5, "s", true, [1], {}, fn(x) { x }, len, error("e"), first([])], type)"#,
+                Object::Array(
+                    vec![
+                        "Integer",
+                        "Float",
+                        "String",
+                        "Boolean",
+                        "Array",
+                        "Map",
+                        "Function",
+                        "Buildin Function",
+                        "Error",
+                        "null",
+                    ]
+                    .into_iter()
+                    .map(|name| Object::String(name.to_string()))
+                    .collect(),
+                ),
+            ),
+            (
+                r#"let describe = fn(x) { if (type(x) == "Array") { len(x) } else { x } }; [describe([1, 2]), describe(7)]"#,
+                Object::Array(vec![Object::Integer(2), Object::Integer(7)]),
+            ),
+            (
+                "let a = [1, 2, 3, 4]; [slice(a, 1, 3), slice(a, -2), slice(a, 0, -1), slice(a, 3, 1), slice(a, -9, 9)]",
+                Object::Array(vec![
+                    Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+                    Object::Array(vec![Object::Integer(3), Object::Integer(4)]),
+                    Object::Array(vec![
+                        Object::Integer(1),
+                        Object::Integer(2),
+                        Object::Integer(3),
+                    ]),
+                    Object::Array(vec![]),
+                    Object::Array(vec![
+                        Object::Integer(1),
+                        Object::Integer(2),
+                        Object::Integer(3),
+                        Object::Integer(4),
+                    ]),
+                ]),
+            ),
+            (
+                r#"[slice("日本語です", 1, 3), slice("hello", -3), slice("hi", 5)]"#,
+                Object::Array(vec![
+                    Object::String("本語".to_string()),
+                    Object::String("llo".to_string()),
+                    Object::String("".to_string()),
+                ]),
+            ),
+            ("remove_at([1], 0)", Object::Array(vec![])),
+            (
+                r#"split("a,b,c", ",")"#,
+                Object::Array(vec![
+                    Object::String("a".to_string()),
+                    Object::String("b".to_string()),
+                    Object::String("c".to_string()),
+                ]),
+            ),
+            (
+                r#"split("日本", "")"#,
+                Object::Array(vec![
+                    Object::String("日".to_string()),
+                    Object::String("本".to_string()),
+                ]),
+            ),
+            (
+                r#"split(",a,", ",")"#,
+                Object::Array(vec![
+                    Object::String("".to_string()),
+                    Object::String("a".to_string()),
+                    Object::String("".to_string()),
+                ]),
+            ),
+            (r#"split("", "")"#, Object::Array(vec![])),
+            (
+                r#"chars("日本a")"#,
+                Object::Array(vec![
+                    Object::String("日".to_string()),
+                    Object::String("本".to_string()),
+                    Object::String("a".to_string()),
+                ]),
+            ),
+            (r#"chars("")"#, Object::Array(vec![])),
+            (
+                r#"let x = 1; let y = [2]; format("x = {}, y = {}", x, y)"#,
+                Object::String("x = 1, y = [2]".to_string()),
+            ),
+            (
+                r#"format("{1}{0}{1} {{}} {}", "a", "b")"#,
+                Object::String("bab {} a".to_string()),
+            ),
+            (r#"format("plain")"#, Object::String("plain".to_string())),
+            (
+                r#"[starts_with("hello", "he"), starts_with("hello", "lo"), starts_with("a", "")]"#,
+                Object::Array(vec![
+                    Object::Boolean(true),
+                    Object::Boolean(false),
+                    Object::Boolean(true),
+                ]),
+            ),
+            (
+                r#"[ends_with("hello", "lo"), ends_with("hello", "he"), ends_with("", "a")]"#,
+                Object::Array(vec![
+                    Object::Boolean(true),
+                    Object::Boolean(false),
+                    Object::Boolean(false),
+                ]),
+            ),
+            (
+                r#"[starts_with([1, 2], 1), starts_with([1, 2], 2), ends_with([1, "a"], "a"), ends_with([], 1)]"#,
+                Object::Array(vec![
+                    Object::Boolean(true),
+                    Object::Boolean(false),
+                    Object::Boolean(true),
+                    Object::Boolean(false),
+                ]),
+            ),
+            (
+                r#"len(filter(chars("banana"), fn(ch) { ch == "a" }))"#,
+                Object::Integer(3),
+            ),
+            (
+                r#"[substr("hello", 1, 3), substr("hello", -3, 2), substr("日本語", 1, 10), substr("abc", 5, 1)]"#,
+                Object::Array(vec![
+                    Object::String("ell".to_string()),
+                    Object::String("ll".to_string()),
+                    Object::String("本語".to_string()),
+                    Object::String("".to_string()),
+                ]),
+            ),
+            (
+                r#"[replace("a-b-c", "-", "+"), replace("aaa", "aa", "b"), replace("abc", "x", "y")]"#,
+                Object::Array(vec![
+                    Object::String("a+b+c".to_string()),
+                    Object::String("ba".to_string()),
+                    Object::String("abc".to_string()),
+                ]),
+            ),
+            (
+                r#"join(split("a-b", "-"), "+")"#,
+                Object::String("a+b".to_string()),
+            ),
+        ];
+
+        assert_objects(tests);
+
+        let tests = vec![
+            (
+                r#"char_at(1, "a")"#,
+                "arguments to `char_at` must be String and Integer, got Integer and String",
+            ),
+            (
+                r#"ord("ab")"#,
+                r#"argument to `ord` must be a single character, got "ab""#,
+            ),
+            (
+                r#"ord("")"#,
+                r#"argument to `ord` must be a single character, got """#,
+            ),
+            ("chr(-1)", "invalid code point: -1"),
+            ("chr(55296)", "invalid code point: 55296"),
+            (
+                r#"join("abc", "")"#,
+                "arguments to `join` must be Array and String, got String, String",
+            ),
+            (
+                r#"insert([], "a", 1)"#,
+                "argument to `insert` must be Map, got Array",
+            ),
+            (
+                "insert({}, fn(x) { x }, 1)",
+                "unusable as map key: Function",
+            ),
+            (
+                r#"remove({"a": 1})"#,
+                "wrong number of arguments. got=1, want=2",
+            ),
+            ("pop(1)", "argument to `pop` must be Array, got Integer"),
+            (
+                r#"zip([1], "a")"#,
+                "arguments to `zip` must be Array and Array, got Array, String",
+            ),
+            ("enumerate({})", "argument to `enumerate` must be Array, got Map"),
+            ("unique([len])", "unusable as map key: Buildin Function"),
+            ("unique(1)", "argument to `unique` must be Array, got Integer"),
+            (
+                r#"index_of("abc", 1)"#,
+                "arguments to `index_of` must be Array and a value, or String and String, got String, Integer",
+            ),
+            (
+                "flatten([[1]], -1)",
+                "depth of `flatten` must not be negative, got -1",
+            ),
+            (
+                r#"flatten("ab")"#,
+                "arguments to `flatten` must be Array and Integer, got String",
+            ),
+            (r#"int("abc")"#, r#"cannot convert "abc" to Integer"#),
+            (r#"int("1.5")"#, r#"cannot convert "1.5" to Integer"#),
+            ("int(1e300)", "cannot convert 1e300 to Integer"),
+            ("int([1])", "argument to `int` not supported, got Array"),
+            ("str(1, 2)", "wrong number of arguments. got=2, want=1"),
+            (
+                "slice({}, 0, 1)",
+                "argument to `slice` must be Array or String, got Map",
+            ),
+            (
+                "slice([1], \"0\")",
+                "arguments to `slice` must be Array or String and Integers, got Array, String",
+            ),
+            (
+                r#"remove_at([1], "0")"#,
+                "arguments to `remove_at` must be Array and Integer, got Array, String",
+            ),
+            ("chars(1)", "argument to `chars` must be String, got Integer"),
+            (
+                r#"format("{} {}", 1)"#,
+                "`format` has no argument 1 (got 1 arguments)",
+            ),
+            (r#"format("{}", 1, 2)"#, "argument 1 to `format` is not used"),
+            (r#"format("{x}", 1)"#, "invalid placeholder in `format`: {x}"),
+            (r#"format("{", 1)"#, "unclosed `{` in `format`"),
+            (r#"format("}")"#, "unmatched `}` in `format`"),
+            ("format(1)", "argument to `format` must be String, got Integer"),
+            (
+                r#"starts_with("a", 1)"#,
+                "arguments to `starts_with` must be String and String, or Array and a value, got String, Integer",
+            ),
+            (
+                "ends_with([1])",
+                "wrong number of arguments. got=1, want=2",
+            ),
+            (
+                r#"substr("abc", 1, -1)"#,
+                "length of `substr` must not be negative, got -1",
+            ),
+            (
+                r#"substr("abc", 1)"#,
+                "wrong number of arguments. got=2, want=3",
+            ),
+            (
+                r#"replace("abc", "a", 1)"#,
+                "arguments to `replace` must be String, String and String, got String, String, Integer",
+            ),
+            (
+                r#"split("a", 1)"#,
+                "arguments to `split` must be String and String, got String, Integer",
+            ),
+            (
+                "join([1])",
+                "wrong number of arguments. got=1, want=2",
+            ),
+        ];
+
+        assert_errors(tests);
     }
 
     #[test]
-    fn test_eval_integer_expressions() {
+    fn test_algorithmic_buildin_functions() {
+        let array = |elements: Vec<isize>| {
+            Object::Array(elements.into_iter().map(Object::Integer).collect())
+        };
+
         let tests = vec![
-            ("5", Object::Integer(5)),
-            ("10", Object::Integer(10)),
-            ("-5", Object::Integer(-5)),
-            ("-10", Object::Integer(-10)),
-            ("5 + 5 + 5 + 5 - 10", Object::Integer(10)),
-            ("2 * 2 * 2 * 2 * 2", Object::Integer(32)),
-            ("-50 + 100 + -50", Object::Integer(0)),
-            ("5 * 2 + 10", Object::Integer(20)),
-            ("5 + 2 * 10", Object::Integer(25)),
-            ("20 + 2 * -10", Object::Integer(0)),
-            ("50 / 2 * 2 + 10", Object::Integer(60)),
-            ("2 * (5 + 10)", Object::Integer(30)),
-            ("3 * 3 * 3 + 10", Object::Integer(37)),
-            ("3 * (3 * 3) + 10", Object::Integer(37)),
-            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", Object::Integer(50)),
+            ("binary_search([1, 3, 5, 7, 9], 7)", Object::Integer(3)),
+            ("binary_search([1, 3, 5, 7, 9], 1)", Object::Integer(0)),
+            ("binary_search([1, 3, 5, 7, 9], 4)", Object::Null),
+            ("binary_search([], 4)", Object::Null),
+            (r#"binary_search(["a", "c", "e"], "e")"#, Object::Integer(2)),
+            ("min_by([3, -5, 4], fn(x) { x * x })", Object::Integer(3)),
+            ("max_by([3, -5, 4], fn(x) { x * x })", Object::Integer(-5)),
+            ("min_by([2, 1, 1], fn(x) { return x; })", Object::Integer(1)),
+            ("max_by([], fn(x) { x })", Object::Null),
+            (
+                r#"max_by([{"age": 3}, {"age": 9}], fn(p) { p["age"] })["age"]"#,
+                Object::Integer(9),
+            ),
+            (
+                "chunk([1, 2, 3, 4, 5], 2)",
+                Object::Array(vec![array(vec![1, 2]), array(vec![3, 4]), array(vec![5])]),
+            ),
+            ("chunk([], 3)", array(vec![])),
+            ("map([1, 2, 3], fn(x) { x * 2 })", array(vec![2, 4, 6])),
+            ("map([], fn(x) { x })", array(vec![])),
+            (r#"map(["a", "bc"], len)"#, array(vec![1, 2])),
+            (
+                "let offset = 10; map([1, 2], fn(x) { return x + offset; })",
+                array(vec![11, 12]),
+            ),
+            ("map([[1], [2, 3]], first)", array(vec![1, 2])),
+            ("filter([1, 2, 3, 4], fn(x) { x > 2 })", array(vec![3, 4])),
+            ("filter([1, 2, 3], fn(x) { x })", array(vec![1, 2, 3])),
+            (
+                "filter([[], [1], [2, 3]], fn(x) { first(x) })",
+                Object::Array(vec![array(vec![1]), array(vec![2, 3])]),
+            ),
+            ("filter([], fn(x) { false })", array(vec![])),
+            (
+                "reduce([1, 2, 3, 4], 0, fn(acc, x) { acc + x })",
+                Object::Integer(10),
+            ),
+            ("reduce([], 5, fn(acc, x) { acc + x })", Object::Integer(5)),
+            (
+                "reduce([1, 2, 3], [], fn(acc, x) { push(acc, x * x) })",
+                array(vec![1, 4, 9]),
+            ),
+            (
+                r#"reduce(["a", "b"], "", fn(acc, x) { x + acc })"#,
+                Object::String("ba".to_string()),
+            ),
         ];
 
         assert_objects(tests);
-    }
 
-    #[test]
-    fn test_eval_boolean_expressions() {
         let tests = vec![
-            ("true", Object::Boolean(true)),
-            ("false", Object::Boolean(false)),
-            ("1 < 2", Object::Boolean(true)),
-            ("1 > 2", Object::Boolean(false)),
-            ("1 < 1", Object::Boolean(false)),
-            ("1 > 1", Object::Boolean(false)),
-            ("1 == 1", Object::Boolean(true)),
-            ("1 != 1", Object::Boolean(false)),
-            ("1 == 2", Object::Boolean(false)),
-            ("1 != 2", Object::Boolean(true)),
-            ("true == true", Object::Boolean(true)),
-            ("false == false", Object::Boolean(true)),
-            ("true == false", Object::Boolean(false)),
-            ("true != false", Object::Boolean(true)),
-            ("false != true", Object::Boolean(true)),
-            ("(1 < 2) == true", Object::Boolean(true)),
-            ("(1 < 2) == false", Object::Boolean(false)),
-            ("(1 > 2) == true", Object::Boolean(false)),
-            ("(1 > 2) == false", Object::Boolean(true)),
-            (r#""Hello World!" == "Hello World!""#, Object::Boolean(true)),
             (
-                r#""Hello World!" != "Hello World!""#,
-                Object::Boolean(false),
+                r#"binary_search([1, 2], "a")"#,
+                "cannot compare Integer with String",
+            ),
+            (
+                "min_by([1, 2], fn(x) { x == 1 })",
+                "cannot compare Boolean with Boolean",
+            ),
+            ("min_by([1], 2)", "not a function: Integer"),
+            ("map([1], 2)", "not a function: Integer"),
+            (
+                "map(1, len)",
+                "argument to `map` must be Array, got Integer",
+            ),
+            ("map([1, 0], fn(x) { 1 / x })", "division by zero"),
+            ("filter([1])", "wrong number of arguments. got=1, want=2"),
+            (
+                "reduce([1], fn(acc, x) { acc })",
+                "wrong number of arguments. got=2, want=3",
+            ),
+            (
+                "reduce(1, 0, fn(acc, x) { acc })",
+                "argument to `reduce` must be Array, got Integer",
+            ),
+            (
+                "reduce([1], 0, fn(x) { x })",
+                "expected arity to be 1, got 2 instead",
+            ),
+            (
+                r#"filter("ab", len)"#,
+                "argument to `filter` must be Array, got String",
+            ),
+            ("chunk([1, 2], 0)", "chunk size must be positive, got 0"),
+            (
+                r#"stopwatch()["elapsed_ms"](1)"#,
+                "wrong number of arguments. got=1, want=0",
+            ),
+            (
+                "chunk(1, 2)",
+                "arguments to `chunk` must be Array and Integer, got Integer and Integer",
             ),
         ];
 
-        assert_objects(tests);
+        assert_errors(tests);
     }
 
     #[test]
-    fn test_eval_bang_prefix_expressions() {
+    fn test_collation_buildin_functions() {
+        let strings = |values: Vec<&str>| {
+            Object::Array(
+                values
+                    .into_iter()
+                    .map(|value| Object::String(value.to_string()))
+                    .collect(),
+            )
+        };
+
         let tests = vec![
-            ("!true", Object::Boolean(false)),
-            ("!false", Object::Boolean(true)),
-            ("!5", Object::Boolean(false)),
-            ("!!true", Object::Boolean(true)),
-            ("!!false", Object::Boolean(false)),
-            ("!!5", Object::Boolean(true)),
+            (r#"compare("a", "b")"#, Object::Integer(-1)),
+            (r#"compare("b", "B")"#, Object::Integer(1)),
+            (
+                r#"compare("b", "B", "case_insensitive")"#,
+                Object::Integer(0),
+            ),
+            (
+                r#"compare("Straße", "STRASSE", "case_insensitive")"#,
+                Object::Integer(1),
+            ),
+            ("compare(2, 10)", Object::Integer(-1)),
+            (r#"compare("é", "z", "bytes")"#, Object::Integer(1)),
+            (r#"compare("é", "z", "codepoint")"#, Object::Integer(1)),
+            (r#"sort(["b", "a", "C"])"#, strings(vec!["C", "a", "b"])),
+            (
+                r#"sort(["b", "a", "C", "A"], "case_insensitive")"#,
+                strings(vec!["a", "A", "b", "C"]),
+            ),
+            (
+                "sort([3, 1, 2])",
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                    Object::Integer(3),
+                ]),
+            ),
+            ("sort([])", Object::Array(vec![])),
         ];
 
         assert_objects(tests);
-    }
 
-    #[test]
-    fn test_if_else_expressions() {
         let tests = vec![
-            ("if (true) { 10 }", Object::Integer(10)),
-            ("if (false) { 10 }", Object::Null),
-            ("if (1) { 10 }", Object::Integer(10)),
-            ("if (1 < 2) { 10 }", Object::Integer(10)),
-            ("if (1 > 2) { 10 }", Object::Null),
-            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
-            ("if (1 < 2) { 10 } else { 20 }", Object::Integer(10)),
+            (r#"compare(1, "a")"#, "cannot compare Integer with String"),
+            (
+                r#"compare("a", "b", "locale")"#,
+                r#"collation for `compare` must be "bytes", "codepoint" or "case_insensitive", got locale"#,
+            ),
+            (r#"sort([1, "a"])"#, "cannot compare Integer with String"),
+            ("sort(1)", "argument to `sort` must be Array, got Integer"),
+            (
+                "compare(1)",
+                "wrong number of arguments. got=1, want=2 or 3",
+            ),
         ];
 
-        assert_objects(tests);
+        assert_errors(tests);
     }
 
     #[test]
-    fn test_return_statements() {
+    fn test_matrix_buildin_functions() {
+        let matrix = |rows: Vec<Vec<isize>>| {
+            Object::Array(
+                rows.into_iter()
+                    .map(|row| Object::Array(row.into_iter().map(Object::Integer).collect()))
+                    .collect(),
+            )
+        };
+
         let tests = vec![
-            ("return 10;", Object::Integer(10)),
-            ("return 10; 9;", Object::Integer(10)),
-            ("return 2 * 5; 9;", Object::Integer(10)),
-            ("9; return 2 * 5; 9;", Object::Integer(10)),
+            ("zeros(2, 3)", matrix(vec![vec![0, 0, 0], vec![0, 0, 0]])),
+            ("zeros(0, 3)", matrix(vec![])),
             (
-                "if (10 > 1) { if (10 > 1) { return 10; } return 1; }",
-                Object::Integer(10),
+                "transpose([[1, 2, 3], [4, 5, 6]])",
+                matrix(vec![vec![1, 4], vec![2, 5], vec![3, 6]]),
+            ),
+            ("transpose([])", matrix(vec![])),
+            (
+                "matmul([[1, 2], [3, 4]], [[5, 6], [7, 8]])",
+                matrix(vec![vec![19, 22], vec![43, 50]]),
+            ),
+            (
+                "matmul([[1, 2, 3]], transpose([[1, 2, 3]]))",
+                matrix(vec![vec![14]]),
+            ),
+            (
+                "matmul([[1], [2]], [[3, 4]])",
+                matrix(vec![vec![3, 4], vec![6, 8]]),
+            ),
+            (
+                "matmul([[0.5]], [[2]])",
+                Object::Array(vec![Object::Array(vec![Object::Float(Float(1.0))])]),
             ),
         ];
 
         assert_objects(tests);
-    }
 
-    #[test]
-    fn test_error_handling() {
         let tests = vec![
-            ("5 + true;", "type mismatch: Integer + Boolean"),
-            ("5 + true; 5;", "type mismatch: Integer + Boolean"),
-            ("-true;", "unknown operator: -Boolean"),
-            ("true + false;", "unknown operator: Boolean + Boolean"),
-            ("5; true + false; 5;", "unknown operator: Boolean + Boolean"),
             (
-                "if (10 > 1) { true + false; }",
-                "unknown operator: Boolean + Boolean",
+                "zeros(-1, 2)",
+                "arguments to `zeros` must be non-negative Integers, got -1, 2",
             ),
             (
-                "if (10 > 1) { if (10 > 1) { return true + false; } return 1; }",
-                "unknown operator: Boolean + Boolean",
+                "zeros(2, -1)",
+                "arguments to `zeros` must be non-negative Integers, got 2, -1",
             ),
-            ("foobar", "identifier not found: foobar"),
-            (r#""Hello" - "World""#, "unknown operator: String - String"),
-            ("len(1)", "argument to `len` not supported, got Integer"),
+            ("zeros(4096, 4096)", "maximum length exceeded: 4194304"),
             (
-                r#"len("one", "two")"#,
-                "wrong number of arguments. got=2, want=1",
+                "zeros(0, 9223372036854775807)",
+                "maximum length exceeded: 4194304",
             ),
+            ("zeros(4194305, 0)", "maximum length exceeded: 4194304"),
             (
-                r#"{"name": "Monkey"}[fn(x) { x }]"#,
-                "unusable as map key: Function",
+                "transpose(1)",
+                "argument to `transpose` must be a matrix, got Integer",
+            ),
+            (
+                "transpose([1, 2])",
+                "argument to `transpose` must be a matrix, got a row of Integer",
+            ),
+            (
+                "transpose([[1, 2], [3]])",
+                "ragged matrix in `transpose`: row 1 has 1 columns, expected 2",
+            ),
+            (
+                "matmul([[1, 2]], [[1, 2]])",
+                "matrix shapes do not match in `matmul`: 1x2 and 1x2",
+            ),
+            (
+                "matmul([[true]], [[1]])",
+                "type mismatch: Boolean * Integer",
             ),
         ];
 
@@ -689,111 +5211,111 @@ mod tests {
     }
 
     #[test]
-    fn test_let_statements() {
+    fn test_encoding_buildin_functions() {
+        let string = |value: &str| Object::String(value.to_string());
+
         let tests = vec![
-            ("let a = 5; a;", Object::Integer(5)),
-            ("let a = 5 * 5; a;", Object::Integer(25)),
-            ("let a = 5; let b = a; b;", Object::Integer(5)),
+            (r#"base64_encode("")"#, string("")),
+            (r#"base64_encode("f")"#, string("Zg==")),
+            (r#"base64_encode("fo")"#, string("Zm8=")),
+            (r#"base64_encode("foo")"#, string("Zm9v")),
+            (r#"base64_encode("foobar")"#, string("Zm9vYmFy")),
+            ("base64_encode([0, 255, 128])", string("AP+A")),
+            (r#"base64_decode("Zm9vYmE=")"#, string("fooba")),
+            (r#"base64_decode("Zm9vYmE")"#, string("fooba")),
             (
-                "let a = 5; let b = a; let c = a + b + 5; c;",
-                Object::Integer(15),
+                r#"base64_decode(base64_encode("こんにちは"))"#,
+                string("こんにちは"),
+            ),
+            (
+                r#"url_encode("a b&c=d/é~")"#,
+                string("a%20b%26c%3Dd%2F%C3%A9~"),
+            ),
+            (
+                r#"url_decode("a%20b%26c%3dd%2F%C3%A9~")"#,
+                string("a b&c=d/é~"),
             ),
         ];
 
         assert_objects(tests);
-    }
-
-    #[test]
-    fn test_function_expressions() {
-        let input = "fn(x) { x + 2; };";
-
-        let expected_parameters = vec![Expression::Identifier("x".to_string())];
-        let expected_body = Statement::Block(vec![Statement::Expression(Expression::Infix {
-            left: Box::new(Expression::Identifier("x".to_string())),
-            operator: Token::Plus,
-            right: Box::new(Expression::Integer(2)),
-        })]);
-
-        match test_eval(input) {
-            Response::Reply(Object::Function {
-                parameters, body, ..
-            }) => {
-                assert_eq!(parameters, expected_parameters);
-                assert_eq!(body, expected_body);
-            }
-            _ => unreachable!(),
-        }
-    }
 
-    #[test]
-    fn test_function_applications() {
         let tests = vec![
+            (r#"base64_decode("Zm9v!A")"#, "invalid base64 character: !"),
+            (r#"base64_decode("Zm9vY")"#, "invalid base64 input length"),
             (
-                "let identity = fn(x) { x; }; identity(5);",
-                Object::Integer(5),
-            ),
-            (
-                "let identity = fn(x) { return x; }; identity(5);",
-                Object::Integer(5),
+                "base64_decode(base64_encode([255]))",
+                "result of `base64_decode` is not valid UTF-8",
             ),
             (
-                "let double = fn(x) { x * 2; }; double(5);",
-                Object::Integer(10),
+                "base64_encode([256])",
+                "argument to `base64_encode` must be an Array of bytes",
             ),
             (
-                "let add = fn(x, y) { x + y; }; add(5, 5);",
-                Object::Integer(10),
+                r#"url_decode("%G0")"#,
+                "invalid percent-encoding in `url_decode`",
             ),
             (
-                "let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));",
-                Object::Integer(20),
+                "url_encode(1)",
+                "argument to `url_encode` must be String or Array, got Integer",
             ),
-            ("fn(x) { x; }(5)", Object::Integer(5)),
         ];
 
-        assert_objects(tests);
-    }
-
-    #[test]
-    fn test_closures() {
-        let input = "
-        let newAdder = fn(x) {
-            fn(y) { x + y };
-        };
-        let addTwo = newAdder(2);
-        addTwo(2);
-        ";
-
-        let expected = Object::Integer(4);
-
-        assert_object(input, expected);
+        assert_errors(tests);
     }
 
+    #[cfg(feature = "data")]
     #[test]
-    fn test_string_expressions() {
+    fn test_json_buildin_functions() {
         let tests = vec![
             (
-                r#""Hello World!""#,
-                Object::String("Hello World!".to_string()),
+                r#"json_parse(" [1, true, null, -2.5e1] ")"#,
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Boolean(true),
+                    Object::Null,
+                    Object::Float(Float(-25.0)),
+                ]),
             ),
             (
-                r#""Hello" + " " + "World!""#,
-                Object::String("Hello World!".to_string()),
+                r#"json_parse(json_stringify({"a": {"b": 2}})).a.b"#,
+                Object::Integer(2),
+            ),
+            (
+                r#"json_stringify({"b": [1, 2.5], "a": "x"})"#,
+                Object::String(r#"{"a":"x","b":[1,2.5]}"#.to_string()),
+            ),
+            (
+                r#"let value = {"k": [first([]), false, "s"]}; is(json_parse(json_stringify(value)), value)"#,
+                Object::Boolean(true),
             ),
         ];
 
         assert_objects(tests);
-    }
 
-    #[test]
-    fn test_buildin_functions() {
         let tests = vec![
-            (r#"len("")"#, Object::Integer(0)),
-            (r#"len("four")"#, Object::Integer(4)),
-            (r#"len("hello world")"#, Object::Integer(11)),
+            (
+                r#"json_parse("[1")"#,
+                "invalid JSON: unexpected end of input",
+            ),
+            (
+                r#"json_parse("[" * 200000 + "]" * 200000)"#,
+                "invalid JSON: nesting deeper than 512 at position 512",
+            ),
+            (
+                "json_parse(1)",
+                "argument to `json_parse` must be String, got Integer",
+            ),
+            (
+                "json_stringify({1: 2})",
+                "JSON object keys must be String, got Integer",
+            ),
+            (
+                "json_stringify(len)",
+                "cannot convert Buildin Function to JSON",
+            ),
         ];
 
-        assert_objects(tests);
+        assert_errors(tests);
     }
 
     #[test]
@@ -833,6 +5355,90 @@ mod tests {
         assert_objects(tests);
     }
 
+    #[test]
+    fn test_string_index_expressions() {
+        let tests = vec![
+            ("\"abc\"[0]", Object::String("a".to_string())),
+            ("\"abc\"[2]", Object::String("c".to_string())),
+            ("\"abc\"[-1]", Object::String("c".to_string())),
+            ("\"abc\"[-3]", Object::String("a".to_string())),
+            ("\"日本語\"[1]", Object::String("本".to_string())),
+            ("let s = \"abc\"; s[1 + 1]", Object::String("c".to_string())),
+            ("\"abc\"[3]", Object::Null),
+            ("\"abc\"[-4]", Object::Null),
+            ("\"\"[0]", Object::Null),
+        ];
+
+        assert_objects(tests);
+
+        let tests = vec![("\"abc\"[\"a\"]", "index operator not supported: String")];
+
+        assert_errors(tests);
+    }
+
+    #[test]
+    fn test_range_expressions() {
+        let tests = vec![
+            (
+                "1..4",
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                    Object::Integer(3),
+                ]),
+            ),
+            (
+                "let n = 2; 0..=n",
+                Object::Array(vec![
+                    Object::Integer(0),
+                    Object::Integer(1),
+                    Object::Integer(2),
+                ]),
+            ),
+            ("3..3", Object::Array(vec![])),
+            ("3..=3", Object::Array(vec![Object::Integer(3)])),
+            ("5..1", Object::Array(vec![])),
+            ("len(0..10)", Object::Integer(10)),
+            ("len(1..=4194304)", Object::Integer(4194304)),
+        ];
+
+        assert_objects(tests);
+
+        let tests = vec![
+            ("0..9223372036854775807", "maximum length exceeded: 4194304"),
+            (
+                "-9223372036854775807..=9223372036854775807",
+                "maximum length exceeded: 4194304",
+            ),
+            ("len(0..100000000)", "maximum length exceeded: 4194304"),
+            ("0..=4194304", "maximum length exceeded: 4194304"),
+        ];
+
+        assert_errors(tests);
+    }
+
+    #[test]
+    fn test_array_slice_expressions() {
+        let array = |elements: Vec<isize>| {
+            Object::Array(elements.into_iter().map(Object::Integer).collect())
+        };
+
+        let tests = vec![
+            ("[1, 2, 3, 4][1:3]", array(vec![2, 3])),
+            ("[1, 2, 3, 4][:2]", array(vec![1, 2])),
+            ("[1, 2, 3, 4][2:]", array(vec![3, 4])),
+            ("[1, 2, 3, 4][:]", array(vec![1, 2, 3, 4])),
+            ("let a = [1, 2, 3]; let i = 1; a[i:i + 1]", array(vec![2])),
+            ("[1, 2, 3][-5:2]", array(vec![1, 2])),
+            ("[1, 2, 3][1:10]", array(vec![2, 3])),
+            ("[1, 2, 3][5:]", array(vec![])),
+            ("[1, 2, 3][2:1]", array(vec![])),
+            ("[][:]", array(vec![])),
+        ];
+
+        assert_objects(tests);
+    }
+
     #[test]
     fn test_map_expressions() {
         let input = r#"
@@ -872,6 +5478,68 @@ mod tests {
         assert_object(input, expected);
     }
 
+    #[test]
+    fn test_map_merge_expressions() {
+        let input = r#"
+        let defaults = {"host": "localhost", "port": 80};
+        let merged = defaults + {"port": 8080, "debug": true};
+        [merged, defaults, merged + {}]
+        "#;
+
+        let map = |entries: Vec<(&str, Object)>| {
+            let pairs = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = Object::String(key.to_string());
+                    (MapKey::from(&key), MapPair::new(key, value))
+                })
+                .collect();
+            Object::Map(pairs)
+        };
+        let merged = map(vec![
+            ("host", Object::String("localhost".to_string())),
+            ("port", Object::Integer(8080)),
+            ("debug", Object::Boolean(true)),
+        ]);
+        let defaults = map(vec![
+            ("host", Object::String("localhost".to_string())),
+            ("port", Object::Integer(80)),
+        ]);
+
+        assert_object(input, Object::Array(vec![merged.clone(), defaults, merged]));
+
+        let tests = vec![
+            ("{1: 2} - {1: 2}", "type mismatch: Map - Map"),
+            ("{1: 2} + [1]", "type mismatch: Map + Array"),
+        ];
+
+        assert_errors(tests);
+    }
+
+    #[test]
+    fn test_map_dot_expressions() {
+        let tests = vec![
+            (
+                r#"let person = {"name": "akthrms"}; person.name"#,
+                Object::String("akthrms".to_string()),
+            ),
+            (
+                r#"let person = {"name": "akthrms"}; person.age"#,
+                Object::Null,
+            ),
+            (r#"{"a": {"b": 1}}.a.b"#, Object::Integer(1)),
+            (
+                r#"let m = {"f": fn(x) { x * 2 }}; m.f(21)"#,
+                Object::Integer(42),
+            ),
+            (r#"{"a": 1}.b ?? 2"#, Object::Integer(2)),
+        ];
+
+        assert_objects(tests);
+
+        assert_errors(vec![("1.a", "index operator not supported: Integer")]);
+    }
+
     #[test]
     fn test_map_index_expressions() {
         let tests = vec![
@@ -882,8 +5550,454 @@ mod tests {
             ("{5: 5}[5]", Object::Integer(5)),
             ("{true: 5}[true]", Object::Integer(5)),
             ("{false: 5}[false]", Object::Integer(5)),
+            (r#"{[1, 2]: "a"}[[1, 2]]"#, Object::String("a".to_string())),
+            (r#"{[1, 2]: "a"}[[2, 1]]"#, Object::Null),
+            (
+                r#"let key = ["x", [true]]; {key: 1}[["x", [true]]]"#,
+                Object::Integer(1),
+            ),
         ];
 
         assert_objects(tests);
     }
+
+    #[test]
+    fn test_eval_stepping() {
+        let mut lexer = Lexer::new("let x = 2; 1 + x * 3");
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+        let mut evaluator = Evaluator::new();
+
+        let (response, steps) = evaluator.eval_stepping(program);
+
+        match response {
+            Response::Reply(result) => assert_eq!(result, Object::Integer(7)),
+            _ => unreachable!(),
+        }
+
+        let steps = steps.iter().map(Step::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            steps,
+            vec![
+                "2 => 2",
+                "  1 => 1",
+                "    x => 2",
+                "    3 => 3",
+                "  (x * 3) => 6",
+                "(1 + (x * 3)) => 7",
+            ]
+        );
+
+        match evaluator.eval(Parser::new(&mut Lexer::new("x")).parse_program()) {
+            Response::Reply(result) => assert_eq!(result, Object::Integer(2)),
+            _ => unreachable!(),
+        }
+        assert!(evaluator.steps.is_none());
+    }
+
+    #[test]
+    fn test_call_graph() {
+        let input = r#"
+        let double = fn(x) { x * 2 };
+        let count = fn(n) { if (n > 0) { double(n); count(n - 1) } else { len("") } };
+        count(2);
+        try { count(true) } catch (e) { double(1) };
+        "#;
+
+        let mut lexer = Lexer::new(input);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+        let mut evaluator = Evaluator::new();
+
+        evaluator.enable_call_graph();
+        evaluator.eval(program);
+
+        let call_graph = evaluator.take_call_graph().unwrap();
+        let edges = call_graph
+            .edges()
+            .iter()
+            .map(|((caller, callee), count)| (caller.as_str(), callee.as_str(), *count))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            edges,
+            vec![
+                ("<main>", "count", 2),
+                ("<main>", "double", 1),
+                ("count", "count", 2),
+                ("count", "double", 2),
+                ("count", "len", 1),
+            ]
+        );
+        assert!(call_graph
+            .to_dot()
+            .contains("    \"count\" -> \"double\" [label=\"2\", weight=2];\n"));
+    }
+
+    #[test]
+    fn test_statement_limit() {
+        let mut evaluator = Evaluator::new();
+        evaluator.set_statement_limit(Some(100));
+
+        let program = |input| Parser::new(&mut Lexer::new(input)).parse_program();
+
+        match evaluator.eval(program("let f = fn(n) { f(n + 1) }; f(0)")) {
+            Response::Error(error) => assert_eq!(error.kind(), &EvalError::StatementLimit(100)),
+            _ => unreachable!(),
+        }
+
+        match evaluator.eval(program(
+            "let l = fn() { try { 1 } catch (e) { e }; l() }; l()",
+        )) {
+            Response::Error(error) => assert_eq!(error.kind(), &EvalError::StatementLimit(100)),
+            _ => unreachable!(),
+        }
+
+        match evaluator.eval(program(
+            "let g = fn(n) { if (n > 0) { g(n - 1) } else { n } }; g(20)",
+        )) {
+            Response::Reply(result) => assert_eq!(result, Object::Integer(0)),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_sandbox_capabilities() {
+        let program = |input| Parser::new(&mut Lexer::new(input)).parse_program();
+        let output = Rc::new(RefCell::new(String::new()));
+        let events = Rc::new(RefCell::new(0));
+        let log = events.clone();
+
+        let mut evaluator = Evaluator::new();
+        evaluator.set_output(Some(output.clone()));
+        evaluator.set_input(Some(Rc::new(RefCell::new(|| Some("secret".to_string())))));
+        evaluator.set_audit(Some(Rc::new(RefCell::new(move |_: &AuditEvent| {
+            *log.borrow_mut() += 1
+        }))));
+        evaluator.push_interceptor(Rc::new(RefCell::new(
+            |_: &Evaluator, operation: &Operation| match operation {
+                Operation::CallBuildin { name, .. } if *name != "sandbox" => {
+                    Err(format!("`{}` is restricted", name).into())
+                }
+                _ => Ok(()),
+            },
+        )));
+
+        // 出力・入力・監査・割りこみのどれもホストに届かない
+        match evaluator.eval(program(
+            r#"sandbox("puts(12345); [read_line(), input(), read_source()]")"#,
+        )) {
+            Response::Reply(result) => assert_eq!(result.to_string(), "[null, null, ]"),
+            _ => unreachable!(),
+        }
+
+        assert_eq!(output.borrow().as_str(), "");
+        assert_eq!(*events.borrow(), 0);
+
+        match evaluator.eval(program(
+            r#"let r = sandbox("puts(1, 2); throw(3)", {"capture_output": true}); [r["output"], r["result"]["kind"]]"#,
+        )) {
+            Response::Reply(result) => assert_eq!(
+                result,
+                Object::Array(vec![
+                    Object::String("1\n2\n".to_string()),
+                    Object::String("runtime".to_string()),
+                ])
+            ),
+            _ => unreachable!(),
+        }
+
+        assert_eq!(output.borrow().as_str(), "");
+    }
+
+    #[test]
+    fn test_sandbox_limits() {
+        let program = |input| Parser::new(&mut Lexer::new(input)).parse_program();
+        let runaway = "sandbox(\"let l = fn(n) { l(n + 1) }; l(0)\")";
+
+        let mut evaluator = Evaluator::new();
+        evaluator.set_statement_limit(Some(100));
+
+        match evaluator.eval(program(runaway)) {
+            Response::Error(error) => assert_eq!(error.kind(), &EvalError::StatementLimit(100)),
+            _ => unreachable!(),
+        }
+
+        // 入れ子の評価器で実行した文も呼び出し側の上限に数える
+        match evaluator.eval(program(
+            "let s = \"let f = fn(n) { if (n > 0) { f(n - 1) } else { n } }; f(20)\"; sandbox(s); sandbox(s); sandbox(s)",
+        )) {
+            Response::Error(error) => assert_eq!(error.kind(), &EvalError::StatementLimit(100)),
+            _ => unreachable!(),
+        }
+
+        // 呼び出し側の上限より小さい `max_steps` は、これまでどおりマップで返す
+        match evaluator.eval(program(
+            "sandbox(\"let l = fn(n) { l(n + 1) }; l(0)\", {\"max_steps\": 10})[\"kind\"]",
+        )) {
+            Response::Reply(result) => assert_eq!(result, Object::String("limit".to_string())),
+            _ => unreachable!(),
+        }
+
+        let mut evaluator = Evaluator::new();
+        evaluator.set_recursion_limit(20);
+
+        match evaluator.eval(program(
+            "let f = fn() { sandbox(\"let g = fn(n) { 1 + g(n + 1) }; g(0)\", {\"max_depth\": 100}) }; f()",
+        )) {
+            Response::Error(error) => assert_eq!(error.kind(), &EvalError::RecursionLimit(20)),
+            _ => unreachable!(),
+        }
+
+        let interrupt = Arc::new(AtomicBool::new(true));
+        let mut evaluator = Evaluator::new();
+        evaluator.set_interrupt(Some(interrupt));
+
+        match evaluator.eval(program(runaway)) {
+            Response::Error(error) => assert_eq!(error.kind(), &EvalError::Interrupted),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_recursion_limit_on_thread() {
+        // 埋めこんだ側の普通のスレッド（スタックは 2 MiB）でも、上限でエラーになって落ちない
+        let handle = std::thread::spawn(|| {
+            let mut evaluator = Evaluator::new();
+            let program = |input: &str| Parser::new(&mut Lexer::new(input)).parse_program();
+            let f = "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } };";
+
+            let deep = match evaluator.eval(program(&format!("{} f(999)", f))) {
+                Response::Reply(result) => result.to_string(),
+                _ => unreachable!(),
+            };
+            let too_deep = match evaluator.eval(program(&format!("{} f(1000)", f))) {
+                Response::Error(error) => error.to_string(),
+                _ => unreachable!(),
+            };
+
+            (deep, too_deep)
+        });
+
+        let (deep, too_deep) = handle.join().unwrap();
+        assert_eq!(deep, "999");
+        assert_eq!(too_deep, "maximum recursion depth exceeded: 1000");
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        let mut evaluator = Evaluator::new();
+        evaluator.set_recursion_limit(20);
+
+        let program = |input| Parser::new(&mut Lexer::new(input)).parse_program();
+
+        match evaluator.eval(program("let f = fn(n) { 1 + f(n + 1) }; f(0)")) {
+            Response::Error(error) => assert_eq!(error.kind(), &EvalError::RecursionLimit(20)),
+            _ => unreachable!(),
+        }
+
+        // 末尾呼び出しは深さを増やさず、組み込み関数から呼んだ関数は数える
+        let tests = vec![
+            (
+                "let g = fn(n) { if (n > 0) { g(n - 1) } else { n } }; g(100)",
+                Ok(0),
+            ),
+            (
+                "let h = fn(n) { if (n > 0) { 1 + h(n - 1) } else { n } }; h(19)",
+                Ok(19),
+            ),
+            (
+                "let h = fn(n) { if (n > 0) { 1 + h(n - 1) } else { n } }; h(20)",
+                Err(20),
+            ),
+            (
+                "let m = fn(n) { map([n], fn(x) { m(x + 1) }) }; m(0)",
+                Err(20),
+            ),
+            (
+                "let t = fn(n) { try { 1 + t(n + 1) } catch (e) { 0 } }; t(0)",
+                Err(20),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            match (evaluator.eval(program(input)), expected) {
+                (Response::Reply(result), Ok(value)) => {
+                    assert_eq!(result, Object::Integer(value), "{}", input)
+                }
+                (Response::Error(error), Err(limit)) => {
+                    assert_eq!(error.kind(), &EvalError::RecursionLimit(limit), "{}", input)
+                }
+                (_, _) => panic!("{}", input),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_record_and_replay() {
+        let input = r#"let s = stopwatch(); [s["elapsed_ms"](), s["elapsed_ms"]()]"#;
+        let program = || Parser::new(&mut Lexer::new(input)).parse_program();
+
+        let mut evaluator = Evaluator::new();
+        evaluator.start_recording();
+        let recorded = match evaluator.eval(program()) {
+            Response::Reply(Object::Array(elements)) => elements,
+            _ => unreachable!(),
+        };
+        let results = evaluator.take_recorded();
+
+        assert_eq!(
+            results
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["stopwatch", "elapsed_ms", "elapsed_ms"]
+        );
+
+        let mut results = results;
+        results[2].1 = Object::Integer(1000);
+
+        let mut evaluator = Evaluator::new();
+        evaluator.start_replay(results.clone());
+        match evaluator.eval(program()) {
+            Response::Reply(Object::Array(elements)) => {
+                assert_eq!(elements[0], recorded[0]);
+                assert_eq!(elements[1], Object::Integer(1000));
+            }
+            _ => unreachable!(),
+        }
+
+        let mut evaluator = Evaluator::new();
+        evaluator.start_replay(results[..1].to_vec());
+        match evaluator.eval(program()) {
+            Response::Error(error) => {
+                assert_eq!(error.to_string(), "replay exhausted at elapsed_ms")
+            }
+            _ => unreachable!(),
+        }
+
+        let mut evaluator = Evaluator::new();
+        evaluator.start_replay(results[1..].to_vec());
+        match evaluator.eval(program()) {
+            Response::Error(error) => {
+                assert_eq!(
+                    error.to_string(),
+                    "replay diverged: expected elapsed_ms, got stopwatch"
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_replay_file_reads() {
+        let input = r#"read_file("/tmp/data.txt") + "!""#;
+        let program = || Parser::new(&mut Lexer::new(input)).parse_program();
+
+        let mut vfs = MemoryVfs::new();
+        vfs.insert("/tmp/data.txt", "hello");
+
+        let mut evaluator = Evaluator::new();
+        evaluator.set_vfs(Some(Rc::new(vfs)));
+        evaluator.start_recording();
+        assert!(matches!(evaluator.eval(program()), Response::Reply(_)));
+        let results = evaluator.take_recorded();
+
+        assert_eq!(
+            results,
+            vec![("read_file".to_string(), Object::String("hello".to_string()))]
+        );
+
+        // 再生するときはファイルシステムがなくても記録した中身を返す
+        let mut evaluator = Evaluator::new();
+        evaluator.start_replay(results);
+        match evaluator.eval(program()) {
+            Response::Reply(result) => assert_eq!(result, Object::String("hello!".to_string())),
+            _ => unreachable!(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_replay_read_source() {
+        let input = "read_source()";
+        let program = || Parser::new(&mut Lexer::new(input)).parse_program();
+        let lines = Rc::new(RefCell::new(VecDeque::from(vec![
+            "puts(1);".to_string(),
+            "puts(2);".to_string(),
+        ])));
+
+        let mut evaluator = Evaluator::new();
+        evaluator.set_input(Some(Rc::new(RefCell::new(move || {
+            lines.borrow_mut().pop_front()
+        }))));
+        evaluator.start_recording();
+        assert!(matches!(evaluator.eval(program()), Response::Reply(_)));
+        let results = evaluator.take_recorded();
+
+        let mut evaluator = Evaluator::new();
+        evaluator.set_input(Some(Rc::new(RefCell::new(|| None))));
+        evaluator.start_replay(results);
+        match evaluator.eval(program()) {
+            Response::Reply(result) => {
+                assert_eq!(result, Object::String("puts(1);\nputs(2);\n".to_string()))
+            }
+            _ => unreachable!(),
+        }
+
+        let mut evaluator = Evaluator::new();
+        evaluator.set_pure(true);
+        match evaluator.eval(program()) {
+            Response::Error(error) => assert_eq!(
+                error.to_string(),
+                "`read_source` has side effects and is not allowed here"
+            ),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_debug_snapshot() {
+        let input = "let a = 1; let f = fn(x) { let y = x + a; y + z }; f(2);";
+
+        let mut lexer = Lexer::new(input);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+        let mut evaluator = Evaluator::new();
+        evaluator.enable_debug(2);
+
+        match evaluator.eval(program) {
+            Response::Error(error) => {
+                assert_eq!(error.kind(), &EvalError::UnknownIdentifier("z".into()))
+            }
+            _ => unreachable!(),
+        }
+
+        let snapshot = evaluator.take_snapshot().unwrap();
+
+        assert_eq!(snapshot.statement, "(y + z)");
+        assert_eq!(
+            snapshot.scopes,
+            vec![
+                vec![
+                    ("x".to_string(), "2 (Integer)".to_string()),
+                    ("y".to_string(), "3 (Integer)".to_string()),
+                ],
+                vec![
+                    ("a".to_string(), "1 (Integer)".to_string()),
+                    ("f".to_string(), "Function".to_string()),
+                ],
+            ]
+        );
+        assert_eq!(
+            snapshot.events,
+            vec![
+                TraceEvent::Statement("let y = (x + a);".to_string()),
+                TraceEvent::Statement("(y + z)".to_string()),
+            ]
+        );
+        assert!(evaluator.take_snapshot().is_none());
+    }
 }
@@ -0,0 +1,140 @@
+use crate::evaluator::Response;
+use crate::object::Object;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 評価の記録
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// 入力
+    pub input: String,
+    /// 結果（値を返さなかった場合は None、評価エラーは Err）
+    pub result: Result<Option<Object>, String>,
+    /// 評価した時刻
+    pub timestamp: SystemTime,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        let (hours, minutes, seconds) = (seconds / 3600 % 24, seconds / 60 % 60, seconds % 60);
+
+        write!(
+            f,
+            "[{:02}:{:02}:{:02}] {}",
+            hours,
+            minutes,
+            seconds,
+            self.input.trim()
+        )?;
+
+        match &self.result {
+            Ok(Some(result)) => write!(f, " => {} : {}", result, result.get_type()),
+            Ok(None) => Ok(()),
+            Err(error) => write!(f, " => error: {}", error),
+        }
+    }
+}
+
+/// REPL で評価した入力と結果の履歴（1 から番号をふる）
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    entries: Vec<Entry>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 評価した入力と結果を記録する
+    pub fn push(&mut self, input: &str, response: &Response) {
+        let result = match response {
+            Response::Reply(result) => Ok(Some(result.clone())),
+            Response::NoReply => Ok(None),
+            Response::Error(error) => Err(error.to_string()),
+        };
+
+        self.entries.push(Entry {
+            input: input.to_string(),
+            result,
+            timestamp: SystemTime::now(),
+        });
+    }
+
+    /// 番号と記録の組を古い順に返す
+    pub fn entries(&self) -> impl Iterator<Item = (usize, &Entry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (i + 1, entry))
+    }
+
+    /// 入力に `query` を含む記録を返す
+    pub fn search<'a>(&'a self, query: &'a str) -> impl Iterator<Item = (usize, &'a Entry)> {
+        self.entries()
+            .filter(move |(_, entry)| entry.input.contains(query))
+    }
+
+    /// `number` 番目の記録の値を返す
+    pub fn result(&self, number: usize) -> Result<Object, String> {
+        let entry = match number.checked_sub(1).and_then(|i| self.entries.get(i)) {
+            Some(entry) => entry,
+            None => {
+                let message = format!("no history entry: {}", number);
+                return Err(message);
+            }
+        };
+
+        match &entry.result {
+            Ok(Some(result)) => Ok(result.clone()),
+            _ => {
+                let message = format!("history entry {} has no result", number);
+                Err(message)
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluator::Response;
+    use crate::history::History;
+    use crate::object::Object;
+
+    #[test]
+    fn test_history() {
+        let mut history = History::new();
+        history.push("let m = map([1], f)", &Response::NoReply);
+        history.push("1 + 2", &Response::Reply(Object::Integer(3)));
+        history.push("map(x)", &Response::Error("oops".into()));
+
+        assert_eq!(history.len(), 3);
+
+        let found = history.search("map(").map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(found, vec![1, 3]);
+
+        assert_eq!(history.result(2), Ok(Object::Integer(3)));
+        assert_eq!(
+            history.result(1),
+            Err("history entry 1 has no result".to_string())
+        );
+        assert_eq!(history.result(0), Err("no history entry: 0".to_string()));
+        assert_eq!(history.result(4), Err("no history entry: 4".to_string()));
+
+        let (_, entry) = history.entries().nth(1).unwrap();
+        assert!(entry.to_string().ends_with("] 1 + 2 => 3 : Integer"));
+    }
+}
@@ -0,0 +1,158 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::{Any, TypeId};
+use core::fmt;
+
+/// 組み込み関数が評価器ごとに持つ状態（乱数の種、キャッシュ、開いたファイルなど）
+pub trait HostResource: Any {
+    /// 評価器を破棄するときに呼ばれる後始末
+    fn release(&mut self) {}
+}
+
+/// 型ごとに 1 つずつ状態を持つ表。評価器ごとに別の表を持つので、評価器どうしで状態は共有されない
+#[derive(Default)]
+pub struct HostState {
+    /// 追加した順に並べた状態（後始末は逆順に行う）
+    resources: Vec<(TypeId, Box<dyn HostResource>)>,
+}
+
+impl HostState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn position<T: HostResource>(&self) -> Option<usize> {
+        let id = TypeId::of::<T>();
+        self.resources.iter().position(|(key, _)| *key == id)
+    }
+
+    pub fn contains<T: HostResource>(&self) -> bool {
+        self.position::<T>().is_some()
+    }
+
+    pub fn get<T: HostResource>(&self) -> Option<&T> {
+        let (_, resource) = &self.resources[self.position::<T>()?];
+        let resource: &dyn Any = resource.as_ref();
+        resource.downcast_ref()
+    }
+
+    pub fn get_mut<T: HostResource>(&mut self) -> Option<&mut T> {
+        let i = self.position::<T>()?;
+        let resource: &mut dyn Any = self.resources[i].1.as_mut();
+        resource.downcast_mut()
+    }
+
+    /// 状態がなければ `init` で作って追加する
+    pub fn get_or_insert_with<T: HostResource>(&mut self, init: impl FnOnce() -> T) -> &mut T {
+        if !self.contains::<T>() {
+            self.resources.push((TypeId::of::<T>(), Box::new(init())));
+        }
+
+        self.get_mut().expect("resource was just inserted")
+    }
+
+    /// 状態を追加し、同じ型の古い状態があれば（後始末をせずに）返す
+    pub fn insert<T: HostResource>(&mut self, resource: T) -> Option<T> {
+        let old = self.remove::<T>();
+        self.resources.push((TypeId::of::<T>(), Box::new(resource)));
+        old
+    }
+
+    /// 状態を取り除いて（後始末をせずに）返す
+    pub fn remove<T: HostResource>(&mut self) -> Option<T> {
+        let i = self.position::<T>()?;
+        let (_, resource) = self.resources.remove(i);
+        let resource: Box<dyn Any> = resource;
+        resource.downcast().ok().map(|resource| *resource)
+    }
+
+    /// すべての状態を追加と逆の順に後始末して取り除く
+    pub fn clear(&mut self) {
+        while let Some((_, mut resource)) = self.resources.pop() {
+            resource.release();
+        }
+    }
+}
+
+impl fmt::Debug for HostState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HostState")
+            .field("resources", &self.resources.len())
+            .finish()
+    }
+}
+
+impl Drop for HostState {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::host::{HostResource, HostState};
+    use alloc::rc::Rc;
+    use alloc::vec::Vec;
+    use core::cell::RefCell;
+
+    struct Counter(usize);
+
+    impl HostResource for Counter {}
+
+    struct Handle {
+        name: &'static str,
+        released: Rc<RefCell<Vec<&'static str>>>,
+    }
+
+    impl HostResource for Handle {
+        fn release(&mut self) {
+            self.released.borrow_mut().push(self.name);
+        }
+    }
+
+    struct Cache(Rc<RefCell<Vec<&'static str>>>);
+
+    impl HostResource for Cache {
+        fn release(&mut self) {
+            self.0.borrow_mut().push("cache");
+        }
+    }
+
+    #[test]
+    fn test_host_state() {
+        let mut state = HostState::new();
+        assert!(state.get::<Counter>().is_none());
+
+        state.get_or_insert_with(|| Counter(0)).0 += 1;
+        state.get_or_insert_with(|| Counter(0)).0 += 1;
+        assert_eq!(state.get::<Counter>().map(|counter| counter.0), Some(2));
+
+        let old = state.insert(Counter(10));
+        assert_eq!(old.map(|counter| counter.0), Some(2));
+        assert_eq!(state.remove::<Counter>().map(|counter| counter.0), Some(10));
+        assert!(!state.contains::<Counter>());
+    }
+
+    #[test]
+    fn test_release_on_drop() {
+        let released = Rc::new(RefCell::new(vec![]));
+
+        let mut state = HostState::new();
+        state.insert(Handle {
+            name: "handle",
+            released: Rc::clone(&released),
+        });
+        state.insert(Cache(Rc::clone(&released)));
+        state.insert(Counter(0));
+
+        let removed = state.remove::<Handle>();
+        assert!(removed.is_some());
+        state.insert(Handle {
+            name: "reopened",
+            released: Rc::clone(&released),
+        });
+        drop(state);
+
+        assert_eq!(*released.borrow(), vec!["reopened", "cache"]);
+    }
+}
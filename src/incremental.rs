@@ -0,0 +1,151 @@
+use crate::ast::{Expression, Program, Statement};
+use crate::evaluator::{Evaluator, Response};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// 変更のなかった先頭の定義を評価し直さずにソースを繰り返し評価する評価器
+///
+/// 先頭から続く変更のない `let` 文は前回の束縛をそのまま使う。
+/// 定義の値に副作用がある場合、その副作用は最初の評価でしか起こらない
+pub struct Incremental {
+    evaluator: Evaluator,
+    /// 前回構文解析したソースとその結果
+    parsed: Option<(String, Program)>,
+    /// 評価済みの先頭の定義（文と束縛した名前）
+    definitions: Vec<(Statement, String)>,
+    /// 前回の評価で再利用した定義の数
+    reused: usize,
+}
+
+impl Default for Incremental {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Incremental {
+    pub fn new() -> Self {
+        Self {
+            evaluator: Evaluator::new(),
+            parsed: None,
+            definitions: vec![],
+            reused: 0,
+        }
+    }
+
+    pub fn evaluator(&mut self) -> &mut Evaluator {
+        &mut self.evaluator
+    }
+
+    /// 前回の評価で再利用した定義の数
+    pub fn reused(&self) -> usize {
+        self.reused
+    }
+
+    /// ソースを評価する。構文解析に失敗した場合はそのエラーを返す
+    pub fn eval(&mut self, source: &str) -> Result<Response, Vec<String>> {
+        let program = self.parse(source)?;
+
+        let reused = self
+            .definitions
+            .iter()
+            .zip(program.statements.iter())
+            .take_while(|((previous, _), statement)| previous == *statement)
+            .count();
+
+        for (_, name) in self.definitions.drain(reused..) {
+            self.evaluator.remove(&name);
+        }
+
+        self.reused = reused;
+
+        let rest = Program {
+            statements: program.statements[reused..].to_vec(),
+        };
+        let response = self.evaluator.eval(rest);
+
+        if !matches!(response, Response::Error(_)) {
+            let definitions =
+                program.statements[reused..]
+                    .iter()
+                    .map_while(|statement| match statement {
+                        Statement::Let {
+                            name: Expression::Identifier(name),
+                            ..
+                        } => Some((statement.clone(), name.to_string())),
+                        _ => None,
+                    });
+            self.definitions.extend(definitions);
+        }
+
+        Ok(response)
+    }
+
+    /// ソースが前回と同じなら前回の構文解析の結果を使う
+    fn parse(&mut self, source: &str) -> Result<Program, Vec<String>> {
+        if let Some((parsed, program)) = &self.parsed {
+            if parsed == source {
+                return Ok(program.clone());
+            }
+        }
+
+        let mut lexer = Lexer::with_interner(source, self.evaluator.interner());
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+
+        if parser.exists_errors() {
+            return Err(parser.get_errors());
+        }
+
+        self.parsed = Some((source.to_string(), program.clone()));
+        Ok(program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluator::Response;
+    use crate::incremental::Incremental;
+    use crate::object::Object;
+
+    fn assert_reply(incremental: &mut Incremental, source: &str, expected: Object) {
+        match incremental.eval(source) {
+            Ok(Response::Reply(result)) => assert_eq!(result, expected),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_incremental_eval() {
+        let mut incremental = Incremental::new();
+
+        let source = "let a = [1]; let f = fn(x) { x + b }; let b = 10; f(a[0])";
+        assert_reply(&mut incremental, source, Object::Integer(11));
+        assert_eq!(incremental.reused(), 0);
+
+        assert_reply(&mut incremental, source, Object::Integer(11));
+        assert_eq!(incremental.reused(), 3);
+
+        let source = "let a = [1]; let f = fn(x) { x + b }; let b = 20; f(a[0])";
+        assert_reply(&mut incremental, source, Object::Integer(21));
+        assert_eq!(incremental.reused(), 2);
+
+        let source = "let a = [2]; let f = fn(x) { x * 2 }; f(a[0])";
+        assert_reply(&mut incremental, source, Object::Integer(4));
+        assert_eq!(incremental.reused(), 0);
+        assert_eq!(incremental.evaluator().get("b"), None);
+
+        assert!(incremental.eval("let = 1").is_err());
+
+        let source = "let a = [2]; let c = a[5] + 1; c";
+        assert!(matches!(incremental.eval(source), Ok(Response::Error(_))));
+        assert_eq!(incremental.reused(), 1);
+
+        let source = "let a = [2]; let c = a[0] + 1; c";
+        assert_reply(&mut incremental, source, Object::Integer(3));
+        assert_eq!(incremental.reused(), 1);
+    }
+}
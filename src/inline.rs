@@ -0,0 +1,128 @@
+use crate::ast::{Expression, Program, Statement};
+use crate::evaluator::{Evaluator, Response};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::token::Position;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+
+/// 選択範囲の評価で許す文の数の既定の上限
+pub const DEFAULT_INLINE_STEPS: usize = 10_000;
+
+/// エディターの「選択範囲を評価」のために、ファイルのトップレベルの定義のもとで式を評価する
+///
+/// `let` 以外のトップレベルの文は実行しない。副作用のある組み込み関数とファイルの読みこみは
+/// エラーになり、定義と選択範囲はそれぞれ `max_steps` 文までしか実行しない。
+/// 評価できなかった定義は束縛されないまま残る
+pub fn evaluate_selection(
+    source: &str,
+    selection: &str,
+    max_steps: usize,
+) -> Result<String, String> {
+    let expression = parse_selection(selection)?;
+
+    let mut evaluator = Evaluator::new();
+    evaluator.set_pure(true);
+    evaluator.set_statement_limit(Some(max_steps));
+
+    for statement in definitions(source) {
+        let program = Program {
+            statements: vec![statement],
+        };
+        evaluator.eval(program);
+    }
+
+    let program = Program {
+        statements: vec![Statement::Expression(expression, Position::default())],
+    };
+
+    match evaluator.eval(program) {
+        Response::Reply(result) => Ok(result.to_string()),
+        Response::NoReply => Ok("null".to_string()),
+        Response::Error(error) => Err(error.to_string()),
+    }
+}
+
+/// 選択範囲を 1 つの式として構文解析する
+fn parse_selection(selection: &str) -> Result<Expression, String> {
+    let mut lexer = Lexer::new(selection);
+    let mut parser = Parser::new(&mut lexer);
+    let mut program = parser.parse_program();
+
+    if parser.exists_errors() {
+        return Err(parser.get_errors().join(", "));
+    }
+
+    match (program.statements.pop(), program.statements.is_empty()) {
+        (Some(Statement::Expression(expression, _)), true) => Ok(expression),
+        _ => {
+            let message = format!("selection is not a single expression: {}", selection.trim());
+            Err(message)
+        }
+    }
+}
+
+/// ソースのトップレベルの `let` 文（構文エラーがあれば解析できたところまで）
+fn definitions(source: &str) -> impl Iterator<Item = Statement> {
+    let mut lexer = Lexer::new(source);
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+
+    program
+        .statements
+        .into_iter()
+        .filter(|statement| matches!(statement, Statement::Let { .. }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inline::{evaluate_selection, DEFAULT_INLINE_STEPS};
+
+    #[test]
+    fn test_evaluate_selection() {
+        let source = "
+            let double = fn(x) { x * 2 };
+            let limit = 10;
+            puts(\"main\");
+            let loop = fn(n) { loop(n + 1) };
+            let broken = 1 +;
+        ";
+
+        let tests = vec![
+            ("double(limit)", Ok("20")),
+            ("[double(1), limit]", Ok("[2, 10]")),
+            ("limit - 1", Ok("9")),
+            ("", Err("selection is not a single expression: ")),
+            (
+                "let a = 1",
+                Err("selection is not a single expression: let a = 1"),
+            ),
+            (
+                "puts(1)",
+                Err("`puts` has side effects and is not allowed here"),
+            ),
+            ("loop(0)", Err("statement limit exceeded: 10000")),
+            (
+                "sandbox(\"let l = fn(n) { l(n + 1) }; l(0)\")",
+                Err("statement limit exceeded: 10000"),
+            ),
+            (
+                "read_file(\"a.txt\")",
+                Err("no filesystem available to read a.txt"),
+            ),
+            (
+                "sandbox(\"puts(1)\")[\"error\"]",
+                Ok("`puts` has side effects and is not allowed here"),
+            ),
+        ];
+
+        for (selection, expected) in tests {
+            let expected = expected.map(str::to_string).map_err(str::to_string);
+            assert_eq!(
+                evaluate_selection(source, selection, DEFAULT_INLINE_STEPS),
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,507 @@
+use crate::numeric::{format_float, Float};
+use crate::object::{MapKey, MapPair, Object};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use core::fmt::Write;
+use core::iter::Peekable;
+use core::str::CharIndices;
+
+/// 配列とオブジェクトの入れ子の深さの上限（深すぎる入れ子でスタックがあふれないようにする）
+pub const MAX_DEPTH: usize = 512;
+
+/// JSON のテキストを読む。オブジェクトはマップ、整数に収まる数は Integer、それ以外の数は Float になる
+pub fn parse(text: &str) -> Result<Object, String> {
+    let mut parser = JsonParser {
+        chars: text.char_indices().peekable(),
+        text,
+        depth: 0,
+    };
+
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+
+    match parser.chars.next() {
+        Some((position, ch)) => Err(unexpected(ch, position)),
+        None => Ok(value),
+    }
+}
+
+/// 値を 1 行の JSON にする（マップのキーは文字列でなければならない）
+pub fn stringify(value: &Object) -> Result<String, String> {
+    let mut json = String::new();
+    write_value(&mut json, value, 0)?;
+    Ok(json)
+}
+
+fn unexpected(ch: char, position: usize) -> String {
+    format!("invalid JSON: unexpected {:?} at position {}", ch, position)
+}
+
+struct JsonParser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    text: &'a str,
+    /// 読んでいる配列とオブジェクトの入れ子の深さ
+    depth: usize,
+}
+
+impl JsonParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while let Some((_, ' ' | '\t' | '\n' | '\r')) = self.chars.peek() {
+            self.chars.next();
+        }
+    }
+
+    fn next(&mut self) -> Result<(usize, char), String> {
+        self.chars
+            .next()
+            .ok_or_else(|| "invalid JSON: unexpected end of input".to_string())
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next()? {
+            (_, ch) if ch == expected => Ok(()),
+            (position, ch) => Err(unexpected(ch, position)),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Object, String> {
+        self.skip_whitespace();
+
+        let (position, ch) = match self.chars.peek() {
+            Some(next) => *next,
+            None => return Err("invalid JSON: unexpected end of input".to_string()),
+        };
+
+        match ch {
+            '{' | '[' if self.depth == MAX_DEPTH => Err(format!(
+                "invalid JSON: nesting deeper than {} at position {}",
+                MAX_DEPTH, position
+            )),
+            '{' | '[' => {
+                self.depth += 1;
+                let value = match ch {
+                    '{' => self.parse_object(),
+                    _ => self.parse_array(),
+                };
+                self.depth -= 1;
+                value
+            }
+            '"' => self.parse_string().map(Object::String),
+            '-' | '0'..='9' => self.parse_number(),
+            't' => self.parse_keyword("true", Object::Boolean(true)),
+            'f' => self.parse_keyword("false", Object::Boolean(false)),
+            'n' => self.parse_keyword("null", Object::Null),
+            _ => Err(unexpected(ch, position)),
+        }
+    }
+
+    fn parse_keyword(&mut self, keyword: &str, value: Object) -> Result<Object, String> {
+        for expected in keyword.chars() {
+            self.expect(expected)?;
+        }
+
+        Ok(value)
+    }
+
+    fn parse_object(&mut self) -> Result<Object, String> {
+        self.expect('{')?;
+        let mut pairs = BTreeMap::new();
+        self.skip_whitespace();
+
+        if let Some((_, '}')) = self.chars.peek() {
+            self.chars.next();
+            return Ok(Object::Map(pairs));
+        }
+
+        loop {
+            self.skip_whitespace();
+
+            let key = match self.chars.peek() {
+                Some((_, '"')) => Object::String(self.parse_string()?),
+                Some((position, ch)) => return Err(unexpected(*ch, *position)),
+                None => return Err("invalid JSON: unexpected end of input".to_string()),
+            };
+
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            pairs.insert(MapKey::from(&key), MapPair::new(key, value));
+            self.skip_whitespace();
+
+            match self.next()? {
+                (_, ',') => continue,
+                (_, '}') => return Ok(Object::Map(pairs)),
+                (position, ch) => return Err(unexpected(ch, position)),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Object, String> {
+        self.expect('[')?;
+        let mut elements = vec![];
+        self.skip_whitespace();
+
+        if let Some((_, ']')) = self.chars.peek() {
+            self.chars.next();
+            return Ok(Object::Array(elements));
+        }
+
+        loop {
+            elements.push(self.parse_value()?);
+            self.skip_whitespace();
+
+            match self.next()? {
+                (_, ',') => continue,
+                (_, ']') => return Ok(Object::Array(elements)),
+                (position, ch) => return Err(unexpected(ch, position)),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut value = String::new();
+
+        loop {
+            match self.next()? {
+                (_, '"') => return Ok(value),
+                (_, '\\') => match self.next()? {
+                    (_, '"') => value.push('"'),
+                    (_, '\\') => value.push('\\'),
+                    (_, '/') => value.push('/'),
+                    (_, 'b') => value.push('\u{8}'),
+                    (_, 'f') => value.push('\u{c}'),
+                    (_, 'n') => value.push('\n'),
+                    (_, 'r') => value.push('\r'),
+                    (_, 't') => value.push('\t'),
+                    (position, 'u') => value.push(self.parse_unicode_escape(position)?),
+                    (position, ch) => return Err(unexpected(ch, position)),
+                },
+                (position, ch) if ch < ' ' => return Err(unexpected(ch, position)),
+                (_, ch) => value.push(ch),
+            }
+        }
+    }
+
+    /// `\u` のあとの 4 桁を読む（サロゲートペアは続く `\uXXXX` とあわせて 1 文字にする）
+    fn parse_unicode_escape(&mut self, position: usize) -> Result<char, String> {
+        let high = self.parse_hex4()?;
+
+        let code = if (0xd800..0xdc00).contains(&high) {
+            self.expect('\\')?;
+            self.expect('u')?;
+            let low = self.parse_hex4()?;
+
+            if !(0xdc00..0xe000).contains(&low) {
+                let message = format!(
+                    "invalid JSON: invalid surrogate pair at position {}",
+                    position
+                );
+                return Err(message);
+            }
+
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+
+        char::from_u32(code).ok_or_else(|| {
+            format!(
+                "invalid JSON: invalid unicode escape at position {}",
+                position
+            )
+        })
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+
+        for _ in 0..4 {
+            let (position, ch) = self.next()?;
+
+            match ch.to_digit(16) {
+                Some(digit) => code = code * 16 + digit,
+                None => return Err(unexpected(ch, position)),
+            }
+        }
+
+        Ok(code)
+    }
+
+    fn parse_number(&mut self) -> Result<Object, String> {
+        let start = match self.chars.peek() {
+            Some((position, _)) => *position,
+            None => return Err("invalid JSON: unexpected end of input".to_string()),
+        };
+
+        if let Some((_, '-')) = self.chars.peek() {
+            self.chars.next();
+        }
+
+        // 整数部は 0 か、0 で始まらない数字の並び
+        match self.next()? {
+            (_, '0') => (),
+            (_, '1'..='9') => self.skip_digits(),
+            (position, ch) => return Err(unexpected(ch, position)),
+        }
+
+        let mut integer = true;
+
+        if let Some((_, '.')) = self.chars.peek() {
+            self.chars.next();
+            integer = false;
+            self.expect_digits()?;
+        }
+
+        if let Some((_, 'e' | 'E')) = self.chars.peek() {
+            self.chars.next();
+            integer = false;
+
+            if let Some((_, '+' | '-')) = self.chars.peek() {
+                self.chars.next();
+            }
+
+            self.expect_digits()?;
+        }
+
+        let end = match self.chars.peek() {
+            Some((position, _)) => *position,
+            None => self.text.len(),
+        };
+        let literal = &self.text[start..end];
+
+        // 整数に収まらない整数は Float として読む
+        match literal.parse::<isize>() {
+            Ok(value) if integer => Ok(Object::Integer(value)),
+            _ => match literal.parse::<f64>() {
+                Ok(value) => Ok(Object::Float(Float(value))),
+                Err(_) => Err(format!("invalid JSON: invalid number {}", literal)),
+            },
+        }
+    }
+
+    fn skip_digits(&mut self) {
+        while let Some((_, '0'..='9')) = self.chars.peek() {
+            self.chars.next();
+        }
+    }
+
+    fn expect_digits(&mut self) -> Result<(), String> {
+        match self.next()? {
+            (_, '0'..='9') => {
+                self.skip_digits();
+                Ok(())
+            }
+            (position, ch) => Err(unexpected(ch, position)),
+        }
+    }
+}
+
+fn write_value(json: &mut String, value: &Object, depth: usize) -> Result<(), String> {
+    match value {
+        Object::Array(_) | Object::Map(_) if depth == MAX_DEPTH => {
+            let message = format!("cannot convert to JSON: nesting deeper than {}", MAX_DEPTH);
+            return Err(message);
+        }
+        Object::Null => json.push_str("null"),
+        Object::Boolean(value) => json.push_str(if *value { "true" } else { "false" }),
+        Object::Integer(value) => json.push_str(&value.to_string()),
+        Object::Float(value) if value.0.is_finite() => json.push_str(&format_float(value.0)),
+        Object::String(value) => write_string(json, value),
+        Object::Array(elements) => {
+            json.push('[');
+
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+
+                write_value(json, element, depth + 1)?;
+            }
+
+            json.push(']');
+        }
+        Object::Map(pairs) => {
+            json.push('{');
+
+            for (i, pair) in pairs.values().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+
+                match &pair.key {
+                    Object::String(key) => write_string(json, key),
+                    key => {
+                        let message =
+                            format!("JSON object keys must be String, got {}", key.get_type());
+                        return Err(message);
+                    }
+                }
+
+                json.push(':');
+                write_value(json, &pair.value, depth + 1)?;
+            }
+
+            json.push('}');
+        }
+        value => {
+            let message = format!("cannot convert {} to JSON", value.get_type());
+            return Err(message);
+        }
+    }
+
+    Ok(())
+}
+
+fn write_string(json: &mut String, value: &str) {
+    json.push('"');
+
+    for ch in value.chars() {
+        match ch {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            ch if ch < ' ' => {
+                let _ = write!(json, "\\u{:04x}", ch as u32);
+            }
+            ch => json.push(ch),
+        }
+    }
+
+    json.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::{parse, stringify, MAX_DEPTH};
+    use crate::numeric::Float;
+    use crate::object::{MapKey, MapPair, Object};
+    use std::collections::BTreeMap;
+
+    fn map(pairs: Vec<(&str, Object)>) -> Object {
+        let pairs = pairs
+            .into_iter()
+            .map(|(key, value)| {
+                let key = Object::String(key.to_string());
+                (MapKey::from(&key), MapPair::new(key, value))
+            })
+            .collect::<BTreeMap<_, _>>();
+        Object::Map(pairs)
+    }
+
+    #[test]
+    fn test_parse() {
+        let tests = vec![
+            ("null", Object::Null),
+            (" true ", Object::Boolean(true)),
+            ("-12", Object::Integer(-12)),
+            ("1.5e2", Object::Float(Float(150.0))),
+            ("0.25", Object::Float(Float(0.25))),
+            (
+                "99999999999999999999",
+                Object::Float(Float(99999999999999999999.0)),
+            ),
+            (
+                r#""a\"b\\c\né😀""#,
+                Object::String("a\"b\\c\né😀".to_string()),
+            ),
+            (
+                r#"{"name": "monkey", "tags": [1, [], {}], "ok": false}"#,
+                map(vec![
+                    ("name", Object::String("monkey".to_string())),
+                    (
+                        "tags",
+                        Object::Array(vec![Object::Integer(1), Object::Array(vec![]), map(vec![])]),
+                    ),
+                    ("ok", Object::Boolean(false)),
+                ]),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(parse(input), Ok(expected), "{}", input);
+        }
+
+        let tests = vec![
+            ("", "invalid JSON: unexpected end of input"),
+            ("[1, 2", "invalid JSON: unexpected end of input"),
+            ("[1,]", "invalid JSON: unexpected ']' at position 3"),
+            ("{1: 2}", "invalid JSON: unexpected '1' at position 1"),
+            ("01", "invalid JSON: unexpected '1' at position 1"),
+            ("1.", "invalid JSON: unexpected end of input"),
+            ("nul", "invalid JSON: unexpected end of input"),
+            ("tru3", "invalid JSON: unexpected '3' at position 3"),
+            ("\"a\nb\"", "invalid JSON: unexpected '\\n' at position 2"),
+            (
+                r#""\ud83d\u0041""#,
+                "invalid JSON: invalid surrogate pair at position 2",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(parse(input), Err(expected.to_string()), "{}", input);
+        }
+
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            parse(&nested(MAX_DEPTH + 1)),
+            Err("invalid JSON: nesting deeper than 512 at position 512".to_string())
+        );
+        assert_eq!(
+            parse(&nested(200000)),
+            Err("invalid JSON: nesting deeper than 512 at position 512".to_string())
+        );
+    }
+
+    #[test]
+    fn test_stringify() {
+        let value = map(vec![
+            (
+                "b",
+                Object::Array(vec![Object::Null, Object::Float(Float(0.5))]),
+            ),
+            ("a", Object::String("say \"hi\"\n\u{1}".to_string())),
+        ]);
+
+        assert_eq!(
+            stringify(&value),
+            Ok(r#"{"a":"say \"hi\"\n\u0001","b":[null,0.5]}"#.to_string())
+        );
+        assert_eq!(parse(&stringify(&value).unwrap()), Ok(value));
+
+        let key = Object::Integer(1);
+        let mut pairs = BTreeMap::new();
+        pairs.insert(MapKey::from(&key), MapPair::new(key, Object::Null));
+
+        let tests = vec![
+            (
+                Object::Map(pairs),
+                "JSON object keys must be String, got Integer",
+            ),
+            (
+                Object::Float(Float(f64::NAN)),
+                "cannot convert Float to JSON",
+            ),
+            (
+                Object::Array(vec![Object::File(0)]),
+                "cannot convert File to JSON",
+            ),
+        ];
+
+        for (value, expected) in tests {
+            assert_eq!(stringify(&value), Err(expected.to_string()));
+        }
+
+        let nested = |depth| (0..depth).fold(Object::Null, |value, _| Object::Array(vec![value]));
+        assert!(stringify(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            stringify(&nested(MAX_DEPTH + 1)),
+            Err("cannot convert to JSON: nesting deeper than 512".to_string())
+        );
+    }
+}
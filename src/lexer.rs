@@ -1,5 +1,11 @@
+#[cfg(feature = "rational")]
+use crate::numeric::Rational;
+use crate::numeric::{parse_float, Float};
+use crate::symbol::{Interner, SharedInterner, Symbol};
 use crate::token::*;
-use std::iter::FromIterator;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::iter::FromIterator;
 
 /// 字句解析器
 pub struct Lexer {
@@ -10,22 +16,55 @@ pub struct Lexer {
     read_position: usize,
     /// 現在検査中の文字
     ch: char,
+    /// 現在の文字の行と列
+    line: usize,
+    column: usize,
+    /// 最後に読んだトークンの始まりの位置
+    token_position: Position,
+    /// 識別子の名前の表
+    interner: SharedInterner,
 }
 
 impl Lexer {
     pub fn new(input: &str) -> Self {
+        Self::with_interner(input, Interner::shared())
+    }
+
+    /// 名前の表から `Symbol` を得る（構文解析器が識別子を補うときに使う）
+    pub(crate) fn intern(&self, name: &str) -> Symbol {
+        self.interner.borrow_mut().intern(name)
+    }
+
+    /// 評価器などと名前の表を共有する字句解析器を作る
+    pub fn with_interner(input: &str, interner: SharedInterner) -> Self {
         let mut lexer = Lexer {
             input: input.chars().collect(),
             position: 0,
             read_position: 0,
             ch: 0 as char,
+            line: 1,
+            column: 0,
+            token_position: Position::default(),
+            interner,
         };
 
         lexer.read_char();
         lexer
     }
 
+    /// 最後に読んだトークンの始まりの位置
+    pub fn position(&self) -> Position {
+        self.token_position
+    }
+
     fn read_char(&mut self) {
+        if self.ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
         if self.read_position >= self.input.len() {
             self.ch = 0 as char;
         } else {
@@ -38,6 +77,7 @@ impl Lexer {
 
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
+        self.token_position = Position::new(self.line, self.column);
 
         let token = match self.ch {
             '=' => match self.peek_char() {
@@ -45,6 +85,10 @@ impl Lexer {
                     self.read_char();
                     Token::Eq
                 }
+                '>' => {
+                    self.read_char();
+                    Token::FatArrow
+                }
                 _ => Token::Assign,
             },
             '+' => Token::Plus,
@@ -58,6 +102,31 @@ impl Lexer {
                 }
                 _ => Token::Bang,
             },
+            '.' => match self.peek_char() {
+                '.' => {
+                    self.read_char();
+
+                    match self.peek_char() {
+                        '=' => {
+                            self.read_char();
+                            Token::DotDotEq
+                        }
+                        '.' => {
+                            self.read_char();
+                            Token::Ellipsis
+                        }
+                        _ => Token::DotDot,
+                    }
+                }
+                _ => Token::Dot,
+            },
+            '?' => match self.peek_char() {
+                '?' => {
+                    self.read_char();
+                    Token::Coalesce
+                }
+                _ => Token::Question,
+            },
             '<' => Token::Lt,
             '>' => Token::Gt,
             ',' => Token::Comma,
@@ -75,7 +144,7 @@ impl Lexer {
                 if self.is_letter() {
                     return self.read_identifier();
                 } else if self.is_digit() {
-                    return self.read_integer();
+                    return self.read_number();
                 } else {
                     Token::Illegal(self.ch)
                 }
@@ -87,17 +156,20 @@ impl Lexer {
     }
 
     fn peek_char(&self) -> char {
-        if self.read_position >= self.input.len() {
-            0 as char
-        } else {
-            self.input[self.read_position]
+        self.peek_nth_char(0)
+    }
+
+    fn peek_nth_char(&self, n: usize) -> char {
+        match self.input.get(self.read_position + n) {
+            Some(ch) => *ch,
+            None => 0 as char,
         }
     }
 
     fn read_identifier(&mut self) -> Token {
         let start_position = self.position;
 
-        while self.is_letter() {
+        while self.is_letter() || self.is_digit() {
             self.read_char();
         }
 
@@ -111,22 +183,77 @@ impl Lexer {
             "if" => Token::If,
             "else" => Token::Else,
             "return" => Token::Return,
-            _ => Token::Identifier(identifier),
+            "try" => Token::Try,
+            "catch" => Token::Catch,
+            "match" => Token::Match,
+            "macro" => Token::Macro,
+            "yield" => Token::Yield,
+            "import" => Token::Import,
+            "as" => Token::As,
+            _ => Token::Identifier(self.interner.borrow_mut().intern(&identifier)),
         }
     }
 
-    fn read_integer(&mut self) -> Token {
+    fn read_number(&mut self) -> Token {
         let start_position = self.position;
+        let mut is_float = false;
 
-        while self.is_digit() {
+        self.read_digits();
+
+        if self.ch == '.' && self.peek_char().is_ascii_digit() {
+            is_float = true;
+            self.read_char();
+            self.read_digits();
+        }
+
+        if self.ch == 'e' || self.ch == 'E' {
+            let sign = matches!(self.peek_char(), '+' | '-') as usize;
+
+            if self.peek_nth_char(sign).is_ascii_digit() {
+                is_float = true;
+                self.read_char();
+
+                if sign == 1 {
+                    self.read_char();
+                }
+
+                self.read_digits();
+            }
+        }
+
+        let number = String::from_iter(&self.input[start_position..self.position]);
+
+        if is_float {
+            return match parse_float(&number) {
+                Some(f) => Token::Float(Float(f)),
+                None => Token::Illegal(self.input[start_position]),
+            };
+        }
+
+        let integer = match number.parse() {
+            Ok(i) => i,
+            Err(_) => {
+                let Position { line, column } = self.token_position;
+                return Token::OutOfRange {
+                    literal: number,
+                    line,
+                    column,
+                };
+            }
+        };
+
+        #[cfg(feature = "rational")]
+        if self.ch == 'r' && !(self.peek_char().is_alphanumeric() || self.peek_char() == '_') {
             self.read_char();
+            return Token::Rational(Rational::from_integer(integer));
         }
 
-        let int = String::from_iter(&self.input[start_position..self.position]);
+        Token::Integer(integer)
+    }
 
-        match int.parse() {
-            Ok(i) => Token::Integer(i),
-            Err(_) => Token::Illegal(self.input[start_position]),
+    fn read_digits(&mut self) {
+        while self.is_digit() {
+            self.read_char();
         }
     }
 
@@ -144,7 +271,7 @@ impl Lexer {
     }
 
     fn is_letter(&self) -> bool {
-        self.ch.is_alphabetic()
+        self.ch.is_alphabetic() || self.ch == '_'
     }
 
     fn is_digit(&self) -> bool {
@@ -161,7 +288,120 @@ impl Lexer {
 #[cfg(test)]
 mod tests {
     use crate::lexer::Lexer;
+    use crate::numeric::Float;
+    #[cfg(feature = "rational")]
+    use crate::numeric::Rational;
+    use crate::symbol::Interner;
     use crate::token::Token;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_out_of_range_integers() {
+        let literal = "12345678901234567890123456789012345678901234567890";
+        let input = format!("let x = 1;\n  {} + 1", literal);
+        let mut lexer = Lexer::new(&input);
+
+        for _ in 0..5 {
+            lexer.next_token();
+        }
+
+        assert_eq!(
+            lexer.next_token(),
+            Token::OutOfRange {
+                literal: literal.to_string(),
+                line: 2,
+                column: 3,
+            }
+        );
+        assert_eq!(lexer.next_token(), Token::Plus);
+    }
+
+    #[test]
+    fn test_token_positions() {
+        let mut lexer = Lexer::new("let x = 1;\n  puts(\"a\nb\", x)");
+        let mut positions = vec![];
+
+        loop {
+            let token = lexer.next_token();
+            let position = lexer.position();
+            positions.push((token.to_string(), position.line, position.column));
+
+            if token == Token::Eof {
+                break;
+            }
+        }
+
+        assert_eq!(
+            positions,
+            vec![
+                ("let".to_string(), 1, 1),
+                ("x".to_string(), 1, 5),
+                ("=".to_string(), 1, 7),
+                ("Int(1)".to_string(), 1, 9),
+                (";".to_string(), 1, 10),
+                ("puts".to_string(), 2, 3),
+                ("(".to_string(), 2, 7),
+                ("String(a\nb)".to_string(), 2, 8),
+                (",".to_string(), 3, 3),
+                ("x".to_string(), 3, 5),
+                (")".to_string(), 3, 6),
+                ("EOF".to_string(), 3, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interned_identifiers() {
+        let interner = Interner::shared();
+        let mut lexer = Lexer::with_interner("x + x", interner.clone());
+        let mut other = Lexer::with_interner("x", interner.clone());
+
+        match (
+            lexer.next_token(),
+            lexer.next_token(),
+            lexer.next_token(),
+            other.next_token(),
+        ) {
+            (Token::Identifier(a), Token::Plus, Token::Identifier(b), Token::Identifier(c)) => {
+                assert!(Arc::ptr_eq(&a, &b));
+                assert!(Arc::ptr_eq(&a, &c));
+            }
+            tokens => panic!("unexpected tokens: {:?}", tokens),
+        }
+
+        assert_eq!(interner.borrow().len(), 1);
+    }
+
+    #[cfg(feature = "rational")]
+    #[test]
+    fn test_rational_literals() {
+        let tests = vec![
+            ("3r", vec![Token::Rational(Rational::from_integer(3))]),
+            (
+                "3/4r",
+                vec![
+                    Token::Integer(3),
+                    Token::Slash,
+                    Token::Rational(Rational::from_integer(4)),
+                ],
+            ),
+            (
+                "4rx",
+                vec![Token::Integer(4), Token::Identifier("rx".into())],
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut lexer = Lexer::new(input);
+            let tokens = expected
+                .iter()
+                .map(|_| lexer.next_token())
+                .collect::<Vec<_>>();
+
+            assert_eq!(tokens, expected);
+            assert_eq!(lexer.next_token(), Token::Eof);
+        }
+    }
 
     #[test]
     fn test_next_token() {
@@ -172,6 +412,7 @@ mod tests {
             x + y;
         };
         let result = add(five, ten);
+        let snake_case = _x1;
         !-/*5;
         5 < 10 > 5;
         if (5 < 10) {
@@ -185,45 +426,56 @@ mod tests {
         "foo bar";
         [1, 2];
         {"foo": "bar"};
+        1..10;
+        1..=10;
+        1.5 2e3 1.5e-3 1.e;
+        try { } catch (e) { }
+        match (x) { [_, ...rest] => rest }
+        a ?? b ?;
         "#;
 
         let expected_token = [
             Token::Let,
-            Token::Identifier("five".to_string()),
+            Token::Identifier("five".into()),
             Token::Assign,
             Token::Integer(5),
             Token::Semicolon,
             Token::Let,
-            Token::Identifier("ten".to_string()),
+            Token::Identifier("ten".into()),
             Token::Assign,
             Token::Integer(10),
             Token::Semicolon,
             Token::Let,
-            Token::Identifier("add".to_string()),
+            Token::Identifier("add".into()),
             Token::Assign,
             Token::Function,
             Token::LParen,
-            Token::Identifier("x".to_string()),
+            Token::Identifier("x".into()),
             Token::Comma,
-            Token::Identifier("y".to_string()),
+            Token::Identifier("y".into()),
             Token::RParen,
             Token::LBrace,
-            Token::Identifier("x".to_string()),
+            Token::Identifier("x".into()),
             Token::Plus,
-            Token::Identifier("y".to_string()),
+            Token::Identifier("y".into()),
             Token::Semicolon,
             Token::RBrace,
             Token::Semicolon,
             Token::Let,
-            Token::Identifier("result".to_string()),
+            Token::Identifier("result".into()),
             Token::Assign,
-            Token::Identifier("add".to_string()),
+            Token::Identifier("add".into()),
             Token::LParen,
-            Token::Identifier("five".to_string()),
+            Token::Identifier("five".into()),
             Token::Comma,
-            Token::Identifier("ten".to_string()),
+            Token::Identifier("ten".into()),
             Token::RParen,
             Token::Semicolon,
+            Token::Let,
+            Token::Identifier("snake_case".into()),
+            Token::Assign,
+            Token::Identifier("_x1".into()),
+            Token::Semicolon,
             Token::Bang,
             Token::Minus,
             Token::Slash,
@@ -277,6 +529,49 @@ mod tests {
             Token::String("bar".to_string()),
             Token::RBrace,
             Token::Semicolon,
+            Token::Integer(1),
+            Token::DotDot,
+            Token::Integer(10),
+            Token::Semicolon,
+            Token::Integer(1),
+            Token::DotDotEq,
+            Token::Integer(10),
+            Token::Semicolon,
+            Token::Float(Float(1.5)),
+            Token::Float(Float(2e3)),
+            Token::Float(Float(1.5e-3)),
+            Token::Integer(1),
+            Token::Dot,
+            Token::Identifier("e".into()),
+            Token::Semicolon,
+            Token::Try,
+            Token::LBrace,
+            Token::RBrace,
+            Token::Catch,
+            Token::LParen,
+            Token::Identifier("e".into()),
+            Token::RParen,
+            Token::LBrace,
+            Token::RBrace,
+            Token::Match,
+            Token::LParen,
+            Token::Identifier("x".into()),
+            Token::RParen,
+            Token::LBrace,
+            Token::LBracket,
+            Token::Identifier("_".into()),
+            Token::Comma,
+            Token::Ellipsis,
+            Token::Identifier("rest".into()),
+            Token::RBracket,
+            Token::FatArrow,
+            Token::Identifier("rest".into()),
+            Token::RBrace,
+            Token::Identifier("a".into()),
+            Token::Coalesce,
+            Token::Identifier("b".into()),
+            Token::Question,
+            Token::Semicolon,
             Token::Eof,
         ];
 
@@ -1,8 +1,41 @@
-mod ast;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod ast;
 mod buildin;
-mod evaluator;
-mod lexer;
-mod object;
-mod parser;
+pub mod error;
+pub mod evaluator;
+#[cfg(feature = "std")]
+pub mod history;
+pub mod host;
+pub mod incremental;
+pub mod inline;
+#[cfg(feature = "data")]
+pub mod json;
+pub mod lexer;
+#[cfg(feature = "std")]
+pub mod manifest;
+pub mod mdtest;
+pub mod messages;
+pub mod mutate;
+pub mod numeric;
+pub mod object;
+#[cfg(feature = "std")]
+pub mod package;
+pub mod parser;
+#[cfg(feature = "std")]
 pub mod repl;
-mod token;
+#[cfg(feature = "std")]
+mod report;
+#[cfg(feature = "std")]
+pub mod script;
+pub mod session;
+pub mod symbol;
+pub mod token;
+pub mod trace;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod validate;
+pub mod version;
+pub mod vfs;
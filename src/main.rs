@@ -1,14 +1,277 @@
-use ronkey::repl;
+use ronkey::inline::{evaluate_selection, DEFAULT_INLINE_STEPS};
+use ronkey::manifest::Manifest;
+use ronkey::messages::{localize, set_locale, text, Locale, Message};
+use ronkey::script::RunConfig;
+use ronkey::version::{VersionInfo, VERSION};
+use ronkey::{package, repl, script};
+use std::env;
+use std::fs;
 use std::io;
-use whoami;
+use std::path::Path;
+use std::process;
 
 fn main() -> io::Result<()> {
-    let username = whoami::username();
-    println!(
-        "Hello {}! This is the Monkey programming language!",
-        username
-    );
-    println!("Feel free to type in commands");
-
-    repl::start()
+    let arguments = env::args().skip(1).collect::<Vec<_>>();
+
+    if let Ok(tag) = env::var("RONKEY_LANG") {
+        set_locale(Locale::from_tag(&tag));
+    }
+
+    match arguments.first().map(String::as_str) {
+        Some("callgraph") => return callgraph(&arguments[1..]),
+        Some("mutate") => return mutate(&arguments[1..]),
+        Some("mdtest") => return mdtest(&arguments[1..]),
+        Some("test") => return test(&arguments[1..]),
+        Some("add") => return add(&arguments[1..]),
+        Some("eval") => return eval(&arguments[1..]),
+        _ => (),
+    }
+
+    if arguments.iter().any(|argument| argument == "--version") {
+        version(&arguments);
+        return Ok(());
+    }
+
+    let manifest = Manifest::load(Path::new("."))?.unwrap_or_default();
+    let mut config = RunConfig {
+        statement_limit: manifest.max_steps,
+        recursion_limit: manifest.max_depth,
+        import_paths: import_paths(&manifest)?,
+        ..RunConfig::default()
+    };
+    let mut run = false;
+    let mut check = false;
+    #[cfg(feature = "tui")]
+    let mut tui = false;
+    let mut path = None;
+    let mut replay = None;
+    let mut arguments = arguments.into_iter();
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "run" if path.is_none() => run = true,
+            "--debug" => config.debug = true,
+            "--coverage" => config.coverage = true,
+            "--deterministic" => config.deterministic = true,
+            "--allow-exec" => config.exec = true,
+            "--check" => check = true,
+            "--record" => config.record = arguments.next(),
+            "--replay" => replay = arguments.next(),
+            #[cfg(feature = "tui")]
+            "--tui" => tui = true,
+            _ => path = Some(argument),
+        }
+    }
+
+    if run && path.is_none() {
+        path = manifest.entry.clone();
+    }
+
+    if let Some(path) = path {
+        let ok = if check {
+            script::check(&path)?
+        } else {
+            script::run(&path, &config)?
+        };
+
+        if !ok {
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(replay) = replay {
+        if !script::replay(&replay)? {
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    #[cfg(feature = "tui")]
+    if tui {
+        return ronkey::tui::start();
+    }
+
+    match &config.record {
+        Some(record) => repl::record(config.debug, config.exec, record),
+        None => repl::start(config.debug, config.exec),
+    }
+}
+
+/// `ronkey --version [--features [--json]]`
+fn version(arguments: &[String]) {
+    let has = |flag: &str| arguments.iter().any(|argument| argument == flag);
+
+    match (has("--features"), has("--json")) {
+        (_, true) => println!("{}", VersionInfo::new().to_json()),
+        (true, false) => println!("{}", VersionInfo::new()),
+        (false, false) => println!("ronkey {}", VERSION),
+    }
+}
+
+/// カレントディレクトリのプロジェクトでモジュールを探すディレクトリ
+fn import_paths(manifest: &Manifest) -> io::Result<Vec<String>> {
+    let paths = manifest
+        .search_paths(Path::new("."))?
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+
+    Ok(paths)
+}
+
+/// `ronkey callgraph <script> [-o <file>]`
+fn callgraph(arguments: &[String]) -> io::Result<()> {
+    let mut path = None;
+    let mut output = None;
+    let mut arguments = arguments.iter();
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "-o" => output = arguments.next(),
+            _ => path = Some(argument),
+        }
+    }
+
+    let path = match path {
+        Some(path) => path,
+        None => {
+            eprintln!(
+                "{}",
+                text(Message::Usage, &[&"ronkey callgraph <script> [-o <file>]"])
+            );
+            process::exit(2);
+        }
+    };
+
+    if !script::callgraph(path, output.map(String::as_str))? {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// `ronkey mutate <script>`
+fn mutate(arguments: &[String]) -> io::Result<()> {
+    let path = match arguments.first() {
+        Some(path) => path,
+        None => {
+            eprintln!("{}", text(Message::Usage, &[&"ronkey mutate <script>"]));
+            process::exit(2);
+        }
+    };
+
+    if !script::mutate(path)? {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// `ronkey test [--debug] [--coverage] [--deterministic] [--allow-exec]`（`monkey.toml` の `tests` を実行する）
+fn test(arguments: &[String]) -> io::Result<()> {
+    let manifest = match Manifest::load(Path::new("."))? {
+        Some(manifest) => manifest,
+        None => {
+            eprintln!(
+                "{}",
+                text(Message::Usage, &[&"ronkey test (requires monkey.toml)"])
+            );
+            process::exit(2);
+        }
+    };
+
+    let config = RunConfig {
+        debug: arguments.iter().any(|argument| argument == "--debug"),
+        coverage: arguments.iter().any(|argument| argument == "--coverage"),
+        deterministic: arguments
+            .iter()
+            .any(|argument| argument == "--deterministic"),
+        exec: arguments.iter().any(|argument| argument == "--allow-exec"),
+        statement_limit: manifest.max_steps,
+        recursion_limit: manifest.max_depth,
+        import_paths: import_paths(&manifest)?,
+        ..RunConfig::default()
+    };
+
+    if !script::test(&manifest, Path::new("."), &config)? {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// `ronkey add [<git-url-or-path>]`（引数がなければ `monkey.lock` のライブラリを取りこみ直す）
+fn add(arguments: &[String]) -> io::Result<()> {
+    let root = Path::new(".");
+    let added = match arguments.first() {
+        Some(source) => vec![package::add(root, source, None)?],
+        None => package::install(root)?,
+    };
+
+    for locked in added {
+        println!(
+            "{}",
+            text(Message::PackageAdded, &[&locked.name, &locked.revision])
+        );
+    }
+
+    Ok(())
+}
+
+/// `ronkey eval <script> <expression> [--max-steps <n>]`（エディターの「選択範囲を評価」用）
+fn eval(arguments: &[String]) -> io::Result<()> {
+    let mut positional = vec![];
+    let mut max_steps = Some(DEFAULT_INLINE_STEPS);
+    let mut arguments = arguments.iter();
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--max-steps" => max_steps = arguments.next().and_then(|steps| steps.parse().ok()),
+            _ => positional.push(argument),
+        }
+    }
+
+    let (path, expression, max_steps) = match (positional.as_slice(), max_steps) {
+        ([path, expression], Some(max_steps)) => (path, expression, max_steps),
+        _ => {
+            eprintln!(
+                "{}",
+                text(
+                    Message::Usage,
+                    &[&"ronkey eval <script> <expression> [--max-steps <n>]"]
+                )
+            );
+            process::exit(2);
+        }
+    };
+
+    let source = fs::read_to_string(path)?;
+
+    match evaluate_selection(&source, expression, max_steps) {
+        Ok(result) => println!("{}", result),
+        Err(error) => {
+            eprintln!("{}", text(Message::Error, &[&localize(&error)]));
+            process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// `ronkey mdtest <markdown>`
+fn mdtest(arguments: &[String]) -> io::Result<()> {
+    let path = match arguments.first() {
+        Some(path) => path,
+        None => {
+            eprintln!("{}", text(Message::Usage, &[&"ronkey mdtest <markdown>"]));
+            process::exit(2);
+        }
+    };
+
+    if !script::mdtest(path)? {
+        process::exit(1);
+    }
+
+    Ok(())
 }
@@ -0,0 +1,320 @@
+use crate::package::module_paths;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// プロジェクトファイルの名前
+pub const MANIFEST_FILE: &str = "monkey.toml";
+
+/// `monkey.toml` に書くプロジェクトの設定
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Manifest {
+    /// `ronkey run` で実行するファイル
+    pub entry: Option<String>,
+    /// モジュールを探すディレクトリ
+    pub import_paths: Vec<String>,
+    /// `ronkey test` で実行するファイルのパターン（`*` と `?` が使える）
+    pub tests: Vec<String>,
+    /// 1 回の実行で評価できる文の数の上限（`[dialect]` の `max_steps`）
+    pub max_steps: Option<usize>,
+    /// 関数呼び出しの入れ子の深さの上限（`[dialect]` の `max_depth`）
+    pub max_depth: Option<usize>,
+}
+
+impl Manifest {
+    /// ディレクトリの `monkey.toml` を読みこむ（なければ None）
+    pub fn load(dir: &Path) -> io::Result<Option<Self>> {
+        let path = dir.join(MANIFEST_FILE);
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let source = fs::read_to_string(&path)?;
+        let manifest = Self::parse(&source).map_err(|error| {
+            let message = format!("{}: {}", path.display(), error);
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        })?;
+
+        Ok(Some(manifest))
+    }
+
+    /// TOML のうち、文字列・整数・文字列の配列とテーブル見出しだけを解釈する
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut manifest = Self::default();
+        let mut table = String::new();
+
+        for (i, line) in source.lines().enumerate() {
+            let line = strip_comment(line).trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                table = name.trim().to_string();
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => {
+                    let message = format!("line {}: expected `key = value`", i + 1);
+                    return Err(message);
+                }
+            };
+
+            manifest
+                .set(&table, key, value)
+                .map_err(|error| format!("line {}: {}", i + 1, error))?;
+        }
+
+        Ok(manifest)
+    }
+
+    fn set(&mut self, table: &str, key: &str, value: &str) -> Result<(), String> {
+        match (table, key) {
+            ("", "entry") => self.entry = Some(parse_string(value)?),
+            ("", "import_paths") => self.import_paths = parse_strings(value)?,
+            ("", "tests") => self.tests = parse_strings(value)?,
+            ("dialect", "max_steps") => self.max_steps = Some(parse_limit(value)?),
+            ("dialect", "max_depth") => self.max_depth = Some(parse_limit(value)?),
+            ("", key) => {
+                let message = format!("unknown key: {}", key);
+                return Err(message);
+            }
+            (table, key) => {
+                let message = format!("unknown key: {}.{}", table, key);
+                return Err(message);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// モジュールを探すディレクトリ（`import_paths` のあとに `monkey_modules` の各ライブラリ）
+    pub fn search_paths(&self, root: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut paths = self
+            .import_paths
+            .iter()
+            .map(|path| root.join(path))
+            .collect::<Vec<_>>();
+        paths.extend(module_paths(root)?);
+
+        Ok(paths)
+    }
+
+    /// テストのパターンに一致するファイルを、プロジェクトのルートからの順に並べて返す
+    pub fn test_files(&self, root: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut files = vec![];
+
+        for pattern in self.tests.iter() {
+            let pattern = Path::new(pattern);
+            let dir = root.join(pattern.parent().unwrap_or_else(|| Path::new("")));
+            let name = match pattern.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            if !dir.is_dir() {
+                continue;
+            }
+
+            let mut matched = fs::read_dir(&dir)?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|file| file.to_str())
+                        .is_some_and(|file| wildcard(name, file))
+                })
+                .collect::<Vec<_>>();
+            matched.sort();
+
+            for path in matched {
+                if !files.contains(&path) {
+                    files.push(path);
+                }
+            }
+        }
+
+        Ok(files)
+    }
+}
+
+/// 文字列の外にある `#` 以降を取り除く
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+
+    for (i, ch) in line.char_indices() {
+        match ch {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => (),
+        }
+    }
+
+    line
+}
+
+fn parse_string(value: &str) -> Result<String, String> {
+    match value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        Some(value) if !value.contains('"') => Ok(value.to_string()),
+        _ => {
+            let message = format!("expected a string, got {}", value);
+            Err(message)
+        }
+    }
+}
+
+fn parse_limit(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(limit) => Ok(limit),
+        Err(_) => {
+            let message = format!("expected a non-negative integer, got {}", value);
+            Err(message)
+        }
+    }
+}
+
+fn parse_strings(value: &str) -> Result<Vec<String>, String> {
+    let elements = match value
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+    {
+        Some(elements) => elements,
+        None => {
+            let message = format!("expected an array of strings, got {}", value);
+            return Err(message);
+        }
+    };
+
+    elements
+        .split(',')
+        .map(str::trim)
+        .filter(|element| !element.is_empty())
+        .map(parse_string)
+        .collect()
+}
+
+/// `*`（0 文字以上）と `?`（1 文字）を使ったパターンに名前が一致するか
+fn wildcard(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    let mut star = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&ch) if ch == '?' || ch == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::{wildcard, Manifest};
+    use std::fs;
+
+    #[test]
+    fn test_parse() {
+        let source = r#"
+            # project
+            entry = "src/main.monkey"
+            import_paths = ["src", "lib"]
+            tests = ["tests/*.monkey"]
+
+            [dialect]
+            max_steps = 100000 # per run
+            max_depth = 500
+        "#;
+
+        let expected = Manifest {
+            entry: Some("src/main.monkey".to_string()),
+            import_paths: vec!["src".to_string(), "lib".to_string()],
+            tests: vec!["tests/*.monkey".to_string()],
+            max_steps: Some(100000),
+            max_depth: Some(500),
+        };
+        assert_eq!(Manifest::parse(source), Ok(expected));
+
+        let tests = vec![
+            ("entry", "line 1: expected `key = value`"),
+            ("entry = main", "line 1: expected a string, got main"),
+            (
+                "tests = \"a\"",
+                "line 1: expected an array of strings, got \"a\"",
+            ),
+            ("name = \"x\"", "line 1: unknown key: name"),
+            (
+                "[dialect]\nmax_steps = -1",
+                "line 2: expected a non-negative integer, got -1",
+            ),
+            (
+                "[dialect]\nmax_depth = deep",
+                "line 2: expected a non-negative integer, got deep",
+            ),
+            ("[build]\nentry = \"x\"", "line 2: unknown key: build.entry"),
+        ];
+
+        for (source, expected) in tests {
+            assert_eq!(Manifest::parse(source), Err(expected.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_wildcard() {
+        assert!(wildcard("*.monkey", "a.monkey"));
+        assert!(wildcard("test_?.monkey", "test_1.monkey"));
+        assert!(wildcard("*_test*", "map_test.monkey"));
+        assert!(!wildcard("*.monkey", "a.md"));
+        assert!(!wildcard("test_?.monkey", "test_10.monkey"));
+    }
+
+    #[test]
+    fn test_test_files() {
+        let root = std::env::temp_dir().join(format!("ronkey-manifest-{}", std::process::id()));
+        fs::create_dir_all(root.join("tests")).unwrap();
+
+        for name in ["b.monkey", "a.monkey", "notes.md"] {
+            fs::write(root.join("tests").join(name), "").unwrap();
+        }
+
+        let manifest = Manifest {
+            tests: vec!["tests/*.monkey".to_string(), "tests/a.*".to_string()],
+            ..Manifest::default()
+        };
+        let files = manifest.test_files(&root).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let names = files
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a.monkey", "b.monkey"]);
+    }
+}
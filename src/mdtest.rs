@@ -0,0 +1,187 @@
+use crate::evaluator::{Evaluator, Response};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Markdown から取り出した ```monkey ブロック
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snippet {
+    /// ブロックの開始行（1 始まり）
+    pub line: usize,
+    pub source: String,
+    /// 直後の ```output ブロックの内容
+    pub expected: Option<String>,
+}
+
+impl Snippet {
+    /// スニペットを評価し、期待する出力があれば最後の式の値と比べる
+    pub fn check(&self) -> Result<(), String> {
+        let mut lexer = Lexer::new(&self.source);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+
+        if parser.exists_errors() {
+            let message = format!("parse error: {}", parser.get_errors().join(", "));
+            return Err(message);
+        }
+
+        let actual = match Evaluator::new().eval(program) {
+            Response::Reply(result) => result.to_string(),
+            Response::NoReply => String::new(),
+            Response::Error(error) => return Err(format!("error: {}", error)),
+        };
+
+        match &self.expected {
+            Some(expected) if expected.trim_end() != actual => {
+                let message = format!("expected {:?}, got {:?}", expected.trim_end(), actual);
+                Err(message)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// フェンスで囲まれたブロック
+struct Fence {
+    line: usize,
+    language: String,
+    content: String,
+    /// 直前のブロックとの間に空行しかないかどうか
+    adjacent: bool,
+}
+
+/// ```monkey ブロックを、直後に続く ```output ブロックとあわせて取り出す
+pub fn extract(markdown: &str) -> Vec<Snippet> {
+    let mut fences = vec![];
+    let mut current: Option<Fence> = None;
+    let mut adjacent = false;
+
+    for (i, line) in markdown.lines().enumerate() {
+        let trimmed = line.trim();
+
+        match &mut current {
+            Some(fence) => {
+                if trimmed.starts_with("```") {
+                    fences.extend(current.take());
+                    adjacent = true;
+                } else {
+                    fence.content.push_str(line);
+                    fence.content.push('\n');
+                }
+            }
+            None => match trimmed.strip_prefix("```") {
+                Some(language) => {
+                    current = Some(Fence {
+                        line: i + 1,
+                        language: language.trim().to_string(),
+                        content: String::new(),
+                        adjacent,
+                    });
+                }
+                None if trimmed.is_empty() => (),
+                None => adjacent = false,
+            },
+        }
+    }
+
+    let mut snippets = vec![];
+    let mut fences = fences.into_iter().peekable();
+
+    while let Some(fence) = fences.next() {
+        if fence.language != "monkey" {
+            continue;
+        }
+
+        let expected = match fences.peek() {
+            Some(next) if next.adjacent && next.language == "output" => {
+                fences.next().map(|output| output.content)
+            }
+            _ => None,
+        };
+
+        snippets.push(Snippet {
+            line: fence.line,
+            source: fence.content,
+            expected,
+        });
+    }
+
+    snippets
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mdtest::extract;
+
+    #[test]
+    fn test_extract_and_check() {
+        let markdown = r#"
+# Example
+
+```monkey
+let add = fn(x, y) { x + y };
+add(1, 2)
+```
+
+```output
+3
+```
+
+```monkey
+[1, 2][0] + 1
+```
+
+text between the blocks
+
+```output
+3
+```
+
+```rust
+fn main() {}
+```
+
+```monkey
+let x = 1;
+x - "a"
+```
+
+```monkey
+"a" + "b"
+```
+```output
+ab
+```
+"#;
+
+        let snippets = extract(markdown);
+
+        assert_eq!(snippets.len(), 4);
+        assert_eq!(snippets[0].line, 4);
+        assert_eq!(
+            snippets[0].source,
+            "let add = fn(x, y) { x + y };\nadd(1, 2)\n"
+        );
+        assert_eq!(snippets[0].expected.as_deref(), Some("3\n"));
+        assert_eq!(snippets[1].expected, None);
+        assert_eq!(snippets[3].expected.as_deref(), Some("ab\n"));
+
+        let results = snippets
+            .iter()
+            .map(|snippet| snippet.check())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            results,
+            vec![
+                Ok(()),
+                Ok(()),
+                Err("error: type mismatch: Integer - String".to_string()),
+                Ok(()),
+            ]
+        );
+    }
+}
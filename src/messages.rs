@@ -0,0 +1,405 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// 表示言語
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Locale {
+    /// 英語
+    En,
+    /// 日本語
+    Ja,
+}
+
+impl Locale {
+    /// `ja` や `ja_JP.UTF-8` のような言語タグから表示言語を選ぶ（不明なものは英語）
+    pub fn from_tag(tag: &str) -> Self {
+        if tag.to_ascii_lowercase().starts_with("ja") {
+            Self::Ja
+        } else {
+            Self::En
+        }
+    }
+}
+
+static LOCALE: AtomicU8 = AtomicU8::new(0);
+
+/// プロセス全体の表示言語を設定する
+pub fn set_locale(locale: Locale) {
+    LOCALE.store(locale as u8, Ordering::Relaxed);
+}
+
+/// プロセス全体の表示言語を返す
+pub fn locale() -> Locale {
+    match LOCALE.load(Ordering::Relaxed) {
+        1 => Locale::Ja,
+        _ => Locale::En,
+    }
+}
+
+/// メッセージのキー
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Message {
+    // REPL・コマンドライン
+    Greeting,
+    FeelFree,
+    MonkeyBusiness,
+    ParserErrors,
+    Error,
+    ParseError,
+    CrashReport,
+    SessionRecorded,
+    Usage,
+    MutationBaseline,
+    MutantResult,
+    Killed,
+    Survived,
+    MutantsKilled,
+    SnippetsSummary,
+    PackageAdded,
+
+    // 評価・構文解析のエラー
+    IdentifierNotFound,
+    TypeMismatch,
+    UnknownOperator,
+    NotAFunction,
+    WrongArity,
+    WrongArguments,
+    CallArity,
+    ArgumentMustBe,
+    ArgumentsMustBe,
+    ArgumentNotSupported,
+    IndexNotSupported,
+    SliceNotSupported,
+    SliceIndex,
+    UnusableMapKey,
+    NoMatchArm,
+    UncaughtThrow,
+    StatementLimit,
+    RecursionLimit,
+    LengthLimit,
+    IntegerOverflow,
+    DivisionByZero,
+    NegativeRepetition,
+    CannotUnquote,
+    MacroResult,
+    NoFilesystem,
+    NoSuchFile,
+    SideEffects,
+    NotAnExpression,
+    ExpectedNextToken,
+    NoPrefixParse,
+    IllegalChar,
+    IntegerLiteralOutOfRange,
+}
+
+/// 英語の文面から訳せるエラーメッセージ（照合する順に並べる）
+const DIAGNOSTICS: &[Message] = &[
+    Message::IdentifierNotFound,
+    Message::TypeMismatch,
+    Message::UnknownOperator,
+    Message::NotAFunction,
+    Message::WrongArity,
+    Message::WrongArguments,
+    Message::CallArity,
+    Message::ArgumentMustBe,
+    Message::ArgumentsMustBe,
+    Message::ArgumentNotSupported,
+    Message::IndexNotSupported,
+    Message::SliceNotSupported,
+    Message::SliceIndex,
+    Message::UnusableMapKey,
+    Message::NoMatchArm,
+    Message::UncaughtThrow,
+    Message::StatementLimit,
+    Message::RecursionLimit,
+    Message::LengthLimit,
+    Message::IntegerOverflow,
+    Message::DivisionByZero,
+    Message::NegativeRepetition,
+    Message::CannotUnquote,
+    Message::MacroResult,
+    Message::NoFilesystem,
+    Message::NoSuchFile,
+    Message::SideEffects,
+    Message::NotAnExpression,
+    Message::ExpectedNextToken,
+    Message::NoPrefixParse,
+    Message::IllegalChar,
+    Message::IntegerLiteralOutOfRange,
+];
+
+impl Message {
+    /// 表示言語ごとのテンプレート（`{}` を引数で順に置きかえる）
+    pub fn template(self, locale: Locale) -> &'static str {
+        let (en, ja) = match self {
+            Self::Greeting => (
+                "Hello {}! This is the Monkey programming language!",
+                "こんにちは {} さん！ Monkey プログラミング言語へようこそ！",
+            ),
+            Self::FeelFree => (
+                "Feel free to type in commands",
+                "自由にコマンドを入力してください",
+            ),
+            Self::MonkeyBusiness => (
+                "Woops! We ran into some monkey business here!",
+                "おっと！ ちょっとしたおさるの悪ふざけに出くわしたようです！",
+            ),
+            Self::ParserErrors => ("parser errors:", "構文エラー:"),
+            Self::Error => ("error: {}", "エラー: {}"),
+            Self::ParseError => ("parse error: {}", "構文エラー: {}"),
+            Self::CrashReport => (
+                "crash report written to {}",
+                "クラッシュレポートを {} に書き出しました",
+            ),
+            Self::SessionRecorded => ("session recorded to {}", "セッションを {} に記録しました"),
+            Self::Usage => ("usage: {}", "使い方: {}"),
+            Self::MutationBaseline => (
+                "test suite fails before mutation: {}",
+                "変異を加える前にテストスイートが失敗しています: {}",
+            ),
+            Self::MutantResult => ("mutant {}: {} ... {}", "変異体 {}: {} ... {}"),
+            Self::Killed => ("killed", "検出"),
+            Self::Survived => ("survived", "生存"),
+            Self::MutantsKilled => ("{}/{} mutants killed", "{}/{} 個の変異体を検出しました"),
+            Self::SnippetsSummary => ("{} passed, {} failed", "成功 {} 件、失敗 {} 件"),
+            Self::PackageAdded => ("added {} ({})", "{} を追加しました（{}）"),
+            Self::IdentifierNotFound => ("identifier not found: {}", "識別子が見つかりません: {}"),
+            Self::TypeMismatch => ("type mismatch: {} {} {}", "型が一致しません: {} {} {}"),
+            Self::UnknownOperator => ("unknown operator: {}", "未対応の演算子です: {}"),
+            Self::NotAFunction => ("not a function: {}", "関数ではありません: {}"),
+            Self::WrongArity => (
+                "expected arity to be {}, got {} instead",
+                "引数は {} 個のはずですが、{} 個でした",
+            ),
+            Self::WrongArguments => (
+                "wrong number of arguments. got={}, want={}",
+                "引数の数が違います。実際={}、期待={}",
+            ),
+            Self::CallArity => (
+                "`{}` expects {} arguments, got {}",
+                "`{}` の引数は {} 個のはずですが、{} 個でした",
+            ),
+            Self::ArgumentMustBe => (
+                "argument to `{}` must be {}, got {}",
+                "`{}` の引数は {} のはずですが、{} でした",
+            ),
+            Self::ArgumentsMustBe => (
+                "arguments to `{}` must be {}, got {}",
+                "`{}` の引数は {} のはずですが、{} でした",
+            ),
+            Self::ArgumentNotSupported => (
+                "argument to `{}` not supported, got {}",
+                "`{}` は引数の {} に対応していません",
+            ),
+            Self::IndexNotSupported => (
+                "index operator not supported: {}",
+                "インデックス演算子に対応していません: {}",
+            ),
+            Self::SliceNotSupported => (
+                "slice operator not supported: {}",
+                "スライス演算子に対応していません: {}",
+            ),
+            Self::SliceIndex => (
+                "slice index must be Integer, got {}",
+                "スライスの添字は Integer のはずですが、{} でした",
+            ),
+            Self::UnusableMapKey => ("unusable as map key: {}", "マップのキーに使えません: {}"),
+            Self::NoMatchArm => (
+                "no match arm for value: {}",
+                "値に一致する match の腕がありません: {}",
+            ),
+            Self::UncaughtThrow => ("uncaught throw: {}", "捕捉されなかった throw: {}"),
+            Self::StatementLimit => (
+                "statement limit exceeded: {}",
+                "実行できる文の数の上限を超えました: {}",
+            ),
+            Self::RecursionLimit => (
+                "maximum recursion depth exceeded: {}",
+                "関数呼び出しの入れ子の深さの上限を超えました: {}",
+            ),
+            Self::LengthLimit => ("maximum length exceeded: {}", "長さの上限を超えました: {}"),
+            Self::IntegerOverflow => ("integer overflow in {}", "整数がオーバーフローしました: {}"),
+            Self::DivisionByZero => ("division by zero", "0 で割りました"),
+            Self::NegativeRepetition => (
+                "negative repetition count: {}",
+                "繰り返す回数が負の数です: {}",
+            ),
+            Self::CannotUnquote => ("cannot unquote {}", "{} は unquote できません"),
+            Self::MacroResult => (
+                "we only support returning AST-nodes from macros",
+                "マクロは AST ノードしか返せません",
+            ),
+            Self::NoFilesystem => (
+                "no filesystem available to read {}",
+                "{} を読むためのファイルシステムがありません",
+            ),
+            Self::NoSuchFile => ("no such file: {}", "ファイルがありません: {}"),
+            Self::SideEffects => (
+                "`{}` has side effects and is not allowed here",
+                "`{}` は副作用があるためここでは使えません",
+            ),
+            Self::NotAnExpression => (
+                "selection is not a single expression: {}",
+                "選択範囲が 1 つの式ではありません: {}",
+            ),
+            Self::ExpectedNextToken => (
+                "expected next token to be {}, got {} instead",
+                "次のトークンは {} のはずですが、{} でした",
+            ),
+            Self::NoPrefixParse => (
+                "no prefix parse function for {} found",
+                "{} から始まる式は解析できません",
+            ),
+            Self::IllegalChar => ("illegal char found: {}", "不正な文字があります: {}"),
+            Self::IntegerLiteralOutOfRange => (
+                "integer literal out of range: {} at line {}, column {}",
+                "整数リテラルが範囲外です: {}（{} 行 {} 列）",
+            ),
+        };
+
+        match locale {
+            Locale::En => en,
+            Locale::Ja => ja,
+        }
+    }
+}
+
+/// テンプレートの `{}` を引数で順に置きかえる
+pub fn render(template: &str, arguments: &[&dyn fmt::Display]) -> String {
+    let mut result = String::new();
+    let mut arguments = arguments.iter();
+
+    for (i, piece) in template.split("{}").enumerate() {
+        if i > 0 {
+            if let Some(argument) = arguments.next() {
+                let _ = write!(result, "{}", argument);
+            }
+        }
+        result.push_str(piece);
+    }
+
+    result
+}
+
+/// 現在の表示言語でメッセージを組み立てる
+pub fn text(message: Message, arguments: &[&dyn fmt::Display]) -> String {
+    render(message.template(locale()), arguments)
+}
+
+/// 英語のエラーメッセージを現在の表示言語に訳す
+pub fn localize(error: &str) -> String {
+    translate(locale(), error)
+}
+
+/// 英語のエラーメッセージを表示言語に訳す（カタログにないものはそのまま返す）
+pub fn translate(locale: Locale, error: &str) -> String {
+    if locale == Locale::En {
+        return error.to_string();
+    }
+
+    for message in DIAGNOSTICS.iter() {
+        if let Some(arguments) = extract(message.template(Locale::En), error) {
+            let arguments = arguments
+                .iter()
+                .map(|argument| argument as &dyn fmt::Display)
+                .collect::<Vec<_>>();
+            return render(message.template(locale), &arguments);
+        }
+    }
+
+    error.to_string()
+}
+
+/// テンプレートに一致する文字列から、`{}` にあたる部分を取り出す
+fn extract<'a>(template: &str, text: &'a str) -> Option<Vec<&'a str>> {
+    let pieces = template.split("{}").collect::<Vec<_>>();
+    let mut rest = text.strip_prefix(pieces[0])?;
+    let mut arguments = Vec::with_capacity(pieces.len() - 1);
+
+    for (i, piece) in pieces.iter().enumerate().skip(1) {
+        if i == pieces.len() - 1 {
+            arguments.push(rest.strip_suffix(piece)?);
+            rest = "";
+        } else {
+            let end = rest.find(piece)?;
+            arguments.push(&rest[..end]);
+            rest = &rest[end + piece.len()..];
+        }
+    }
+
+    if rest.is_empty() {
+        Some(arguments)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::messages::{render, translate, Locale, Message};
+
+    #[test]
+    fn test_locale_from_tag() {
+        assert_eq!(Locale::from_tag("ja"), Locale::Ja);
+        assert_eq!(Locale::from_tag("ja_JP.UTF-8"), Locale::Ja);
+        assert_eq!(Locale::from_tag("en_US"), Locale::En);
+        assert_eq!(Locale::from_tag(""), Locale::En);
+    }
+
+    #[test]
+    fn test_render() {
+        let template = Message::Greeting.template(Locale::En);
+        assert_eq!(
+            render(template, &[&"akthrms"]),
+            "Hello akthrms! This is the Monkey programming language!"
+        );
+
+        let template = Message::MutantsKilled.template(Locale::Ja);
+        assert_eq!(render(template, &[&2, &3]), "2/3 個の変異体を検出しました");
+    }
+
+    #[test]
+    fn test_translate() {
+        let tests = vec![
+            ("identifier not found: x", "識別子が見つかりません: x"),
+            (
+                "type mismatch: Integer + Boolean",
+                "型が一致しません: Integer + Boolean",
+            ),
+            ("unknown operator: -true", "未対応の演算子です: -true"),
+            (
+                "argument to `first` must be Array, got Integer",
+                "`first` の引数は Array のはずですが、Integer でした",
+            ),
+            (
+                "argument to `len` not supported, got Integer",
+                "`len` は引数の Integer に対応していません",
+            ),
+            (
+                "expected next token to be Ident, got = instead",
+                "次のトークンは Ident のはずですが、= でした",
+            ),
+            (
+                "we only support returning AST-nodes from macros",
+                "マクロは AST ノードしか返せません",
+            ),
+            (
+                "`add` expects 2 arguments, got 1",
+                "`add` の引数は 2 個のはずですが、1 個でした",
+            ),
+            (
+                "integer literal out of range: 99999999999999999999 at line 2, column 5",
+                "整数リテラルが範囲外です: 99999999999999999999（2 行 5 列）",
+            ),
+            ("something else", "something else"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(translate(Locale::Ja, input), expected);
+            assert_eq!(translate(Locale::En, input), input);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use crate::ast::{Expression, Program, Statement};
+use crate::token::Token;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// 変異を 1 か所だけ加えたプログラム
+#[derive(Clone, Debug)]
+pub struct Mutant {
+    /// 変異の説明（変異前 -> 変異後）
+    pub description: String,
+    pub program: Program,
+}
+
+/// `+` と `-` の入れかえ、if の条件の否定を 1 か所ずつ加えた変異体を列挙する
+pub fn mutants(program: &Program) -> Vec<Mutant> {
+    let mut sites = 0;
+    walk_program(&mut program.clone(), &mut |expression| {
+        if mutate(expression).is_some() {
+            sites += 1;
+        }
+    });
+
+    let mut mutants = Vec::with_capacity(sites);
+
+    for target in 0..sites {
+        let mut program = program.clone();
+        let mut site = 0;
+        let mut description = String::new();
+
+        walk_program(&mut program, &mut |expression| {
+            let original = expression.clone();
+
+            if let Some(mutated) = mutate(expression) {
+                if site == target {
+                    description = format!("{} -> {}", original, mutated);
+                    *expression = mutated;
+                }
+                site += 1;
+            }
+        });
+
+        mutants.push(Mutant {
+            description,
+            program,
+        });
+    }
+
+    mutants
+}
+
+/// 変異を加えた式を返す（変異させられない式なら None）
+fn mutate(expression: &Expression) -> Option<Expression> {
+    let mutated = match expression {
+        Expression::Infix {
+            left,
+            operator,
+            right,
+        } => {
+            let operator = match operator {
+                Token::Plus => Token::Minus,
+                Token::Minus => Token::Plus,
+                _ => return None,
+            };
+
+            Expression::Infix {
+                left: left.clone(),
+                operator,
+                right: right.clone(),
+            }
+        }
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+        } => Expression::If {
+            condition: Box::new(Expression::Prefix {
+                operator: Token::Bang,
+                right: condition.clone(),
+            }),
+            consequence: consequence.clone(),
+            alternative: alternative.clone(),
+        },
+        _ => return None,
+    };
+
+    Some(mutated)
+}
+
+fn walk_program(program: &mut Program, f: &mut dyn FnMut(&mut Expression)) {
+    for statement in program.statements.iter_mut() {
+        walk_statement(statement, f);
+    }
+}
+
+fn walk_statement(statement: &mut Statement, f: &mut dyn FnMut(&mut Expression)) {
+    match statement {
+        Statement::Let { value, .. } => walk_expression(value, f),
+        Statement::Return(expression, _) | Statement::Expression(expression, _) => {
+            walk_expression(expression, f)
+        }
+        Statement::Block(statements) => {
+            for statement in statements.iter_mut() {
+                walk_statement(statement, f);
+            }
+        }
+    }
+}
+
+/// 式を親から子の順にたどる
+fn walk_expression(expression: &mut Expression, f: &mut dyn FnMut(&mut Expression)) {
+    f(expression);
+
+    match expression {
+        Expression::Prefix { right, .. } => walk_expression(right, f),
+        Expression::Infix { left, right, .. } => {
+            walk_expression(left, f);
+            walk_expression(right, f);
+        }
+        Expression::Grouped(expression) | Expression::Yield(expression) => {
+            walk_expression(expression, f)
+        }
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+        } => {
+            walk_expression(condition, f);
+            walk_statement(consequence, f);
+
+            if let Some(alternative) = alternative {
+                walk_statement(alternative, f);
+            }
+        }
+        Expression::Try { body, handler, .. } => {
+            walk_statement(body, f);
+            walk_statement(handler, f);
+        }
+        Expression::Match { subject, arms } => {
+            walk_expression(subject, f);
+
+            for arm in arms.iter_mut() {
+                if let Some(guard) = &mut arm.guard {
+                    walk_expression(guard, f);
+                }
+                walk_expression(&mut arm.body, f);
+            }
+        }
+        Expression::Function { body, .. } | Expression::Macro { body, .. } => {
+            walk_statement(body, f)
+        }
+        Expression::Call {
+            function,
+            arguments,
+        } => {
+            walk_expression(function, f);
+
+            for argument in arguments.iter_mut() {
+                walk_expression(argument, f);
+            }
+        }
+        Expression::Array(elements) => {
+            for element in elements.iter_mut() {
+                walk_expression(element, f);
+            }
+        }
+        Expression::Index { left, index } => {
+            walk_expression(left, f);
+            walk_expression(index, f);
+        }
+        Expression::Slice { left, start, end } => {
+            walk_expression(left, f);
+
+            for bound in start.iter_mut().chain(end.iter_mut()) {
+                walk_expression(bound, f);
+            }
+        }
+        Expression::Map(pairs) => {
+            // マップのキーは変更できないので値だけをたどる
+            let mut entries = core::mem::take(pairs).into_iter().collect::<Vec<_>>();
+
+            for (_, value) in entries.iter_mut() {
+                walk_expression(value, f);
+            }
+
+            *pairs = entries.into_iter().collect();
+        }
+        Expression::Identifier(_)
+        | Expression::Integer(_)
+        | Expression::Float(_)
+        | Expression::String(_)
+        | Expression::Boolean(_) => (),
+        #[cfg(feature = "rational")]
+        Expression::Rational(_) => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::Lexer;
+    use crate::mutate::mutants;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_mutants() {
+        let input = "let f = fn(x) { if (x > 0) { x - 1 } else { x + 1 } }; f(1) * 2";
+        let mut lexer = Lexer::new(input);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+
+        let mutants = mutants(&program)
+            .into_iter()
+            .map(|mutant| {
+                let statement = mutant.program.statements[0].to_string();
+                (mutant.description, statement)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            mutants,
+            vec![
+                (
+                    "if (x > 0) { (x - 1) } else { (x + 1) } -> if (!(x > 0)) { (x - 1) } else { (x + 1) }".to_string(),
+                    "let f = fn (x) { if (!(x > 0)) { (x - 1) } else { (x + 1) } };".to_string(),
+                ),
+                (
+                    "(x - 1) -> (x + 1)".to_string(),
+                    "let f = fn (x) { if (x > 0) { (x + 1) } else { (x + 1) } };".to_string(),
+                ),
+                (
+                    "(x + 1) -> (x - 1)".to_string(),
+                    "let f = fn (x) { if (x > 0) { (x - 1) } else { (x - 1) } };".to_string(),
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,574 @@
+use crate::error::EvalError;
+use crate::object::{Object, MAX_LENGTH};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+#[cfg(feature = "rational")]
+use core::convert::TryFrom;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+
+/// 浮動小数点数（マップやトークンの比較のために全順序で比較する）
+#[derive(Clone, Copy, Debug)]
+pub struct Float(pub f64);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Float {}
+
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Float {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Hash for Float {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl fmt::Display for Float {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format_float(self.0))
+    }
+}
+
+/// 既約分数で表した有理数（分母は常に正）
+#[cfg(feature = "rational")]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Rational {
+    numerator: isize,
+    denominator: isize,
+}
+
+#[cfg(feature = "rational")]
+impl Rational {
+    /// 約分して有理数をつくる
+    pub fn new(numerator: isize, denominator: isize) -> Result<Self, ArithmeticError> {
+        Self::reduce(numerator as i128, denominator as i128)
+    }
+
+    pub fn from_integer(value: isize) -> Self {
+        Self {
+            numerator: value,
+            denominator: 1,
+        }
+    }
+
+    pub fn numerator(self) -> isize {
+        self.numerator
+    }
+
+    pub fn denominator(self) -> isize {
+        self.denominator
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// 中間結果を i128 で受けとって約分し、isize に収まらなければオーバーフローにする
+    fn reduce(numerator: i128, denominator: i128) -> Result<Self, ArithmeticError> {
+        if denominator == 0 {
+            return Err(ArithmeticError::DivisionByZero);
+        }
+
+        let sign = if denominator < 0 { -1 } else { 1 };
+        let divisor = gcd(numerator.unsigned_abs(), denominator.unsigned_abs()) as i128;
+        let numerator = sign * numerator / divisor;
+        let denominator = sign * denominator / divisor;
+
+        match (isize::try_from(numerator), isize::try_from(denominator)) {
+            (Ok(numerator), Ok(denominator)) => Ok(Self {
+                numerator,
+                denominator,
+            }),
+            _ => Err(ArithmeticError::Overflow),
+        }
+    }
+
+    fn parts(self) -> (i128, i128) {
+        (self.numerator as i128, self.denominator as i128)
+    }
+}
+
+#[cfg(feature = "rational")]
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+
+    a.max(1)
+}
+
+#[cfg(feature = "rational")]
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "rational")]
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b) = self.parts();
+        let (c, d) = other.parts();
+        (a * d).cmp(&(c * b))
+    }
+}
+
+#[cfg(feature = "rational")]
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+/// 算術演算の失敗
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ArithmeticError {
+    Overflow,
+    DivisionByZero,
+}
+
+/// 評価器が算術演算と比較に使う数値型
+///
+/// 異なる型どうしの演算は [`Number::promote`] で同じ型にそろえてから行う
+pub trait Numeric: Copy + fmt::Display {
+    /// エラーメッセージに使う型の名前
+    const TYPE: &'static str;
+
+    fn add(self, other: Self) -> Result<Self, ArithmeticError>;
+    fn sub(self, other: Self) -> Result<Self, ArithmeticError>;
+    fn mul(self, other: Self) -> Result<Self, ArithmeticError>;
+    fn div(self, other: Self) -> Result<Self, ArithmeticError>;
+    fn neg(self) -> Result<Self, ArithmeticError>;
+
+    /// 比較できない値（NaN）どうしは None
+    fn compare(self, other: Self) -> Option<Ordering>;
+
+    /// `..` と `..=` の範囲（範囲をつくれない型は None、長さが [`MAX_LENGTH`] を超えればエラー）
+    fn range(self, _end: Self, _inclusive: bool) -> Option<Result<Vec<Self>, EvalError>> {
+        None
+    }
+
+    fn into_object(self) -> Object;
+}
+
+impl Numeric for isize {
+    const TYPE: &'static str = "Integer";
+
+    fn add(self, other: Self) -> Result<Self, ArithmeticError> {
+        self.checked_add(other).ok_or(ArithmeticError::Overflow)
+    }
+
+    fn sub(self, other: Self) -> Result<Self, ArithmeticError> {
+        self.checked_sub(other).ok_or(ArithmeticError::Overflow)
+    }
+
+    fn mul(self, other: Self) -> Result<Self, ArithmeticError> {
+        self.checked_mul(other).ok_or(ArithmeticError::Overflow)
+    }
+
+    fn div(self, other: Self) -> Result<Self, ArithmeticError> {
+        if other == 0 {
+            return Err(ArithmeticError::DivisionByZero);
+        }
+
+        self.checked_div(other).ok_or(ArithmeticError::Overflow)
+    }
+
+    fn neg(self) -> Result<Self, ArithmeticError> {
+        self.checked_neg().ok_or(ArithmeticError::Overflow)
+    }
+
+    fn compare(self, other: Self) -> Option<Ordering> {
+        Some(self.cmp(&other))
+    }
+
+    fn range(self, end: Self, inclusive: bool) -> Option<Result<Vec<Self>, EvalError>> {
+        let length = (end as i128 - self as i128 + inclusive as i128).max(0);
+
+        if length > MAX_LENGTH as i128 {
+            return Some(Err(EvalError::LengthLimit(MAX_LENGTH)));
+        }
+
+        match inclusive {
+            true => Some(Ok((self..=end).collect())),
+            false => Some(Ok((self..end).collect())),
+        }
+    }
+
+    fn into_object(self) -> Object {
+        Object::Integer(self)
+    }
+}
+
+/// 結果は約分し、isize に収まらなければオーバーフローにする
+#[cfg(feature = "rational")]
+impl Numeric for Rational {
+    const TYPE: &'static str = "Rational";
+
+    fn add(self, other: Self) -> Result<Self, ArithmeticError> {
+        let ((a, b), (c, d)) = (self.parts(), other.parts());
+        let numerator = (a * d).checked_add(c * b);
+        Self::reduce(numerator.ok_or(ArithmeticError::Overflow)?, b * d)
+    }
+
+    fn sub(self, other: Self) -> Result<Self, ArithmeticError> {
+        let ((a, b), (c, d)) = (self.parts(), other.parts());
+        let numerator = (a * d).checked_sub(c * b);
+        Self::reduce(numerator.ok_or(ArithmeticError::Overflow)?, b * d)
+    }
+
+    fn mul(self, other: Self) -> Result<Self, ArithmeticError> {
+        let ((a, b), (c, d)) = (self.parts(), other.parts());
+        Self::reduce(a * c, b * d)
+    }
+
+    fn div(self, other: Self) -> Result<Self, ArithmeticError> {
+        let ((a, b), (c, d)) = (self.parts(), other.parts());
+        Self::reduce(a * d, b * c)
+    }
+
+    fn neg(self) -> Result<Self, ArithmeticError> {
+        let (a, b) = self.parts();
+        Self::reduce(-a, b)
+    }
+
+    fn compare(self, other: Self) -> Option<Ordering> {
+        Some(self.cmp(&other))
+    }
+
+    fn into_object(self) -> Object {
+        Object::Rational(self)
+    }
+}
+
+/// IEEE 754 のとおり、0 での除算は無限大か NaN になる
+impl Numeric for Float {
+    const TYPE: &'static str = "Float";
+
+    fn add(self, other: Self) -> Result<Self, ArithmeticError> {
+        Ok(Float(self.0 + other.0))
+    }
+
+    fn sub(self, other: Self) -> Result<Self, ArithmeticError> {
+        Ok(Float(self.0 - other.0))
+    }
+
+    fn mul(self, other: Self) -> Result<Self, ArithmeticError> {
+        Ok(Float(self.0 * other.0))
+    }
+
+    fn div(self, other: Self) -> Result<Self, ArithmeticError> {
+        Ok(Float(self.0 / other.0))
+    }
+
+    fn neg(self) -> Result<Self, ArithmeticError> {
+        Ok(Float(-self.0))
+    }
+
+    fn compare(self, other: Self) -> Option<Ordering> {
+        self.0.partial_cmp(&other.0)
+    }
+
+    fn into_object(self) -> Object {
+        Object::Float(self)
+    }
+}
+
+/// 数値のオブジェクト
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Number {
+    Integer(isize),
+    #[cfg(feature = "rational")]
+    Rational(Rational),
+    Float(Float),
+}
+
+/// 同じ型にそろえた 2 つの数値
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pair {
+    Integer(isize, isize),
+    #[cfg(feature = "rational")]
+    Rational(Rational, Rational),
+    Float(Float, Float),
+}
+
+impl Number {
+    /// 数値でないオブジェクトは None
+    pub fn from_object(object: &Object) -> Option<Self> {
+        match object {
+            Object::Integer(value) => Some(Self::Integer(*value)),
+            #[cfg(feature = "rational")]
+            Object::Rational(value) => Some(Self::Rational(*value)),
+            Object::Float(value) => Some(Self::Float(*value)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn to_float(self) -> Float {
+        match self {
+            Self::Integer(value) => Float(value as f64),
+            #[cfg(feature = "rational")]
+            Self::Rational(value) => Float(value.to_f64()),
+            Self::Float(value) => value,
+        }
+    }
+
+    #[cfg(feature = "rational")]
+    fn to_rational(self) -> Option<Rational> {
+        match self {
+            Self::Integer(value) => Some(Rational::from_integer(value)),
+            Self::Rational(value) => Some(value),
+            Self::Float(_) => None,
+        }
+    }
+
+    /// 2 つの数値を階層の上の方の型にそろえる（Integer < Rational < Float）
+    pub fn promote(left: Self, right: Self) -> Pair {
+        if let (Self::Integer(left), Self::Integer(right)) = (left, right) {
+            return Pair::Integer(left, right);
+        }
+
+        #[cfg(feature = "rational")]
+        if let (Some(left), Some(right)) = (left.to_rational(), right.to_rational()) {
+            return Pair::Rational(left, right);
+        }
+
+        Pair::Float(left.to_float(), right.to_float())
+    }
+
+    /// 整数に丸める（Integer と Rational は Integer に、Float は Float のまま丸める）
+    pub fn round(self, rounding: Rounding) -> Object {
+        match self {
+            Self::Integer(value) => Object::Integer(value),
+            #[cfg(feature = "rational")]
+            Self::Rational(value) => {
+                let (numerator, denominator) = value.parts();
+                let rounded = match rounding {
+                    Rounding::Floor => numerator.div_euclid(denominator),
+                    Rounding::Ceil => -(-numerator).div_euclid(denominator),
+                    Rounding::Round if numerator < 0 => {
+                        -(-2 * numerator + denominator).div_euclid(2 * denominator)
+                    }
+                    Rounding::Round => (2 * numerator + denominator).div_euclid(2 * denominator),
+                };
+                Object::Integer(rounded as isize)
+            }
+            Self::Float(value) => Object::Float(Float(round_float(value.0, rounding))),
+        }
+    }
+}
+
+/// 丸めの方向
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rounding {
+    Floor,
+    Ceil,
+    /// 0.5 は 0 から遠い方へ
+    Round,
+}
+
+/// std なしでも使えるように、小数部をもちうる大きさ（2^52 未満）の値だけ整数部を取り出して丸める
+fn round_float(value: f64, rounding: Rounding) -> f64 {
+    const INTEGRAL: f64 = 4503599627370496.0;
+
+    if value.is_nan() || value.abs() >= INTEGRAL {
+        return value;
+    }
+
+    let truncated = value as i64 as f64;
+    let fraction = value - truncated;
+    let rounded = match rounding {
+        Rounding::Floor if fraction < 0.0 => truncated - 1.0,
+        Rounding::Ceil if fraction > 0.0 => truncated + 1.0,
+        Rounding::Round if fraction.abs() >= 0.5 => truncated + fraction.signum(),
+        _ => truncated,
+    };
+
+    rounded.copysign(value)
+}
+
+/// 浮動小数点数を読み戻せる最短の表現で文字列にする
+pub fn format_float(value: f64) -> String {
+    if value.is_nan() {
+        return "NaN".into();
+    }
+
+    if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.into();
+    }
+
+    format!("{:?}", value)
+}
+
+/// `1.5`、`2e10`、`1.5e-3` の形式の浮動小数点数を読む
+pub fn parse_float(literal: &str) -> Option<f64> {
+    let (mantissa, exponent) = match literal.find(['e', 'E']) {
+        Some(index) => (&literal[..index], Some(&literal[index + 1..])),
+        None => (literal, None),
+    };
+
+    let (integer, fraction) = match mantissa.find('.') {
+        Some(index) => (&mantissa[..index], Some(&mantissa[index + 1..])),
+        None => (mantissa, None),
+    };
+
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+
+    if !is_digits(integer) || fraction.is_some_and(|f| !is_digits(f)) {
+        return None;
+    }
+
+    if let Some(exponent) = exponent {
+        let digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+
+        if !is_digits(digits) {
+            return None;
+        }
+    } else if fraction.is_none() {
+        return None;
+    }
+
+    literal.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::numeric::{format_float, parse_float, Float, Number, Pair, Rounding};
+    use crate::object::Object;
+
+    #[test]
+    fn test_promote() {
+        let tests = vec![
+            (Number::Integer(1), Number::Integer(2), Pair::Integer(1, 2)),
+            (
+                Number::Integer(1),
+                Number::Float(Float(2.5)),
+                Pair::Float(Float(1.0), Float(2.5)),
+            ),
+            (
+                Number::Float(Float(0.5)),
+                Number::Integer(3),
+                Pair::Float(Float(0.5), Float(3.0)),
+            ),
+        ];
+
+        for (left, right, expected) in tests {
+            assert_eq!(Number::promote(left, right), expected);
+        }
+    }
+
+    #[test]
+    fn test_round() {
+        let tests = vec![
+            (2.5, [2.0, 3.0, 3.0]),
+            (-2.5, [-3.0, -2.0, -3.0]),
+            (-2.4, [-3.0, -2.0, -2.0]),
+            (3.0, [3.0, 3.0, 3.0]),
+            (0.3, [0.0, 1.0, 0.0]),
+            (1e300, [1e300, 1e300, 1e300]),
+            (f64::NEG_INFINITY, [f64::NEG_INFINITY; 3]),
+        ];
+        let roundings = [Rounding::Floor, Rounding::Ceil, Rounding::Round];
+
+        for (value, expected) in tests {
+            for (rounding, expected) in roundings.iter().zip(expected.iter()) {
+                assert_eq!(
+                    Number::Float(Float(value)).round(*rounding),
+                    Object::Float(Float(*expected)),
+                    "{:?}({})",
+                    rounding,
+                    value
+                );
+            }
+        }
+
+        assert_eq!(
+            Number::Float(Float(-0.3)).round(Rounding::Round),
+            Object::Float(Float(-0.0))
+        );
+        assert_eq!(
+            Number::Integer(7).round(Rounding::Floor),
+            Object::Integer(7)
+        );
+    }
+
+    #[cfg(feature = "rational")]
+    #[test]
+    fn test_round_rational() {
+        use crate::numeric::Rational;
+
+        let tests = vec![
+            ((5, 2), [2, 3, 3]),
+            ((-5, 2), [-3, -2, -3]),
+            ((-7, 3), [-3, -2, -2]),
+            ((4, 1), [4, 4, 4]),
+        ];
+        let roundings = [Rounding::Floor, Rounding::Ceil, Rounding::Round];
+
+        for ((numerator, denominator), expected) in tests {
+            let value = Number::Rational(Rational::new(numerator, denominator).unwrap());
+
+            for (rounding, expected) in roundings.iter().zip(expected.iter()) {
+                assert_eq!(value.round(*rounding), Object::Integer(*expected));
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_float() {
+        let tests = vec![
+            (0.1 + 0.2, "0.30000000000000004"),
+            (1.0, "1.0"),
+            (-2.5, "-2.5"),
+            (1e300, "1e300"),
+            (1.5e-10, "1.5e-10"),
+            (f64::NAN, "NaN"),
+            (f64::INFINITY, "inf"),
+            (f64::NEG_INFINITY, "-inf"),
+        ];
+
+        for (value, expected) in tests {
+            assert_eq!(format_float(value), expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_float() {
+        let tests = vec![
+            ("1.5", Some(1.5)),
+            ("0.1", Some(0.1)),
+            ("2e10", Some(2e10)),
+            ("1.5e-3", Some(1.5e-3)),
+            ("1E+2", Some(100.0)),
+            ("1", None),
+            ("1.", None),
+            (".5", None),
+            ("1e", None),
+            ("1.5e+", None),
+        ];
+
+        for (literal, expected) in tests {
+            assert_eq!(parse_float(literal), expected);
+        }
+
+        for value in [0.1 + 0.2, 1.0, 1e300, 1.5e-10, 123456.789] {
+            assert_eq!(parse_float(&format_float(value)), Some(value));
+        }
+    }
+}
@@ -1,13 +1,29 @@
 use crate::ast::{Expression, Statement};
-use crate::evaluator::{Environment, EvalResult};
-use std::collections::BTreeMap;
-use std::fmt;
+use crate::evaluator::{Env, EvalResult, Evaluator, Generator};
+use crate::numeric::Float;
+#[cfg(feature = "rational")]
+use crate::numeric::Rational;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "date")]
+use chrono::NaiveDate;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
 
 /// オブジェクト
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Object {
     /// 整数
     Integer(isize),
+    /// 浮動小数点数
+    Float(Float),
+    /// 有理数
+    #[cfg(feature = "rational")]
+    Rational(Rational),
     /// 真偽値
     Boolean(bool),
     /// 文字列
@@ -20,16 +36,39 @@ pub enum Object {
     Function {
         parameters: Vec<Expression>,
         body: Statement,
-        env: Environment,
+        env: Env,
     },
     /// 組み込み関数
-    Buildin {
-        function: fn(Vec<Object>) -> EvalResult,
+    Buildin { function: Buildin },
+    /// 先頭の引数を束縛した関数
+    Bound {
+        function: Box<Object>,
+        arguments: Vec<Object>,
     },
     /// 配列
     Array(Vec<Object>),
     /// マップ
     Map(BTreeMap<MapKey, MapPair>),
+    /// quote された式
+    Quote(Expression),
+    /// マクロ
+    Macro {
+        parameters: Vec<Expression>,
+        body: Statement,
+        env: Env,
+    },
+    /// ジェネレーター
+    Generator(Generator),
+    /// エラー値（評価を止めずに失敗を返す）
+    Error(String),
+    /// 日付
+    #[cfg(feature = "date")]
+    Date(NaiveDate),
+    /// 日数で表した期間
+    #[cfg(feature = "date")]
+    Interval(isize),
+    /// `open` で開いたファイルのハンドル
+    File(usize),
     /// let
     Let,
     /// デフォルト
@@ -40,6 +79,9 @@ impl fmt::Display for Object {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Integer(value) => write!(f, "{}", value),
+            Self::Float(value) => write!(f, "{}", value),
+            #[cfg(feature = "rational")]
+            Self::Rational(value) => write!(f, "{}", value),
             Self::Boolean(value) => write!(f, "{}", value),
             Self::String(value) => write!(f, "{}", value),
             Self::Null => write!(f, "null"),
@@ -54,12 +96,21 @@ impl fmt::Display for Object {
             }
             Self::Map(pairs) => {
                 let pairs = pairs
-                    .iter()
-                    .map(|(_, pair)| pair.to_string())
+                    .values()
+                    .map(|pair| pair.to_string())
                     .collect::<Vec<_>>()
                     .join(", ");
                 write!(f, "{{{}}}", pairs)
             }
+            Self::Quote(expression) => write!(f, "QUOTE({})", expression),
+            Self::Error(message) => write!(f, "ERROR: {}", message),
+            #[cfg(feature = "date")]
+            Self::Date(date) => write!(f, "{}", date),
+            #[cfg(feature = "date")]
+            Self::Interval(days @ (1 | -1)) => write!(f, "{} day", days),
+            #[cfg(feature = "date")]
+            Self::Interval(days) => write!(f, "{} days", days),
+            Self::File(id) => write!(f, "<file #{}>", id),
             _ => write!(f, ""),
         }
     }
@@ -69,37 +120,145 @@ impl Object {
     pub fn get_type(&self) -> String {
         match self {
             Self::Integer(_) => "Integer".to_string(),
+            Self::Float(_) => "Float".to_string(),
+            #[cfg(feature = "rational")]
+            Self::Rational(_) => "Rational".to_string(),
             Self::Boolean(_) => "Boolean".to_string(),
             Self::String(_) => "String".to_string(),
             Self::Null => "null".to_string(),
             Self::Function { .. } => "Function".to_string(),
             Self::Buildin { .. } => "Buildin Function".to_string(),
+            Self::Bound { function, .. } => function.get_type(),
             Self::Array(_) => "Array".to_string(),
+            Self::Map(_) => "Map".to_string(),
+            Self::Quote(_) => "Quote".to_string(),
+            Self::Macro { .. } => "Macro".to_string(),
+            Self::Generator(_) => "Generator".to_string(),
+            Self::Error(_) => "Error".to_string(),
+            #[cfg(feature = "date")]
+            Self::Date(_) => "Date".to_string(),
+            #[cfg(feature = "date")]
+            Self::Interval(_) => "Interval".to_string(),
+            Self::File(_) => "File".to_string(),
             _ => "".to_string(),
         }
     }
 }
 
+/// 組み込み関数の本体
+pub type BuildinFunction = dyn Fn(&mut Evaluator, Vec<Object>) -> EvalResult;
+
+/// 名前のついた組み込み関数の本体。ホストの状態を捕まえたクロージャーも登録できる
+/// （比較とハッシュは名前と参照先の同一性で行う）
+#[derive(Clone)]
+pub struct Buildin {
+    name: Rc<str>,
+    function: Rc<BuildinFunction>,
+}
+
+impl Buildin {
+    pub fn new(
+        name: &str,
+        function: impl Fn(&mut Evaluator, Vec<Object>) -> EvalResult + 'static,
+    ) -> Self {
+        Self {
+            name: Rc::from(name),
+            function: Rc::new(function),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn call(&self, evaluator: &mut Evaluator, arguments: Vec<Object>) -> EvalResult {
+        (self.function)(evaluator, arguments)
+    }
+
+    fn address(&self) -> *const () {
+        Rc::as_ptr(&self.function) as *const ()
+    }
+}
+
+impl fmt::Debug for Buildin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Buildin({})", self.name)
+    }
+}
+
+impl PartialEq for Buildin {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Buildin {}
+
+impl PartialOrd for Buildin {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Buildin {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.name, self.address()).cmp(&(&other.name, other.address()))
+    }
+}
+
+impl Hash for Buildin {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.address().hash(state);
+    }
+}
+
+/// 範囲や繰り返しでつくる配列の要素の数と文字列のバイト数の上限
+pub const MAX_LENGTH: usize = 1 << 22;
+
+/// 配列をマップのキーにするときの入れ子の深さの上限
+pub const MAX_MAP_KEY_DEPTH: usize = 8;
+
 /// マップのキー
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum MapKey {
     Integer(isize),
     Boolean(bool),
     String(String),
+    /// 要素がすべてキーとして使える配列
+    Array(Vec<MapKey>),
     Unusable,
 }
 
-impl From<&Object> for MapKey {
-    fn from(object: &Object) -> Self {
+impl MapKey {
+    fn from_object(object: &Object, depth: usize) -> Self {
         match object {
-            Object::Integer(value) => MapKey::Integer(value.clone()),
-            Object::Boolean(value) => MapKey::Boolean(value.clone()),
+            Object::Integer(value) => MapKey::Integer(*value),
+            Object::Boolean(value) => MapKey::Boolean(*value),
             Object::String(value) => MapKey::String(value.clone()),
+            Object::Array(elements) if depth < MAX_MAP_KEY_DEPTH => {
+                let mut keys = Vec::with_capacity(elements.len());
+
+                for element in elements.iter() {
+                    match Self::from_object(element, depth + 1) {
+                        MapKey::Unusable => return MapKey::Unusable,
+                        key => keys.push(key),
+                    }
+                }
+
+                MapKey::Array(keys)
+            }
             _ => MapKey::Unusable,
         }
     }
 }
 
+impl From<&Object> for MapKey {
+    fn from(object: &Object) -> Self {
+        Self::from_object(object, 0)
+    }
+}
+
 /// マップの値
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct MapPair {
@@ -121,7 +280,7 @@ impl fmt::Display for MapPair {
 
 #[cfg(test)]
 mod tests {
-    use crate::object::MapKey;
+    use crate::object::{MapKey, Object, MAX_MAP_KEY_DEPTH};
 
     #[test]
     fn test_string_map_key() {
@@ -134,4 +293,23 @@ mod tests {
         assert!(diff1 == diff2);
         assert!(hello1 != diff2);
     }
+
+    #[test]
+    fn test_array_map_key() {
+        let point1 = Object::Array(vec![Object::Integer(1), Object::Integer(2)]);
+        let point2 = Object::Array(vec![Object::Integer(1), Object::Integer(2)]);
+        let point3 = Object::Array(vec![Object::Integer(2), Object::Integer(1)]);
+
+        assert!(MapKey::from(&point1) == MapKey::from(&point2));
+        assert!(MapKey::from(&point1) != MapKey::from(&point3));
+
+        let unusable = Object::Array(vec![Object::Integer(1), Object::Null]);
+        assert_eq!(MapKey::from(&unusable), MapKey::Unusable);
+
+        let mut nested = Object::Array(vec![]);
+        for _ in 0..MAX_MAP_KEY_DEPTH {
+            nested = Object::Array(vec![nested]);
+        }
+        assert_eq!(MapKey::from(&nested), MapKey::Unusable);
+    }
 }
@@ -0,0 +1,375 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// ライブラリを置くディレクトリ
+pub const MODULES_DIR: &str = "monkey_modules";
+
+/// ロックファイルの名前
+pub const LOCK_FILE: &str = "monkey.lock";
+
+const LOCK_HEADER: &str = "ronkey-lock 1";
+
+/// ロックファイルに記録するライブラリ
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Locked {
+    /// `monkey_modules` の下のディレクトリ名
+    pub name: String,
+    /// 取得元（git の URL かローカルのパス）
+    pub source: String,
+    /// git のコミット、またはローカルのソースの内容のハッシュ
+    pub revision: String,
+}
+
+/// ロックファイル
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Lock {
+    pub packages: Vec<Locked>,
+}
+
+impl Lock {
+    /// プロジェクトのロックファイルを読みこむ（なければ空）
+    pub fn load(root: &Path) -> io::Result<Self> {
+        let path = root.join(LOCK_FILE);
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let text = fs::read_to_string(path)?;
+        Self::decode(&text).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    pub fn save(&self, root: &Path) -> io::Result<()> {
+        fs::write(root.join(LOCK_FILE), self.encode())
+    }
+
+    pub fn encode(&self) -> String {
+        let mut text = format!("{}\n", LOCK_HEADER);
+
+        for package in self.packages.iter() {
+            text.push_str(&format!(
+                "{}\t{}\t{}\n",
+                package.name, package.source, package.revision
+            ));
+        }
+
+        text
+    }
+
+    pub fn decode(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+
+        if lines.next() != Some(LOCK_HEADER) {
+            let message = format!("expected lockfile header: {}", LOCK_HEADER);
+            return Err(message);
+        }
+
+        let mut packages = vec![];
+
+        for (i, line) in lines.enumerate() {
+            let fields = line.split('\t').collect::<Vec<_>>();
+
+            match fields.as_slice() {
+                [name, source, revision] => packages.push(Locked {
+                    name: name.to_string(),
+                    source: source.to_string(),
+                    revision: revision.to_string(),
+                }),
+                _ => {
+                    let message = format!("line {}: expected name, source and revision", i + 2);
+                    return Err(message);
+                }
+            }
+        }
+
+        Ok(Self { packages })
+    }
+
+    /// 同じ名前の記録を置きかえて追加する
+    pub fn insert(&mut self, locked: Locked) {
+        self.packages.retain(|package| package.name != locked.name);
+        self.packages.push(locked);
+        self.packages.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+}
+
+/// 取得元からライブラリ名を決める（`https://host/user/lib.git` なら `lib`）
+pub fn package_name(source: &str) -> Option<String> {
+    let name = source
+        .trim_end_matches('/')
+        .rsplit(['/', '\\', ':'])
+        .next()?;
+    let name = name.strip_suffix(".git").unwrap_or(name);
+
+    if name.is_empty() || name == "." || name == ".." {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+fn is_git_source(source: &str) -> bool {
+    source.ends_with(".git")
+        || ["https://", "http://", "git://", "ssh://", "git@"]
+            .iter()
+            .any(|scheme| source.starts_with(scheme))
+}
+
+/// ライブラリを `monkey_modules` に取りこみ、ロックファイルに記録する。
+/// `revision` を指定すると、その版でなければエラーにする
+pub fn add(root: &Path, source: &str, revision: Option<&str>) -> io::Result<Locked> {
+    let name = package_name(source).ok_or_else(|| {
+        let message = format!("cannot derive a package name from {}", source);
+        io::Error::new(io::ErrorKind::InvalidInput, message)
+    })?;
+    let target = root.join(MODULES_DIR).join(&name);
+
+    if target.exists() {
+        fs::remove_dir_all(&target)?;
+    }
+
+    let actual = if is_git_source(source) {
+        fetch_git(source, &target, revision)?
+    } else {
+        copy_sources(Path::new(source), &target)?;
+        content_hash(&target)?
+    };
+
+    if let Some(revision) = revision {
+        if revision != actual {
+            let message = format!(
+                "{} resolved to {}, but the lockfile expects {}",
+                source, actual, revision
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+    }
+
+    let locked = Locked {
+        name,
+        source: source.to_string(),
+        revision: actual,
+    };
+
+    let mut lock = Lock::load(root)?;
+    lock.insert(locked.clone());
+    lock.save(root)?;
+
+    Ok(locked)
+}
+
+/// ロックファイルに記録したライブラリを記録した版で取りこみ直す
+pub fn install(root: &Path) -> io::Result<Vec<Locked>> {
+    let lock = Lock::load(root)?;
+
+    lock.packages
+        .iter()
+        .map(|package| add(root, &package.source, Some(&package.revision)))
+        .collect()
+}
+
+/// `monkey_modules` の下の各ライブラリのディレクトリ（インポートの探索パスに加える）
+pub fn module_paths(root: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = root.join(MODULES_DIR);
+
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut paths = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    Ok(paths)
+}
+
+fn git(arguments: &[&str], dir: Option<&Path>) -> io::Result<String> {
+    let mut command = Command::new("git");
+    command.args(arguments);
+
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+
+    let output = command.output()?;
+
+    if !output.status.success() {
+        let message = format!(
+            "git {} failed: {}",
+            arguments.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        return Err(io::Error::other(message));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// git リポジトリを取得し、チェックアウトしたコミットを返す
+fn fetch_git(source: &str, target: &Path, revision: Option<&str>) -> io::Result<String> {
+    let target_str = target.to_string_lossy();
+
+    match revision {
+        Some(revision) => {
+            git(&["clone", "--quiet", source, &target_str], None)?;
+            git(&["checkout", "--quiet", revision], Some(target))?;
+        }
+        None => git(
+            &["clone", "--quiet", "--depth", "1", source, &target_str],
+            None,
+        )
+        .map(|_| ())?,
+    }
+
+    let commit = git(&["rev-parse", "HEAD"], Some(target))?;
+    fs::remove_dir_all(target.join(".git"))?;
+
+    Ok(commit)
+}
+
+/// ローカルのディレクトリから `.monkey` ファイルだけを（ディレクトリ構造ごと）写す
+fn copy_sources(source: &Path, target: &Path) -> io::Result<()> {
+    if !source.is_dir() {
+        let message = format!("not a directory: {}", source.display());
+        return Err(io::Error::new(io::ErrorKind::NotFound, message));
+    }
+
+    fs::create_dir_all(target)?;
+
+    for entry in fs::read_dir(source)? {
+        let path = entry?.path();
+        let destination = target.join(path.file_name().unwrap_or_default());
+
+        if path.is_dir() {
+            copy_sources(&path, &destination)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "monkey")
+        {
+            fs::copy(&path, &destination)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// ディレクトリの中身（相対パスと内容）の FNV-1a ハッシュ
+fn content_hash(dir: &Path) -> io::Result<String> {
+    let mut files = vec![];
+    collect_files(dir, dir, &mut files)?;
+    files.sort();
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    for (relative, path) in files {
+        for byte in relative
+            .bytes()
+            .chain([0])
+            .chain(fs::read(path)?)
+            .chain([0])
+        {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    Ok(format!("fnv1a-{:016x}", hash))
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            files.push((relative, path));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::Manifest;
+    use crate::package::{add, install, module_paths, package_name, Lock, Locked, MODULES_DIR};
+    use std::fs;
+
+    #[test]
+    fn test_package_name() {
+        let tests = vec![
+            ("https://github.com/user/lib.git", Some("lib")),
+            ("git@github.com:user/strings.git", Some("strings")),
+            ("../libs/math/", Some("math")),
+            ("..", None),
+        ];
+
+        for (source, expected) in tests {
+            assert_eq!(package_name(source).as_deref(), expected);
+        }
+    }
+
+    #[test]
+    fn test_lock() {
+        let lock = Lock {
+            packages: vec![Locked {
+                name: "math".to_string(),
+                source: "../math".to_string(),
+                revision: "fnv1a-0123456789abcdef".to_string(),
+            }],
+        };
+
+        assert_eq!(Lock::decode(&lock.encode()), Ok(lock));
+        assert_eq!(
+            Lock::decode("ronkey-lock 1\nmath"),
+            Err("line 2: expected name, source and revision".to_string())
+        );
+    }
+
+    #[test]
+    fn test_add_and_install() {
+        let base = std::env::temp_dir().join(format!("ronkey-package-{}", std::process::id()));
+        let library = base.join("math");
+        let project = base.join("project");
+        fs::create_dir_all(library.join("src")).unwrap();
+        fs::create_dir_all(&project).unwrap();
+        fs::write(library.join("src").join("add.monkey"), "let add = 1;").unwrap();
+        fs::write(library.join("README.md"), "ignored").unwrap();
+
+        let source = library.to_string_lossy().to_string();
+        let locked = add(&project, &source, None).unwrap();
+        let vendored = project.join(MODULES_DIR).join("math");
+
+        assert_eq!(locked.name, "math");
+        assert!(vendored.join("src").join("add.monkey").exists());
+        assert!(!vendored.join("README.md").exists());
+        assert_eq!(module_paths(&project).unwrap(), vec![vendored.clone()]);
+
+        let manifest = Manifest {
+            import_paths: vec!["lib".to_string()],
+            ..Manifest::default()
+        };
+        assert_eq!(
+            manifest.search_paths(&project).unwrap(),
+            vec![project.join("lib"), vendored.clone()]
+        );
+        assert_eq!(Lock::load(&project).unwrap().packages, vec![locked.clone()]);
+
+        fs::remove_dir_all(&vendored).unwrap();
+        assert_eq!(install(&project).unwrap(), vec![locked]);
+        assert!(vendored.join("src").join("add.monkey").exists());
+
+        fs::write(library.join("src").join("add.monkey"), "let add = 2;").unwrap();
+        assert!(install(&project).is_err());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -1,7 +1,13 @@
-use crate::ast::{Expression, Program, Statement};
+use crate::ast::{Expression, MatchArm, Pattern, Program, Statement};
 use crate::lexer::Lexer;
-use crate::token::Token;
-use std::collections::BTreeMap;
+use crate::symbol::Symbol;
+use crate::token::{Position, Token};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
 
 /// 構文解析エラー
 type ParseError = String;
@@ -10,6 +16,12 @@ type ParseError = String;
 #[derive(Debug, PartialEq, PartialOrd)]
 enum Precedence {
     Lowest,
+    /// x ? y : z
+    Ternary,
+    /// ??
+    Coalesce,
+    /// .. ..=
+    Range,
     /// ==
     Equals,
     /// > <
@@ -29,12 +41,15 @@ enum Precedence {
 impl From<Token> for Precedence {
     fn from(token: Token) -> Self {
         match token {
+            Token::Question => Self::Ternary,
+            Token::Coalesce => Self::Coalesce,
+            Token::DotDot | Token::DotDotEq => Self::Range,
             Token::Eq | Token::Ne => Self::Equals,
             Token::Lt | Token::Gt => Self::LessGreater,
             Token::Plus | Token::Minus => Self::Sum,
             Token::Slash | Token::Asterisk => Self::Product,
             Token::LParen => Self::Call,
-            Token::LBracket => Self::Index,
+            Token::LBracket | Token::Dot => Self::Index,
             _ => Self::Lowest,
         }
     }
@@ -45,6 +60,9 @@ pub struct Parser<'a> {
     lexer: &'a mut Lexer,
     current_token: Token,
     peek_token: Token,
+    /// 現在のトークンと次のトークンの始まりの位置
+    current_position: Position,
+    peek_position: Position,
     errors: Vec<ParseError>,
 }
 
@@ -54,6 +72,8 @@ impl<'a> Parser<'a> {
             lexer,
             current_token: Token::Eof,
             peek_token: Token::Eof,
+            current_position: Position::default(),
+            peek_position: Position::default(),
             errors: vec![],
         };
 
@@ -63,7 +83,7 @@ impl<'a> Parser<'a> {
     }
 
     pub fn exists_errors(&mut self) -> bool {
-        self.errors.len() > 0
+        !self.errors.is_empty()
     }
 
     pub fn get_errors(&mut self) -> Vec<String> {
@@ -74,7 +94,11 @@ impl<'a> Parser<'a> {
         let mut program = Program::new();
 
         while !self.is_current_token(&Token::Eof) {
+            let errors = self.errors.len();
+
             match self.parse_statement() {
+                // 解析を続けるために代わりの値を置いた文は、プログラムに含めない
+                Ok(_) if self.errors.len() > errors => (),
                 Ok(statement) => program.statements.push(statement),
                 Err(error) => self.errors.push(error),
             }
@@ -87,25 +111,67 @@ impl<'a> Parser<'a> {
 
     fn next_token(&mut self) {
         self.current_token = self.peek_token.clone();
+        self.current_position = self.peek_position;
         self.peek_token = self.lexer.next_token();
+        self.peek_position = self.lexer.position();
     }
 
     fn parse_statement(&mut self) -> Result<Statement, ParseError> {
         match self.current_token {
             Token::Let => self.parse_let_statement(),
             Token::Return => self.parse_return_statement(),
+            Token::Import => self.parse_import_statement(),
             _ => self.parse_expression_statement(),
         }
     }
 
     fn parse_let_statement(&mut self) -> Result<Statement, ParseError> {
+        let position = self.current_position;
         let name = Expression::Identifier(self.expect_peek_identifier()?);
 
         self.expect_peek(&Token::Assign)?;
         self.next_token();
 
         let value = self.parse_expression(Precedence::Lowest)?;
-        let statement = Statement::Let { name, value };
+        let statement = Statement::Let {
+            name,
+            value,
+            position,
+        };
+
+        while self.is_peek_token(&Token::Semicolon) {
+            self.next_token();
+        }
+
+        Ok(statement)
+    }
+
+    /// `import "path" as name` を `let name = import("path")` として読む
+    fn parse_import_statement(&mut self) -> Result<Statement, ParseError> {
+        let position = self.current_position;
+        let path = match &self.peek_token {
+            Token::String(path) => path.to_string(),
+            _ => {
+                return Err(format!(
+                    "expected next token to be String, got {} instead",
+                    &self.peek_token
+                ))
+            }
+        };
+
+        self.next_token();
+        self.expect_peek(&Token::As)?;
+
+        let name = Expression::Identifier(self.expect_peek_identifier()?);
+        let value = Expression::Call {
+            function: Box::new(Expression::Identifier(self.lexer.intern("import"))),
+            arguments: vec![Expression::String(path)],
+        };
+        let statement = Statement::Let {
+            name,
+            value,
+            position,
+        };
 
         while self.is_peek_token(&Token::Semicolon) {
             self.next_token();
@@ -115,10 +181,11 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_return_statement(&mut self) -> Result<Statement, ParseError> {
+        let position = self.current_position;
         self.next_token();
 
         let expression = self.parse_expression(Precedence::Lowest)?;
-        let statement = Statement::Return(expression);
+        let statement = Statement::Return(expression, position);
 
         while self.is_peek_token(&Token::Semicolon) {
             self.next_token();
@@ -128,8 +195,9 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_expression_statement(&mut self) -> Result<Statement, ParseError> {
+        let position = self.current_position;
         let expression = self.parse_expression(Precedence::Lowest)?;
-        let statement = Statement::Expression(expression);
+        let statement = Statement::Expression(expression, position);
 
         while self.is_peek_token(&Token::Semicolon) {
             self.next_token();
@@ -156,20 +224,41 @@ impl<'a> Parser<'a> {
     fn parse_expression(&mut self, precedence: Precedence) -> Result<Expression, ParseError> {
         let mut expression = match &self.current_token {
             Token::Identifier(value) => Expression::Identifier(value.clone()),
-            Token::Integer(value) => Expression::Integer(value.clone()),
+            Token::Integer(value) => Expression::Integer(*value),
+            Token::Float(value) => Expression::Float(*value),
+            #[cfg(feature = "rational")]
+            Token::Rational(value) => Expression::Rational(*value),
             Token::String(value) => Expression::String(value.clone()),
             Token::Bang | Token::Minus => self.parse_prefix_expression()?,
             Token::True => Expression::Boolean(true),
             Token::False => Expression::Boolean(false),
             Token::LParen => self.parse_grouped_expression()?,
             Token::If => self.parse_if_expression()?,
+            Token::Try => self.parse_try_expression()?,
+            Token::Match => self.parse_match_expression()?,
             Token::Function => self.parse_function_expression()?,
+            Token::Macro => self.parse_macro_expression()?,
+            Token::Yield => self.parse_yield_expression()?,
             Token::LBracket => self.parse_array_expression()?,
             Token::LBrace => self.parse_map_expression()?,
             Token::Illegal(value) => {
                 let message = format!("illegal char found: {}", value);
                 return Err(message);
             }
+            Token::OutOfRange {
+                literal,
+                line,
+                column,
+            } => {
+                // エラーを記録したうえで代わりの値を置いて解析を続け、後ろのトークンで
+                // エラーが連鎖しないようにする
+                let message = format!(
+                    "integer literal out of range: {} at line {}, column {}",
+                    literal, line, column
+                );
+                self.errors.push(message);
+                Expression::Integer(0)
+            }
             _ => {
                 let message = format!("no prefix parse function for {} found", self.current_token);
                 return Err(message);
@@ -191,7 +280,10 @@ impl<'a> Parser<'a> {
                 | &Token::Lt
                 | &Token::Gt
                 | &Token::Eq
-                | &Token::Ne => {
+                | &Token::Ne
+                | &Token::DotDot
+                | &Token::DotDotEq
+                | &Token::Coalesce => {
                     self.next_token();
                     self.parse_infix_expression(expression)?
                 }
@@ -199,6 +291,14 @@ impl<'a> Parser<'a> {
                     self.next_token();
                     self.parse_index_expression(expression)?
                 }
+                &Token::Dot => {
+                    self.next_token();
+                    self.parse_dot_expression(expression)?
+                }
+                &Token::Question => {
+                    self.next_token();
+                    self.parse_ternary_expression(expression)?
+                }
                 &Token::Illegal(value) => {
                     let message = format!("illegal char found: {}", value);
                     return Err(message);
@@ -224,6 +324,15 @@ impl<'a> Parser<'a> {
         Ok(expression)
     }
 
+    fn parse_yield_expression(&mut self) -> Result<Expression, ParseError> {
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+        let expression = Expression::Yield(Box::new(value));
+
+        Ok(expression)
+    }
+
     fn parse_infix_expression(&mut self, left: Expression) -> Result<Expression, ParseError> {
         let operator = self.current_token.clone();
         let precedence = Precedence::from(self.current_token.clone());
@@ -279,6 +388,192 @@ impl<'a> Parser<'a> {
         Ok(expression)
     }
 
+    /// `x ? y : z` を `if (x) { y } else { z }` として読む（右結合）
+    fn parse_ternary_expression(
+        &mut self,
+        condition: Expression,
+    ) -> Result<Expression, ParseError> {
+        self.next_token();
+
+        let consequence_position = self.current_position;
+        let consequence = self.parse_expression(Precedence::Lowest)?;
+
+        self.expect_peek(&Token::Colon)?;
+        self.next_token();
+
+        let alternative_position = self.current_position;
+        let alternative = self.parse_expression(Precedence::Lowest)?;
+        let expression = Expression::If {
+            condition: Box::new(condition),
+            consequence: Box::new(Statement::Block(vec![Statement::Expression(
+                consequence,
+                consequence_position,
+            )])),
+            alternative: Some(Box::new(Statement::Block(vec![Statement::Expression(
+                alternative,
+                alternative_position,
+            )]))),
+        };
+
+        Ok(expression)
+    }
+
+    fn parse_try_expression(&mut self) -> Result<Expression, ParseError> {
+        self.expect_peek(&Token::LBrace)?;
+
+        let body = self.parse_block_statement()?;
+
+        self.expect_peek(&Token::Catch)?;
+        self.expect_peek(&Token::LParen)?;
+
+        let parameter = Expression::Identifier(self.expect_peek_identifier()?);
+
+        self.expect_peek(&Token::RParen)?;
+        self.expect_peek(&Token::LBrace)?;
+
+        let handler = self.parse_block_statement()?;
+        let expression = Expression::Try {
+            body: Box::new(body),
+            parameter: Box::new(parameter),
+            handler: Box::new(handler),
+        };
+
+        Ok(expression)
+    }
+
+    fn parse_match_expression(&mut self) -> Result<Expression, ParseError> {
+        self.expect_peek(&Token::LParen)?;
+        self.next_token();
+
+        let subject = self.parse_expression(Precedence::Lowest)?;
+
+        self.expect_peek(&Token::RParen)?;
+        self.expect_peek(&Token::LBrace)?;
+
+        let mut arms = vec![];
+
+        while !self.is_peek_token(&Token::RBrace) {
+            self.next_token();
+
+            let pattern = self.parse_pattern()?;
+            let guard = if self.is_peek_token(&Token::If) {
+                self.next_token();
+                self.next_token();
+                Some(self.parse_expression(Precedence::Lowest)?)
+            } else {
+                None
+            };
+
+            self.expect_peek(&Token::FatArrow)?;
+            self.next_token();
+
+            let body = self.parse_expression(Precedence::Lowest)?;
+
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+            });
+
+            if !self.is_peek_token(&Token::RBrace) {
+                self.expect_peek(&Token::Comma)?;
+            }
+        }
+
+        self.expect_peek(&Token::RBrace)?;
+
+        let expression = Expression::Match {
+            subject: Box::new(subject),
+            arms,
+        };
+
+        Ok(expression)
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let pattern = match &self.current_token {
+            Token::Identifier(name) if &**name == "_" => Pattern::Wildcard,
+            Token::Identifier(name) => Pattern::Binding(name.clone()),
+            Token::Integer(value) => Pattern::Literal(Expression::Integer(*value)),
+            Token::Float(value) => Pattern::Literal(Expression::Float(*value)),
+            #[cfg(feature = "rational")]
+            Token::Rational(value) => Pattern::Literal(Expression::Rational(*value)),
+            Token::String(value) => Pattern::Literal(Expression::String(value.clone())),
+            Token::True => Pattern::Literal(Expression::Boolean(true)),
+            Token::False => Pattern::Literal(Expression::Boolean(false)),
+            Token::Minus if matches!(self.peek_token, Token::Integer(_) | Token::Float(_)) => {
+                Pattern::Literal(self.parse_prefix_expression()?)
+            }
+            Token::LBracket => self.parse_array_pattern()?,
+            Token::LBrace => self.parse_map_pattern()?,
+            _ => {
+                let message = format!("no pattern parse function for {} found", self.current_token);
+                return Err(message);
+            }
+        };
+
+        Ok(pattern)
+    }
+
+    fn parse_array_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let mut elements = vec![];
+        let mut rest = None;
+
+        while !self.is_peek_token(&Token::RBracket) {
+            self.next_token();
+
+            if self.is_current_token(&Token::Ellipsis) {
+                rest = Some(self.expect_peek_identifier()?);
+                break;
+            }
+
+            elements.push(self.parse_pattern()?);
+
+            if !self.is_peek_token(&Token::RBracket) {
+                self.expect_peek(&Token::Comma)?;
+            }
+        }
+
+        self.expect_peek(&Token::RBracket)?;
+
+        Ok(Pattern::Array { elements, rest })
+    }
+
+    fn parse_map_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let mut entries = vec![];
+
+        while !self.is_peek_token(&Token::RBrace) {
+            self.next_token();
+
+            let key = match &self.current_token {
+                Token::Identifier(key) => key.to_string(),
+                Token::String(key) => key.clone(),
+                _ => {
+                    let message = format!("unexpected map pattern key: {}", self.current_token);
+                    return Err(message);
+                }
+            };
+
+            let pattern = if self.is_peek_token(&Token::Colon) {
+                self.next_token();
+                self.next_token();
+                self.parse_pattern()?
+            } else {
+                Pattern::Binding(self.lexer.intern(&key))
+            };
+
+            entries.push((key, pattern));
+
+            if !self.is_peek_token(&Token::RBrace) {
+                self.expect_peek(&Token::Comma)?;
+            }
+        }
+
+        self.expect_peek(&Token::RBrace)?;
+
+        Ok(Pattern::Map(entries))
+    }
+
     fn parse_function_expression(&mut self) -> Result<Expression, ParseError> {
         self.expect_peek(&Token::LParen)?;
 
@@ -295,6 +590,22 @@ impl<'a> Parser<'a> {
         Ok(expression)
     }
 
+    fn parse_macro_expression(&mut self) -> Result<Expression, ParseError> {
+        self.expect_peek(&Token::LParen)?;
+
+        let parameters = self.parse_function_parameters()?;
+
+        self.expect_peek(&Token::LBrace)?;
+
+        let body = self.parse_block_statement()?;
+        let expression = Expression::Macro {
+            parameters,
+            body: Box::new(body),
+        };
+
+        Ok(expression)
+    }
+
     fn parse_function_parameters(&mut self) -> Result<Vec<Expression>, ParseError> {
         let mut parameters = vec![];
 
@@ -357,10 +668,20 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_index_expression(&mut self, left: Expression) -> Result<Expression, ParseError> {
+        if self.is_peek_token(&Token::Colon) {
+            self.next_token();
+            return self.parse_slice_expression(left, None);
+        }
+
         self.next_token();
 
         let index = self.parse_expression(Precedence::Lowest)?;
 
+        if self.is_peek_token(&Token::Colon) {
+            self.next_token();
+            return self.parse_slice_expression(left, Some(Box::new(index)));
+        }
+
         self.expect_peek(&Token::RBracket)?;
 
         let expression = Expression::Index {
@@ -371,6 +692,41 @@ impl<'a> Parser<'a> {
         Ok(expression)
     }
 
+    /// `expr.name` を `expr["name"]` として解析する
+    fn parse_dot_expression(&mut self, left: Expression) -> Result<Expression, ParseError> {
+        let name = self.expect_peek_identifier()?;
+
+        let expression = Expression::Index {
+            left: Box::new(left),
+            index: Box::new(Expression::String(name.to_string())),
+        };
+
+        Ok(expression)
+    }
+
+    fn parse_slice_expression(
+        &mut self,
+        left: Expression,
+        start: Option<Box<Expression>>,
+    ) -> Result<Expression, ParseError> {
+        let end = if self.is_peek_token(&Token::RBracket) {
+            None
+        } else {
+            self.next_token();
+            Some(Box::new(self.parse_expression(Precedence::Lowest)?))
+        };
+
+        self.expect_peek(&Token::RBracket)?;
+
+        let expression = Expression::Slice {
+            left: Box::new(left),
+            start,
+            end,
+        };
+
+        Ok(expression)
+    }
+
     fn parse_map_expression(&mut self) -> Result<Expression, ParseError> {
         let mut pairs = BTreeMap::new();
 
@@ -398,9 +754,9 @@ impl<'a> Parser<'a> {
         Ok(expression)
     }
 
-    fn expect_peek_identifier(&mut self) -> Result<String, ParseError> {
+    fn expect_peek_identifier(&mut self) -> Result<Symbol, ParseError> {
         let value = match &self.peek_token {
-            Token::Identifier(value) => value.to_string(),
+            Token::Identifier(value) => value.clone(),
             _ => {
                 return Err(format!(
                     "expected next token to be Ident, got {} instead",
@@ -445,10 +801,10 @@ impl<'a> Parser<'a> {
 
 #[cfg(test)]
 mod tests {
-    use crate::ast::{Expression, Statement};
+    use crate::ast::{Expression, MatchArm, Pattern, Statement};
     use crate::lexer::Lexer;
     use crate::parser::Parser;
-    use crate::token::Token;
+    use crate::token::{Position, Token};
     use std::collections::BTreeMap;
 
     fn assert_statements(tests: Vec<(&str, Statement)>) {
@@ -461,8 +817,8 @@ mod tests {
                 println!("{}", error);
             }
 
-            assert!(parser.errors.len() == 0);
-            assert!(program.statements.len() > 0);
+            assert!(parser.errors.is_empty());
+            assert!(!program.statements.is_empty());
 
             assert_eq!(program.statements[0], expected);
         }
@@ -478,8 +834,8 @@ mod tests {
                 println!("{}", error);
             }
 
-            assert!(parser.errors.len() == 0);
-            assert!(program.statements.len() > 0);
+            assert!(parser.errors.is_empty());
+            assert!(!program.statements.is_empty());
 
             assert_eq!(program.statements[0].to_string(), expected.to_string());
         }
@@ -491,22 +847,25 @@ mod tests {
             (
                 "let x = 5;",
                 Statement::Let {
-                    name: Expression::Identifier("x".to_string()),
+                    name: Expression::Identifier("x".into()),
                     value: Expression::Integer(5),
+                    position: Position::default(),
                 },
             ),
             (
                 "let y = 10;",
                 Statement::Let {
-                    name: Expression::Identifier("y".to_string()),
+                    name: Expression::Identifier("y".into()),
                     value: Expression::Integer(10),
+                    position: Position::default(),
                 },
             ),
             (
                 "let foobar = 838383;",
                 Statement::Let {
-                    name: Expression::Identifier("foobar".to_string()),
+                    name: Expression::Identifier("foobar".into()),
                     value: Expression::Integer(838383),
+                    position: Position::default(),
                 },
             ),
         ];
@@ -514,14 +873,37 @@ mod tests {
         assert_statements(tests);
     }
 
+    #[test]
+    fn test_import_statements() {
+        let tests = vec![(
+            "import \"std/list\" as list;",
+            Statement::Let {
+                name: Expression::Identifier("list".into()),
+                value: Expression::Call {
+                    function: Box::new(Expression::Identifier("import".into())),
+                    arguments: vec![Expression::String("std/list".to_string())],
+                },
+                position: Position::default(),
+            },
+        )];
+
+        assert_statements(tests);
+    }
+
     #[test]
     fn test_return_statements() {
         let tests = vec![
-            ("return 5;", Statement::Return(Expression::Integer(5))),
-            ("return 10;", Statement::Return(Expression::Integer(10))),
+            (
+                "return 5;",
+                Statement::Return(Expression::Integer(5), Position::default()),
+            ),
+            (
+                "return 10;",
+                Statement::Return(Expression::Integer(10), Position::default()),
+            ),
             (
                 "return 993322;",
-                Statement::Return(Expression::Integer(993322)),
+                Statement::Return(Expression::Integer(993322), Position::default()),
             ),
         ];
 
@@ -532,7 +914,7 @@ mod tests {
     fn test_identifier_expressions() {
         let tests = vec![(
             "foobar;",
-            Statement::Expression(Expression::Identifier("foobar".to_string())),
+            Statement::Expression(Expression::Identifier("foobar".into()), Position::default()),
         )];
 
         assert_statements(tests);
@@ -540,7 +922,10 @@ mod tests {
 
     #[test]
     fn test_integer_expressions() {
-        let tests = vec![("5;", Statement::Expression(Expression::Integer(5)))];
+        let tests = vec![(
+            "5;",
+            Statement::Expression(Expression::Integer(5), Position::default()),
+        )];
 
         assert_statements(tests);
     }
@@ -550,31 +935,43 @@ mod tests {
         let tests = vec![
             (
                 "!5;",
-                Statement::Expression(Expression::Prefix {
-                    operator: Token::Bang,
-                    right: Box::new(Expression::Integer(5)),
-                }),
+                Statement::Expression(
+                    Expression::Prefix {
+                        operator: Token::Bang,
+                        right: Box::new(Expression::Integer(5)),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "-15;",
-                Statement::Expression(Expression::Prefix {
-                    operator: Token::Minus,
-                    right: Box::new(Expression::Integer(15)),
-                }),
+                Statement::Expression(
+                    Expression::Prefix {
+                        operator: Token::Minus,
+                        right: Box::new(Expression::Integer(15)),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "!true;",
-                Statement::Expression(Expression::Prefix {
-                    operator: Token::Bang,
-                    right: Box::new(Expression::Boolean(true)),
-                }),
+                Statement::Expression(
+                    Expression::Prefix {
+                        operator: Token::Bang,
+                        right: Box::new(Expression::Boolean(true)),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "!false;",
-                Statement::Expression(Expression::Prefix {
-                    operator: Token::Bang,
-                    right: Box::new(Expression::Boolean(false)),
-                }),
+                Statement::Expression(
+                    Expression::Prefix {
+                        operator: Token::Bang,
+                        right: Box::new(Expression::Boolean(false)),
+                    },
+                    Position::default(),
+                ),
             ),
         ];
 
@@ -586,91 +983,124 @@ mod tests {
         let tests = vec![
             (
                 "5 + 5;",
-                Statement::Expression(Expression::Infix {
-                    left: Box::new(Expression::Integer(5)),
-                    operator: Token::Plus,
-                    right: Box::new(Expression::Integer(5)),
-                }),
+                Statement::Expression(
+                    Expression::Infix {
+                        left: Box::new(Expression::Integer(5)),
+                        operator: Token::Plus,
+                        right: Box::new(Expression::Integer(5)),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "5 - 5;",
-                Statement::Expression(Expression::Infix {
-                    left: Box::new(Expression::Integer(5)),
-                    operator: Token::Minus,
-                    right: Box::new(Expression::Integer(5)),
-                }),
+                Statement::Expression(
+                    Expression::Infix {
+                        left: Box::new(Expression::Integer(5)),
+                        operator: Token::Minus,
+                        right: Box::new(Expression::Integer(5)),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "5 * 5;",
-                Statement::Expression(Expression::Infix {
-                    left: Box::new(Expression::Integer(5)),
-                    operator: Token::Asterisk,
-                    right: Box::new(Expression::Integer(5)),
-                }),
+                Statement::Expression(
+                    Expression::Infix {
+                        left: Box::new(Expression::Integer(5)),
+                        operator: Token::Asterisk,
+                        right: Box::new(Expression::Integer(5)),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "5 / 5;",
-                Statement::Expression(Expression::Infix {
-                    left: Box::new(Expression::Integer(5)),
-                    operator: Token::Slash,
-                    right: Box::new(Expression::Integer(5)),
-                }),
+                Statement::Expression(
+                    Expression::Infix {
+                        left: Box::new(Expression::Integer(5)),
+                        operator: Token::Slash,
+                        right: Box::new(Expression::Integer(5)),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "5 > 5;",
-                Statement::Expression(Expression::Infix {
-                    left: Box::new(Expression::Integer(5)),
-                    operator: Token::Gt,
-                    right: Box::new(Expression::Integer(5)),
-                }),
+                Statement::Expression(
+                    Expression::Infix {
+                        left: Box::new(Expression::Integer(5)),
+                        operator: Token::Gt,
+                        right: Box::new(Expression::Integer(5)),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "5 < 5;",
-                Statement::Expression(Expression::Infix {
-                    left: Box::new(Expression::Integer(5)),
-                    operator: Token::Lt,
-                    right: Box::new(Expression::Integer(5)),
-                }),
+                Statement::Expression(
+                    Expression::Infix {
+                        left: Box::new(Expression::Integer(5)),
+                        operator: Token::Lt,
+                        right: Box::new(Expression::Integer(5)),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "5 == 5;",
-                Statement::Expression(Expression::Infix {
-                    left: Box::new(Expression::Integer(5)),
-                    operator: Token::Eq,
-                    right: Box::new(Expression::Integer(5)),
-                }),
+                Statement::Expression(
+                    Expression::Infix {
+                        left: Box::new(Expression::Integer(5)),
+                        operator: Token::Eq,
+                        right: Box::new(Expression::Integer(5)),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "5 != 5;",
-                Statement::Expression(Expression::Infix {
-                    left: Box::new(Expression::Integer(5)),
-                    operator: Token::Ne,
-                    right: Box::new(Expression::Integer(5)),
-                }),
+                Statement::Expression(
+                    Expression::Infix {
+                        left: Box::new(Expression::Integer(5)),
+                        operator: Token::Ne,
+                        right: Box::new(Expression::Integer(5)),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "true == true;",
-                Statement::Expression(Expression::Infix {
-                    left: Box::new(Expression::Boolean(true)),
-                    operator: Token::Eq,
-                    right: Box::new(Expression::Boolean(true)),
-                }),
+                Statement::Expression(
+                    Expression::Infix {
+                        left: Box::new(Expression::Boolean(true)),
+                        operator: Token::Eq,
+                        right: Box::new(Expression::Boolean(true)),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "true != false;",
-                Statement::Expression(Expression::Infix {
-                    left: Box::new(Expression::Boolean(true)),
-                    operator: Token::Ne,
-                    right: Box::new(Expression::Boolean(false)),
-                }),
+                Statement::Expression(
+                    Expression::Infix {
+                        left: Box::new(Expression::Boolean(true)),
+                        operator: Token::Ne,
+                        right: Box::new(Expression::Boolean(false)),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "false == false;",
-                Statement::Expression(Expression::Infix {
-                    left: Box::new(Expression::Boolean(false)),
-                    operator: Token::Eq,
-                    right: Box::new(Expression::Boolean(false)),
-                }),
+                Statement::Expression(
+                    Expression::Infix {
+                        left: Box::new(Expression::Boolean(false)),
+                        operator: Token::Eq,
+                        right: Box::new(Expression::Boolean(false)),
+                    },
+                    Position::default(),
+                ),
             ),
         ];
 
@@ -722,6 +1152,29 @@ mod tests {
                 "add(a * b[2], b[1], 2 * [1, 2][1]);",
                 "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))",
             ),
+            ("1..n + 1;", "(1 .. (n + 1))"),
+            ("a * 2..=b == c;", "((a * 2) ..= (b == c))"),
+            ("a ?? b ?? c;", "((a ?? b) ?? c)"),
+            ("a.b.c;", "((a[b])[c])"),
+            ("-a.b * c;", "((-(a[b])) * c)"),
+            ("a.b(c);", "(a[b])(c)"),
+            ("yield a + b * c;", "yield (a + (b * c))"),
+            ("m[k] ?? 1 + 2;", "((m[k]) ?? (1 + 2))"),
+            ("a ?? 0..n;", "(a ?? (0 .. n))"),
+            ("a ? b : c;", "if a { b } else { c }"),
+            (
+                "a == b ? c + 1 : d * 2;",
+                "if (a == b) { (c + 1) } else { (d * 2) }",
+            ),
+            (
+                "a ? b : c ? d : e;",
+                "if a { b } else { if c { d } else { e } }",
+            ),
+            (
+                "a ?? b ? c : d ?? e;",
+                "if (a ?? b) { c } else { (d ?? e) }",
+            ),
+            ("f(a ? b : c, d);", "f(if a { b } else { c }, d)"),
         ];
 
         assert_statements_with_string(tests);
@@ -730,20 +1183,28 @@ mod tests {
     #[test]
     fn test_boolean_expressions() {
         let tests = vec![
-            ("true;", Statement::Expression(Expression::Boolean(true))),
-            ("false;", Statement::Expression(Expression::Boolean(false))),
+            (
+                "true;",
+                Statement::Expression(Expression::Boolean(true), Position::default()),
+            ),
+            (
+                "false;",
+                Statement::Expression(Expression::Boolean(false), Position::default()),
+            ),
             (
                 "let foobar = true;",
                 Statement::Let {
-                    name: Expression::Identifier("foobar".to_string()),
+                    name: Expression::Identifier("foobar".into()),
                     value: Expression::Boolean(true),
+                    position: Position::default(),
                 },
             ),
             (
                 "let barfoo = false;",
                 Statement::Let {
-                    name: Expression::Identifier("barfoo".to_string()),
+                    name: Expression::Identifier("barfoo".into()),
                     value: Expression::Boolean(false),
+                    position: Position::default(),
                 },
             ),
         ];
@@ -755,17 +1216,21 @@ mod tests {
     fn test_if_expressions() {
         let tests = vec![(
             "if (x < y) { x }",
-            Statement::Expression(Expression::If {
-                condition: Box::new(Expression::Infix {
-                    left: Box::new(Expression::Identifier("x".to_string())),
-                    operator: Token::Lt,
-                    right: Box::new(Expression::Identifier("y".to_string())),
-                }),
-                consequence: Box::new(Statement::Block(vec![Statement::Expression(
-                    Expression::Identifier("x".to_string()),
-                )])),
-                alternative: None,
-            }),
+            Statement::Expression(
+                Expression::If {
+                    condition: Box::new(Expression::Infix {
+                        left: Box::new(Expression::Identifier("x".into())),
+                        operator: Token::Lt,
+                        right: Box::new(Expression::Identifier("y".into())),
+                    }),
+                    consequence: Box::new(Statement::Block(vec![Statement::Expression(
+                        Expression::Identifier("x".into()),
+                        Position::default(),
+                    )])),
+                    alternative: None,
+                },
+                Position::default(),
+            ),
         )];
 
         assert_statements(tests);
@@ -775,19 +1240,97 @@ mod tests {
     fn test_if_else_expressions() {
         let tests = vec![(
             "if (x < y) { x } else { y }",
-            Statement::Expression(Expression::If {
-                condition: Box::new(Expression::Infix {
-                    left: Box::new(Expression::Identifier("x".to_string())),
-                    operator: Token::Lt,
-                    right: Box::new(Expression::Identifier("y".to_string())),
-                }),
-                consequence: Box::new(Statement::Block(vec![Statement::Expression(
-                    Expression::Identifier("x".to_string()),
-                )])),
-                alternative: Some(Box::new(Statement::Block(vec![Statement::Expression(
-                    Expression::Identifier("y".to_string()),
-                )]))),
-            }),
+            Statement::Expression(
+                Expression::If {
+                    condition: Box::new(Expression::Infix {
+                        left: Box::new(Expression::Identifier("x".into())),
+                        operator: Token::Lt,
+                        right: Box::new(Expression::Identifier("y".into())),
+                    }),
+                    consequence: Box::new(Statement::Block(vec![Statement::Expression(
+                        Expression::Identifier("x".into()),
+                        Position::default(),
+                    )])),
+                    alternative: Some(Box::new(Statement::Block(vec![Statement::Expression(
+                        Expression::Identifier("y".into()),
+                        Position::default(),
+                    )]))),
+                },
+                Position::default(),
+            ),
+        )];
+
+        assert_statements(tests);
+    }
+
+    #[test]
+    fn test_try_expressions() {
+        let tests = vec![(
+            "try { x } catch (e) { e }",
+            Statement::Expression(
+                Expression::Try {
+                    body: Box::new(Statement::Block(vec![Statement::Expression(
+                        Expression::Identifier("x".into()),
+                        Position::default(),
+                    )])),
+                    parameter: Box::new(Expression::Identifier("e".into())),
+                    handler: Box::new(Statement::Block(vec![Statement::Expression(
+                        Expression::Identifier("e".into()),
+                        Position::default(),
+                    )])),
+                },
+                Position::default(),
+            ),
+        )];
+
+        assert_statements(tests);
+    }
+
+    #[test]
+    fn test_match_expressions() {
+        let tests = vec![(
+            r#"match (x) { -1 => 0, [a, ...rest] if a > 1 => rest, {name, "age": _} => name, _ => x }"#,
+            Statement::Expression(
+                Expression::Match {
+                    subject: Box::new(Expression::Identifier("x".into())),
+                    arms: vec![
+                        MatchArm {
+                            pattern: Pattern::Literal(Expression::Prefix {
+                                operator: Token::Minus,
+                                right: Box::new(Expression::Integer(1)),
+                            }),
+                            guard: None,
+                            body: Expression::Integer(0),
+                        },
+                        MatchArm {
+                            pattern: Pattern::Array {
+                                elements: vec![Pattern::Binding("a".into())],
+                                rest: Some("rest".into()),
+                            },
+                            guard: Some(Expression::Infix {
+                                left: Box::new(Expression::Identifier("a".into())),
+                                operator: Token::Gt,
+                                right: Box::new(Expression::Integer(1)),
+                            }),
+                            body: Expression::Identifier("rest".into()),
+                        },
+                        MatchArm {
+                            pattern: Pattern::Map(vec![
+                                ("name".to_string(), Pattern::Binding("name".into())),
+                                ("age".to_string(), Pattern::Wildcard),
+                            ]),
+                            guard: None,
+                            body: Expression::Identifier("name".into()),
+                        },
+                        MatchArm {
+                            pattern: Pattern::Wildcard,
+                            guard: None,
+                            body: Expression::Identifier("x".into()),
+                        },
+                    ],
+                },
+                Position::default(),
+            ),
         )];
 
         assert_statements(tests);
@@ -797,19 +1340,49 @@ mod tests {
     fn test_function_expressions() {
         let tests = vec![(
             "fn(x, y) { x + y; }",
-            Statement::Expression(Expression::Function {
-                parameters: vec![
-                    Expression::Identifier("x".to_string()),
-                    Expression::Identifier("y".to_string()),
-                ],
-                body: Box::new(Statement::Block(vec![Statement::Expression(
-                    Expression::Infix {
-                        left: Box::new(Expression::Identifier("x".to_string())),
-                        operator: Token::Plus,
-                        right: Box::new(Expression::Identifier("y".to_string())),
-                    },
-                )])),
-            }),
+            Statement::Expression(
+                Expression::Function {
+                    parameters: vec![
+                        Expression::Identifier("x".into()),
+                        Expression::Identifier("y".into()),
+                    ],
+                    body: Box::new(Statement::Block(vec![Statement::Expression(
+                        Expression::Infix {
+                            left: Box::new(Expression::Identifier("x".into())),
+                            operator: Token::Plus,
+                            right: Box::new(Expression::Identifier("y".into())),
+                        },
+                        Position::default(),
+                    )])),
+                },
+                Position::default(),
+            ),
+        )];
+
+        assert_statements(tests);
+    }
+
+    #[test]
+    fn test_macro_expressions() {
+        let tests = vec![(
+            "macro(x, y) { x + y; }",
+            Statement::Expression(
+                Expression::Macro {
+                    parameters: vec![
+                        Expression::Identifier("x".into()),
+                        Expression::Identifier("y".into()),
+                    ],
+                    body: Box::new(Statement::Block(vec![Statement::Expression(
+                        Expression::Infix {
+                            left: Box::new(Expression::Identifier("x".into())),
+                            operator: Token::Plus,
+                            right: Box::new(Expression::Identifier("y".into())),
+                        },
+                        Position::default(),
+                    )])),
+                },
+                Position::default(),
+            ),
         )];
 
         assert_statements(tests);
@@ -820,27 +1393,36 @@ mod tests {
         let tests = vec![
             (
                 "fn() {}",
-                Statement::Expression(Expression::Function {
-                    parameters: vec![],
-                    body: Box::new(Statement::Block(vec![])),
-                }),
+                Statement::Expression(
+                    Expression::Function {
+                        parameters: vec![],
+                        body: Box::new(Statement::Block(vec![])),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "fn(x) {}",
-                Statement::Expression(Expression::Function {
-                    parameters: vec![Expression::Identifier("x".to_string())],
-                    body: Box::new(Statement::Block(vec![])),
-                }),
+                Statement::Expression(
+                    Expression::Function {
+                        parameters: vec![Expression::Identifier("x".into())],
+                        body: Box::new(Statement::Block(vec![])),
+                    },
+                    Position::default(),
+                ),
             ),
             (
                 "fn(x, y) {}",
-                Statement::Expression(Expression::Function {
-                    parameters: vec![
-                        Expression::Identifier("x".to_string()),
-                        Expression::Identifier("y".to_string()),
-                    ],
-                    body: Box::new(Statement::Block(vec![])),
-                }),
+                Statement::Expression(
+                    Expression::Function {
+                        parameters: vec![
+                            Expression::Identifier("x".into()),
+                            Expression::Identifier("y".into()),
+                        ],
+                        body: Box::new(Statement::Block(vec![])),
+                    },
+                    Position::default(),
+                ),
             ),
         ];
 
@@ -851,22 +1433,25 @@ mod tests {
     fn test_call_expressions() {
         let tests = vec![(
             "add(1, 2 * 3, 4 + 5);",
-            Statement::Expression(Expression::Call {
-                function: Box::new(Expression::Identifier("add".to_string())),
-                arguments: vec![
-                    Expression::Integer(1),
-                    Expression::Infix {
-                        left: Box::new(Expression::Integer(2)),
-                        operator: Token::Asterisk,
-                        right: Box::new(Expression::Integer(3)),
-                    },
-                    Expression::Infix {
-                        left: Box::new(Expression::Integer(4)),
-                        operator: Token::Plus,
-                        right: Box::new(Expression::Integer(5)),
-                    },
-                ],
-            }),
+            Statement::Expression(
+                Expression::Call {
+                    function: Box::new(Expression::Identifier("add".into())),
+                    arguments: vec![
+                        Expression::Integer(1),
+                        Expression::Infix {
+                            left: Box::new(Expression::Integer(2)),
+                            operator: Token::Asterisk,
+                            right: Box::new(Expression::Integer(3)),
+                        },
+                        Expression::Infix {
+                            left: Box::new(Expression::Integer(4)),
+                            operator: Token::Plus,
+                            right: Box::new(Expression::Integer(5)),
+                        },
+                    ],
+                },
+                Position::default(),
+            ),
         )];
 
         assert_statements(tests);
@@ -876,7 +1461,10 @@ mod tests {
     fn test_string_expressions() {
         let tests = vec![(
             r#""hello world""#,
-            Statement::Expression(Expression::String("hello world".to_string())),
+            Statement::Expression(
+                Expression::String("hello world".to_string()),
+                Position::default(),
+            ),
         )];
 
         assert_statements(tests);
@@ -891,7 +1479,13 @@ mod tests {
 
     #[test]
     fn test_index_expressions() {
-        let tests = vec![("myArray[1 + 1]", "(myArray[(1 + 1)])")];
+        let tests = vec![
+            ("myArray[1 + 1]", "(myArray[(1 + 1)])"),
+            ("myArray[1:3]", "(myArray[1:3])"),
+            ("myArray[:a + 1]", "(myArray[:(a + 1)])"),
+            ("myArray[2:]", "(myArray[2:])"),
+            ("myArray[:]", "(myArray[:])"),
+        ];
 
         assert_statements_with_string(tests);
     }
@@ -915,11 +1509,11 @@ mod tests {
                     Expression::Integer(3),
                 );
 
-                Statement::Expression(Expression::Map(pairs))
+                Statement::Expression(Expression::Map(pairs), Position::default())
             }),
             (
                 "{}",
-                Statement::Expression(Expression::Map(BTreeMap::new())),
+                Statement::Expression(Expression::Map(BTreeMap::new()), Position::default()),
             ),
             (r#"{"one": 0 + 1, "two": 10 - 8, "three": 15 / 5}"#, {
                 let mut pairs = BTreeMap::new();
@@ -949,10 +1543,39 @@ mod tests {
                     },
                 );
 
-                Statement::Expression(Expression::Map(pairs))
+                Statement::Expression(Expression::Map(pairs), Position::default())
             }),
         ];
 
         assert_statements(tests);
     }
+
+    #[test]
+    fn test_out_of_range_integers() {
+        let mut lexer = Lexer::new("let x = 1 +\n    99999999999999999999;");
+        let mut parser = Parser::new(&mut lexer);
+        parser.parse_program();
+
+        assert_eq!(
+            parser.errors,
+            vec!["integer literal out of range: 99999999999999999999 at line 2, column 5"]
+        );
+
+        let mut lexer = Lexer::new(
+            "let x = 99999999999999999999;\nf(99999999999999999999, [1, 99999999999999999999]);",
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+
+        assert!(program.statements.is_empty());
+
+        assert_eq!(
+            parser.errors,
+            vec![
+                "integer literal out of range: 99999999999999999999 at line 1, column 9",
+                "integer literal out of range: 99999999999999999999 at line 2, column 3",
+                "integer literal out of range: 99999999999999999999 at line 2, column 29",
+            ]
+        );
+    }
 }
@@ -1,42 +1,614 @@
-use crate::evaluator::{Environment, Response};
+use crate::ast::{Expression, Program, Statement};
+use crate::evaluator::{Evaluator, Response};
+use crate::history::History;
 use crate::lexer::Lexer;
+use crate::messages::{localize, text, Message};
+use crate::object::Object;
 use crate::parser::Parser;
-use colored::Colorize;
+use crate::report;
+use crate::script;
+use crate::session::Session;
+use crate::trace::{Step, DEFAULT_TRACE_CAPACITY};
+use crate::validate::validate;
+use crate::vfs::OsVfs;
+use colored::{Color, Colorize};
+use std::cell::RefCell;
+use std::env;
+use std::fs;
 use std::io;
-use std::io::Write;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-pub fn start() -> io::Result<()> {
-    let mut env = Environment::new();
+/// 評価前のフック（入力を書き換えられる）
+pub type BeforeEvalHook = Box<dyn FnMut(&mut String)>;
 
-    loop {
-        print!(">> ");
-        io::stdout().flush()?;
+/// 評価後のフック（入力・結果・評価にかかった時間を受けとる）
+pub type AfterEvalHook = Box<dyn FnMut(&str, &Response, Duration)>;
 
-        let mut line = String::new();
-        io::stdin().read_line(&mut line)?;
+/// 定義時のフック（`let` で束縛された名前と値を受けとる）
+pub type DefinitionHook = Box<dyn FnMut(&str, &Object)>;
 
-        let mut lexer = Lexer::new(&line);
-        let mut parser = Parser::new(&mut lexer);
-        let program = parser.parse_program();
+/// REPL の設定（TUI と共有する）
+#[derive(Clone, Debug)]
+pub struct ReplConfig {
+    /// プロンプト
+    pub prompt: String,
+    /// 評価エラー時にクラッシュレポートを書き出すかどうか
+    pub debug: bool,
+    /// 結果とエラーを色つきで出力するかどうか
+    pub color: bool,
+    /// 起動時にあいさつを表示するかどうか
+    pub banner: bool,
+    /// 結果の色（None で色をつけない）
+    pub result_color: Option<Color>,
+    /// エラーの色（None で色をつけない）
+    pub error_color: Option<Color>,
+    /// 評価の前に未定義の名前と引数の数を検証するかどうか
+    pub check: bool,
+    /// `exec` でコマンドを実行できるかどうか（設定ファイルでは変えられず、`--allow-exec` で有効にする）
+    pub exec: bool,
+}
+
+impl Default for ReplConfig {
+    fn default() -> Self {
+        Self {
+            prompt: ">> ".to_string(),
+            debug: false,
+            color: true,
+            banner: true,
+            result_color: None,
+            error_color: Some(Color::Red),
+            check: false,
+            exec: false,
+        }
+    }
+}
+
+impl ReplConfig {
+    /// 設定ファイルを読みこむ（ファイルがなければ既定の設定を返す）
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let mut config = Self::default();
+
+        if path.exists() {
+            let source = fs::read_to_string(path)?;
+            config
+                .load(&source)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        }
+
+        Ok(config)
+    }
+
+    /// `key = value` の行からなる設定を読みこむ（`#` 以降はコメント）
+    pub fn load(&mut self, source: &str) -> Result<(), String> {
+        for (i, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => {
+                    let message = format!("line {}: expected `key = value`", i + 1);
+                    return Err(message);
+                }
+            };
+
+            self.set(key, value)
+                .map_err(|error| format!("line {}: {}", i + 1, error))?;
+        }
+
+        Ok(())
+    }
+
+    /// 設定を 1 つ変更する（`:set` と設定ファイルで共通）
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "prompt" => self.prompt = unquote(value).to_string(),
+            "color" => self.color = parse_bool(value)?,
+            "banner" => self.banner = parse_bool(value)?,
+            "result_color" => self.result_color = parse_color(value)?,
+            "error_color" => self.error_color = parse_color(value)?,
+            "check" => self.check = parse_bool(value)?,
+            _ => {
+                let message = format!("unknown setting: {}", key);
+                return Err(message);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 入力の末尾の名前（`a.b.c` のようにドットでつないだものも含む）を補完する候補を返す。
+/// ドットのあとでは、手前の名前を現在の束縛からたどったマップ（モジュールを含む）のキーを候補にする。
+/// 関数の呼び出しなどは評価しないので、たどれるのは識別子とキーだけ
+pub fn complete(evaluator: &Evaluator, input: &str) -> Vec<String> {
+    let start = input
+        .rfind(|ch: char| !(ch.is_alphanumeric() || ch == '_' || ch == '.'))
+        .map_or(0, |i| {
+            i + input[i..].chars().next().map_or(1, char::len_utf8)
+        });
+    let word = &input[start..];
+
+    let (receiver, partial) = match word.rsplit_once('.') {
+        Some((receiver, partial)) => (Some(receiver), partial),
+        None => (None, word),
+    };
+
+    let names = match receiver {
+        None => {
+            let mut names = evaluator.bindings().into_keys().collect::<Vec<_>>();
+            names.extend(evaluator.buildin_names());
+            names
+        }
+        Some(receiver) => {
+            let mut path = receiver.split('.');
+            let mut value = match path.next().and_then(|name| evaluator.get(name)) {
+                Some(value) => value,
+                None => return vec![],
+            };
+
+            for key in path {
+                value = match &value {
+                    Object::Map(pairs) => match pairs
+                        .values()
+                        .find(|pair| matches!(&pair.key, Object::String(k) if k == key))
+                    {
+                        Some(pair) => pair.value.clone(),
+                        None => return vec![],
+                    },
+                    _ => return vec![],
+                };
+            }
+
+            match &value {
+                Object::Map(pairs) => pairs
+                    .values()
+                    .filter_map(|pair| match &pair.key {
+                        Object::String(key) if is_identifier(key) => Some(key.to_string()),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => return vec![],
+            }
+        }
+    };
+
+    let mut candidates = names
+        .into_iter()
+        .filter(|name| name.starts_with(partial))
+        .map(|name| match receiver {
+            Some(receiver) => format!("{}.{}", receiver, name),
+            None => name,
+        })
+        .collect::<Vec<_>>();
+    candidates.sort();
+    candidates.dedup();
+
+    candidates
+}
+
+/// ドットのあとに書ける名前かどうか
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(ch) if ch.is_alphabetic() || ch == '_' => {
+            chars.all(|ch| ch.is_alphanumeric() || ch == '_')
+        }
+        _ => false,
+    }
+}
+
+/// 値を囲む `"` を外す（前後に空白を含むプロンプトを書けるように）
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "on" => Ok(true),
+        "false" | "off" => Ok(false),
+        _ => {
+            let message = format!("expected true or false, got {}", value);
+            Err(message)
+        }
+    }
+}
+
+fn parse_color(value: &str) -> Result<Option<Color>, String> {
+    match value {
+        "none" => Ok(None),
+        _ => match value.parse() {
+            Ok(color) => Ok(Some(color)),
+            Err(_) => {
+                let message = format!("unknown color: {}", value);
+                Err(message)
+            }
+        },
+    }
+}
 
-        if parser.exists_errors() {
-            print_parse_errors(parser.get_errors())?;
-            continue;
+/// 設定ファイルのパス（`RONKEY_CONFIG`、なければ `~/.ronkeyrc`）
+pub fn config_path() -> Option<PathBuf> {
+    match env::var_os("RONKEY_CONFIG") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(".ronkeyrc")),
+    }
+}
+
+/// 設定ファイルがあれば読みこんだ設定を返す
+pub fn user_config() -> io::Result<ReplConfig> {
+    match config_path() {
+        Some(path) => ReplConfig::from_file(&path),
+        None => Ok(ReplConfig::default()),
+    }
+}
+
+/// 入力元と出力先を差し替えられる REPL
+pub struct Repl<R, W> {
+    evaluator: Evaluator,
+    config: ReplConfig,
+    reader: R,
+    writer: W,
+    before_eval: Vec<BeforeEvalHook>,
+    after_eval: Vec<AfterEvalHook>,
+    on_definition: Vec<DefinitionHook>,
+    history: History,
+}
+
+impl<R: BufRead, W: Write> Repl<R, W> {
+    pub fn new(reader: R, writer: W, config: ReplConfig) -> Self {
+        let mut evaluator = Evaluator::new();
+        evaluator.set_vfs(Some(Rc::new(OsVfs)));
+        evaluator.set_exec(config.exec);
+
+        if config.debug {
+            evaluator.enable_debug(DEFAULT_TRACE_CAPACITY);
+        }
+
+        Self {
+            evaluator,
+            config,
+            reader,
+            writer,
+            before_eval: vec![],
+            after_eval: vec![],
+            on_definition: vec![],
+            history: History::new(),
         }
+    }
+
+    pub fn evaluator(&mut self) -> &mut Evaluator {
+        &mut self.evaluator
+    }
+
+    pub fn writer(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn config(&self) -> &ReplConfig {
+        &self.config
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// 入力元と出力先を返して REPL を終了する
+    pub fn into_parts(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+
+    /// 入力を評価する前に呼ばれるフックを登録する
+    pub fn on_before_eval(&mut self, hook: impl FnMut(&mut String) + 'static) -> &mut Self {
+        self.before_eval.push(Box::new(hook));
+        self
+    }
+
+    /// 入力を評価した後に呼ばれるフックを登録する
+    pub fn on_after_eval(
+        &mut self,
+        hook: impl FnMut(&str, &Response, Duration) + 'static,
+    ) -> &mut Self {
+        self.after_eval.push(Box::new(hook));
+        self
+    }
 
-        match env.eval(program) {
+    /// トップレベルの `let` で束縛されたときに呼ばれるフックを登録する
+    pub fn on_definition(&mut self, hook: impl FnMut(&str, &Object) + 'static) -> &mut Self {
+        self.on_definition.push(Box::new(hook));
+        self
+    }
+
+    /// 1 行分の入力を評価する。構文解析に失敗した場合はそのエラーを返す
+    pub fn eval_line(&mut self, line: &str) -> Result<Response, Vec<String>> {
+        let mut input = line.to_string();
+
+        for hook in self.before_eval.iter_mut() {
+            hook(&mut input);
+        }
+
+        let program = parse(&input, &self.evaluator)?;
+
+        if self.config.check {
+            let mut known = self.evaluator.bindings().into_keys().collect::<Vec<_>>();
+            known.extend(self.evaluator.buildin_names());
+
+            // 評価エラーと同じく最初の 1 つだけを返す
+            if let Some(error) = validate(&program, &known).into_iter().next() {
+                return Ok(Response::Error(error.into()));
+            }
+        }
+
+        let names = program
+            .statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Let {
+                    name: Expression::Identifier(name),
+                    ..
+                } => Some(name.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let start = Instant::now();
+        let response = self.evaluator.eval(program);
+        let elapsed = start.elapsed();
+
+        if !matches!(response, Response::Error(_)) {
+            for name in names.iter() {
+                if let Some(value) = self.evaluator.get(name) {
+                    for hook in self.on_definition.iter_mut() {
+                        hook(name, &value);
+                    }
+                }
+            }
+        }
+
+        for hook in self.after_eval.iter_mut() {
+            hook(&input, &response, elapsed);
+        }
+
+        self.history.push(&input, &response);
+
+        Ok(response)
+    }
+
+    /// 入力の末尾の名前を補完する候補を返す
+    pub fn complete(&self, input: &str) -> Vec<String> {
+        complete(&self.evaluator, input)
+    }
+
+    /// 1 行分の入力をステップ実行し、式ごとの評価結果とあわせて返す
+    pub fn step_line(&mut self, line: &str) -> Result<(Response, Vec<Step>), Vec<String>> {
+        let program = parse(line, &self.evaluator)?;
+        Ok(self.evaluator.eval_stepping(program))
+    }
+
+    /// 入力元を読み切るまで 1 行ずつ評価し、結果を出力先に書き出す
+    pub fn run(&mut self) -> io::Result<()> {
+        if self.config.banner {
+            writeln!(
+                self.writer,
+                "{}",
+                text(Message::Greeting, &[&whoami::username()])
+            )?;
+            writeln!(self.writer, "{}", text(Message::FeelFree, &[]))?;
+        }
+
+        loop {
+            write!(self.writer, "{}", self.config.prompt)?;
+            self.writer.flush()?;
+
+            let mut line = String::new();
+
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+
+            if let Some(result) = self.run_command(line.trim()) {
+                match result {
+                    Ok(lines) => {
+                        for line in lines {
+                            writeln!(self.writer, "{}", line)?;
+                        }
+                    }
+                    Err(error) => {
+                        let message = self.paint_error(text(Message::Error, &[&error]));
+                        writeln!(self.writer, "{}", message)?;
+                    }
+                }
+
+                continue;
+            }
+
+            let step = line.trim_start().strip_prefix(":step ");
+            let result = match step {
+                Some(expression) => self.step_line(expression),
+                None => self.eval_line(&line).map(|response| (response, vec![])),
+            };
+
+            match result {
+                Ok((response, steps)) => {
+                    for step in steps.iter() {
+                        writeln!(self.writer, "{}", step)?;
+                    }
+
+                    self.write_response(response, step.unwrap_or(&line))?;
+                }
+                Err(errors) => self.write_parse_errors(errors)?,
+            }
+
+            self.writer.flush()?;
+        }
+    }
+
+    /// `:set`・`:history`・`:result`・`:complete` を実行して出力する行を返す（コマンドでなければ None）
+    pub fn run_command(&mut self, line: &str) -> Option<Result<Vec<String>, String>> {
+        let (command, argument) = match line.split_once(' ') {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+
+        let result = match command {
+            ":set" => {
+                let (key, value) = argument.split_once(' ').unwrap_or((argument, ""));
+                self.config.set(key, value.trim()).map(|_| vec![])
+            }
+            ":history" => {
+                let entries = match argument.strip_prefix("search") {
+                    Some(query) => self.history.search(query.trim()).collect::<Vec<_>>(),
+                    None if argument.is_empty() => self.history.entries().collect(),
+                    None => {
+                        let message = "usage: :history [search <text>]".to_string();
+                        return Some(Err(message));
+                    }
+                };
+
+                let lines = entries
+                    .iter()
+                    .map(|(number, entry)| format!("{:>4}  {}", number, entry))
+                    .collect();
+                Ok(lines)
+            }
+            ":result" => match argument.parse() {
+                Ok(number) => self.history.result(number).map(|result| {
+                    self.evaluator.set("_", result.clone());
+                    vec![result.to_string()]
+                }),
+                Err(_) => {
+                    let message = format!("expected a history number, got {}", argument);
+                    Err(message)
+                }
+            },
+            ":complete" => Ok(self.complete(argument)),
+            _ => return None,
+        };
+
+        Some(result)
+    }
+
+    /// 評価結果を書き出す（`source` は評価した入力で、エラーが起きた行を示すのに使う）
+    fn write_response(&mut self, response: Response, source: &str) -> io::Result<()> {
+        match response {
             Response::Reply(result) => {
-                println!("{}", result);
-                io::stdout().flush()?;
+                let result = self.paint(result.to_string(), self.config.result_color);
+                writeln!(self.writer, "{}", result)?
             }
             Response::NoReply => (),
             Response::Error(error) => {
-                let message = format!("error: {}", error).red();
-                println!("{}", message);
-                io::stdout().flush()?;
+                let message =
+                    self.paint_error(text(Message::Error, &[&localize(&error.to_string())]));
+                writeln!(self.writer, "{}", message)?;
+
+                // 入力の先頭の文で起きたエラーなら、行を示しても情報は増えない
+                let position = error
+                    .position()
+                    .map(|position| (position.line, position.column));
+
+                if let (Some(excerpt), false) = (error.excerpt(source), position == Some((1, 1))) {
+                    writeln!(self.writer, "{}", excerpt)?;
+                }
+
+                for line in error.stack_trace() {
+                    writeln!(self.writer, "  {}", line)?;
+                }
+
+                if let Some(snapshot) = self.evaluator.take_snapshot() {
+                    let path = report::write(&snapshot)?;
+                    let message = text(Message::CrashReport, &[&path.display()]);
+                    writeln!(self.writer, "{}", message)?;
+                }
             }
         }
+
+        Ok(())
     }
+
+    fn write_parse_errors(&mut self, errors: Vec<String>) -> io::Result<()> {
+        writeln!(self.writer, "{}", MONKEY_FACE)?;
+        writeln!(self.writer, "{}", text(Message::MonkeyBusiness, &[]))?;
+        writeln!(self.writer, "{}", text(Message::ParserErrors, &[]))?;
+
+        for error in errors {
+            let error = self.paint_error(localize(&error.to_string()));
+            writeln!(self.writer, "\t{}", error)?;
+        }
+
+        Ok(())
+    }
+
+    fn paint(&self, message: String, color: Option<Color>) -> String {
+        match color {
+            Some(color) if self.config.color => message.color(color).to_string(),
+            _ => message,
+        }
+    }
+
+    fn paint_error(&self, message: String) -> String {
+        self.paint(message, self.config.error_color)
+    }
+}
+
+pub fn start(debug: bool, exec: bool) -> io::Result<()> {
+    let config = ReplConfig {
+        debug,
+        exec,
+        ..user_config()?
+    };
+
+    Repl::new(stdin(), io::stdout(), config).run()
+}
+
+/// REPL の入力元にする標準入力。評価中の `read_line` や `input` も標準入力を読めるように、
+/// ロックを持ち続けず、先読みもしない（行の読みこみは標準入力自身のバッファを通る）
+fn stdin() -> io::BufReader<io::Stdin> {
+    io::BufReader::with_capacity(1, io::stdin())
+}
+
+/// 入力と組み込み関数の結果を記録しながら REPL を動かし、終了時にセッションを書き出す
+pub fn record(debug: bool, exec: bool, path: &str) -> io::Result<()> {
+    let config = ReplConfig {
+        debug,
+        exec,
+        ..user_config()?
+    };
+    let inputs = Rc::new(RefCell::new(vec![]));
+    let mut repl = Repl::new(stdin(), io::stdout(), config);
+
+    let recorded = inputs.clone();
+    repl.on_before_eval(move |input| recorded.borrow_mut().push(input.clone()));
+    repl.evaluator().start_recording();
+    repl.run()?;
+
+    let session = Session {
+        inputs: inputs.take(),
+        results: repl.evaluator().take_recorded(),
+    };
+    script::write_session(path, &session)
+}
+
+/// 入力を評価器と名前の表を共有して構文解析する
+fn parse(input: &str, evaluator: &Evaluator) -> Result<Program, Vec<String>> {
+    let mut lexer = Lexer::with_interner(input, evaluator.interner());
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+
+    if parser.exists_errors() {
+        return Err(parser.get_errors());
+    }
+
+    Ok(program)
 }
 
 const MONKEY_FACE: &str = r#"
@@ -53,15 +625,257 @@ const MONKEY_FACE: &str = r#"
           '-----'
 "#;
 
-fn print_parse_errors(errors: Vec<String>) -> io::Result<()> {
-    println!("{}", MONKEY_FACE);
-    println!("Woops! We ran into some monkey business here!");
-    println!("parser errors:");
+#[cfg(test)]
+mod tests {
+    use crate::evaluator::Response;
+    use crate::object::Object;
+    use crate::repl::{Repl, ReplConfig};
+    use colored::Color;
+    use std::cell::RefCell;
+    use std::rc::Rc;
 
-    for error in errors {
-        println!("\t{}", error.red());
-        io::stdout().flush()?;
+    #[test]
+    fn test_hooks() {
+        let events = Rc::new(RefCell::new(vec![]));
+        let mut repl = Repl::new(&b""[..], vec![], ReplConfig::default());
+
+        let log = events.clone();
+        repl.on_before_eval(|input| *input = input.replace("plus", "+"))
+            .on_definition(move |name, value| {
+                log.borrow_mut()
+                    .push(format!("define {} = {}", name, value))
+            });
+
+        let log = events.clone();
+        repl.on_after_eval(move |input, response, _| {
+            let result = match response {
+                Response::Reply(result) => result.to_string(),
+                Response::NoReply => "none".to_string(),
+                Response::Error(error) => error.to_string(),
+            };
+            log.borrow_mut()
+                .push(format!("eval {} => {}", input, result))
+        });
+
+        match repl.eval_line("let a = 1 plus 2; let b = a plus 1;") {
+            Ok(Response::NoReply) => (),
+            _ => unreachable!(),
+        }
+
+        match repl.eval_line("a plus b") {
+            Ok(Response::Reply(result)) => assert_eq!(result, Object::Integer(7)),
+            _ => unreachable!(),
+        }
+
+        assert!(repl.eval_line("let c = ;").is_err());
+
+        match repl.eval_line("let c = 1; c + true") {
+            Ok(Response::Error(_)) => (),
+            _ => unreachable!(),
+        }
+
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                "define a = 3",
+                "define b = 4",
+                "eval let a = 1 + 2; let b = a + 1; => none",
+                "eval a + b => 7",
+                "eval let c = 1; c + true => type mismatch: Integer + Boolean",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        let input = "let a = 2\na * 3\na + true\n:step a - 1\nlet = 1\n:set prompt \"$ \"\n1\n";
+        let config = ReplConfig {
+            prompt: "> ".to_string(),
+            color: false,
+            banner: false,
+            ..ReplConfig::default()
+        };
+        let mut repl = Repl::new(input.as_bytes(), vec![], config);
+
+        repl.run().unwrap();
+
+        let (_, output) = repl.into_parts();
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "> > 6");
+        assert_eq!(lines[1], "> error: type mismatch: Integer + Boolean");
+        assert_eq!(lines[2], ">   a => 2");
+        assert_eq!(lines[3], "  1 => 1");
+        assert_eq!(lines[4], "(a - 1) => 1");
+        assert_eq!(lines[5], "1");
+        assert!(output.contains("parser errors:\n\texpected next token to be Ident"));
+        assert!(output.ends_with("> $ 1\n$ "));
+    }
+
+    #[test]
+    fn test_error_excerpt() {
+        let input = "let b = 1;  b + true\nlet f = fn(x) { x / 0 };  f(1)\n";
+        let config = ReplConfig {
+            prompt: "> ".to_string(),
+            color: false,
+            banner: false,
+            ..ReplConfig::default()
+        };
+        let mut repl = Repl::new(input.as_bytes(), vec![], config);
+
+        repl.run().unwrap();
+
+        let (_, output) = repl.into_parts();
+        let output = String::from_utf8(output).unwrap();
+
+        let lines = output.lines().collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                "> error: type mismatch: Integer + Boolean",
+                " --> line 1, column 13",
+                "  |",
+                "1 | let b = 1;  b + true",
+                "  |             ^",
+                "> error: division by zero",
+                " --> line 1, column 17",
+                "  |",
+                "1 | let f = fn(x) { x / 0 };  f(1)",
+                "  |                 ^",
+                "  in f, called from main, line 1, column 27",
+                "> ",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_history_commands() {
+        let mut repl = Repl::new(&b""[..], vec![], ReplConfig::default());
+
+        for line in ["let f = fn(x) { x * 2 }", "f(21)", "bitmap(f)", "[1, 2]"] {
+            assert!(repl.eval_line(line).is_ok());
+        }
+
+        let lines = repl.run_command(":history search f(").unwrap().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("   2  ["));
+        assert!(lines[0].ends_with("] f(21) => 42 : Integer"));
+
+        let lines = repl.run_command(":history search map(").unwrap().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].ends_with("] bitmap(f) => error: identifier not found: bitmap"));
+
+        assert_eq!(repl.run_command(":history").unwrap().unwrap().len(), 4);
+        assert_eq!(
+            repl.run_command(":result 4"),
+            Some(Ok(vec!["[1, 2]".to_string()]))
+        );
+
+        match repl.eval_line("_[1] + 1") {
+            Ok(Response::Reply(result)) => assert_eq!(result, Object::Integer(3)),
+            _ => unreachable!(),
+        }
+
+        assert_eq!(
+            repl.run_command(":result x"),
+            Some(Err("expected a history number, got x".to_string()))
+        );
+        assert_eq!(
+            repl.run_command(":result 9"),
+            Some(Err("no history entry: 9".to_string()))
+        );
+        assert_eq!(repl.run_command("1 + 1"), None);
     }
 
-    Ok(())
+    #[test]
+    fn test_complete() {
+        let mut repl = Repl::new(&b""[..], vec![], ReplConfig::default());
+        let module = r#"{"map": 1, "max": 2, "nested": {"deep": 3}, "not ident": 4, 5: 6}"#;
+        assert!(repl.eval_line(&format!("let list = {};", module)).is_ok());
+        assert!(repl.eval_line("let lister = 1;").is_ok());
+
+        assert_eq!(repl.complete("puts(list.ma"), vec!["list.map", "list.max"]);
+        assert_eq!(
+            repl.complete("list."),
+            vec!["list.map", "list.max", "list.nested"]
+        );
+        assert_eq!(repl.complete("1 + list.nested.d"), vec!["list.nested.deep"]);
+        assert_eq!(repl.complete("lis"), vec!["list", "lister"]);
+        assert_eq!(repl.complete("push([], fir"), vec!["first"]);
+        assert!(repl.complete("lister.").is_empty());
+        assert!(repl.complete("missing.x").is_empty());
+
+        assert_eq!(
+            repl.run_command(":complete list.n"),
+            Some(Ok(vec!["list.nested".to_string()]))
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let mut repl = Repl::new(&b""[..], vec![], ReplConfig::default());
+        repl.run_command(":set check on");
+
+        match repl.eval_line("let add = fn(a, b) { a + b }; puts(1); add(1)") {
+            Ok(Response::Error(error)) => {
+                assert_eq!(error.to_string(), "`add` expects 2 arguments, got 1")
+            }
+            _ => unreachable!(),
+        }
+
+        match repl.eval_line("add(1, 2)") {
+            Ok(Response::Error(error)) => {
+                assert_eq!(error.to_string(), "identifier not found: add")
+            }
+            _ => unreachable!(),
+        }
+
+        assert!(matches!(
+            repl.eval_line("let add = fn(a, b) { a + b };"),
+            Ok(Response::NoReply)
+        ));
+
+        match repl.eval_line("add(1, 2)") {
+            Ok(Response::Reply(result)) => assert_eq!(result, Object::Integer(3)),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_config() {
+        let mut config = ReplConfig::default();
+        let source = r#"
+            # theme
+            prompt = "λ "
+            banner = off
+            result_color = green
+            error_color = none
+        "#;
+
+        config.load(source).unwrap();
+
+        assert_eq!(config.prompt, "λ ");
+        assert!(!config.banner);
+        assert_eq!(config.result_color, Some(Color::Green));
+        assert_eq!(config.error_color, None);
+
+        assert_eq!(
+            config.load("prompt"),
+            Err("line 1: expected `key = value`".to_string())
+        );
+        assert_eq!(
+            config.set("color", "maybe"),
+            Err("expected true or false, got maybe".to_string())
+        );
+        assert_eq!(
+            config.set("result_color", "plaid"),
+            Err("unknown color: plaid".to_string())
+        );
+        assert_eq!(
+            config.set("theme", "dark"),
+            Err("unknown setting: theme".to_string())
+        );
+    }
 }
@@ -0,0 +1,18 @@
+use crate::trace::Snapshot;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// スナップショットをカレントディレクトリのクラッシュレポートに書き出す
+pub fn write(snapshot: &Snapshot) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+
+    let path = PathBuf::from(format!("ronkey-crash-{}.txt", timestamp));
+    fs::write(&path, snapshot.to_string())?;
+
+    Ok(path)
+}
@@ -0,0 +1,374 @@
+use crate::ast::Program;
+use crate::error::EvalError;
+use crate::evaluator::{Evaluator, Response, DEFAULT_RECURSION_LIMIT};
+use crate::lexer::Lexer;
+use crate::manifest::Manifest;
+use crate::mdtest::extract;
+use crate::messages::{localize, text, Message};
+use crate::mutate::mutants;
+use crate::parser::Parser;
+use crate::report;
+use crate::session::Session;
+use crate::trace::DEFAULT_TRACE_CAPACITY;
+use crate::validate::validate;
+use crate::vfs::OsVfs;
+use colored::Colorize;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+
+/// 変異体の実行で許す文の数の上限（無限ループや無限再帰になった変異体を止める）
+const MUTANT_STATEMENT_LIMIT: usize = 10_000;
+
+/// 変異体を実行するスレッドのスタックサイズ
+const MUTANT_STACK_SIZE: usize = 1 << 30;
+
+/// Ctrl-C で立つフラグ（最初に使うときに 1 度だけハンドラーを登録する）
+fn interrupt_flag() -> Arc<AtomicBool> {
+    static INTERRUPT: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+    INTERRUPT
+        .get_or_init(|| {
+            let interrupt = Arc::new(AtomicBool::new(false));
+            let flag = Arc::clone(&interrupt);
+            // ほかにハンドラーが登録されていれば、中断できないだけで実行は続ける
+            let _ = ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst));
+            interrupt
+        })
+        .clone()
+}
+
+/// スクリプトファイルを構文解析する。失敗した場合はエラーを出力して None を返す
+fn load(path: &str) -> io::Result<Option<Program>> {
+    let input = fs::read_to_string(path)?;
+    parse(&input)
+}
+
+/// 入力を構文解析する。失敗した場合はエラーを出力して None を返す
+fn parse(input: &str) -> io::Result<Option<Program>> {
+    let mut lexer = Lexer::new(input);
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+
+    if parser.exists_errors() {
+        for error in parser.get_errors() {
+            eprintln!(
+                "{}",
+                text(Message::ParseError, &[&localize(&error.to_string())]).red()
+            );
+        }
+        return Ok(None);
+    }
+
+    Ok(Some(program))
+}
+
+/// 評価エラーを出力する。位置がわかれば、エラーが起きた行も示す
+fn print_error(error: &EvalError, source: &str) {
+    eprintln!(
+        "{}",
+        text(Message::Error, &[&localize(&error.to_string())]).red()
+    );
+
+    if let Some(excerpt) = error.excerpt(source) {
+        eprintln!("{}", excerpt);
+    }
+
+    for line in error.stack_trace() {
+        eprintln!("  {}", line);
+    }
+}
+
+/// スクリプト実行の設定
+#[derive(Clone, Debug, Default)]
+pub struct RunConfig {
+    /// 評価エラー時にクラッシュレポートを書き出すかどうか
+    pub debug: bool,
+    /// 実行後にカバレッジを出力するかどうか
+    pub coverage: bool,
+    /// セッションを記録するファイル
+    pub record: Option<String>,
+    /// 評価できる文の数の上限
+    pub statement_limit: Option<usize>,
+    /// 関数呼び出しの入れ子の深さの上限（None なら既定の上限）
+    pub recursion_limit: Option<usize>,
+    /// 実行ごとに結果が変わる組み込み関数を禁止するかどうか
+    pub deterministic: bool,
+    /// スクリプトのディレクトリのあとにモジュールを探すディレクトリ
+    pub import_paths: Vec<String>,
+    /// `exec` でコマンドを実行できるかどうか
+    pub exec: bool,
+}
+
+/// スクリプトのディレクトリと設定のディレクトリを、モジュールを探す順に並べる
+fn import_paths(path: &str, config: &RunConfig) -> Vec<String> {
+    let dir = match Path::new(path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.display().to_string(),
+        _ => ".".to_string(),
+    };
+
+    let mut paths = vec![dir];
+    paths.extend(config.import_paths.iter().cloned());
+    paths
+}
+
+/// スクリプトファイルを評価する。評価に成功したかどうかを返す
+pub fn run(path: &str, config: &RunConfig) -> io::Result<bool> {
+    let input = fs::read_to_string(path)?;
+    let program = match parse(&input)? {
+        Some(program) => program,
+        None => return Ok(false),
+    };
+
+    let mut evaluator = Evaluator::new();
+    evaluator.set_vfs(Some(Rc::new(OsVfs)));
+    evaluator.set_statement_limit(config.statement_limit);
+    evaluator.set_recursion_limit(config.recursion_limit.unwrap_or(DEFAULT_RECURSION_LIMIT));
+    evaluator.set_deterministic(config.deterministic);
+    evaluator.set_exec(config.exec);
+    evaluator.set_interrupt(Some(interrupt_flag()));
+    evaluator.set_import_paths(import_paths(path, config));
+
+    if config.debug {
+        evaluator.enable_debug(DEFAULT_TRACE_CAPACITY);
+    }
+
+    if config.coverage {
+        evaluator.enable_coverage();
+    }
+
+    if config.record.is_some() {
+        evaluator.start_recording();
+    }
+
+    let response = evaluator.eval(program.clone());
+
+    if let Some(record) = &config.record {
+        let session = Session {
+            inputs: vec![input.clone()],
+            results: evaluator.take_recorded(),
+        };
+        write_session(record, &session)?;
+    }
+
+    if let Response::Error(error) = &response {
+        print_error(error, &input);
+
+        if let Some(snapshot) = evaluator.take_snapshot() {
+            let path = report::write(&snapshot)?;
+            eprintln!("{}", text(Message::CrashReport, &[&path.display()]));
+        }
+    }
+
+    if let Some(coverage) = evaluator.take_coverage() {
+        print!("{}", coverage.report(&program));
+    }
+
+    Ok(!matches!(response, Response::Error(_)))
+}
+
+/// スクリプトファイルを評価せずに構文解析と検証だけ行う。問題が見つからなかったかどうかを返す
+pub fn check(path: &str) -> io::Result<bool> {
+    let program = match load(path)? {
+        Some(program) => program,
+        None => return Ok(false),
+    };
+
+    let errors = validate(&program, &[]);
+
+    for error in errors.iter() {
+        eprintln!(
+            "{}",
+            text(Message::Error, &[&localize(&error.to_string())]).red()
+        );
+    }
+
+    Ok(errors.is_empty())
+}
+
+/// プロジェクトのテストファイルを 1 つずつ実行する。すべて成功したかどうかを返す
+pub fn test(manifest: &Manifest, root: &Path, config: &RunConfig) -> io::Result<bool> {
+    let files = manifest.test_files(root)?;
+    let mut failed = 0;
+
+    for file in files.iter() {
+        let path = file.display().to_string();
+
+        if run(&path, config)? {
+            println!("{} ... ok", path);
+        } else {
+            failed += 1;
+            println!("{} ... {}", path, "FAILED".red());
+        }
+    }
+
+    let passed = files.len() - failed;
+    println!("{}", text(Message::SnippetsSummary, &[&passed, &failed]));
+
+    Ok(failed == 0)
+}
+
+/// スクリプトファイルを評価し、呼び出しグラフを DOT 形式で書き出す（出力先がなければ標準出力）
+pub fn callgraph(path: &str, output: Option<&str>) -> io::Result<bool> {
+    let input = fs::read_to_string(path)?;
+    let program = match parse(&input)? {
+        Some(program) => program,
+        None => return Ok(false),
+    };
+
+    let mut evaluator = Evaluator::new();
+    evaluator.set_vfs(Some(Rc::new(OsVfs)));
+    evaluator.enable_call_graph();
+
+    let response = evaluator.eval(program);
+
+    if let Response::Error(error) = &response {
+        print_error(error, &input);
+    }
+
+    let dot = evaluator.take_call_graph().unwrap_or_default().to_dot();
+
+    match output {
+        Some(output) => fs::write(output, dot)?,
+        None => print!("{}", dot),
+    }
+
+    Ok(!matches!(response, Response::Error(_)))
+}
+
+/// スクリプトファイルをテストスイートとみなし、変異体ごとに実行して生き残った変異体を報告する。
+/// すべての変異体が評価エラーで失敗したかどうかを返す
+pub fn mutate(path: &str) -> io::Result<bool> {
+    let program = match load(path)? {
+        Some(program) => program,
+        None => return Ok(false),
+    };
+
+    if let Some(error) = run_mutant(program.clone())? {
+        let message = text(Message::MutationBaseline, &[&localize(&error.to_string())]);
+        eprintln!("{}", text(Message::Error, &[&message]).red());
+        return Ok(false);
+    }
+
+    let mutants = mutants(&program);
+    let total = mutants.len();
+    let mut survived = 0;
+
+    for (i, mutant) in mutants.into_iter().enumerate() {
+        let description = mutant.description;
+
+        match run_mutant(mutant.program)? {
+            Some(_) => {
+                let killed = text(Message::Killed, &[]);
+                println!(
+                    "{}",
+                    text(Message::MutantResult, &[&(i + 1), &description, &killed])
+                );
+            }
+            None => {
+                survived += 1;
+                let survived = text(Message::Survived, &[]).red();
+                println!(
+                    "{}",
+                    text(Message::MutantResult, &[&(i + 1), &description, &survived])
+                );
+            }
+        }
+    }
+
+    println!(
+        "{}",
+        text(Message::MutantsKilled, &[&(total - survived), &total])
+    );
+
+    Ok(survived == 0)
+}
+
+/// 変異体を別スレッドで評価し、評価エラーを返す
+fn run_mutant(program: Program) -> io::Result<Option<String>> {
+    let handle = thread::Builder::new()
+        .stack_size(MUTANT_STACK_SIZE)
+        .spawn(move || {
+            let mut evaluator = Evaluator::new();
+            evaluator.set_statement_limit(Some(MUTANT_STATEMENT_LIMIT));
+
+            match evaluator.eval(program) {
+                Response::Error(error) => Some(error.to_string()),
+                _ => None,
+            }
+        })?;
+
+    let result = handle
+        .join()
+        .unwrap_or_else(|_| Some("evaluator panicked".into()));
+
+    Ok(result)
+}
+
+/// Markdown ファイルの ```monkey ブロックを実行して確かめる。すべて成功したかどうかを返す
+pub fn mdtest(path: &str) -> io::Result<bool> {
+    let markdown = fs::read_to_string(path)?;
+    let snippets = extract(&markdown);
+    let mut failed = 0;
+
+    for snippet in snippets.iter() {
+        match snippet.check() {
+            Ok(()) => println!("{}:{} ... ok", path, snippet.line),
+            Err(error) => {
+                failed += 1;
+                println!("{}:{} ... {}", path, snippet.line, "FAILED".red());
+                println!("    {}", error);
+            }
+        }
+    }
+
+    let passed = snippets.len() - failed;
+    println!("{}", text(Message::SnippetsSummary, &[&passed, &failed]));
+
+    Ok(failed == 0)
+}
+
+/// セッションをファイルに書き出す
+pub fn write_session(path: &str, session: &Session) -> io::Result<()> {
+    let encoded = session
+        .encode()
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    fs::write(path, encoded)?;
+    eprintln!("{}", text(Message::SessionRecorded, &[&path]));
+    Ok(())
+}
+
+/// 記録したセッションの入力を、記録した組み込み関数の結果を使って評価し直す。
+/// すべての入力の評価に成功したかどうかを返す
+pub fn replay(path: &str) -> io::Result<bool> {
+    let encoded = fs::read_to_string(path)?;
+    let session = Session::decode(&encoded)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+    let mut evaluator = Evaluator::new();
+    evaluator.start_replay(session.results);
+
+    for input in session.inputs.iter() {
+        println!(">> {}", input.trim_end());
+
+        let program = match parse(input)? {
+            Some(program) => program,
+            None => continue,
+        };
+
+        match evaluator.eval(program) {
+            Response::Reply(result) => println!("{}", result),
+            Response::NoReply => (),
+            Response::Error(error) => {
+                print_error(&error, input);
+                return Ok(false);
+            }
+        }
+    }
+
+    Ok(true)
+}
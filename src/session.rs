@@ -0,0 +1,166 @@
+use crate::numeric::{format_float, Float};
+use crate::object::Object;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// 記録ファイルの先頭行
+const HEADER: &str = "ronkey-session 1";
+
+/// 記録した組み込み関数の結果（関数名と値）
+pub type Recorded = (String, Object);
+
+/// 再現のために記録したセッション（入力と、実行ごとに値が変わる組み込み関数の結果）
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Session {
+    pub inputs: Vec<String>,
+    pub results: Vec<Recorded>,
+}
+
+impl Session {
+    /// 1 行に 1 レコードのテキストにする（文字列は 16 進数で書く）
+    pub fn encode(&self) -> Result<String, String> {
+        let mut lines = vec![HEADER.to_string()];
+
+        for input in self.inputs.iter() {
+            lines.push(format!("input {}", encode_hex(input)));
+        }
+
+        for (name, value) in self.results.iter() {
+            lines.push(format!("result {} {}", name, encode_value(value)?));
+        }
+
+        let mut text = lines.join("\n");
+        text.push('\n');
+        Ok(text)
+    }
+
+    pub fn decode(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+
+        if lines.next() != Some(HEADER) {
+            return Err("not a ronkey session".to_string());
+        }
+
+        let mut session = Self::default();
+
+        for (i, line) in lines.enumerate() {
+            let invalid = || format!("invalid session record at line {}: {}", i + 2, line);
+            let fields = line.split(' ').collect::<Vec<_>>();
+
+            match fields.as_slice() {
+                ["input", input] => session.inputs.push(decode_hex(input).ok_or_else(invalid)?),
+                ["result", name, kind, value] => {
+                    let value = decode_value(kind, value).ok_or_else(invalid)?;
+                    session.results.push((name.to_string(), value));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(session)
+    }
+}
+
+fn encode_value(value: &Object) -> Result<String, String> {
+    let result = match value {
+        Object::Integer(value) => format!("int {}", value),
+        Object::Float(value) => format!("float {}", format_float(value.0)),
+        Object::Boolean(value) => format!("bool {}", value),
+        Object::String(value) => format!("str {}", encode_hex(value)),
+        Object::Null => "null -".to_string(),
+        _ => {
+            let message = format!("cannot record value: {}", value.get_type());
+            return Err(message);
+        }
+    };
+
+    Ok(result)
+}
+
+fn decode_value(kind: &str, value: &str) -> Option<Object> {
+    let result = match kind {
+        "int" => Object::Integer(value.parse().ok()?),
+        "float" => Object::Float(Float(value.parse().ok()?)),
+        "bool" => Object::Boolean(value.parse().ok()?),
+        "str" => Object::String(decode_hex(value)?),
+        "null" => Object::Null,
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+fn encode_hex(text: &str) -> String {
+    if text.is_empty() {
+        return "-".to_string();
+    }
+
+    text.bytes().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<String> {
+    if hex == "-" {
+        return Some(String::new());
+    }
+
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::numeric::Float;
+    use crate::object::Object;
+    use crate::session::Session;
+
+    #[test]
+    fn test_encode_and_decode() {
+        let session = Session {
+            inputs: vec![
+                "let a = 1;".to_string(),
+                "".to_string(),
+                "\"日本\"".to_string(),
+            ],
+            results: vec![
+                ("elapsed_ms".to_string(), Object::Integer(-3)),
+                ("now".to_string(), Object::Float(Float(-1.5e-3))),
+                ("flag".to_string(), Object::Boolean(true)),
+                ("line".to_string(), Object::String("a b".to_string())),
+                ("none".to_string(), Object::Null),
+            ],
+        };
+
+        let text = session.encode().unwrap();
+
+        assert!(text.starts_with("ronkey-session 1\ninput 6c65742061203d20313b\ninput -\n"));
+        assert_eq!(Session::decode(&text), Ok(session));
+
+        assert_eq!(
+            Session::decode("ronkey-session 1\nresult x int y\n"),
+            Err("invalid session record at line 2: result x int y".to_string())
+        );
+        assert_eq!(
+            Session::decode("hello"),
+            Err("not a ronkey session".to_string())
+        );
+
+        let session = Session {
+            inputs: vec![],
+            results: vec![("f".to_string(), Object::Array(vec![]))],
+        };
+        assert_eq!(
+            session.encode(),
+            Err("cannot record value: Array".to_string())
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use core::cell::RefCell;
+use hashbrown::HashSet;
+
+/// 識別子の名前。同じ表で intern した名前は 1 つの文字列を共有するので、複製しても確保は起きない
+/// （構文木を別スレッドで評価できるように `Arc` にする）
+pub type Symbol = Arc<str>;
+
+/// 字句解析器・構文解析器・評価器で共有する名前の表
+pub type SharedInterner = Rc<RefCell<Interner>>;
+
+/// 識別子の名前の表
+#[derive(Debug, Default)]
+pub struct Interner {
+    symbols: HashSet<Symbol>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn shared() -> SharedInterner {
+        Rc::new(RefCell::new(Self::new()))
+    }
+
+    /// 名前の `Symbol` を返す（初めての名前なら表に加える）
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return Arc::clone(symbol);
+        }
+
+        let symbol = Symbol::from(name);
+        self.symbols.insert(Arc::clone(&symbol));
+        symbol
+    }
+
+    /// 表にある名前の数
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::symbol::Interner;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_intern() {
+        let mut interner = Interner::new();
+        let a = interner.intern("count");
+        let b = interner.intern("count");
+        let c = interner.intern("total");
+
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+        assert_eq!(&*a, "count");
+        assert_eq!(interner.len(), 2);
+    }
+}
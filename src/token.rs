@@ -1,17 +1,34 @@
-use std::fmt;
+use crate::numeric::Float;
+#[cfg(feature = "rational")]
+use crate::numeric::Rational;
+use crate::symbol::Symbol;
+use alloc::string::String;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
 
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Token {
     /// 不正な文字
     Illegal(char),
+    /// 範囲外の整数リテラル（リテラルと、その始まりの行と列）
+    OutOfRange {
+        literal: String,
+        line: usize,
+        column: usize,
+    },
     /// 終端
     Eof,
 
     // 識別子 + リテラル
     /// 識別子
-    Identifier(String),
+    Identifier(Symbol),
     /// 数値
     Integer(isize),
+    /// 浮動小数点数
+    Float(Float),
+    #[cfg(feature = "rational")]
+    Rational(Rational),
     /// 文字列
     String(String),
 
@@ -37,6 +54,20 @@ pub enum Token {
     Eq,
     /// !=
     Ne,
+    /// .
+    Dot,
+    /// ..
+    DotDot,
+    /// ..=
+    DotDotEq,
+    /// ...
+    Ellipsis,
+    /// =>
+    FatArrow,
+    /// ??
+    Coalesce,
+    /// ?
+    Question,
 
     // デリミタ
     /// ,
@@ -74,13 +105,33 @@ pub enum Token {
     Else,
     /// return
     Return,
+    /// try
+    Try,
+    /// catch
+    Catch,
+    /// match
+    Match,
+    /// macro
+    Macro,
+    /// yield
+    Yield,
+    /// import
+    Import,
+    /// as
+    As,
 }
 
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Token::Illegal(value) => write!(f, "{}", value),
+            Token::OutOfRange { literal, .. } => write!(f, "{}", literal),
+            Token::Eof => write!(f, "EOF"),
             Token::Identifier(value) => write!(f, "{}", value),
             Token::Integer(value) => write!(f, "Int({})", value),
+            Token::Float(value) => write!(f, "Float({})", value),
+            #[cfg(feature = "rational")]
+            Token::Rational(value) => write!(f, "Rational({})", value),
             Token::String(value) => write!(f, "String({})", value),
             Token::Assign => write!(f, "="),
             Token::Plus => write!(f, "+"),
@@ -92,6 +143,13 @@ impl fmt::Display for Token {
             Token::Gt => write!(f, ">"),
             Token::Eq => write!(f, "=="),
             Token::Ne => write!(f, "!="),
+            Token::Dot => write!(f, "."),
+            Token::DotDot => write!(f, ".."),
+            Token::DotDotEq => write!(f, "..="),
+            Token::Ellipsis => write!(f, "..."),
+            Token::FatArrow => write!(f, "=>"),
+            Token::Coalesce => write!(f, "??"),
+            Token::Question => write!(f, "?"),
             Token::Comma => write!(f, ","),
             Token::Semicolon => write!(f, ";"),
             Token::Colon => write!(f, ":"),
@@ -108,7 +166,63 @@ impl fmt::Display for Token {
             Token::If => write!(f, "if"),
             Token::Else => write!(f, "else"),
             Token::Return => write!(f, "return"),
-            token => write!(f, "{}", token),
+            Token::Try => write!(f, "try"),
+            Token::Catch => write!(f, "catch"),
+            Token::Match => write!(f, "match"),
+            Token::Macro => write!(f, "macro"),
+            Token::Yield => write!(f, "yield"),
+            Token::Import => write!(f, "import"),
+            Token::As => write!(f, "as"),
         }
     }
 }
+
+/// ソース上の位置（行と列は 1 から数え、行が 0 なら位置が分からない）
+///
+/// 構文木や評価エラーの比較では位置を無視する（同じ式はどこに書いても等しい）
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+
+    /// 位置が分かるかどうか（評価器が組み立てた文には位置がない）
+    pub fn is_known(&self) -> bool {
+        self.line > 0
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+impl PartialEq for Position {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Position {}
+
+impl PartialOrd for Position {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Position {
+    fn cmp(&self, _: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl Hash for Position {
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
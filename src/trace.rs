@@ -0,0 +1,491 @@
+use crate::ast::{Expression, Program, Statement};
+use crate::object::Object;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+
+/// 保持するトレースイベントの既定の件数
+pub const DEFAULT_TRACE_CAPACITY: usize = 32;
+
+/// トレースイベント
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraceEvent {
+    /// 文の評価
+    Statement(String),
+    /// 関数呼び出し
+    Call {
+        function: String,
+        arguments: Vec<String>,
+    },
+    /// 関数からの復帰
+    Return(String),
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Statement(statement) => write!(f, "eval {}", statement),
+            Self::Call {
+                function,
+                arguments,
+            } => write!(f, "call {}({})", function, arguments.join(", ")),
+            Self::Return(value) => write!(f, "return {}", value),
+        }
+    }
+}
+
+/// ステップ実行で評価した式の 1 ノード分の記録
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    /// 式の入れ子の深さ
+    pub depth: usize,
+    /// 評価した式
+    pub expression: String,
+    /// 評価結果
+    pub value: String,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indent = "  ".repeat(self.depth);
+        write!(f, "{}{} => {}", indent, self.expression, self.value)
+    }
+}
+
+/// 直近のトレースイベントを保持するリングバッファ
+#[derive(Clone, Debug)]
+pub struct Trace {
+    events: VecDeque<TraceEvent>,
+    capacity: usize,
+}
+
+impl Trace {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn record(&mut self, event: TraceEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+
+        self.events.push_back(event);
+    }
+
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.events.iter().cloned().collect()
+    }
+}
+
+/// 呼び出し元が関数でないときの名前
+pub const MAIN_FRAME: &str = "<main>";
+
+/// 実行時の呼び出しグラフ（辺の重みは呼び出し回数）
+#[derive(Clone, Debug, Default)]
+pub struct CallGraph {
+    edges: BTreeMap<(String, String), usize>,
+    frames: Vec<String>,
+}
+
+impl CallGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 関数の呼び出しを記録し、呼び出し先のフレームに入る
+    pub fn enter(&mut self, callee: String) {
+        self.count(callee.clone());
+        self.frames.push(callee);
+    }
+
+    /// 呼び出し先のフレームから出る
+    pub fn leave(&mut self) {
+        self.frames.pop();
+    }
+
+    /// 末尾呼び出しを記録し、現在のフレームを呼び出し先に置きかえる
+    pub fn tail_call(&mut self, callee: String) {
+        self.count(callee.clone());
+
+        match self.frames.last_mut() {
+            Some(frame) => *frame = callee,
+            None => self.frames.push(callee),
+        }
+    }
+
+    fn count(&mut self, callee: String) {
+        let caller = match self.frames.last() {
+            Some(frame) => frame.to_string(),
+            None => MAIN_FRAME.to_string(),
+        };
+
+        *self.edges.entry((caller, callee)).or_insert(0) += 1;
+    }
+
+    /// 呼び出し元と呼び出し先の組ごとの呼び出し回数
+    pub fn edges(&self) -> &BTreeMap<(String, String), usize> {
+        &self.edges
+    }
+
+    /// Graphviz の DOT 形式で書き出す
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph calls {\n");
+
+        for ((caller, callee), count) in self.edges.iter() {
+            dot.push_str(&format!(
+                "    {:?} -> {:?} [label=\"{}\", weight={}];\n",
+                caller, callee, count, count
+            ));
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// 文ごとの実行回数
+///
+/// 文はソース上の位置をもたないので、表示が同じ文の実行回数はまとめて数える
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    hits: BTreeMap<String, usize>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, statement: &Statement) {
+        *self.hits.entry(statement.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn hits(&self, statement: &Statement) -> usize {
+        self.hits.get(&statement.to_string()).copied().unwrap_or(0)
+    }
+
+    /// プログラムの各文に実行回数を添えた注釈つきの一覧を書き出す（未実行の文は `#####`）
+    pub fn report(&self, program: &Program) -> String {
+        let mut statements = vec![];
+
+        for statement in program.statements.iter() {
+            collect_statements(statement, 0, &mut statements);
+        }
+
+        let mut report = String::new();
+        let mut covered = 0;
+
+        for (depth, statement) in statements.iter() {
+            let hits = match self.hits(statement) {
+                0 => "#####".to_string(),
+                hits => {
+                    covered += 1;
+                    hits.to_string()
+                }
+            };
+
+            report.push_str(&format!(
+                "{:>6} | {}{}\n",
+                hits,
+                "  ".repeat(*depth),
+                statement
+            ));
+        }
+
+        let percent = match statements.len() {
+            0 => 100.0,
+            total => covered as f64 * 100.0 / total as f64,
+        };
+
+        report.push_str(&format!(
+            "coverage: {}/{} statements ({:.1}%)\n",
+            covered,
+            statements.len(),
+            percent
+        ));
+        report
+    }
+}
+
+/// ブロックを展開し、入れ子の深さとともに文を集める
+fn collect_statements<'a>(
+    statement: &'a Statement,
+    depth: usize,
+    statements: &mut Vec<(usize, &'a Statement)>,
+) {
+    match statement {
+        Statement::Block(block) => {
+            for statement in block.iter() {
+                collect_statements(statement, depth, statements);
+            }
+        }
+        Statement::Let { value, .. } => {
+            statements.push((depth, statement));
+            collect_nested_statements(value, depth + 1, statements);
+        }
+        Statement::Return(expression, _) | Statement::Expression(expression, _) => {
+            statements.push((depth, statement));
+            collect_nested_statements(expression, depth + 1, statements);
+        }
+    }
+}
+
+/// 式の中にある文を集める
+fn collect_nested_statements<'a>(
+    expression: &'a Expression,
+    depth: usize,
+    statements: &mut Vec<(usize, &'a Statement)>,
+) {
+    match expression {
+        Expression::Prefix { right, .. } => collect_nested_statements(right, depth, statements),
+        Expression::Infix { left, right, .. } => {
+            collect_nested_statements(left, depth, statements);
+            collect_nested_statements(right, depth, statements);
+        }
+        Expression::Grouped(expression) | Expression::Yield(expression) => {
+            collect_nested_statements(expression, depth, statements)
+        }
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+        } => {
+            collect_nested_statements(condition, depth, statements);
+            collect_statements(consequence, depth, statements);
+
+            if let Some(alternative) = alternative {
+                collect_statements(alternative, depth, statements);
+            }
+        }
+        Expression::Try { body, handler, .. } => {
+            collect_statements(body, depth, statements);
+            collect_statements(handler, depth, statements);
+        }
+        Expression::Match { subject, arms } => {
+            collect_nested_statements(subject, depth, statements);
+
+            for arm in arms.iter() {
+                if let Some(guard) = &arm.guard {
+                    collect_nested_statements(guard, depth, statements);
+                }
+                collect_nested_statements(&arm.body, depth, statements);
+            }
+        }
+        Expression::Function { body, .. } | Expression::Macro { body, .. } => {
+            collect_statements(body, depth, statements)
+        }
+        Expression::Call {
+            function,
+            arguments,
+        } => {
+            collect_nested_statements(function, depth, statements);
+
+            for argument in arguments.iter() {
+                collect_nested_statements(argument, depth, statements);
+            }
+        }
+        Expression::Array(elements) => {
+            for element in elements.iter() {
+                collect_nested_statements(element, depth, statements);
+            }
+        }
+        Expression::Index { left, index } => {
+            collect_nested_statements(left, depth, statements);
+            collect_nested_statements(index, depth, statements);
+        }
+        Expression::Slice { left, start, end } => {
+            collect_nested_statements(left, depth, statements);
+
+            for bound in [start, end].iter().copied().flatten() {
+                collect_nested_statements(bound, depth, statements);
+            }
+        }
+        Expression::Map(pairs) => {
+            for (key, value) in pairs.iter() {
+                collect_nested_statements(key, depth, statements);
+                collect_nested_statements(value, depth, statements);
+            }
+        }
+        Expression::Identifier(_)
+        | Expression::Integer(_)
+        | Expression::Float(_)
+        | Expression::String(_)
+        | Expression::Boolean(_) => (),
+        #[cfg(feature = "rational")]
+        Expression::Rational(_) => (),
+    }
+}
+
+/// 評価エラー発生時の状態のスナップショット
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    /// エラーメッセージ
+    pub error: String,
+    /// エラーが発生した文
+    pub statement: String,
+    /// 内側から順に並べた各スコープの束縛
+    pub scopes: Vec<Vec<(String, String)>>,
+    /// 直近のトレースイベント
+    pub events: Vec<TraceEvent>,
+}
+
+impl Snapshot {
+    pub fn new(
+        error: String,
+        statement: &Statement,
+        scopes: Vec<BTreeMap<String, Object>>,
+        events: Vec<TraceEvent>,
+    ) -> Self {
+        let scopes = scopes
+            .into_iter()
+            .map(|store| {
+                store
+                    .iter()
+                    .map(|(name, object)| (name.to_string(), describe(object)))
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            error,
+            statement: statement.to_string(),
+            scopes,
+            events,
+        }
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "error: {}", self.error)?;
+        writeln!(f, "statement: {}", self.statement)?;
+        writeln!(f)?;
+        writeln!(f, "scopes:")?;
+
+        for (level, bindings) in self.scopes.iter().enumerate() {
+            writeln!(f, "  [{}]", level)?;
+
+            for (name, value) in bindings.iter() {
+                writeln!(f, "    {} = {}", name, value)?;
+            }
+        }
+
+        writeln!(f)?;
+        writeln!(f, "trace (last {} events):", self.events.len())?;
+
+        for event in self.events.iter() {
+            writeln!(f, "  {}", event)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 監査ログで引数 1 つを要約するときの最大の文字数
+pub const AUDIT_ARGUMENT_WIDTH: usize = 64;
+
+/// ホストの資源にふれる組み込み関数の 1 回の呼び出しの記録（埋めこみ先の監査ログ用）
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditEvent {
+    /// 組み込み関数の名前
+    pub name: String,
+    /// 引数の要約（長い引数は `AUDIT_ARGUMENT_WIDTH` 文字で切りつめる）
+    pub arguments: Vec<String>,
+    /// 実行にかかった時間（std がなければ None）
+    pub duration: Option<Duration>,
+    /// 結果（失敗したならエラーメッセージ）
+    pub outcome: Result<(), String>,
+}
+
+impl AuditEvent {
+    /// 引数を要約する
+    pub fn summarize(arguments: &[Object]) -> Vec<String> {
+        arguments
+            .iter()
+            .map(|argument| {
+                let summary = describe(argument);
+
+                match summary.char_indices().nth(AUDIT_ARGUMENT_WIDTH) {
+                    Some((end, _)) => format!("{}...", &summary[..end]),
+                    None => summary,
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for AuditEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", self.name, self.arguments.join(", "))?;
+
+        if let Some(duration) = self.duration {
+            write!(f, " in {}us", duration.as_micros())?;
+        }
+
+        match &self.outcome {
+            Ok(()) => write!(f, ": ok"),
+            Err(error) => write!(f, ": {}", error),
+        }
+    }
+}
+
+/// 型名つきでオブジェクトを文字列にする
+pub fn describe(object: &Object) -> String {
+    match object {
+        Object::Function { .. } | Object::Buildin { .. } | Object::Bound { .. } => {
+            object.get_type()
+        }
+        Object::String(value) => format!("{:?} ({})", value, object.get_type()),
+        _ => format!("{} ({})", object, object.get_type()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluator::Evaluator;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_coverage_report() {
+        let input = r#"
+        let abs = fn(x) { if (x < 0) { return -x; } x };
+        abs(1);
+        abs(2);
+        "#;
+
+        let mut lexer = Lexer::new(input);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+        let mut evaluator = Evaluator::new();
+
+        evaluator.enable_coverage();
+        evaluator.eval(program.clone());
+
+        let report = evaluator.take_coverage().unwrap().report(&program);
+
+        assert_eq!(
+            report.lines().collect::<Vec<_>>(),
+            vec![
+                "     1 | let abs = fn (x) { if (x < 0) return (-x);x };",
+                "     2 |   if (x < 0) return (-x);",
+                " ##### |     return (-x);",
+                "     2 |   x",
+                "     1 | abs(1)",
+                "     1 | abs(2)",
+                "coverage: 5/6 statements (83.3%)",
+            ]
+        );
+    }
+}
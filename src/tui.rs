@@ -0,0 +1,274 @@
+use crate::evaluator::Response;
+use crate::messages::{localize, text, Message};
+use crate::repl::{user_config, Repl, ReplConfig};
+use crate::trace::describe;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position};
+use ratatui::style::{Color, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+/// 出力履歴の 1 行
+enum Output {
+    Input(String),
+    Reply(String),
+    Error(String),
+}
+
+/// 入力・出力履歴・束縛の 3 つのペインをもつ TUI
+pub struct App {
+    repl: Repl<io::Empty, io::Sink>,
+    /// `puts` の出力（画面を崩さないよう出力履歴に移す）
+    printed: Rc<RefCell<String>>,
+    input: String,
+    history: Vec<Output>,
+    exit: bool,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl App {
+    pub fn new() -> Self {
+        Self::with_config(ReplConfig::default())
+    }
+
+    /// REPL と同じ設定（プロンプトと色）で TUI をつくる
+    pub fn with_config(config: ReplConfig) -> Self {
+        let printed = Rc::new(RefCell::new(String::new()));
+        let mut repl = Repl::new(io::empty(), io::sink(), config);
+        repl.evaluator().set_output(Some(printed.clone()));
+
+        Self {
+            repl,
+            printed,
+            input: String::new(),
+            history: vec![],
+            exit: false,
+        }
+    }
+
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        while !self.exit {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+
+                match key.code {
+                    KeyCode::Esc => self.exit = true,
+                    KeyCode::Char('c' | 'd') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.exit = true
+                    }
+                    KeyCode::Char(ch) => self.input.push(ch),
+                    KeyCode::Backspace => {
+                        self.input.pop();
+                    }
+                    KeyCode::Enter => self.submit(),
+                    KeyCode::Tab => self.complete(),
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 入力の末尾の名前を候補に共通する部分までのばす（候補が複数あれば出力履歴に並べる）
+    fn complete(&mut self) {
+        let candidates = self.repl.complete(&self.input);
+
+        let common = match candidates.split_first() {
+            Some((first, rest)) => rest.iter().fold(first.as_str(), |common, candidate| {
+                let len = common
+                    .char_indices()
+                    .zip(candidate.chars())
+                    .find(|((_, a), b)| a != b)
+                    .map_or(common.len().min(candidate.len()), |((i, _), _)| i);
+                &common[..len]
+            }),
+            None => return,
+        };
+
+        // 候補はどれも入力の末尾の名前から始まるので、入力の末尾と重なる部分のあとを足す
+        let overlap = common
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([common.len()])
+            .filter(|&i| self.input.ends_with(&common[..i]))
+            .max()
+            .unwrap_or(0);
+        let rest = common[overlap..].to_string();
+        self.input.push_str(&rest);
+
+        if candidates.len() > 1 {
+            self.history.push(Output::Reply(candidates.join("  ")));
+        }
+    }
+
+    /// 入力ペインの内容を評価して出力履歴に積む
+    fn submit(&mut self) {
+        let input = std::mem::take(&mut self.input);
+
+        if input.trim().is_empty() {
+            return;
+        }
+
+        let response = self.repl.eval_line(&input);
+        let mut outputs = self
+            .printed
+            .take()
+            .lines()
+            .map(|line| Output::Reply(line.to_string()))
+            .collect::<Vec<_>>();
+
+        outputs.extend(match response {
+            Ok(Response::Reply(result)) => vec![Output::Reply(result.to_string())],
+            Ok(Response::NoReply) => vec![],
+            Ok(Response::Error(error)) => {
+                vec![Output::Error(text(
+                    Message::Error,
+                    &[&localize(&error.to_string())],
+                ))]
+            }
+            Err(errors) => errors
+                .into_iter()
+                .map(|error| Output::Error(text(Message::ParseError, &[&localize(&error)])))
+                .collect(),
+        });
+
+        self.history.push(Output::Input(input));
+        self.history.extend(outputs);
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, bindings] =
+            Layout::horizontal([Constraint::Percentage(70), Constraint::Percentage(30)])
+                .areas(frame.area());
+        let [output, input] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(3)]).areas(main);
+
+        let config = self.repl.config();
+        let style = |color: Option<colored::Color>| match color {
+            Some(color) if config.color => Style::new().fg(tui_color(color)),
+            _ => Style::new(),
+        };
+
+        let visible = output.height.saturating_sub(2) as usize;
+        let lines = self
+            .history
+            .iter()
+            .skip(self.history.len().saturating_sub(visible))
+            .map(|output| match output {
+                Output::Input(input) => Line::raw(format!("{}{}", config.prompt, input)),
+                Output::Reply(reply) => Line::styled(reply.as_str(), style(config.result_color)),
+                Output::Error(error) => Line::styled(error.as_str(), style(config.error_color)),
+            })
+            .collect::<Vec<_>>();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("output")),
+            output,
+        );
+
+        frame.render_widget(
+            Paragraph::new(self.input.as_str()).block(Block::bordered().title("input")),
+            input,
+        );
+        frame.set_cursor_position(Position::new(
+            input.x + 1 + self.input.chars().count() as u16,
+            input.y + 1,
+        ));
+
+        let items = self
+            .repl
+            .evaluator()
+            .bindings()
+            .iter()
+            .map(|(name, value)| format!("{} = {}", name, describe(value)))
+            .collect::<Vec<_>>();
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title("bindings")),
+            bindings,
+        );
+    }
+}
+
+/// REPL の色を TUI の色に変換する
+fn tui_color(color: colored::Color) -> Color {
+    match color {
+        colored::Color::Black => Color::Black,
+        colored::Color::Red => Color::Red,
+        colored::Color::Green => Color::Green,
+        colored::Color::Yellow => Color::Yellow,
+        colored::Color::Blue => Color::Blue,
+        colored::Color::Magenta => Color::Magenta,
+        colored::Color::Cyan => Color::Cyan,
+        colored::Color::White => Color::Gray,
+        colored::Color::BrightBlack => Color::DarkGray,
+        colored::Color::BrightRed => Color::LightRed,
+        colored::Color::BrightGreen => Color::LightGreen,
+        colored::Color::BrightYellow => Color::LightYellow,
+        colored::Color::BrightBlue => Color::LightBlue,
+        colored::Color::BrightMagenta => Color::LightMagenta,
+        colored::Color::BrightCyan => Color::LightCyan,
+        colored::Color::BrightWhite => Color::White,
+        colored::Color::TrueColor { r, g, b } => Color::Rgb(r, g, b),
+    }
+}
+
+pub fn start() -> io::Result<()> {
+    let config = user_config()?;
+    let mut terminal = ratatui::try_init()?;
+    let result = App::with_config(config).run(&mut terminal);
+    ratatui::try_restore()?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tui::App;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    #[test]
+    fn test_draw() {
+        let mut app = App::new();
+
+        for input in ["let a = 1 + 2", "a * 2", "a +", "b", "puts(\"hi\")"] {
+            app.input = input.to_string();
+            app.submit();
+        }
+
+        app.input = "let".to_string();
+
+        let mut terminal = Terminal::new(TestBackend::new(80, 16)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let rows = (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        assert!(rows.contains(">> let a = 1 + 2"));
+        assert!(rows.contains("│6"));
+        assert!(rows.contains("parse error: no prefix parse function for EOF found"));
+        assert!(rows.contains("error: identifier not found: b"));
+        assert!(rows.contains("a = 3 (Integer)"));
+        assert!(rows.contains("│hi"));
+        assert!(rows.contains("│let"));
+    }
+}
@@ -0,0 +1,394 @@
+use crate::ast::{Expression, Pattern, Program, Statement};
+use crate::buildin;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// 名前が何に束縛されるか
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Binding {
+    /// 関数（引数の数）
+    Function(usize),
+    /// マクロ（引数は評価されないので調べない）
+    Macro,
+    /// それ以外、または束縛が 1 つに決まらない
+    Other,
+}
+
+/// 関数 1 つ分（またはトップレベル）の束縛
+type Scope = BTreeMap<String, Binding>;
+
+/// 評価の前に、どこにも束縛されていない名前の参照と、ローカルに定義した関数の呼び出しの引数の数を調べる。
+/// `known` は評価器にすでに束縛されている名前（REPL の前の入力など）
+///
+/// 束縛は関数ごと（トップレベルも 1 つの関数とみなす）にまとめ、定義より前の参照も認める。
+/// 評価してみないとわからない束縛は誤りにしないので、見つかるのは明らかな誤りだけになる
+pub fn validate(program: &Program, known: &[String]) -> Vec<String> {
+    let mut scope = buildin::new()
+        .into_keys()
+        .map(|name| (name, Binding::Other))
+        .collect::<Scope>();
+
+    for name in known.iter() {
+        scope.insert(name.to_string(), Binding::Other);
+    }
+
+    for statement in program.statements.iter() {
+        collect_statement(statement, &mut scope);
+    }
+
+    let mut validator = Validator {
+        scopes: vec![scope],
+        reported: BTreeSet::new(),
+        errors: vec![],
+    };
+
+    for statement in program.statements.iter() {
+        validator.check_statement(statement);
+    }
+
+    validator.errors
+}
+
+fn bind(scope: &mut Scope, name: &str, binding: Binding) {
+    scope
+        .entry(name.to_string())
+        .and_modify(|existing| *existing = Binding::Other)
+        .or_insert(binding);
+}
+
+/// 関数の本体の中（入れ子の関数は除く）で束縛される名前を集める
+fn collect_statement(statement: &Statement, scope: &mut Scope) {
+    match statement {
+        Statement::Let { name, value, .. } => {
+            if let Expression::Identifier(name) = name {
+                let binding = match value {
+                    Expression::Function { parameters, .. } => Binding::Function(parameters.len()),
+                    Expression::Macro { .. } => Binding::Macro,
+                    _ => Binding::Other,
+                };
+                bind(scope, name, binding);
+            }
+
+            collect_expression(value, scope);
+        }
+        Statement::Return(expression, _) | Statement::Expression(expression, _) => {
+            collect_expression(expression, scope)
+        }
+        Statement::Block(statements) => {
+            for statement in statements.iter() {
+                collect_statement(statement, scope);
+            }
+        }
+    }
+}
+
+fn collect_expression(expression: &Expression, scope: &mut Scope) {
+    match expression {
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+        } => {
+            collect_expression(condition, scope);
+            collect_statement(consequence, scope);
+
+            if let Some(alternative) = alternative {
+                collect_statement(alternative, scope);
+            }
+        }
+        Expression::Try {
+            body,
+            parameter,
+            handler,
+        } => {
+            collect_statement(body, scope);
+
+            if let Expression::Identifier(name) = parameter.as_ref() {
+                bind(scope, name, Binding::Other);
+            }
+
+            collect_statement(handler, scope);
+        }
+        Expression::Match { subject, arms } => {
+            collect_expression(subject, scope);
+
+            for arm in arms.iter() {
+                collect_pattern(&arm.pattern, scope);
+                collect_expression(&arm.body, scope);
+            }
+        }
+        Expression::Prefix { right, .. } => collect_expression(right, scope),
+        Expression::Infix { left, right, .. } => {
+            collect_expression(left, scope);
+            collect_expression(right, scope);
+        }
+        Expression::Grouped(expression) | Expression::Yield(expression) => {
+            collect_expression(expression, scope)
+        }
+        Expression::Call {
+            function,
+            arguments,
+        } => {
+            collect_expression(function, scope);
+
+            for argument in arguments.iter() {
+                collect_expression(argument, scope);
+            }
+        }
+        Expression::Array(elements) => {
+            for element in elements.iter() {
+                collect_expression(element, scope);
+            }
+        }
+        Expression::Index { left, index } => {
+            collect_expression(left, scope);
+            collect_expression(index, scope);
+        }
+        _ => (),
+    }
+}
+
+fn collect_pattern(pattern: &Pattern, scope: &mut Scope) {
+    match pattern {
+        Pattern::Binding(name) => bind(scope, name, Binding::Other),
+        Pattern::Array { elements, rest } => {
+            for element in elements.iter() {
+                collect_pattern(element, scope);
+            }
+
+            if let Some(rest) = rest {
+                bind(scope, rest, Binding::Other);
+            }
+        }
+        Pattern::Map(entries) => {
+            for (_, pattern) in entries.iter() {
+                collect_pattern(pattern, scope);
+            }
+        }
+        Pattern::Wildcard | Pattern::Literal(_) => (),
+    }
+}
+
+struct Validator {
+    /// 内側の関数ほど後ろに並ぶ束縛
+    scopes: Vec<Scope>,
+    /// 報告済みの未定義の名前（同じ名前は 1 度だけ報告する）
+    reported: BTreeSet<String>,
+    errors: Vec<String>,
+}
+
+impl Validator {
+    fn resolve(&self, name: &str) -> Option<Binding> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Let { value, .. } => self.check_expression(value),
+            Statement::Return(expression, _) | Statement::Expression(expression, _) => {
+                self.check_expression(expression)
+            }
+            Statement::Block(statements) => {
+                for statement in statements.iter() {
+                    self.check_statement(statement);
+                }
+            }
+        }
+    }
+
+    fn check_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Identifier(name)
+                if self.resolve(name).is_none() && self.reported.insert(name.to_string()) =>
+            {
+                let message = format!("identifier not found: {}", name);
+                self.errors.push(message);
+            }
+            Expression::Call {
+                function,
+                arguments,
+            } => {
+                let binding = match function.as_ref() {
+                    Expression::Identifier(name) if &**name == "quote" && arguments.len() == 1 => {
+                        return;
+                    }
+                    Expression::Identifier(name) => {
+                        self.resolve(name).map(|binding| (name, binding))
+                    }
+                    _ => None,
+                };
+
+                match binding {
+                    Some((_, Binding::Macro)) => return,
+                    Some((name, Binding::Function(parameters)))
+                        if parameters != arguments.len() =>
+                    {
+                        let message = format!(
+                            "`{}` expects {} arguments, got {}",
+                            name,
+                            parameters,
+                            arguments.len()
+                        );
+                        self.errors.push(message);
+                    }
+                    _ => (),
+                }
+
+                self.check_expression(function);
+
+                for argument in arguments.iter() {
+                    self.check_expression(argument);
+                }
+            }
+            Expression::Function { parameters, body } => {
+                let mut scope = parameters
+                    .iter()
+                    .filter_map(|parameter| match parameter {
+                        Expression::Identifier(name) => Some((name.to_string(), Binding::Other)),
+                        _ => None,
+                    })
+                    .collect::<Scope>();
+                collect_statement(body, &mut scope);
+
+                self.scopes.push(scope);
+                self.check_statement(body);
+                self.scopes.pop();
+            }
+            // マクロの本体は展開先の束縛で評価されるので調べない
+            Expression::Macro { .. } => (),
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.check_expression(condition);
+                self.check_statement(consequence);
+
+                if let Some(alternative) = alternative {
+                    self.check_statement(alternative);
+                }
+            }
+            Expression::Try { body, handler, .. } => {
+                self.check_statement(body);
+                self.check_statement(handler);
+            }
+            Expression::Match { subject, arms } => {
+                self.check_expression(subject);
+
+                for arm in arms.iter() {
+                    if let Some(guard) = &arm.guard {
+                        self.check_expression(guard);
+                    }
+
+                    self.check_expression(&arm.body);
+                }
+            }
+            Expression::Prefix { right, .. } => self.check_expression(right),
+            Expression::Infix { left, right, .. } => {
+                self.check_expression(left);
+                self.check_expression(right);
+            }
+            Expression::Grouped(expression) | Expression::Yield(expression) => {
+                self.check_expression(expression)
+            }
+            Expression::Array(elements) => {
+                for element in elements.iter() {
+                    self.check_expression(element);
+                }
+            }
+            Expression::Index { left, index } => {
+                self.check_expression(left);
+                self.check_expression(index);
+            }
+            Expression::Slice { left, start, end } => {
+                self.check_expression(left);
+
+                for bound in start.iter().chain(end.iter()) {
+                    self.check_expression(bound);
+                }
+            }
+            Expression::Map(pairs) => {
+                for (key, value) in pairs.iter() {
+                    self.check_expression(key);
+                    self.check_expression(value);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::validate::validate;
+
+    fn assert_validate(input: &str, known: &[&str], expected: Vec<&str>) {
+        let mut lexer = Lexer::new(input);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+        let known = known
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(validate(&program, &known), expected, "{}", input);
+    }
+
+    #[test]
+    fn test_validate() {
+        let tests = vec![
+            ("let add = fn(a, b) { a + b }; add(1, 2)", vec![]),
+            (
+                "let add = fn(a, b) { a + b }; add(1)",
+                vec!["`add` expects 2 arguments, got 1"],
+            ),
+            (
+                "let f = fn(x) { if (x > 0) { prnt(x) } else { x } }; f(1)",
+                vec!["identifier not found: prnt"],
+            ),
+            ("let f = fn() { g() }; let g = fn() { 1 }; f()", vec![]),
+            (
+                "let fact = fn(n) { if (n == 0) { 1 } else { n * fact(n - 1, 0) } }",
+                vec!["`fact` expects 1 arguments, got 2"],
+            ),
+            (
+                "let f = fn(x) { if (x) { let y = 1; } y }; f(true)",
+                vec![],
+            ),
+            ("let f = fn(x) { x }; let f = 1; f(1, 2)", vec![]),
+            (
+                "let f = fn(x) { x }; let g = fn(f) { f(1, 2) }; g(len)",
+                vec![],
+            ),
+            (
+                "match ([1, 2]) { [a, ...rest] => a + len(rest), _ => b }",
+                vec!["identifier not found: b"],
+            ),
+            ("try { throw(1) } catch (e) { e }", vec![]),
+            (
+                "let unless = macro(c, a, b) { quote(if (!(unquote(c))) { unquote(a) }) }; unless(true, x, 1, 2)",
+                vec![],
+            ),
+            ("quote(anything)", vec![]),
+            (
+                "let f = fn() { missing + missing }; [missing, other]",
+                vec!["identifier not found: missing", "identifier not found: other"],
+            ),
+            ("{x: 1}[1:y]", vec!["identifier not found: x", "identifier not found: y"]),
+        ];
+
+        for (input, expected) in tests {
+            assert_validate(input, &[], expected);
+        }
+
+        assert_validate("previous + 1", &["previous"], vec![]);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::evaluator::DEFAULT_RECURSION_LIMIT;
+use crate::inline::DEFAULT_INLINE_STEPS;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// クレートの版
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// ツールが互換性を確かめるための版の情報
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    /// 有効な cargo のフィーチャー
+    pub features: Vec<&'static str>,
+    /// 言語の既定値（名前と JSON の値）
+    pub dialect: Vec<(&'static str, String)>,
+    /// 使える評価器
+    pub engines: Vec<&'static str>,
+}
+
+impl VersionInfo {
+    pub fn new() -> Self {
+        let features = [
+            ("std", cfg!(feature = "std")),
+            ("data", cfg!(feature = "data")),
+            ("rational", cfg!(feature = "rational")),
+            ("date", cfg!(feature = "date")),
+            ("tui", cfg!(feature = "tui")),
+        ]
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect();
+
+        let dialect = vec![
+            ("max_steps", "null".to_string()),
+            ("inline_max_steps", DEFAULT_INLINE_STEPS.to_string()),
+            ("max_depth", DEFAULT_RECURSION_LIMIT.to_string()),
+            ("integer_bits", isize::BITS.to_string()),
+            ("tail_calls", "true".to_string()),
+            ("map_order", "\"sorted\"".to_string()),
+        ];
+
+        Self {
+            version: VERSION,
+            features,
+            dialect,
+            engines: vec!["tree-walking", "incremental"],
+        }
+    }
+
+    /// 1 行の JSON にする
+    pub fn to_json(&self) -> String {
+        let strings = |values: &[&str]| {
+            values
+                .iter()
+                .map(|value| format!("\"{}\"", value))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let dialect = self
+            .dialect
+            .iter()
+            .map(|(key, value)| format!("\"{}\":{}", key, value))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(
+            "{{\"name\":\"ronkey\",\"version\":\"{}\",\"features\":[{}],\"dialect\":{{{}}},\"engines\":[{}]}}",
+            self.version,
+            strings(&self.features),
+            dialect,
+            strings(&self.engines)
+        )
+    }
+}
+
+impl Default for VersionInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for VersionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "ronkey {}", self.version)?;
+        writeln!(f, "features: {}", self.features.join(", "))?;
+
+        let dialect = self
+            .dialect
+            .iter()
+            .map(|(key, value)| format!("{} = {}", key, value))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(f, "dialect: {}", dialect)?;
+        write!(f, "engines: {}", self.engines.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::version::{VersionInfo, VERSION};
+
+    #[test]
+    fn test_version_info() {
+        let info = VersionInfo::new();
+        assert_eq!(info.version, VERSION);
+        assert_eq!(info.features.contains(&"std"), cfg!(feature = "std"));
+
+        let json = info.to_json();
+        assert!(json.starts_with(&format!(
+            "{{\"name\":\"ronkey\",\"version\":\"{}\",\"features\":[",
+            VERSION
+        )));
+        assert!(json.contains(
+            "\"dialect\":{\"max_steps\":null,\"inline_max_steps\":10000,\"max_depth\":1000,"
+        ));
+        assert!(json.ends_with("\"engines\":[\"tree-walking\",\"incremental\"]}"));
+
+        let text = info.to_string();
+        assert!(text.starts_with(&format!("ronkey {}\nfeatures: ", VERSION)));
+        assert!(text.ends_with("\nengines: tree-walking, incremental"));
+    }
+}
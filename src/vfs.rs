@@ -0,0 +1,277 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// 組み込み関数がファイルを読むための仮想ファイルシステム
+pub trait Vfs {
+    /// パスのファイルの内容を読む
+    fn read(&self, path: &str) -> Result<String, String>;
+
+    /// ファイルを開く（既定では読みこみだけを許し、内容をまとめて読んでから 1 行ずつ返す）
+    fn open(&self, path: &str, mode: OpenMode) -> Result<Box<dyn VfsFile>, String> {
+        match mode {
+            OpenMode::Read => {
+                let lines = self
+                    .read(path)?
+                    .lines()
+                    .map(str::to_string)
+                    .collect::<Vec<_>>();
+                Ok(Box::new(Lines(lines.into_iter())))
+            }
+            OpenMode::Write | OpenMode::Append => {
+                let message = format!("cannot open {} for writing", path);
+                Err(message)
+            }
+        }
+    }
+}
+
+/// ファイルを開くモード
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OpenMode {
+    /// 読みこみ（`"r"`）
+    Read,
+    /// 書きこみ、既存の内容は捨てる（`"w"`）
+    Write,
+    /// 末尾への追記（`"a"`）
+    Append,
+}
+
+/// 開いたファイル
+pub trait VfsFile {
+    /// 1 行を読む（行末の改行は含めない。ファイルの終わりなら None）
+    fn read_line(&mut self) -> Result<Option<String>, String>;
+
+    /// 文字列をそのまま書く
+    fn write(&mut self, text: &str) -> Result<(), String>;
+
+    /// 書きこんだ内容を反映して閉じる
+    fn close(&mut self) -> Result<(), String>;
+}
+
+fn not_writable() -> String {
+    "file is not open for writing".to_string()
+}
+
+/// 読みこみ済みの内容を 1 行ずつ返すファイル
+struct Lines(vec::IntoIter<String>);
+
+impl VfsFile for Lines {
+    fn read_line(&mut self) -> Result<Option<String>, String> {
+        Ok(self.0.next())
+    }
+
+    fn write(&mut self, _: &str) -> Result<(), String> {
+        Err(not_writable())
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+fn not_found(path: &str) -> String {
+    format!("no such file: {}", path)
+}
+
+/// メモリ上のファイル
+#[derive(Clone, Debug, Default)]
+pub struct MemoryVfs {
+    files: BTreeMap<String, String>,
+}
+
+impl MemoryVfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ファイルを追加する（同じパスがあれば置きかえる）
+    pub fn insert(&mut self, path: &str, contents: &str) {
+        self.files.insert(path.to_string(), contents.to_string());
+    }
+}
+
+impl Vfs for MemoryVfs {
+    fn read(&self, path: &str) -> Result<String, String> {
+        self.files.get(path).cloned().ok_or_else(|| not_found(path))
+    }
+}
+
+/// バイナリに埋め込まれたファイル（`include_str!` と組み合わせて使う）
+#[derive(Clone, Copy, Debug)]
+pub struct EmbeddedVfs {
+    files: &'static [(&'static str, &'static str)],
+}
+
+impl EmbeddedVfs {
+    pub const fn new(files: &'static [(&'static str, &'static str)]) -> Self {
+        Self { files }
+    }
+}
+
+impl Vfs for EmbeddedVfs {
+    fn read(&self, path: &str) -> Result<String, String> {
+        self.files
+            .iter()
+            .find(|(name, _)| *name == path)
+            .map(|(_, contents)| contents.to_string())
+            .ok_or_else(|| not_found(path))
+    }
+}
+
+/// 実際のファイルシステム
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OsVfs;
+
+#[cfg(feature = "std")]
+impl Vfs for OsVfs {
+    fn read(&self, path: &str) -> Result<String, String> {
+        std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))
+    }
+
+    fn open(&self, path: &str, mode: OpenMode) -> Result<Box<dyn VfsFile>, String> {
+        use std::fs::OpenOptions;
+        use std::io::{BufReader, BufWriter};
+
+        let mut options = OpenOptions::new();
+        match mode {
+            OpenMode::Read => options.read(true),
+            OpenMode::Write => options.write(true).create(true).truncate(true),
+            OpenMode::Append => options.append(true).create(true),
+        };
+
+        let file = options
+            .open(path)
+            .map_err(|error| format!("{}: {}", path, error))?;
+
+        let file = match mode {
+            OpenMode::Read => OsFile::Reader(BufReader::new(file)),
+            OpenMode::Write | OpenMode::Append => OsFile::Writer(BufWriter::new(file)),
+        };
+
+        Ok(Box::new(file))
+    }
+}
+
+/// 実際のファイルシステムで開いたファイル（大きなファイルも 1 行ずつ読み書きできる）
+#[cfg(feature = "std")]
+enum OsFile {
+    Reader(std::io::BufReader<std::fs::File>),
+    Writer(std::io::BufWriter<std::fs::File>),
+}
+
+#[cfg(feature = "std")]
+impl VfsFile for OsFile {
+    fn read_line(&mut self) -> Result<Option<String>, String> {
+        use std::io::BufRead;
+
+        let reader = match self {
+            Self::Reader(reader) => reader,
+            Self::Writer(_) => return Err("file is not open for reading".to_string()),
+        };
+
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) => Ok(None),
+            Ok(_) => {
+                let end = line.trim_end_matches(['\n', '\r']).len();
+                line.truncate(end);
+                Ok(Some(line))
+            }
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    fn write(&mut self, text: &str) -> Result<(), String> {
+        use std::io::Write;
+
+        match self {
+            Self::Writer(writer) => writer
+                .write_all(text.as_bytes())
+                .map_err(|error| error.to_string()),
+            Self::Reader(_) => Err(not_writable()),
+        }
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        use std::io::Write;
+
+        match self {
+            Self::Writer(writer) => writer.flush().map_err(|error| error.to_string()),
+            Self::Reader(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use crate::vfs::OsVfs;
+    use crate::vfs::{EmbeddedVfs, MemoryVfs, OpenMode, Vfs};
+
+    #[test]
+    fn test_memory_vfs() {
+        let mut vfs = MemoryVfs::new();
+        vfs.insert("a.txt", "hello");
+
+        assert_eq!(vfs.read("a.txt"), Ok("hello".to_string()));
+        assert_eq!(vfs.read("b.txt"), Err("no such file: b.txt".to_string()));
+    }
+
+    #[test]
+    fn test_embedded_vfs() {
+        const VFS: EmbeddedVfs = EmbeddedVfs::new(&[("lib.monkey", "let x = 1;")]);
+
+        assert_eq!(VFS.read("lib.monkey"), Ok("let x = 1;".to_string()));
+        assert_eq!(VFS.read("x"), Err("no such file: x".to_string()));
+    }
+
+    #[test]
+    fn test_open() {
+        let mut vfs = MemoryVfs::new();
+        vfs.insert("a.txt", "one\ntwo\n");
+
+        let mut file = vfs.open("a.txt", OpenMode::Read).unwrap();
+        assert_eq!(file.read_line(), Ok(Some("one".to_string())));
+        assert_eq!(file.read_line(), Ok(Some("two".to_string())));
+        assert_eq!(file.read_line(), Ok(None));
+        assert_eq!(
+            file.write("x"),
+            Err("file is not open for writing".to_string())
+        );
+        assert_eq!(
+            vfs.open("a.txt", OpenMode::Write).err(),
+            Some("cannot open a.txt for writing".to_string())
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_os_vfs_open() {
+        let path = std::env::temp_dir().join(format!("ronkey-vfs-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        for (mode, text) in [(OpenMode::Write, "one\r\n"), (OpenMode::Append, "two")] {
+            let mut file = OsVfs.open(path, mode).unwrap();
+            file.write(text).unwrap();
+            file.close().unwrap();
+        }
+
+        let mut file = OsVfs.open(path, OpenMode::Read).unwrap();
+        let lines = [file.read_line(), file.read_line(), file.read_line()];
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(
+            lines,
+            [
+                Ok(Some("one".to_string())),
+                Ok(Some("two".to_string())),
+                Ok(None)
+            ]
+        );
+    }
+}
@@ -0,0 +1,19 @@
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+/// `std` フィーチャなしでライブラリがビルドできることを確認する
+#[test]
+fn test_no_std_build() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let target_dir = Path::new(manifest_dir).join("target").join("no_std");
+
+    let status = Command::new(env!("CARGO"))
+        .args(["check", "--lib", "--no-default-features"])
+        .current_dir(manifest_dir)
+        .env("CARGO_TARGET_DIR", target_dir)
+        .status()
+        .expect("failed to run cargo");
+
+    assert!(status.success());
+}
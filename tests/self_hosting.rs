@@ -0,0 +1,60 @@
+#![cfg(feature = "std")]
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Monkey で書いた Monkey のインタプリタ（`examples/monkey.monkey`）にプログラムを標準入力で渡して実行する
+fn run_self_hosted(program: &str) -> (bool, String, String) {
+    let interpreter = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("examples")
+        .join("monkey.monkey");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ronkey"))
+        .arg(interpreter)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run ronkey");
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(program.as_bytes())
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+#[test]
+fn test_self_hosted_interpreter() {
+    let program = r#"
+        let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } };
+        puts(fib(10));
+        let adder = fn(x) { fn(y) { x + y } };
+        puts(adder(2)(40));
+        puts([1, 2 * 3, "x" + "y", !true, -5, len("four")]);
+        let size = fn(n) { if (n > 3) { return "big"; } "small" };
+        puts(size(5));
+        puts(size(1));
+        puts(rest(push([1, 2], 3))[1] != 2);
+    "#;
+
+    let (success, stdout, _) = run_self_hosted(program);
+    assert!(success);
+    assert_eq!(
+        stdout,
+        "55\n42\n[1, 6, xy, false, -5, 4]\nbig\nsmall\ntrue\n"
+    );
+
+    let (success, _, stderr) = run_self_hosted("puts(missing);");
+    assert!(!success);
+    assert!(stderr.contains("identifier not found: missing"));
+}